@@ -1,4 +1,5 @@
 use crate::datamanager::Manager;
+use crate::error::Result;
 use crate::grid::{self, *};
 use crate::particleselector::ParticleSelector;
 use itertools::Itertools;
@@ -26,11 +27,11 @@ pub trait Comparison {
             aligned: false,
         }
     }
-    fn align(&mut self);
+    fn align(&mut self) -> Result<()>;
 }
 
 impl Comparison for Comparer {
-    fn align(&mut self) {
+    fn align(&mut self) -> Result<()> {
         let quantile_threshold = 0.01;
         let steps = 100;
         // we need to generate grids such that the data oerlaps perfect and we also may need to set rotation of datasets
@@ -59,8 +60,15 @@ impl Comparison for Comparer {
             dim2[[1, 1]] - dim2[[0, 1]],
             dim2[[1, 2]] - dim2[[0, 2]],
         ];
-        // check if the dimensions are roughly 800 times larger than the other, then the unit is different
-        let set2_adjustment = if size[0] / size2[0] > 800.0 {
+        // convert with the recorded units if both datasets have one, otherwise check if the
+        // dimensions are roughly 800 times larger than the other, then the unit is different
+        let units = (
+            *stats1.provenance().length_unit(),
+            *stats2.provenance().length_unit(),
+        );
+        let set2_adjustment = if let (Some(unit1), Some(unit2)) = units {
+            unit2.factor_to(unit1)
+        } else if size[0] / size2[0] > 800.0 {
             1000.0
         } else if size2[0] / size[0] > 800.0 {
             1.0 / 1000.0
@@ -121,8 +129,8 @@ impl Comparison for Comparer {
                     let grid1 = make_grid(&dim1_, gridtype, [100, 100, 100]);
                     let grid2 = make_grid(&dim2, gridtype, [100, 100, 100]);
                     // Generate a field with the number of particles in each cell
-                    let field1 = self.data.numberfield(grid1, &selector);
-                    let field2 = self.data2.numberfield(grid2, &selector);
+                    let field1 = self.data.numberfield(grid1, &selector)?;
+                    let field2 = self.data2.numberfield(grid2, &selector)?;
                     // threshold the field at 1% low
                     let binary_field1 = binary_threshold(field1.get_data(), quantile_threshold);
                     let binary_field2 = binary_threshold(field2.get_data(), quantile_threshold);
@@ -140,6 +148,7 @@ impl Comparison for Comparer {
                 }
             }
         }
+        Ok(())
     }
 }
 
@@ -189,24 +198,24 @@ impl Comparer {
         mode: &str,
         min_velocity: f64,
         max_velocity: f64,
-    ) -> (Array1<f64>, Array1<f64>) {
+    ) -> Result<(Array1<f64>, Array1<f64>)> {
         let field1 = self.data.velocityfield(
             self.grid.clone(),
             &ParticleSelector::default(),
             mode,
             min_velocity,
             max_velocity,
-        );
+        )?;
         let field2 = self.data2.velocityfield(
             self.grid2.clone(),
             &ParticleSelector::default(),
             mode,
             min_velocity,
             max_velocity,
-        );
-        (
+        )?;
+        Ok((
             field1.get_data().iter().copied().collect(),
             field2.get_data().iter().copied().collect(),
-        )
+        ))
     }
 }
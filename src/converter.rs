@@ -4,7 +4,10 @@
 #![allow(unused_imports)]
 use crate::{
     check_signals,
-    converter::convertertools::{make_dataset_builder, make_sortlist, sort_by_array},
+    converter::convertertools::{make_dataset_builder, make_sortlist, sort_by_array, sort_field},
+    datamanager::format::{write_format, FileLayout},
+    datamanager::writer::write_particle_types,
+    datamanager::{count_types, merge_type_counts, Provenance},
     print_debug, print_warning, setup_bar,
 };
 use csv;
@@ -17,6 +20,7 @@ use ndarray::{self, s, Array, Array1, Array2, ArrayBase, Axis, Dim, OwnedRepr};
 use ndarray_csv::Array2Reader;
 use numpy::array;
 use regex::Regex;
+use std::collections::BTreeMap;
 use std::path::Path;
 use vtkio::model::DataSet;
 mod convertertools;
@@ -33,6 +37,9 @@ const BLOSC_COMPRESSION: u8 = 9;
 
 
 /// Convert a single trajectory csv file to Hdf5
+///
+/// `fields` lists additional named fields and the csv columns they are stored in, one
+/// column for scalar and several columns for vector fields.
 // The number of arguments is necessary for proper csv reading.
 #[allow(clippy::too_many_arguments)]
 pub fn csv_converter(
@@ -46,6 +53,7 @@ pub fn csv_converter(
     interpolate: bool,
     radius: f64,
     sampling_steps: usize,
+    fields: Vec<(String, Vec<i64>)>,
 ) {
     if !Path::new(&filename).exists() {
         panic!("CSV file {} does not exist.", &filename);
@@ -53,14 +61,22 @@ pub fn csv_converter(
 
     // TODO: CHeck if we can buffer that for big datafiles!
     let hdf5file = File::create(outname).expect("Unable to create HDF5 file.");
-    // TODO Read this in the readers. check if
-    // the types are correct! 0x1 --> tdata, 0x2 --> pdata
-    hdf5file
-        .new_attr::<u8>()
-        .create("hdf5_up4_type")
-        .unwrap()
-        .write_scalar(&0x2_i32)
-        .unwrap();
+    // Place an identifier for if the data is tdata (0x1) or pdata (0x2) and the schema version
+    write_format(&hdf5file, FileLayout::PData)
+        .expect("Unable to write the up4 format attributes.");
+    Provenance::converter("csv")
+        .add_source_files([filename])
+        .add_setting("columns", format!("{:?}", columns))
+        .add_setting("delimiter", delimiter)
+        .add_setting("header", header)
+        .add_setting("comment", comment)
+        .add_setting("velocity", vel)
+        .add_setting("interpolate", interpolate)
+        .add_setting("radius", radius)
+        .add_setting("sampling steps", sampling_steps)
+        .add_setting("fields", format!("{:?}", fields))
+        .write(&hdf5file)
+        .expect("Unable to write the provenance attributes.");
     #[cfg(feature = "blosc")]
     let threads = blosc_get_nthreads();
     #[cfg(feature = "blosc")]
@@ -80,7 +96,11 @@ pub fn csv_converter(
         .comment(Some(comment.as_bytes()[0]))
         .from_path(filename)
         .expect("Unable to open CSV file.");
-    let data: Array2<f64> = {
+    let field_columns: Vec<i64> = fields
+        .iter()
+        .flat_map(|(_, columns)| columns.clone())
+        .collect();
+    let (data, field_time, field_data): (Array2<f64>, Array1<f64>, Array2<f64>) = {
         let mut data: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> = rdr
             .deserialize_array2_dynamic()
             .expect("Unable to extract CSV data to ndarray! \nYour delimiter might be wrong.\n");
@@ -88,12 +108,21 @@ pub fn csv_converter(
         // slice the read array to only get the colums requested
         if !columns.is_empty() {
             
-            let mut temp_data = Array2::<f64>::from_elem((data.shape()[0], 7), f64::NAN);
+            let mut temp_data = Array2::<f64>::from_elem(
+                (data.shape()[0], 7 + field_columns.len()),
+                f64::NAN,
+            );
             for (i, column) in columns.iter().enumerate() {
                 temp_data
                     .slice_mut(s![.., i])
                     .assign(&data.slice(s![.., *column as usize]));
             }
+            // additional fields are placed behind the 7 position and velocity columns
+            for (i, column) in field_columns.iter().enumerate() {
+                temp_data
+                    .slice_mut(s![.., 7 + i])
+                    .assign(&data.slice(s![.., *column as usize]));
+            }
             data = temp_data;
         } else {
             panic!("No columns selected to extract!");
@@ -107,9 +136,14 @@ pub fn csv_converter(
             let new_data = data;
             data = convertertools::sort_by_column(new_data, 0);
         }
+        // keep the additional fields on the time axis of the raw data, they are
+        // interpolated onto the final time axis when written
+        let mut field_time = data.column(0).to_owned();
+        let field_data = data.slice(s![.., 7..]).to_owned();
         if interpolate {
             let mut t = data.slice_mut(s![.., 0_usize]);
             //set first timestep to 0 by substracting the first timestep from all timesteps
+            field_time -= t[0];
             t -= t[0];
             let max_t = t[t.len() - 1];
             let steps = t.len();
@@ -137,8 +171,7 @@ pub fn csv_converter(
                 );
             }
         }
-        data
-        
+        (data, field_time, field_data)
     };
     print_debug!("Data: {:?}", data);
     print_debug!("Data shape: {:?}", data.shape());
@@ -277,6 +310,32 @@ pub fn csv_converter(
     let pos_array = pos_array.slice(s![ ..pos_array.shape()[0] - total_failcount, ..]);
     let vel_array = vel_array.slice(s![ ..vel_array.shape()[0] - total_failcount, ..]);
     let timesteps = time_array.len();
+    if !fields.is_empty() {
+        let fields_group = group
+            .create_group("fields")
+            .unwrap_or_else(|_| panic!("Can not create group fields in file {}", outname));
+        let mut column = 0;
+        for (name, columns) in fields.iter() {
+            let mut field = Array2::<f64>::zeros((timesteps, columns.len()));
+            for component in 0..columns.len() {
+                field
+                    .column_mut(component)
+                    .assign(&convertertools::interpolate_column(
+                        field_time.view(),
+                        field_data.column(column + component),
+                        time_array,
+                    ));
+            }
+            column += columns.len();
+            let builder = make_dataset_builder!(fields_group);
+            builder
+                .with_data(&field)
+                .create(name.as_str())
+                .unwrap_or_else(|_| {
+                    panic!("Unable to create dataset \"{}\" in file {}", name, filename)
+                });
+        }
+    }
     
     let builder = make_dataset_builder!(group);
     builder
@@ -374,6 +433,8 @@ pub fn csv_converter(
         .with_data(&time_array)
         .create("time array")
         .unwrap();
+    write_particle_types(&hdf5file, &count_types(particle_type_array.iter().take(1).copied()))
+        .expect("Unable to write the particle types.");
 
     bar.finish();
     print_debug!("Finished with conversion from vtk to HDF5 ");
@@ -442,6 +503,7 @@ pub struct XMLVTKConverter {
     type_field_name: Option<String>,
     file_type: VTKType,
     diameter_field_name: Option<String>,
+    extra_field_names: Vec<String>,
 }
 
 impl XMLVTKConverter {
@@ -487,6 +549,12 @@ impl XMLVTKConverter {
         }
     }
 
+    /// Store an additional scalar or vector point field under its name
+    pub fn add_extra_field(mut self, name: &str) -> Self {
+        self.extra_field_names.push(name.to_string());
+        self
+    }
+
     /// Convert a folder with modern, xml-based vtk files into a HDF5 file
     ///
     ///
@@ -595,13 +663,25 @@ impl XMLVTKConverter {
 
         let re = Regex::new(filter).expect("Unable to create Regex filter.");
         let output_file = File::create(outname).expect("Unable to create HDF5 file.");
-        // Place an identifier for if the data is tdata (0x1) or pdata (0x2)
-        output_file
-            .new_attr::<u8>()
-            .create("hdf5_up4_type")
-            .unwrap()
-            .write_scalar(&0x1_i32)
-            .unwrap();
+        // Place an identifier for if the data is tdata (0x1) or pdata (0x2) and the schema version
+        write_format(&output_file, FileLayout::TData)
+            .expect("Unable to write the up4 format attributes.");
+        vtk_provenance(
+            "xml vtk",
+            &filenames,
+            timestep,
+            filter,
+            &[
+                ("velocity field", &self.velocity_field_name),
+                ("id field", &self.id_field_name),
+                ("radius field", &self.radius_field_name),
+                ("type field", &self.type_field_name),
+                ("diameter field", &self.diameter_field_name),
+            ],
+            &self.extra_field_names,
+        )
+        .write(&output_file)
+        .expect("Unable to write the provenance attributes.");
         let progress_bar = setup_bar!("Vtk Read Data", filenames.len() as u64);
         // Find and set the attributes
         let mut dimensions: Array2<f64> = Array2::<f64>::zeros((2, 3)); // [min:[x,y,z],max:[x,y,z]]
@@ -622,6 +702,7 @@ impl XMLVTKConverter {
         velocity_mag[0] = f64::MAX;
         velocity_mag[2] = f64::MIN;
         let mut mean_counter: usize = 0;
+        let mut particle_types = BTreeMap::new();
         for (file_id, filename) in filenames.iter().enumerate() {
             print_debug!("Creating group: {timestep}");
             let group = output_file
@@ -713,6 +794,10 @@ impl XMLVTKConverter {
                 print_warning!("No type field specified, defaulting to 1");
                 Array1::<u64>::ones(particle_radius.len()).into_raw_vec()
             };
+            merge_type_counts(
+                &mut particle_types,
+                &count_types(particle_type.iter().map(|&ptype| ptype as f64)),
+            );
             let builder = make_dataset_builder!(group);
             builder
                 .with_data(&particle_type)
@@ -785,6 +870,25 @@ impl XMLVTKConverter {
                 .unwrap_or_else(|_| {
                     panic!("Unable to create dataset \"position\" in file {}", filename)
                 });
+            if !self.extra_field_names.is_empty() {
+                print_debug!("  Creating extra field datasets");
+                let fields = group.create_group("fields").unwrap_or_else(|_| {
+                    panic!("Can not create group fields in timestep {}", file_id)
+                });
+                for field_name in self.extra_field_names.iter() {
+                    let field = sort_field(xmltools::get_field::<f64>(filename, field_name, &self.file_type), &sort_list);
+                    let builder = make_dataset_builder!(fields);
+                    builder
+                        .with_data(&field)
+                        .create(field_name.as_str())
+                        .unwrap_or_else(|_| {
+                            panic!(
+                                "Unable to create dataset \"{}\" in file {}",
+                                field_name, filename
+                            )
+                        });
+                }
+            }
             mean_counter += particle_ids.len();
             sample_rate = current_time - old_time;
             old_time = current_time;
@@ -850,6 +954,8 @@ impl XMLVTKConverter {
             .with_data(&time_array)
             .create("time array")
             .unwrap();
+        write_particle_types(&output_file, &particle_types)
+            .expect("Unable to write the particle types.");
 
         progress_bar.finish();
         print_debug!("Finished with conversion from vtk to HDF5 ");
@@ -873,6 +979,7 @@ pub struct LegacyVTKConverter {
     radius_field_name: Option<String>,
     type_field_name: Option<String>,
     diameter_field_name: Option<String>,
+    extra_field_names: Vec<String>,
 }
 
 impl LegacyVTKConverter {
@@ -917,6 +1024,12 @@ impl LegacyVTKConverter {
         }
     }
 
+    /// Store an additional scalar or vector point field under its name
+    pub fn add_extra_field(mut self, name: &str) -> Self {
+        self.extra_field_names.push(name.to_string());
+        self
+    }
+
     /// Convert a folder with legacy vtk files into a HDF5 file
     ///
     ///
@@ -1021,13 +1134,25 @@ impl LegacyVTKConverter {
         }
         let re = Regex::new(filter).expect("Unable to create Regex filter.");
         let output_file = File::create(outname).expect("Unable to create HDF5 file.");
-        // Place an identifier for if the data is tdata (0x1) or pdata (0x2)
-        output_file
-            .new_attr::<u8>()
-            .create("hdf5_up4_type")
-            .unwrap()
-            .write_scalar(&0x1_i32)
-            .unwrap();
+        // Place an identifier for if the data is tdata (0x1) or pdata (0x2) and the schema version
+        write_format(&output_file, FileLayout::TData)
+            .expect("Unable to write the up4 format attributes.");
+        vtk_provenance(
+            "legacy vtk",
+            &filenames,
+            timestep,
+            filter,
+            &[
+                ("velocity field", &self.velocity_field_name),
+                ("id field", &self.id_field_name),
+                ("radius field", &self.radius_field_name),
+                ("type field", &self.type_field_name),
+                ("diameter field", &self.diameter_field_name),
+            ],
+            &self.extra_field_names,
+        )
+        .write(&output_file)
+        .expect("Unable to write the provenance attributes.");
         let progress_bar = setup_bar!("Vtk Read Data", filenames.len() as u64);
         // Find and set the attributes
         let mut dimensions: Array2<f64> = Array2::<f64>::zeros((2, 3)); // [min:[x,y,z],max:[x,y,z]]
@@ -1048,6 +1173,7 @@ impl LegacyVTKConverter {
         velocity_mag[0] = f64::MAX;
         velocity_mag[2] = f64::MIN;
         let mut mean_counter: usize = 0;
+        let mut particle_types = BTreeMap::new();
         for (file_id, filename) in filenames.iter().enumerate() {
             print_debug!("Creating group: {timestep}");
             let group = output_file
@@ -1139,6 +1265,10 @@ impl LegacyVTKConverter {
                 print_warning!("No type field specified, defaulting to 1");
                 Array1::<u64>::ones(particle_radius.len()).into_raw_vec()
             };
+            merge_type_counts(
+                &mut particle_types,
+                &count_types(particle_type.iter().map(|&ptype| ptype as f64)),
+            );
             let builder = make_dataset_builder!(group);
             builder
                 .with_data(&particle_type)
@@ -1209,6 +1339,25 @@ impl LegacyVTKConverter {
                 .unwrap_or_else(|_| {
                     panic!("Unable to create dataset \"position\" in file {}", filename)
                 });
+            if !self.extra_field_names.is_empty() {
+                print_debug!("  Creating extra field datasets");
+                let fields = group.create_group("fields").unwrap_or_else(|_| {
+                    panic!("Can not create group fields in timestep {}", file_id)
+                });
+                for field_name in self.extra_field_names.iter() {
+                    let field = sort_field(legacytools::get_field::<f64>(filename, field_name), &sort_list);
+                    let builder = make_dataset_builder!(fields);
+                    builder
+                        .with_data(&field)
+                        .create(field_name.as_str())
+                        .unwrap_or_else(|_| {
+                            panic!(
+                                "Unable to create dataset \"{}\" in file {}",
+                                field_name, filename
+                            )
+                        });
+                }
+            }
             mean_counter += particle_ids.len();
             sample_rate = current_time - old_time;
             old_time = current_time;
@@ -1274,8 +1423,37 @@ impl LegacyVTKConverter {
             .with_data(&time_array)
             .create("time array")
             .unwrap();
+        write_particle_types(&output_file, &particle_types)
+            .expect("Unable to write the particle types.");
 
         progress_bar.finish();
         print_debug!("Finished with conversion from vtk to HDF5 ");
     }
 }
+
+/// Provenance of a VTK conversion: the converted files, the time between them, the
+/// filter extracting the timestep from the filenames and the names of all fields read
+fn vtk_provenance(
+    converter: &str,
+    filenames: &[&str],
+    timestep: f64,
+    filter: &str,
+    fields: &[(&str, &Option<String>)],
+    extra_fields: &[String],
+) -> Provenance {
+    let provenance = Provenance::converter(converter)
+        .add_source_files(filenames)
+        .add_setting("timestep", timestep)
+        .add_setting("filter", filter);
+    let provenance = fields
+        .iter()
+        .filter_map(|(setting, field)| field.as_ref().map(|field| (setting, field)))
+        .fold(provenance, |provenance, (setting, field)| {
+            provenance.add_setting(setting, field)
+        });
+    if extra_fields.is_empty() {
+        provenance
+    } else {
+        provenance.add_setting("extra fields", extra_fields.join(","))
+    }
+}
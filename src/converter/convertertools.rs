@@ -211,6 +211,34 @@ pub fn velocity_polynom(
     new_data
 }
 
+/// Linearly interpolate `values` given at `time` onto `new_time`. Both time axes must be
+/// sorted, times outside of `time` result in NaN.
+pub fn interpolate_column(
+    time: ArrayView1<f64>,
+    values: ArrayView1<f64>,
+    new_time: ArrayView1<f64>,
+) -> ndarray::Array1<f64> {
+    let mut index = 0;
+    new_time.mapv(|current_time| {
+        if time.is_empty() || current_time < time[0] || current_time > time[time.len() - 1] {
+            return f64::NAN;
+        }
+        while index + 1 < time.len() && time[index + 1] < current_time {
+            index += 1;
+        }
+        if index + 1 == time.len() || time[index] == time[index + 1] {
+            return values[index];
+        }
+        interpolate_step(
+            time[index],
+            time[index + 1],
+            current_time,
+            values[index],
+            values[index + 1],
+        )
+    })
+}
+
 fn interpolate_step(
     time_old: f64,
     time_new: f64,
@@ -245,6 +273,22 @@ where
     array_sorted
 }
 
+/// Sort a flattened per-particle field by `idx` and return it with the shape
+/// (particles, components). The number of components is derived from the length.
+pub fn sort_field(data: Vec<f64>, idx: &[usize]) -> ndarray::Array2<f64> {
+    let components = if idx.is_empty() { 1 } else { data.len() / idx.len() };
+    if data.len() != components * idx.len() {
+        panic!(
+            "Field with {} values can not be split into {} particles",
+            data.len(),
+            idx.len()
+        );
+    }
+    ndarray::Array2::from_shape_fn((idx.len(), components), |(particle, component)| {
+        data[components * idx[particle] + component]
+    })
+}
+
 pub fn make_sortlist(particle_ids: &[u64]) -> Vec<usize> {
     // make vec with length of particle_ids
     // thanks to that stackoverflow....
@@ -1,5 +1,8 @@
 use crate::{
     check_signals,
+    datamanager::format::{write_format, FileLayout},
+    datamanager::writer::write_particle_types,
+    datamanager::{count_types, merge_type_counts, Provenance},
     converter::convertertools::{make_sortlist, sort_by_array, sort_by_column},
     print_debug, print_warning, setup_bar,
 };
@@ -10,7 +13,7 @@ use itertools::Itertools;
 use ndarray;
 use ndarray_csv::Array2Reader;
 use regex::Regex;
-use std::{path::Path, process::id};
+use std::{collections::BTreeMap, path::Path, process::id};
 
 use crate::converter::convertertools;
 // Maximum amount of failiures in a row available for a process
@@ -38,14 +41,21 @@ pub fn csv_multi_file_time_step(
     }
     // TODO: CHeck if we can buffer that for big datafiles!
     let hdf5file = File::create(outname).expect("Unable to create HDF5 file.");
-    // TODO Read this in the readers. check weather
-    // the types are correct! 0x1 --> tdata, 0x2 --> pdata
-    hdf5file
-        .new_attr::<u8>()
-        .create("hdf5_up4_type")
-        .unwrap()
-        .write_scalar(&0x1_i32)
-        .unwrap();
+    // Place an identifier for if the data is tdata (0x1) or pdata (0x2) and the schema version
+    write_format(&hdf5file, FileLayout::TData)
+        .expect("Unable to write the up4 format attributes.");
+    Provenance::converter("csv multi file")
+        .add_source_files(&filenames)
+        .add_setting("columns", format!("{:?}", columns))
+        .add_setting("delimiter", delimiter)
+        .add_setting("header", header)
+        .add_setting("comment", comment)
+        .add_setting("velocity", vel)
+        .add_setting("interpolate", interpolate)
+        .add_setting("radius", radius)
+        .add_setting("times", format!("{:?}", time_))
+        .write(&hdf5file)
+        .expect("Unable to write the provenance attributes.");
 
     let bar = setup_bar!("Vtk Read Data", filenames.len() as u64);
     // Attributes
@@ -68,6 +78,7 @@ pub fn csv_multi_file_time_step(
     velocity_mag[0] = f64::MAX;
     velocity_mag[2] = f64::MIN;
     let mut mean_counter: usize = 0;
+    let mut particle_types = BTreeMap::new();
     // for each file
     for (id, filename) in filenames.iter().enumerate() {
         let group = hdf5file
@@ -134,6 +145,7 @@ pub fn csv_multi_file_time_step(
             .with_data(&zero_array)
             .create("ppcloud")
             .unwrap_or_else(|_| panic!("Unable to create dataset \"radius\" in file {}", filename));
+        merge_type_counts(&mut particle_types, &count_types(zero_array.iter().copied()));
         let builder = group.new_dataset_builder();
         builder
             .with_data(&zero_array)
@@ -261,6 +273,7 @@ pub fn csv_multi_file_time_step(
         .with_data(&time_array)
         .create("time array")
         .unwrap();
+    write_particle_types(&hdf5file, &particle_types).expect("Unable to write the particle types.");
 
     print_debug!("Finished with conversion from vtk to HDF5 ");
     bar.finish()
@@ -1,5 +1,8 @@
 use crate::{
     check_signals,
+    datamanager::format::{write_format, FileLayout},
+    datamanager::writer::write_particle_types,
+    datamanager::{count_types, Provenance},
     converter::convertertools::{make_sortlist, sort_by_array, sort_by_column},
     print_debug, print_warning, setup_bar,
 };
@@ -36,14 +39,20 @@ pub fn csv_multi_idline(
     }
     // TODO: CHeck if we can buffer that for big datafiles!
     let hdf5file = File::create(outname).expect("Unable to create HDF5 file.");
-    // TODO Read this in the readers. check weather
-    // the types are correct! 0x1 --> tdata, 0x2 --> pdata
-    hdf5file
-        .new_attr::<u8>()
-        .create("hdf5_up4_type")
-        .unwrap()
-        .write_scalar(&0x2_i32)
-        .unwrap();
+    // Place an identifier for if the data is tdata (0x1) or pdata (0x2) and the schema version
+    write_format(&hdf5file, FileLayout::PData)
+        .expect("Unable to write the up4 format attributes.");
+    Provenance::converter("csv id_line")
+        .add_source_files([filename])
+        .add_setting("columns", format!("{:?}", columns))
+        .add_setting("delimiter", delimiter)
+        .add_setting("header", header)
+        .add_setting("comment", comment)
+        .add_setting("velocity", vel)
+        .add_setting("interpolate", interpolate)
+        .add_setting("radius", radius)
+        .write(&hdf5file)
+        .expect("Unable to write the provenance attributes.");
 
     // Read csv data
     let mut rdr = csv::ReaderBuilder::new()
@@ -145,6 +154,8 @@ pub fn csv_multi_idline(
     let mut time: ndarray::Array1<f64> = ndarray::Array1::<f64>::zeros(2);
     let mut sample_rate: f64 = 0.0;
     let mut time_array = ndarray::Array1::<f64>::zeros(100); // random value as it will be overwritten anyways
+    // the type of a particle does not change, so its first entry is counted
+    let mut first_types = Vec::new();
     bar.inc(10);
     for (p_id, data) in particle_data.iter().enumerate() {
         // progress bar
@@ -161,6 +172,7 @@ pub fn csv_multi_idline(
         let particle_radius_array = ndarray::Array1::from_elem(data_length, radius);
         let ppclouds_array = ndarray::Array1::<f64>::ones(data_length);
         let particle_type_array = ndarray::Array1::<f64>::zeros(data_length);
+        first_types.extend(particle_type_array.first().copied());
         let mut vel_array = ndarray::Array2::<f64>::zeros((data_length, 3));
         let mut pos_array = ndarray::Array2::<f64>::zeros((data_length, 3));
         let mut failcount = 0;
@@ -360,6 +372,8 @@ pub fn csv_multi_idline(
         .with_data(&time_array)
         .create("time array")
         .unwrap();
+    write_particle_types(&hdf5file, &count_types(first_types))
+        .expect("Unable to write the particle types.");
     bar.finish()
 }
 
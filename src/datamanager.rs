@@ -5,48 +5,86 @@ use super::functions::conditional::Conditional;
 use super::functions::extractions::Extraction;
 use super::functions::mixing::Mixing;
 use super::functions::Granular;
-use crate::{print_warning, types::*};
+use crate::error::{Result, Up4Error};
+use crate::types::*;
 use derive_getters::Getters;
 use ndarray::prelude::*;
+use std::collections::{BTreeMap, HashMap};
 
 pub mod pdata;
 pub use pdata::PData;
 pub mod tdata;
 pub use tdata::TData;
+pub mod mdata;
+pub use mdata::MData;
+pub mod cache;
+pub use cache::DEFAULT_CACHE_MEMORY;
+pub mod concat;
+pub use concat::ConcatData;
+pub mod format;
+pub use format::{FileLayout, FORMAT_VERSION};
+pub mod time;
+pub use time::TimeLookup;
+pub mod transform;
+pub use transform::Transform;
+pub mod writer;
+pub use writer::write_hdf5;
+pub mod inspect;
+pub use inspect::{inspect, InspectOptions, InspectionReport};
+pub mod provenance;
+pub use provenance::Provenance;
 mod utilities;
+pub(crate) use utilities::{count_types, merge_type_counts};
 /// Defines main access functions for post processing functions.
 pub trait DataManager {
     /// return the pointer to `Timestep` at the step `timestep`
-    fn get_timestep(&mut self, timestep: usize) -> &Timestep;
+    fn get_timestep(&mut self, timestep: usize) -> Result<&Timestep>;
 
     /// Return `GlobalStats` for global system information
     fn global_stats(&self) -> GlobalStats;
 
+    /// Return the number of particles of each type, the largest number found in one
+    /// timestep. Files that do not list the particle types are counted on first use.
+    fn particle_types(&mut self) -> Result<BTreeMap<usize, usize>>;
+
     /// Development function
     fn stats(&self);
 
     /// return a timetsep that is not in the buffer
-    fn get_timestep_unbuffered(&self, timestep: usize) -> Timestep;
-
-    /// setup a new buffer
-    fn setup_buffer(&mut self, external_buffer_id: usize);
+    fn get_timestep_unbuffered(&self, timestep: usize) -> Result<Timestep>;
 
-    /// read from other buffer then the main one
-    fn get_timestep_buffer(&mut self, timestep: usize, buffer: usize) -> &Timestep;
+    /// Set the memory in bytes used to keep timesteps read from the file. Chunks of
+    /// timesteps that were used least recently are dropped first.
+    fn set_cache_memory(&mut self, cache_memory: usize);
 
     /// Return a string containung information about the dataset
-    fn info(&self) -> Result<String, &'static str>;
+    fn info(&self) -> Result<String>;
 
-    /// set rotation angle
-    fn set_rotation_angle(&mut self, angle: f64, axis: f64);
+    /// Set the transform applied to positions and velocities of every timestep read
+    /// afterwards. It replaces the previous transform and is reflected in `global_stats`.
+    fn set_transform(&mut self, transform: Transform) -> Result<()>;
 
-    /// set rotation anker, the point around which the rotation is performed
-    /// default is zero
-    fn set_rotation_anker(&mut self, point: [f64; 3]);
+    /// Return the transform currently applied to the data
+    fn transform(&self) -> Transform;
 }
 
 pub trait Manager: DataManager + Granular + Extraction + Mixing + Conditional {}
 
+/// Open an up4 HDF5 file with the manager matching its `hdf5_up4_type` attribute, reading
+/// `buffersize` timesteps at once.
+pub fn open(filename: &str, buffersize: usize) -> Result<Box<dyn Manager + Send>> {
+    let file = hdf5::File::open(filename).map_err(|source| Up4Error::FileOpen {
+        filename: filename.to_string(),
+        source,
+    })?;
+    let layout = FileLayout::of_file(&file)?;
+    file.close()?;
+    Ok(match layout {
+        FileLayout::TData => Box::new(TData::with_buffersize(filename, buffersize)?),
+        FileLayout::PData => Box::new(PData::with_buffersize(filename, buffersize)?),
+    })
+}
+
 /// Data-struct containing all necessery information for a timestep
 #[derive(Debug, Default, Getters, Clone)]
 pub struct Timestep {
@@ -58,10 +96,43 @@ pub struct Timestep {
     clouds: Array1<f64>,
     density: Array1<f64>,
     ptype: Array1<f64>, // type of particles
+    /// additional named per-particle fields, scalars are stored with a single column
+    #[getter(skip)]
+    fields: BTreeMap<String, Array2<f64>>,
+}
+
+impl Timestep {
+    /// Return the additional per-particle field `name` with the shape (N, components)
+    pub fn field(&self, name: &str) -> Option<&Array2<f64>> {
+        self.fields.get(name)
+    }
+
+    /// Return the names of all additional per-particle fields
+    pub fn field_names(&self) -> Vec<&str> {
+        self.fields.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// Return a map from particle ID to the index of the particle in this timestep.
+    /// Particles can be inserted, deleted or reordered between timesteps, so particles of
+    /// two timesteps must be matched by ID and not by index.
+    pub fn id_map(&self) -> HashMap<usize, usize> {
+        self.particleid
+            .iter()
+            .enumerate()
+            .map(|(index, &id)| (id as usize, index))
+            .collect()
+    }
+
+    /// Return the index of the particle with the ID `particleid` in this timestep
+    pub fn index_of(&self, particleid: usize) -> Option<usize> {
+        self.particleid
+            .iter()
+            .position(|&id| id as usize == particleid)
+    }
 }
 
 /// Data-struct containing overall stats for a dataset. (e.g system dimensions)
-#[derive(Debug, Default, Getters)]
+#[derive(Debug, Default, Getters, Clone)]
 pub struct GlobalStats {
     dimensions: Array2<f64>,
     nparticles: usize,
@@ -73,35 +144,9 @@ pub struct GlobalStats {
     velocity: Array2<f64>,
     velocity_mag: Array1<f64>,
     time_array: Array1<f64>,
-}
-
-impl GlobalStats {
-    /// Returns the timestep at this time in the dataset
-    pub fn timestep_at_seconds_closest(&self, seconds: f64) -> Result<usize, &'static str> {
-        print_warning!("This function is not tested yet!");
-        // finding index in ordered list of self.time_array
-        // using the binary search algorithm
-        let mut low = 0;
-        let mut high = self.time_array.len() - 1;
-
-        if self.time_array[high] < seconds {
-            return Err("Timestep is greater than the number of timesteps");
-        }
-        if self.time_array[low] > seconds {
-            return Err("Timestep is smaller than the number of timesteps");
-        }
-        let mut mid = (low + high) / 2;
-        while low <= high {
-            mid = (low + high) / 2;
-            if (self.time_array[mid] - seconds).abs() < 0.01 {
-                return Ok(mid);
-            } else if self.time_array[mid] < seconds {
-                low = mid + 1;
-            } else {
-                high = mid - 1;
-            }
-        }
-
-        Ok(mid)
-    }
+    /// number of particles of each type, the largest number found in one timestep. Empty
+    /// until counted if the file does not list them, see `DataManager::particle_types`
+    particle_types: BTreeMap<usize, usize>,
+    /// units and origin of the dataset
+    provenance: Provenance,
 }
@@ -0,0 +1,184 @@
+//! Chunked LRU cache of timesteps
+//!
+//! Functions access timesteps in different patterns: forward loops, several lagged
+//! timesteps at once or random access. File based managers keep chunks of consecutive
+//! timesteps in a `TimestepCache` and only evict the least recently used chunks once the
+//! memory budget is exceeded, so lagged access does not read the same chunk again and
+//! again.
+
+use super::Timestep;
+use std::collections::{HashMap, VecDeque};
+
+/// Memory used for cached timesteps if no other budget is given, 2 GiB
+pub const DEFAULT_CACHE_MEMORY: usize = 2 << 30;
+
+/// Chunks of consecutive timesteps, the chunk holding timestep `t` starts at
+/// `t / chunksize * chunksize`
+pub struct TimestepCache {
+    chunksize: usize,
+    memory_budget: usize,
+    chunks: HashMap<usize, Vec<Timestep>>,
+    /// estimated memory of each cached chunk in bytes
+    chunk_memory: HashMap<usize, usize>,
+    /// chunk ids from least to most recently used
+    usage: VecDeque<usize>,
+    memory: usize,
+}
+
+impl TimestepCache {
+    /// Create an empty cache of chunks with `chunksize` timesteps that keeps at most
+    /// `memory_budget` bytes. The most recently used chunk is always kept, even if it is
+    /// larger than the budget.
+    pub fn new(chunksize: usize, memory_budget: usize) -> Self {
+        TimestepCache {
+            chunksize: chunksize.max(1),
+            memory_budget,
+            chunks: HashMap::new(),
+            chunk_memory: HashMap::new(),
+            usage: VecDeque::new(),
+            memory: 0,
+        }
+    }
+
+    /// Number of timesteps read at once
+    pub fn chunksize(&self) -> usize {
+        self.chunksize
+    }
+
+    /// Estimated memory of all cached timesteps in bytes
+    pub fn memory(&self) -> usize {
+        self.memory
+    }
+
+    /// Change the memory budget and evict chunks until it is met
+    pub fn set_memory_budget(&mut self, memory_budget: usize) {
+        self.memory_budget = memory_budget;
+        self.evict();
+    }
+
+    /// Remove all cached timesteps, e.g. after the way timesteps are read changed
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.chunk_memory.clear();
+        self.usage.clear();
+        self.memory = 0;
+    }
+
+    /// Return true if the chunk holding `timestep` is cached
+    pub fn contains(&self, timestep: usize) -> bool {
+        self.chunks.contains_key(&(timestep / self.chunksize))
+    }
+
+    /// Range of the chunk holding `timestep` in a dataset with `timesteps` timesteps
+    pub fn chunk_range(&self, timestep: usize, timesteps: usize) -> (usize, usize) {
+        let start = timestep / self.chunksize * self.chunksize;
+        (start, (start + self.chunksize).min(timesteps))
+    }
+
+    /// Add the timesteps of the chunk starting at `start`, which must be the start of a
+    /// range returned by `chunk_range`
+    pub fn insert(&mut self, start: usize, timesteps: Vec<Timestep>) {
+        let chunk = start / self.chunksize;
+        let memory = timesteps.iter().map(memory_size).sum();
+        self.chunks.insert(chunk, timesteps);
+        if let Some(old) = self.chunk_memory.insert(chunk, memory) {
+            self.memory -= old;
+        }
+        self.memory += memory;
+        self.touch(chunk);
+        self.evict();
+    }
+
+    /// Return a cached timestep and mark its chunk as most recently used
+    pub fn get(&mut self, timestep: usize) -> Option<&Timestep> {
+        let chunk = timestep / self.chunksize;
+        if !self.chunks.contains_key(&chunk) {
+            return None;
+        }
+        self.touch(chunk);
+        self.chunks[&chunk].get(timestep - chunk * self.chunksize)
+    }
+
+    fn touch(&mut self, chunk: usize) {
+        if self.usage.back() == Some(&chunk) {
+            return;
+        }
+        if let Some(position) = self.usage.iter().position(|&used| used == chunk) {
+            self.usage.remove(position);
+        }
+        self.usage.push_back(chunk);
+    }
+
+    /// Drop the least recently used chunks until the budget is met, keeping the most
+    /// recently used one
+    fn evict(&mut self) {
+        while self.memory > self.memory_budget && self.usage.len() > 1 {
+            if let Some(chunk) = self.usage.pop_front() {
+                self.chunks.remove(&chunk);
+                self.memory -= self.chunk_memory.remove(&chunk).unwrap_or(0);
+            }
+        }
+    }
+}
+
+/// Estimated memory of the arrays in `timestep` in bytes
+fn memory_size(timestep: &Timestep) -> usize {
+    let values = timestep.position.len() * 3
+        + timestep.velocity.len()
+        + timestep.radius.len()
+        + timestep.particleid.len()
+        + timestep.clouds.len()
+        + timestep.density.len()
+        + timestep.ptype.len()
+        + timestep.fields.values().map(|field| field.len()).sum::<usize>();
+    values * std::mem::size_of::<f64>() + std::mem::size_of::<Timestep>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(range: (usize, usize)) -> Vec<Timestep> {
+        (range.0..range.1)
+            .map(|timestep| Timestep {
+                time: timestep as f64,
+                ..Timestep::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lookup() {
+        let mut cache = TimestepCache::new(4, DEFAULT_CACHE_MEMORY);
+        assert_eq!(cache.chunk_range(9, 10), (8, 10));
+        assert!(!cache.contains(9));
+        cache.insert(8, chunk((8, 10)));
+        assert!(cache.contains(9));
+        assert_eq!(*cache.get(9).unwrap().time(), 9.0);
+        assert!(cache.get(3).is_none());
+    }
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let chunk_memory = 4 * memory_size(&Timestep::default());
+        let mut cache = TimestepCache::new(4, 2 * chunk_memory);
+        cache.insert(0, chunk((0, 4)));
+        cache.insert(4, chunk((4, 8)));
+        // use the first chunk again, so the second one is the least recently used
+        cache.get(1);
+        cache.insert(8, chunk((8, 12)));
+        assert!(cache.contains(1));
+        assert!(!cache.contains(5));
+        assert!(cache.contains(9));
+        assert_eq!(cache.memory(), 2 * chunk_memory);
+    }
+
+    #[test]
+    fn newest_chunk_is_kept() {
+        let mut cache = TimestepCache::new(4, 0);
+        cache.insert(0, chunk((0, 4)));
+        cache.insert(4, chunk((4, 8)));
+        assert!(!cache.contains(0));
+        assert!(cache.contains(4));
+    }
+}
@@ -0,0 +1,402 @@
+//! Chain several datasets into one timeline
+//!
+//! Restarted simulations and PEPT experiments recorded in chunks produce several files.
+//! `ConcatData` presents them as one dataset: the timesteps of all parts follow each other
+//! and the time of every part can be shifted so that the timeline keeps increasing.
+
+use super::utilities::merge_type_counts;
+use super::{open, DataManager, GlobalStats, Manager, Timestep, Transform};
+use crate::error::{Result, Up4Error};
+use crate::print_debug;
+use ndarray::prelude::*;
+use std::collections::BTreeMap;
+
+/// Several datasets chained in the order they were given
+pub struct ConcatData {
+    parts: Vec<Box<dyn Manager + Send>>,
+    /// global index of the first timestep of each part
+    first_timestep: Vec<usize>,
+    /// time added to every timestep of each part
+    time_offsets: Vec<f64>,
+    /// copy of the last requested timestep with the shifted time
+    timestep: Timestep,
+    global_stats_: GlobalStats,
+    /// transform applied to the timesteps of all parts
+    transform: Transform,
+}
+
+impl ConcatData {
+    /// Chain `parts` into one timeline. A part that does not start after the end of the
+    /// previous part, e.g. a restart that resets the time to zero, is shifted to start one
+    /// sample after it. Parts that already continue the timeline are left untouched.
+    pub fn new(parts: Vec<Box<dyn Manager + Send>>) -> Result<Self> {
+        let mut time_offsets = Vec::with_capacity(parts.len());
+        let mut end_time = f64::NEG_INFINITY;
+        for part in parts.iter() {
+            let stats = part.global_stats();
+            let offset = if stats.min_time > end_time {
+                0.0
+            } else {
+                end_time - stats.min_time + sample_step(&stats)
+            };
+            end_time = stats.max_time + offset;
+            time_offsets.push(offset);
+        }
+        ConcatData::with_time_offsets(parts, time_offsets)
+    }
+
+    /// Chain `parts` into one timeline and add `time_offsets[i]` to the time of part `i`
+    pub fn with_time_offsets(
+        parts: Vec<Box<dyn Manager + Send>>,
+        time_offsets: Vec<f64>,
+    ) -> Result<Self> {
+        if parts.is_empty() {
+            return Err(Up4Error::InvalidArgument(
+                "At least one dataset is needed for concatenation".to_string(),
+            ));
+        }
+        if parts.len() != time_offsets.len() {
+            return Err(Up4Error::InvalidArgument(format!(
+                "{} time offsets were given for {} datasets",
+                time_offsets.len(),
+                parts.len()
+            )));
+        }
+        let stats: Vec<GlobalStats> = parts.iter().map(|part| part.global_stats()).collect();
+        for id in 1..stats.len() {
+            let end = stats[id - 1].max_time + time_offsets[id - 1];
+            let start = stats[id].min_time + time_offsets[id];
+            if start <= end {
+                return Err(Up4Error::InvalidArgument(format!(
+                    "Dataset {} starts at {} which is not after the end of dataset {} at {}",
+                    id,
+                    start,
+                    id - 1,
+                    end
+                )));
+            }
+        }
+        let mut first_timestep = Vec::with_capacity(stats.len());
+        let mut timesteps = 0;
+        for part_stats in stats.iter() {
+            first_timestep.push(timesteps);
+            timesteps += part_stats.ntimesteps;
+        }
+        let global_stats_ = merge_stats(&stats, &time_offsets);
+        print_debug!(
+            "ConcatData: Chained {} datasets with {} timesteps",
+            parts.len(),
+            timesteps
+        );
+        Ok(ConcatData {
+            parts,
+            first_timestep,
+            time_offsets,
+            timestep: Timestep::default(),
+            global_stats_,
+            transform: Transform::identity(),
+        })
+    }
+
+    /// Open all `filenames` with the matching manager and chain them with `ConcatData::new`
+    pub fn from_files(filenames: &[&str], buffersize: usize) -> Result<Self> {
+        let parts = filenames
+            .iter()
+            .map(|filename| open(filename, buffersize))
+            .collect::<Result<Vec<_>>>()?;
+        ConcatData::new(parts)
+    }
+
+    /// Time added to each part
+    pub fn time_offsets(&self) -> &[f64] {
+        &self.time_offsets
+    }
+
+    /// Move `timestep` of `part` into the common timeline and frame
+    fn shifted(&self, mut timestep: Timestep, part: usize) -> Timestep {
+        timestep.time += self.time_offsets[part];
+        self.transform.apply(&mut timestep);
+        timestep
+    }
+
+    /// Return the part holding the global `timestep` and the timestep within that part
+    fn locate(&self, timestep: usize) -> Result<(usize, usize)> {
+        if timestep >= self.global_stats_.ntimesteps {
+            return Err(Up4Error::TimestepOutOfRange {
+                timestep,
+                timesteps: self.global_stats_.ntimesteps,
+            });
+        }
+        // the first part with a first timestep above `timestep` follows the wanted part
+        let part = self
+            .first_timestep
+            .partition_point(|&first| first <= timestep)
+            - 1;
+        Ok((part, timestep - self.first_timestep[part]))
+    }
+}
+
+/// Time between two samples of a part, used as the gap between chained parts
+fn sample_step(stats: &GlobalStats) -> f64 {
+    if stats.sample_rate > 0.0 {
+        stats.sample_rate
+    } else if stats.ntimesteps > 1 && stats.max_time > stats.min_time {
+        (stats.max_time - stats.min_time) / (stats.ntimesteps - 1) as f64
+    } else {
+        1.0
+    }
+}
+
+/// Merge the stats of all parts: the extrema of the dimensions and velocities are taken
+/// over all parts, the means are weighted by the number of timesteps.
+fn merge_stats(stats: &[GlobalStats], time_offsets: &[f64]) -> GlobalStats {
+    let mut dimensions = stats[0].dimensions.clone();
+    let mut velocity = stats[0].velocity.clone();
+    let mut velocity_mag = stats[0].velocity_mag.clone();
+    velocity.column_mut(1).fill(0.0);
+    velocity_mag[1] = 0.0;
+    let mut sample_rate = 0.0;
+    let mut time_array = Vec::new();
+    let ntimesteps: usize = stats.iter().map(|part| part.ntimesteps).sum();
+    let mut particle_types = BTreeMap::new();
+    let mut provenance = stats[0].provenance.clone();
+    for (index, part) in stats.iter().enumerate() {
+        merge_type_counts(&mut particle_types, &part.particle_types);
+        if index > 0 {
+            provenance.merge(&part.provenance);
+        }
+    }
+    for (part, offset) in stats.iter().zip(time_offsets.iter()) {
+        let weight = part.ntimesteps as f64 / ntimesteps.max(1) as f64;
+        for dim in 0..3 {
+            dimensions[[0, dim]] = dimensions[[0, dim]].min(part.dimensions[[0, dim]]);
+            dimensions[[1, dim]] = dimensions[[1, dim]].max(part.dimensions[[1, dim]]);
+            velocity[[dim, 0]] = velocity[[dim, 0]].min(part.velocity[[dim, 0]]);
+            velocity[[dim, 1]] += weight * part.velocity[[dim, 1]];
+            velocity[[dim, 2]] = velocity[[dim, 2]].max(part.velocity[[dim, 2]]);
+        }
+        velocity_mag[0] = velocity_mag[0].min(part.velocity_mag[0]);
+        velocity_mag[1] += weight * part.velocity_mag[1];
+        velocity_mag[2] = velocity_mag[2].max(part.velocity_mag[2]);
+        sample_rate += weight * part.sample_rate;
+        time_array.extend(part.time_array.iter().map(|time| time + offset));
+    }
+    let last = stats.len() - 1;
+    GlobalStats {
+        dimensions,
+        nparticles: stats.iter().map(|part| part.nparticles).max().unwrap_or(0),
+        ntimesteps,
+        min_time: stats[0].min_time + time_offsets[0],
+        max_time: stats[last].max_time + time_offsets[last],
+        sample_rate,
+        velocity,
+        velocity_mag,
+        time_array: Array1::from(time_array),
+        particle_types,
+        provenance,
+    }
+}
+
+impl DataManager for ConcatData {
+    fn get_timestep(&mut self, timestep: usize) -> Result<&Timestep> {
+        print_debug!("ConcatData: Extracting timestep number {}", timestep);
+        let (part, local) = self.locate(timestep)?;
+        let timestep = self.parts[part].get_timestep(local)?.clone();
+        self.timestep = self.shifted(timestep, part);
+        Ok(&self.timestep)
+    }
+
+    fn global_stats(&self) -> GlobalStats {
+        self.transform.apply_stats(&self.global_stats_)
+    }
+
+    fn particle_types(&mut self) -> Result<BTreeMap<usize, usize>> {
+        let mut particle_types = BTreeMap::new();
+        for part in self.parts.iter_mut() {
+            merge_type_counts(&mut particle_types, &part.particle_types()?);
+        }
+        Ok(particle_types)
+    }
+
+    fn stats(&self) {
+        let global_stats = self.global_stats();
+        let time = global_stats.max_time();
+        let dim = global_stats.dimensions();
+        println!("Number of chained datasets: {}", self.parts.len());
+        println!("Dimensions of the system:: {:?}", dim);
+        println!("The max time of this set is : {:?}", time);
+        let part_num = global_stats.nparticles();
+        println!("Number of Particles: {}", part_num);
+        let vel_mag = global_stats.velocity_mag();
+        println!("Mean velocity of: {} m/s", vel_mag[1usize]);
+        println!(
+            "Minimum velocity {} m/s \nMaximum Velocity {} m/s",
+            vel_mag[0usize], vel_mag[2usize]
+        );
+    }
+
+    fn get_timestep_unbuffered(&self, timestep: usize) -> Result<Timestep> {
+        let (part, local) = self.locate(timestep)?;
+        let timestep = self.parts[part].get_timestep_unbuffered(local)?;
+        Ok(self.shifted(timestep, part))
+    }
+
+    /// The budget is split evenly between the caches of all parts
+    fn set_cache_memory(&mut self, cache_memory: usize) {
+        let part_memory = cache_memory / self.parts.len();
+        for part in self.parts.iter_mut() {
+            part.set_cache_memory(part_memory);
+        }
+    }
+
+    fn info(&self) -> Result<String> {
+        let global_stats = self.global_stats();
+        let time = global_stats.max_time();
+        let timesteps = global_stats.timesteps();
+        let dim = global_stats.dimensions();
+        let part_num = global_stats.nparticles();
+        let vel_mag = global_stats.velocity_mag();
+        let mut base_string = format!(
+            "Concatenated Dataset of {} parts.\n\
+            Dimensions of the system:\n\
+            |   Min --> Max\t||   Diff\n\
+            |x {:.2}-->{:.2}\t||  {:.2}\n\
+            |y {:.2}-->{:.2}\t||  {:.2}\n\
+            |z {:.2}-->{:.2}\t||  {:.2}\n\
+            The max time of this set is : {:.2}\n\
+            Number of Particles: {:.2}\n\
+            Number of Timesteps: {:.2}\n\
+            Mean velocity of: {:.2} m/s\n\
+            Minimum velocity {:.2} m/s \nMaximum Velocity {:.2} m/s\n",
+            self.parts.len(),
+            dim[[0, 0]],
+            dim[[1, 0]],
+            dim[[1, 0]] - dim[[0, 0]],
+            dim[[0, 1]],
+            dim[[1, 1]],
+            dim[[1, 1]] - dim[[0, 1]],
+            dim[[0, 2]],
+            dim[[1, 2]],
+            dim[[1, 2]] - dim[[0, 2]],
+            time,
+            part_num,
+            timesteps,
+            vel_mag[1usize],
+            vel_mag[0usize],
+            vel_mag[2usize]
+        );
+        for (id, offset) in self.time_offsets.iter().enumerate() {
+            base_string.push_str(&format!(
+                "Part {}: timesteps from {}, time offset {:.2} s\n",
+                id, self.first_timestep[id], offset
+            ));
+        }
+        base_string.push_str(&global_stats.provenance().to_string());
+        if !self.transform.is_identity() {
+            base_string.push_str(&self.transform.to_string());
+        }
+        Ok(base_string)
+    }
+
+    fn set_transform(&mut self, transform: Transform) -> Result<()> {
+        self.transform = transform;
+        Ok(())
+    }
+
+    fn transform(&self) -> Transform {
+        self.transform
+    }
+}
+
+impl Manager for ConcatData {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamanager::MData;
+
+    /// One particle moving with `speed` along x between the times `start` and `end`
+    fn part(start: f64, end: f64, timesteps: usize, speed: f64) -> Box<dyn Manager + Send> {
+        let time = Array1::linspace(start, end, timesteps);
+        let position = Array3::from_shape_fn((timesteps, 1, 3), |(t, _, d)| {
+            if d == 0 {
+                speed * time[t]
+            } else {
+                0.0
+            }
+        });
+        let velocity =
+            Array3::from_shape_fn((timesteps, 1, 3), |(_, _, d)| if d == 0 { speed } else { 0.0 });
+        let data = MData::from_arrays(time, position, Some(velocity), None, None, None, None);
+        Box::new(data.unwrap())
+    }
+
+    #[test]
+    fn automatic_offsets() {
+        // a restart at time zero and a part that already continues the timeline
+        let parts = vec![
+            part(0.0, 1.0, 11, 1.0),
+            part(0.0, 0.5, 6, 1.0),
+            part(5.0, 6.0, 11, 1.0),
+        ];
+        let data = ConcatData::new(parts).unwrap();
+        let offsets = data.time_offsets();
+        assert_eq!(offsets[0], 0.0);
+        assert!((offsets[1] - 1.1).abs() < 1e-12);
+        assert_eq!(offsets[2], 0.0);
+        assert!(ConcatData::with_time_offsets(
+            vec![part(0.0, 1.0, 11, 1.0), part(0.0, 0.5, 6, 1.0)],
+            vec![0.0, 0.0]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn sample_steps() {
+        let mut stats = part(0.0, 1.0, 11, 1.0).global_stats();
+        assert!((sample_step(&stats) - 0.1).abs() < 1e-12);
+        // without a sample rate the step follows from the time span
+        stats.sample_rate = 0.0;
+        assert!((sample_step(&stats) - 0.1).abs() < 1e-12);
+        let mut stats = part(0.0, 0.0, 1, 1.0).global_stats();
+        stats.sample_rate = 0.0;
+        assert_eq!(sample_step(&stats), 1.0);
+    }
+
+    #[test]
+    fn locate_at_boundaries() {
+        let parts = vec![part(0.0, 1.0, 11, 1.0), part(0.0, 0.5, 6, 1.0)];
+        let mut data = ConcatData::new(parts).unwrap();
+        assert_eq!(data.locate(0).unwrap(), (0, 0));
+        assert_eq!(data.locate(10).unwrap(), (0, 10));
+        assert_eq!(data.locate(11).unwrap(), (1, 0));
+        assert_eq!(data.locate(16).unwrap(), (1, 5));
+        assert!(matches!(
+            data.locate(17),
+            Err(Up4Error::TimestepOutOfRange { .. })
+        ));
+        let first = data.get_timestep(11).unwrap();
+        assert!((first.time - 1.1).abs() < 1e-12);
+        assert_eq!(first.position[0][0], 0.0);
+    }
+
+    #[test]
+    fn merged_stats() {
+        let parts = [part(0.0, 1.0, 11, 1.0), part(0.0, 0.5, 6, 4.0)];
+        let stats: Vec<GlobalStats> = parts.iter().map(|part| part.global_stats()).collect();
+        let merged = merge_stats(&stats, &[0.0, 1.1]);
+        assert_eq!(merged.ntimesteps, 17);
+        assert_eq!(merged.nparticles, 1);
+        assert_eq!(merged.min_time, 0.0);
+        assert!((merged.max_time - 1.6).abs() < 1e-12);
+        assert_eq!(merged.time_array.len(), 17);
+        assert!((merged.time_array[11] - 1.1).abs() < 1e-12);
+        assert!((merged.dimensions[[1, 0]] - 2.0).abs() < 1e-12);
+        assert_eq!(merged.velocity[[0, 0]], 1.0);
+        assert_eq!(merged.velocity[[0, 2]], 4.0);
+        // the means are weighted by the number of timesteps of each part
+        assert!((merged.velocity[[0, 1]] - 35.0 / 17.0).abs() < 1e-12);
+        assert!((merged.velocity_mag[1] - 35.0 / 17.0).abs() < 1e-12);
+        assert!((merged.sample_rate - 0.1).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,125 @@
+//! Identification of up4 HDF5 files
+//!
+//! Every up4 file carries the root attributes `hdf5_up4_type` (0x1 --> tdata, 0x2 --> pdata)
+//! and `hdf5_up4_version`. Files written before the version attribute was introduced are
+//! treated as version 0, their layout is identical to version 1.
+
+use super::utilities::attribute;
+use crate::error::{Result, Up4Error};
+
+/// Version of the file schema written by this crate
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Name of the root attribute holding the file layout
+pub const TYPE_ATTRIBUTE: &str = "hdf5_up4_type";
+
+/// Name of the root attribute holding the schema version
+pub const VERSION_ATTRIBUTE: &str = "hdf5_up4_version";
+
+/// Layout of an up4 HDF5 file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileLayout {
+    /// Particle based layout with one group per particle, used for experimental data
+    PData,
+    /// Timestep based layout with one group per timestep, used for simulation data
+    TData,
+}
+
+impl FileLayout {
+    /// Identifier stored in the `hdf5_up4_type` attribute: 0x1 --> tdata, 0x2 --> pdata
+    pub fn up4_type(&self) -> i32 {
+        match self {
+            FileLayout::TData => 0x1_i32,
+            FileLayout::PData => 0x2_i32,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            FileLayout::TData => "tdata",
+            FileLayout::PData => "pdata",
+        }
+    }
+
+    /// Read the layout of `file` and check that its schema version can be read
+    pub fn of_file(file: &hdf5::File) -> Result<Self> {
+        let filename = file.filename();
+        let up4_type: i32 = attribute(file, TYPE_ATTRIBUTE)
+            .map_err(|_| Up4Error::WrongFileKind {
+                filename: filename.clone(),
+                expected: "an up4 file with the attribute \"hdf5_up4_type\"".to_string(),
+                found: "no such attribute, the file is not an up4 file or was written by an \
+                    older schema. Convert the raw data again with up4.Converter"
+                    .to_string(),
+            })?
+            .read_scalar()
+            .map_err(|e| Up4Error::read(TYPE_ATTRIBUTE, &filename, e))?;
+        let layout = match up4_type {
+            0x1_i32 => FileLayout::TData,
+            0x2_i32 => FileLayout::PData,
+            _ => {
+                return Err(Up4Error::WrongFileKind {
+                    filename,
+                    expected: "up4 TData (0x1) or PData (0x2)".to_string(),
+                    found: format!("hdf5_up4_type {}", up4_type),
+                })
+            }
+        };
+        let version = match file.attr(VERSION_ATTRIBUTE) {
+            Ok(version) => version
+                .read_scalar::<u32>()
+                .map_err(|e| Up4Error::read(VERSION_ATTRIBUTE, &filename, e))?,
+            Err(_) => 0,
+        };
+        if version > FORMAT_VERSION {
+            return Err(Up4Error::WrongFileKind {
+                filename,
+                expected: format!("up4 file version {} or older", FORMAT_VERSION),
+                found: format!(
+                    "version {}. The file was written by a newer up4, please update",
+                    version
+                ),
+            });
+        }
+        Ok(layout)
+    }
+
+    /// Return an error if `file` does not have this layout
+    pub(super) fn check(&self, file: &hdf5::File) -> Result<()> {
+        let found = FileLayout::of_file(file)?;
+        if found != *self {
+            return Err(Up4Error::WrongFileKind {
+                filename: file.filename(),
+                expected: self.name().to_string(),
+                found: format!("{}, open it with Data.from_{}", found.name(), found.name()),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for FileLayout {
+    type Err = Up4Error;
+
+    fn from_str(layout: &str) -> Result<Self> {
+        match layout.to_lowercase().as_str() {
+            "pdata" => Ok(FileLayout::PData),
+            "tdata" => Ok(FileLayout::TData),
+            _ => Err(Up4Error::InvalidArgument(format!(
+                "Layout {} is not valid. Valid layouts are: \"pdata\", \"tdata\"",
+                layout
+            ))),
+        }
+    }
+}
+
+/// Write the `hdf5_up4_type` and `hdf5_up4_version` root attributes
+pub fn write_format(file: &hdf5::File, layout: FileLayout) -> Result<()> {
+    file.new_attr::<i32>()
+        .create(TYPE_ATTRIBUTE)?
+        .write_scalar(&layout.up4_type())?;
+    file.new_attr::<u32>()
+        .create(VERSION_ATTRIBUTE)?
+        .write_scalar(&FORMAT_VERSION)?;
+    Ok(())
+}
@@ -0,0 +1,282 @@
+//! Integrity checks of a dataset
+//!
+//! Converted files can contain particles that are only found in a few timesteps, NaN
+//! values, gaps or jumps back in time, positions outside of the system dimensions and
+//! single velocity outliers. `inspect` walks all timesteps once and collects these
+//! problems in an `InspectionReport`, so a file can be vetted before a long analysis.
+
+use super::DataManager;
+use crate::check_signals;
+use crate::error::Result;
+use derive_getters::Getters;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Thresholds used by `inspect`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InspectOptions {
+    /// a time step longer than `gap_factor` times the median time step is a gap
+    pub gap_factor: f64,
+    /// a speed above `outlier_factor` times the mean speed of the dataset is an outlier
+    pub outlier_factor: f64,
+}
+
+impl Default for InspectOptions {
+    fn default() -> Self {
+        InspectOptions {
+            gap_factor: 2.0,
+            outlier_factor: 10.0,
+        }
+    }
+}
+
+/// Timesteps in which a particle has a valid position
+#[derive(Debug, Getters, Clone)]
+pub struct ParticleCoverage {
+    timesteps: usize,
+    /// time of the first valid position, NaN if there is none
+    first_time: f64,
+    /// time of the last valid position, NaN if there is none
+    last_time: f64,
+}
+
+/// Problems found in a dataset by `inspect`
+#[derive(Debug, Default, Getters, Clone)]
+pub struct InspectionReport {
+    timesteps: usize,
+    /// coverage of every particle ID
+    particles: BTreeMap<usize, ParticleCoverage>,
+    /// fraction of NaN entries of every per-particle quantity, additional fields are
+    /// listed as "fields/name"
+    nan_fractions: BTreeMap<String, f64>,
+    /// (timestep before the gap, length of the gap in seconds)
+    time_gaps: Vec<(usize, f64)>,
+    /// timesteps whose time is not after the time of the previous timestep
+    non_monotonic: Vec<usize>,
+    /// number of positions outside of the dimensions of the dataset
+    out_of_bounds: usize,
+    /// number of velocities faster than `outlier_speed`
+    velocity_outliers: usize,
+    outlier_speed: f64,
+    max_speed: f64,
+}
+
+impl InspectionReport {
+    /// Fraction of all timesteps in which the particle `particleid` has a valid position
+    pub fn coverage(&self, particleid: usize) -> Option<f64> {
+        self.particles
+            .get(&particleid)
+            .map(|particle| particle.timesteps as f64 / self.timesteps.max(1) as f64)
+    }
+
+    /// Return true if no problem was found. NaN values are not counted as a problem, as
+    /// particle oriented files mark timesteps without a particle with NaN.
+    pub fn is_clean(&self) -> bool {
+        self.time_gaps.is_empty()
+            && self.non_monotonic.is_empty()
+            && self.out_of_bounds == 0
+            && self.velocity_outliers == 0
+    }
+}
+
+impl fmt::Display for InspectionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Inspection of {} timesteps and {} particles",
+            self.timesteps,
+            self.particles.len()
+        )?;
+        let (min_coverage, max_coverage) = self
+            .particles
+            .keys()
+            .filter_map(|&particleid| self.coverage(particleid))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), coverage| {
+                (min.min(coverage), max.max(coverage))
+            });
+        if !self.particles.is_empty() {
+            writeln!(
+                f,
+                "Particle coverage: {:.1}% --> {:.1}%",
+                100. * min_coverage,
+                100. * max_coverage
+            )?;
+        }
+        for (name, fraction) in self.nan_fractions.iter() {
+            writeln!(f, "NaN in {}: {:.2}%", name, 100. * fraction)?;
+        }
+        writeln!(f, "Gaps in time: {}", self.time_gaps.len())?;
+        writeln!(f, "Timesteps going back in time: {}", self.non_monotonic.len())?;
+        writeln!(f, "Positions out of bounds: {}", self.out_of_bounds)?;
+        writeln!(
+            f,
+            "Velocity outliers faster than {:.2} m/s: {} (maximum speed {:.2} m/s)",
+            self.outlier_speed, self.velocity_outliers, self.max_speed
+        )
+    }
+}
+
+/// Walk all timesteps of `data` and report coverage, NaN values, time gaps, timesteps
+/// going back in time, positions outside of the dimensions in the global stats and
+/// velocity outliers
+pub fn inspect<T>(data: &mut T, options: InspectOptions) -> Result<InspectionReport>
+where
+    T: DataManager + ?Sized,
+{
+    let global_stats = data.global_stats();
+    let dimensions = global_stats.dimensions().clone();
+    let bounded = dimensions.shape() == [2, 3];
+    let mean_speed = global_stats
+        .velocity_mag()
+        .get(1)
+        .copied()
+        .unwrap_or(f64::NAN);
+    let outlier_speed = options.outlier_factor * mean_speed;
+    let mut report = InspectionReport {
+        timesteps: *global_stats.timesteps(),
+        outlier_speed,
+        ..InspectionReport::default()
+    };
+    // (NaN entries, all entries) of every quantity
+    let mut nan_counts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    let mut count = |name: &str, nan: usize, total: usize| {
+        let counts = nan_counts.entry(name.to_string()).or_insert((0, 0));
+        counts.0 += nan;
+        counts.1 += total;
+    };
+    let mut times = Vec::with_capacity(report.timesteps);
+    for timestep in 0..report.timesteps {
+        let timestep_data = data.get_timestep(timestep)?;
+        let time = timestep_data.time;
+        times.push(time);
+        let mut invalid_positions = 0;
+        for (position, &particleid) in timestep_data
+            .position
+            .iter()
+            .zip(timestep_data.particleid.iter())
+        {
+            let coverage = report
+                .particles
+                .entry(particleid as usize)
+                .or_insert(ParticleCoverage {
+                    timesteps: 0,
+                    first_time: f64::NAN,
+                    last_time: f64::NAN,
+                });
+            if position.iter().any(|x| x.is_nan()) {
+                invalid_positions += 1;
+                continue;
+            }
+            if coverage.timesteps == 0 {
+                coverage.first_time = time;
+            }
+            coverage.timesteps += 1;
+            coverage.last_time = time;
+            if bounded
+                && (0..3).any(|dim| {
+                    position[dim] < dimensions[[0, dim]] || position[dim] > dimensions[[1, dim]]
+                })
+            {
+                report.out_of_bounds += 1;
+            }
+        }
+        count("position", invalid_positions, timestep_data.position.len());
+
+        let mut invalid_velocities = 0;
+        for velocity in timestep_data.velocity.outer_iter() {
+            let speed = velocity.dot(&velocity).sqrt();
+            if speed.is_nan() {
+                invalid_velocities += 1;
+                continue;
+            }
+            report.max_speed = report.max_speed.max(speed);
+            if speed > outlier_speed {
+                report.velocity_outliers += 1;
+            }
+        }
+        count("velocity", invalid_velocities, timestep_data.velocity.nrows());
+        for (name, values) in [
+            ("radius", &timestep_data.radius),
+            ("density", &timestep_data.density),
+            ("particletype", &timestep_data.ptype),
+        ] {
+            let nan = values.iter().filter(|value| value.is_nan()).count();
+            count(name, nan, values.len());
+        }
+        for (name, field) in timestep_data.fields.iter() {
+            let nan = field.iter().filter(|value| value.is_nan()).count();
+            count(&format!("fields/{}", name), nan, field.len());
+        }
+        check_signals!();
+    }
+    report.nan_fractions = nan_counts
+        .into_iter()
+        .map(|(name, (nan, total))| (name, nan as f64 / total.max(1) as f64))
+        .collect();
+    let (time_gaps, non_monotonic) = time_issues(&times, options.gap_factor);
+    report.time_gaps = time_gaps;
+    report.non_monotonic = non_monotonic;
+    Ok(report)
+}
+
+/// Return the gaps longer than `gap_factor` times the median time step and the timesteps
+/// that are not after their previous timestep
+fn time_issues(times: &[f64], gap_factor: f64) -> (Vec<(usize, f64)>, Vec<usize>) {
+    let steps: Vec<f64> = times.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let non_monotonic = steps
+        .iter()
+        .enumerate()
+        .filter(|(_, &step)| step.is_nan() || step <= 0.0)
+        .map(|(timestep, _)| timestep + 1)
+        .collect();
+    let mut positive: Vec<f64> = steps.iter().copied().filter(|&step| step > 0.0).collect();
+    if positive.is_empty() {
+        return (vec![], non_monotonic);
+    }
+    positive.sort_by(|a, b| a.total_cmp(b));
+    let median = positive[positive.len() / 2];
+    let gaps = steps
+        .iter()
+        .enumerate()
+        .filter(|(_, &step)| step > gap_factor * median)
+        .map(|(timestep, &step)| (timestep, step))
+        .collect();
+    (gaps, non_monotonic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamanager::MData;
+    use ndarray::prelude::*;
+
+    #[test]
+    fn gaps_and_jumps() {
+        let (gaps, non_monotonic) = time_issues(&[0.0, 0.1, 0.2, 0.6, 0.7, 0.7, 0.65], 2.0);
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].0, 2);
+        assert!((gaps[0].1 - 0.4).abs() < 1e-12);
+        assert_eq!(non_monotonic, vec![5, 6]);
+    }
+
+    #[test]
+    fn report() {
+        let time = Array1::linspace(0.0, 1.0, 11);
+        let mut position = Array3::from_shape_fn((11, 2, 3), |(t, _, _)| time[t]);
+        // the second particle is lost for two timesteps
+        position.slice_mut(s![3..5, 1, ..]).fill(f64::NAN);
+        let mut velocity = Array3::from_elem((11, 2, 3), 1.0);
+        velocity[[7, 0, 2]] = 1000.0;
+        let mut data =
+            MData::from_arrays(time, position, Some(velocity), None, None, None, None).unwrap();
+        let report = inspect(&mut data, InspectOptions::default()).unwrap();
+        assert_eq!(*report.timesteps(), 11);
+        assert_eq!(report.coverage(0), Some(1.0));
+        assert!((report.coverage(1).unwrap() - 9. / 11.).abs() < 1e-12);
+        assert!((report.nan_fractions()["position"] - 2. / 22.).abs() < 1e-12);
+        assert_eq!(*report.velocity_outliers(), 1);
+        assert_eq!(*report.out_of_bounds(), 0);
+        assert!(report.time_gaps().is_empty());
+        assert!(!report.is_clean());
+    }
+}
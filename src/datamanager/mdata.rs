@@ -0,0 +1,421 @@
+//! In-memory dataset manager
+//!
+//! Holds all timesteps in memory. Used for freshly tracked or synthetic data that
+//! has not been written to a HDF5 file.
+
+use super::utilities::compute_global_stats;
+use super::{DataManager, GlobalStats, Manager, Timestep, Transform};
+use crate::error::{Result, Up4Error};
+use crate::print_debug;
+use crate::types::*;
+use crate::units::LengthUnit;
+use ndarray::prelude::*;
+use std::collections::BTreeMap;
+
+pub struct MData {
+    // A data managing system for data that lives in memory
+    timesteps: Vec<Timestep>,
+    global_stats_: GlobalStats,
+    /// transform applied to every timestep when it is read
+    transform: Transform,
+    /// transformed copy of the last requested timestep
+    timestep: Timestep,
+}
+
+impl MData {
+    /// Create a new dataset from a list of timesteps. All timesteps must be
+    /// sorted in time.
+    pub fn new(timesteps: Vec<Timestep>) -> Result<Self> {
+        print_debug!("MData: Generating new instance with {} timesteps", timesteps.len());
+        if timesteps.is_empty() {
+            return Err(Up4Error::InvalidArgument(
+                "A dataset needs at least one timestep".to_string(),
+            ));
+        }
+        for (id, timestep) in timesteps.iter().enumerate() {
+            check_timestep_shape(timestep, id)?;
+        }
+        if timesteps.windows(2).any(|pair| pair[1].time < pair[0].time) {
+            return Err(Up4Error::InvalidArgument(
+                "The timesteps must be sorted in time".to_string(),
+            ));
+        }
+        let global_stats_ = compute_global_stats(&timesteps);
+        Ok(MData {
+            timesteps,
+            global_stats_,
+            transform: Transform::identity(),
+            timestep: Timestep::default(),
+        })
+    }
+
+    /// Create a new dataset from arrays with the time axis first.
+    ///
+    /// # Arguments
+    /// * `time` -- Time of each timestep, shape (T,)
+    /// * `position` -- Particle positions, shape (T, N, 3)
+    /// * `velocity` -- Particle velocities, shape (T, N, 3). If `None` the velocity is
+    ///   calculated from the positions with finite differences
+    /// * `radius`, `particleid`, `ptype`, `density` -- Optional per particle
+    ///   properties, shape (T, N). Missing properties default to zeros, the particle
+    ///   index, zeros and ones respectively
+    pub fn from_arrays(
+        time: Array1<f64>,
+        position: Array3<f64>,
+        velocity: Option<Array3<f64>>,
+        radius: Option<Array2<f64>>,
+        particleid: Option<Array2<f64>>,
+        ptype: Option<Array2<f64>>,
+        density: Option<Array2<f64>>,
+    ) -> Result<Self> {
+        let (ntimesteps, nparticles, ndim) = position.dim();
+        if ndim != 3 || ntimesteps != time.len() {
+            return Err(Up4Error::InvalidArgument(format!(
+                "position must have the shape ({}, N, 3) but has the shape {:?}",
+                time.len(),
+                position.shape()
+            )));
+        }
+        let velocity = match velocity {
+            Some(velocity) => {
+                check_shape(velocity.shape(), position.shape(), "velocity")?;
+                velocity
+            }
+            None => finite_difference_velocity(&time, &position),
+        };
+        let per_particle = |data: Option<Array2<f64>>,
+                            name: &str,
+                            default: Array2<f64>|
+         -> Result<Array2<f64>> {
+            match data {
+                Some(data) => {
+                    check_shape(data.shape(), &[ntimesteps, nparticles], name)?;
+                    Ok(data)
+                }
+                None => Ok(default),
+            }
+        };
+        let radius = per_particle(radius, "radius", Array2::zeros((ntimesteps, nparticles)))?;
+        let particleid = per_particle(
+            particleid,
+            "particleid",
+            Array2::from_shape_fn((ntimesteps, nparticles), |(_, id)| id as f64),
+        )?;
+        let ptype = per_particle(ptype, "ptype", Array2::zeros((ntimesteps, nparticles)))?;
+        let density = per_particle(density, "density", Array2::ones((ntimesteps, nparticles)))?;
+
+        let timesteps = (0..ntimesteps)
+            .map(|timestep| Timestep {
+                time: time[timestep],
+                position: position
+                    .slice(s![timestep, .., ..])
+                    .outer_iter()
+                    .map(|x| [x[0], x[1], x[2]])
+                    .collect::<Array1<Position>>(),
+                velocity: velocity.slice(s![timestep, .., ..]).to_owned(),
+                radius: radius.slice(s![timestep, ..]).to_owned(),
+                particleid: particleid.slice(s![timestep, ..]).to_owned(),
+                clouds: Array1::<f64>::ones(nparticles),
+                density: density.slice(s![timestep, ..]).to_owned(),
+                ptype: ptype.slice(s![timestep, ..]).to_owned(),
+                fields: BTreeMap::new(),
+            })
+            .collect();
+        MData::new(timesteps)
+    }
+
+    /// Record that all positions and radii are given in `length_unit`
+    pub fn with_length_unit(mut self, length_unit: LengthUnit) -> Self {
+        let provenance = std::mem::take(&mut self.global_stats_.provenance);
+        self.global_stats_.provenance = provenance.add_length_unit(length_unit);
+        self
+    }
+
+    /// Add the additional per-particle field `name` with the shape (T, N, components)
+    pub fn with_field(mut self, name: &str, data: Array3<f64>) -> Result<Self> {
+        let (ntimesteps, nparticles, _) = data.dim();
+        if ntimesteps != self.timesteps.len() {
+            return Err(Up4Error::InvalidArgument(format!(
+                "Field {} has {} timesteps but the dataset has {}",
+                name,
+                ntimesteps,
+                self.timesteps.len()
+            )));
+        }
+        for (id, timestep) in self.timesteps.iter_mut().enumerate() {
+            if timestep.position.len() != nparticles {
+                return Err(Up4Error::InvalidArgument(format!(
+                    "Timestep {}: {} has {} entries but there are {} positions",
+                    id,
+                    name,
+                    nparticles,
+                    timestep.position.len()
+                )));
+            }
+            timestep
+                .fields
+                .insert(name.to_string(), data.slice(s![id, .., ..]).to_owned());
+        }
+        Ok(self)
+    }
+
+    /// Return an error if `timestep` is not part of the dataset
+    fn check_timestep(&self, timestep: usize) -> Result<()> {
+        let timesteps = self.timesteps.len();
+        if timestep >= timesteps {
+            return Err(Up4Error::TimestepOutOfRange {
+                timestep,
+                timesteps,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Make sure all per-particle arrays of a timestep describe the same particles
+fn check_timestep_shape(timestep: &Timestep, id: usize) -> Result<()> {
+    let particles = timestep.position.len();
+    for (name, len) in [
+        ("velocity", timestep.velocity.nrows()),
+        ("radius", timestep.radius.len()),
+        ("particleid", timestep.particleid.len()),
+        ("clouds", timestep.clouds.len()),
+        ("density", timestep.density.len()),
+        ("ptype", timestep.ptype.len()),
+    ]
+    .into_iter()
+    .chain(
+        timestep
+            .fields
+            .iter()
+            .map(|(name, field)| (name.as_str(), field.nrows())),
+    ) {
+        if len != particles {
+            return Err(Up4Error::InvalidArgument(format!(
+                "Timestep {}: {} has {} entries but there are {} positions",
+                id, name, len, particles
+            )));
+        }
+    }
+    if timestep.velocity.ncols() != 3 {
+        return Err(Up4Error::InvalidArgument(format!(
+            "Timestep {}: velocity must have the shape (N, 3)",
+            id
+        )));
+    }
+    Ok(())
+}
+
+fn check_shape(shape: &[usize], expected: &[usize], name: &str) -> Result<()> {
+    if shape != expected {
+        return Err(Up4Error::InvalidArgument(format!(
+            "{} must have the shape {:?} but has the shape {:?}",
+            name, expected, shape
+        )));
+    }
+    Ok(())
+}
+
+/// Calculate the velocity of all particles from their positions. Central differences
+/// are used inside, one sided differences at the first and last timestep.
+fn finite_difference_velocity(time: &Array1<f64>, position: &Array3<f64>) -> Array3<f64> {
+    let ntimesteps = time.len();
+    let mut velocity = Array3::<f64>::zeros(position.dim());
+    if ntimesteps < 2 {
+        return velocity;
+    }
+    for timestep in 0..ntimesteps {
+        let before = timestep.saturating_sub(1);
+        let after = (timestep + 1).min(ntimesteps - 1);
+        let dt = time[after] - time[before];
+        let displacement =
+            &position.slice(s![after, .., ..]) - &position.slice(s![before, .., ..]);
+        velocity
+            .slice_mut(s![timestep, .., ..])
+            .assign(&(displacement / dt));
+    }
+    velocity
+}
+
+impl DataManager for MData {
+    fn get_timestep(&mut self, timestep: usize) -> Result<&Timestep> {
+        print_debug!("MData: Extracting timestep number {}", timestep);
+        self.check_timestep(timestep)?;
+        if self.transform.is_identity() {
+            return Ok(&self.timesteps[timestep]);
+        }
+        self.timestep = self.timesteps[timestep].clone();
+        self.transform.apply(&mut self.timestep);
+        Ok(&self.timestep)
+    }
+
+    fn global_stats(&self) -> GlobalStats {
+        self.transform.apply_stats(&self.global_stats_)
+    }
+
+    fn particle_types(&mut self) -> Result<BTreeMap<usize, usize>> {
+        Ok(self.global_stats_.particle_types.clone())
+    }
+
+    fn stats(&self) {
+        let global_stats = self.global_stats();
+        let time = global_stats.max_time();
+        let dim = global_stats.dimensions();
+        println!("Dimensions of the system:: {:?}", dim);
+        println!("The max time of this set is : {:?}", time);
+        let part_num = global_stats.nparticles();
+        println!("Number of Particles: {}", part_num);
+        let vel_mag = global_stats.velocity_mag();
+        println!("Mean velocity of: {} m/s", vel_mag[1usize]);
+        println!(
+            "Minimum velocity {} m/s \nMaximum Velocity {} m/s",
+            vel_mag[0usize], vel_mag[2usize]
+        );
+    }
+
+    fn get_timestep_unbuffered(&self, timestep: usize) -> Result<Timestep> {
+        self.check_timestep(timestep)?;
+        let mut timestep = self.timesteps[timestep].clone();
+        self.transform.apply(&mut timestep);
+        Ok(timestep)
+    }
+
+    /// All data is in memory, there is nothing to cache
+    fn set_cache_memory(&mut self, _cache_memory: usize) {}
+
+    fn info(&self) -> Result<String> {
+        let global_stats = self.global_stats();
+        let time = global_stats.max_time();
+        let timesteps = global_stats.timesteps();
+        let dim = global_stats.dimensions();
+        let part_num = global_stats.nparticles();
+        let vel_mag = global_stats.velocity_mag();
+        let mut base_string = format!(
+            "In-Memory Dataset.\n\
+            Dimensions of the system:\n\
+            |   Min --> Max\t||   Diff\n\
+            |x {:.2}-->{:.2}\t||  {:.2}\n\
+            |y {:.2}-->{:.2}\t||  {:.2}\n\
+            |z {:.2}-->{:.2}\t||  {:.2}\n\
+            The max time of this set is : {:.2}\n\
+            Number of Particles: {:.2}\n\
+            Number of Timesteps: {:.2}\n\
+            Mean velocity of: {:.2} m/s\n\
+            Minimum velocity {:.2} m/s \nMaximum Velocity {:.2} m/s\n",
+            dim[[0, 0]],
+            dim[[1, 0]],
+            dim[[1, 0]] - dim[[0, 0]],
+            dim[[0, 1]],
+            dim[[1, 1]],
+            dim[[1, 1]] - dim[[0, 1]],
+            dim[[0, 2]],
+            dim[[1, 2]],
+            dim[[1, 2]] - dim[[0, 2]],
+            time,
+            part_num,
+            timesteps,
+            vel_mag[1usize],
+            vel_mag[0usize],
+            vel_mag[2usize]
+        );
+        base_string.push_str(&global_stats.provenance().to_string());
+        if !self.transform.is_identity() {
+            base_string.push_str(&self.transform.to_string());
+        }
+        Ok(base_string)
+    }
+
+    fn set_transform(&mut self, transform: Transform) -> Result<()> {
+        self.transform = transform;
+        Ok(())
+    }
+
+    fn transform(&self) -> Transform {
+        self.transform
+    }
+}
+
+// Implement Manager which is just a sum of Granular + DataManager
+impl Manager for MData {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linear_motion() -> MData {
+        // two particles moving with 1 m/s and 2 m/s along x
+        let time = Array1::linspace(0.0, 1.0, 11);
+        let position = Array3::from_shape_fn((11, 2, 3), |(t, p, d)| {
+            if d == 0 {
+                time[t] * (p + 1) as f64
+            } else {
+                0.0
+            }
+        });
+        MData::from_arrays(time, position, None, None, None, None, None).unwrap()
+    }
+
+    #[test]
+    fn velocity_from_positions() {
+        let mut data = linear_motion();
+        let timestep = data.get_timestep(5).unwrap();
+        assert!((timestep.velocity()[[0, 0]] - 1.0).abs() < 1e-10);
+        assert!((timestep.velocity()[[1, 0]] - 2.0).abs() < 1e-10);
+        assert_eq!(timestep.particleid()[1], 1.0);
+    }
+
+    #[test]
+    fn global_stats() {
+        let data = linear_motion();
+        let stats = data.global_stats();
+        assert_eq!(*stats.timesteps(), 11);
+        assert_eq!(*stats.nparticles(), 2);
+        assert!((stats.dimensions()[[1, 0]] - 2.0).abs() < 1e-10);
+        assert!((stats.sample_rate() - 0.1).abs() < 1e-10);
+    }
+
+    #[test]
+    fn wrong_shape() {
+        let time = Array1::linspace(0.0, 1.0, 11);
+        let position = Array3::zeros((10, 2, 3));
+        assert!(MData::from_arrays(time, position, None, None, None, None, None).is_err());
+    }
+
+    #[test]
+    fn extra_field() {
+        let data = linear_motion();
+        let temperature = Array3::from_elem((11, 2, 1), 300.0);
+        let mut data = data.with_field("temperature", temperature).unwrap();
+        let timestep = data.get_timestep(3).unwrap();
+        assert_eq!(timestep.field_names(), vec!["temperature"]);
+        assert_eq!(timestep.field("temperature").unwrap().shape(), &[2, 1]);
+        assert!(timestep.field("force").is_none());
+    }
+
+    #[test]
+    fn transform() {
+        let mut data = linear_motion();
+        let transform = Transform::swap_axes(0, 1)
+            .unwrap()
+            .then(&Transform::translation([0.0, 0.0, 1.0]));
+        data.set_transform(transform).unwrap();
+        let timestep = data.get_timestep(10).unwrap();
+        assert!((timestep.position()[1][1] - 2.0).abs() < 1e-10);
+        assert!((timestep.velocity()[[1, 1]] - 2.0).abs() < 1e-10);
+        assert_eq!(timestep.position()[1][2], 1.0);
+        let stats = data.global_stats();
+        assert!((stats.dimensions()[[1, 1]] - 2.0).abs() < 1e-10);
+        assert_eq!(stats.dimensions()[[0, 2]], 1.0);
+        data.set_transform(Transform::identity()).unwrap();
+        assert_eq!(data.get_timestep(10).unwrap().position()[1][2], 0.0);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut data = linear_motion();
+        assert!(matches!(
+            data.get_timestep(11),
+            Err(Up4Error::TimestepOutOfRange { .. })
+        ));
+    }
+}
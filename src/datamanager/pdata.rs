@@ -2,9 +2,12 @@
 //!
 //! Implementation of reading + buffering functions.
 
-use super::{DataManager, GlobalStats, Manager, Timestep};
+use super::cache::{TimestepCache, DEFAULT_CACHE_MEMORY};
+use super::utilities::{count_types, dataset, field_names, group, read_field, read_global_stats};
+use super::{DataManager, FileLayout, GlobalStats, Manager, Timestep, Transform};
 
 extern crate ndarray;
+use crate::error::{Result, Up4Error};
 use crate::particleselector::Selector;
 use crate::types::*;
 use crate::ParticleSelector;
@@ -12,814 +15,309 @@ use crate::{print_debug, print_warning};
 #[cfg(feature = "blosc")]
 use hdf5::filters::blosc_set_nthreads;
 use ndarray::prelude::*;
+use std::collections::BTreeMap;
 use std::time::Instant;
-const BUFFERSIZE: usize = 20000;
+/// Number of timesteps read at once if no other buffer size is given
+pub const DEFAULT_BUFFERSIZE: usize = 20000;
+
+/// Rows of a tracer before and after a timestep and the weight of the row after it
+type Sample = Option<(usize, usize, f64)>;
+
+/// How tracers located at different instants are placed onto the timesteps of a file.
+/// In multi-tracer PEPT every tracer has its own time dataset, the timesteps follow the
+/// "time array" of the file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TracerSync {
+    /// interpolate linearly between the locations before and after a timestep
+    Interpolate,
+    /// use the location closest to a timestep if it is at most half of the window away
+    Window(f64),
+}
+
+impl TracerSync {
+    /// Return the rows of a tracer located at `tracer_time` that are needed for the
+    /// timesteps at `targets` and one sample per timestep, relative to the first row.
+    /// Timesteps without a location of the tracer have no sample.
+    fn sample(&self, tracer_time: &[f64], targets: &[f64]) -> ((usize, usize), Vec<Sample>) {
+        let margin = match self {
+            TracerSync::Interpolate => 0.0,
+            TracerSync::Window(window) => 0.5 * window,
+        };
+        let (first, last) = match (targets.first(), targets.last()) {
+            (Some(&first), Some(&last)) => (first - margin, last + margin),
+            _ => return ((0, 0), vec![]),
+        };
+        // one more row on each side to interpolate at the edges of the timesteps
+        let start = tracer_time
+            .partition_point(|&time| time < first)
+            .saturating_sub(1);
+        let end = (tracer_time.partition_point(|&time| time <= last) + 1).min(tracer_time.len());
+        let times = &tracer_time[start..end];
+        let samples = targets
+            .iter()
+            .map(|&target| {
+                let after = times.partition_point(|&time| time <= target);
+                let before = after.checked_sub(1);
+                match self {
+                    TracerSync::Interpolate => {
+                        let before = before?;
+                        if times[before] == target {
+                            return Some((before, before, 0.0));
+                        }
+                        let span = times.get(after)? - times[before];
+                        Some((before, after, (target - times[before]) / span))
+                    }
+                    TracerSync::Window(_) => [before, Some(after)]
+                        .into_iter()
+                        .flatten()
+                        .filter(|&row| row < times.len())
+                        .map(|row| (row, (times[row] - target).abs()))
+                        .filter(|&(_, distance)| distance <= margin)
+                        .min_by(|a, b| a.1.total_cmp(&b.1))
+                        .map(|(row, _)| (row, row, 0.0)),
+                }
+            })
+            .collect();
+        ((start, end), samples)
+    }
+}
 
 //#[pyclass]
 pub struct PData {
     // A data managing system for HDF5 files in the
     // H5Part format or H5TimePart information
     file: hdf5::File,
-    buffer: Vec<Timestep>, // Replace with Option(Timestep, None)
-    number_buffers: usize, // number of extra buffers
-    extra_buffers: Vec<Vec<Timestep>>,
-    range: (usize, usize), // current range of timesteps loaded
-    range_extra: Vec<(usize, usize)>,
+    /// chunks of timesteps read so far
+    cache: TimestepCache,
+    /// maximum number of timesteps read from the file at once
     pub buffersize: usize,
-    pub buffersize_extra: Vec<usize>,
-    single_data: Timestep,
     global_stats_: GlobalStats,
-    rotation_is_set: bool,
-    rotation: [f64; 3],
-    rotation_center: [f64; 3],
-    external_buffer_names: Vec<usize>,
+    /// transform applied to every timestep when it is read
+    transform: Transform,
+    /// time of every particle if the particles are located at different instants
+    tracer_times: Option<Vec<Vec<f64>>>,
+    tracer_sync: TracerSync,
+    /// ID of every particle, read from its "id" dataset or its group index
+    particle_ids: Vec<f64>,
 }
 
 impl PData {
     ///new conste
-    pub fn new(filename: &str) -> Self {
+    pub fn new(filename: &str) -> Result<Self> {
+        PData::with_buffersize(filename, DEFAULT_BUFFERSIZE)
+    }
+
+    /// Open a file and read `buffersize` timesteps at once
+    pub fn with_buffersize(filename: &str, buffersize: usize) -> Result<Self> {
+        PData::with_cache(filename, buffersize, DEFAULT_CACHE_MEMORY)
+    }
+
+    /// Open a file, read `buffersize` timesteps at once and keep chunks of timesteps
+    /// using up to `cache_memory` bytes
+    pub fn with_cache(filename: &str, buffersize: usize, cache_memory: usize) -> Result<Self> {
         #[cfg(feature = "blosc")]
         blosc_set_nthreads(8);
         print_debug!(
             "PData: Generating new instance with file: {}\
             and buffersize: {}",
             &filename,
-            BUFFERSIZE
+            buffersize
         );
-        let file = hdf5::File::open(filename)
-            .unwrap_or_else(|_| panic!("Can not read HDF5 file {}. ", &filename));
-        let buffer = vec![Timestep::default(); BUFFERSIZE];
+        if buffersize == 0 {
+            return Err(Up4Error::InvalidArgument(
+                "The buffer size must be at least one timestep".to_string(),
+            ));
+        }
+        let file = hdf5::File::open(filename).map_err(|source| Up4Error::FileOpen {
+            filename: filename.to_string(),
+            source,
+        })?;
+        FileLayout::PData.check(&file)?;
+        let global_stats_ = read_global_stats(&file)?;
+        let tracer_times = read_tracer_times(&file, *global_stats_.nparticles())?;
+        let particle_ids = read_particle_ids(&file, *global_stats_.nparticles())?;
         let mut data = PData {
             file,
-            buffer,
-            number_buffers: 0,
-            extra_buffers: vec![],
-            range: (0, 0),
-            range_extra: vec![],
-            buffersize: BUFFERSIZE,
-            buffersize_extra: vec![],
-            single_data: Timestep::default(),
-            global_stats_: GlobalStats::default(),
-            rotation_is_set: false,
-            rotation: [0.0, 0.0, 0.0],
-            rotation_center: [0.0, 0.0, 0.0],
-            external_buffer_names: vec![],
+            cache: TimestepCache::new(buffersize, cache_memory),
+            buffersize,
+            global_stats_,
+            transform: Transform::identity(),
+            tracer_times,
+            tracer_sync: TracerSync::Interpolate,
+            particle_ids,
         };
         print_debug!(
             "PData: Generation complete. First buffer update starting, buffer size: {}",
-            data.buffer.len()
+            data.buffersize
         );
 
-        let global_stats = data.global_stats();
-        data.global_stats_ = global_stats;
-        data.update((0, data.buffersize));
+        if *data.global_stats_.timesteps() > 0 {
+            data.get_timestep(0)?;
+        }
+        if data.global_stats_.particle_types.is_empty() {
+            data.global_stats_.particle_types = data.count_particle_types()?;
+        }
 
         print_debug!("PData: Buffer updated. Returning initiated PData.");
-        data
+        Ok(data)
     }
+
     #[inline(never)]
-    fn read_single(&self, timestep: usize) -> Timestep {
+    fn read_single(&self, timestep: usize) -> Result<Timestep> {
         print_debug!("PData: Reading a single timestep: {}", timestep);
+        let mut timesteps = self.read_range((timestep, timestep + 1))?;
+        Ok(timesteps.remove(0))
+    }
 
-        let particles = *self.global_stats_.nparticles();
-        let mut position: Array1<Position> = Array1::from_elem(particles, [0.0, 0.0, 0.0]);
-        let mut velocity: Array2<f64> = Array2::<f64>::zeros((particles, 3));
-        let mut radius: Array1<f64> = Array1::<f64>::zeros(particles);
-        let mut ptype: Array1<f64> = Array1::<f64>::zeros(particles);
-        let mut density: Array1<f64> = Array1::<f64>::zeros(particles);
-        let mut particleid: Array1<f64> = Array1::<f64>::zeros(particles);
-        let mut time: Array1<f64> = Array1::<f64>::zeros(particles);
-        let clouds: Array1<f64> = Array1::<f64>::ones(particles);
-        print_debug!("PData: Looping over all particles.");
-        for particle_id in 0..particles {
-            print_debug!("\tParticle {}: Extracting position from HDF5.", particle_id);
-            let part_pos = self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find group \"particle {}\" in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("position")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"position\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .read_2d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read dataset \"position\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .slice(s![timestep, ..])
-                .to_owned()
-                .into_shape((particles, 3))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not shape dataset \"position\" in file {} into a ({}, 3) array",
-                        self.file.filename(),
-                        particles
-                    )
-                })
-                .axis_iter(Axis(0))
-                .map(|x| [x[0], x[1], x[2]])
-                .collect::<Array1<Position>>();
-            // iter over elements in 2D array. make  it a 1D array of static arrays with 3 elements.
-
-            print_debug!("\tParticle {}: Extracting velocity from HDF5.", particle_id);
-            let part_vel = self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find group \"particle {}\" in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("velocity")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"velocity\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .read_2d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read dataset \"velocity\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .slice(s![timestep, ..])
-                .to_owned();
-
-            //Radius
-            print_debug!("\tParticle {}: Extracting radius from HDF5.", particle_id);
-            let part_rad = self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not fine particle {} in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("radius")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"radius\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"radius\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned()[timestep];
-
-            let part_type = self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not fine particle {} in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("particletype")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"particletype\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"particletype\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned()[timestep];
-            let part_id = self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not fine particle {} in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("id")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"id\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"id\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned()[timestep];
-
-            //.slice(s![timestep])
-
-            print_debug!("\tParticle {}: Extracting density from HDF5.", particle_id);
-            let part_density = match self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find particle {} in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("density")
-            {
-                Ok(s) => s
-                    .read_1d::<f64>()
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "Can not read data from \"density\" dataset. \
-                        Data type or data format might be wrong. \
-                        Check creation of HDF5 file  \"{:?}\"",
-                            self.file.filename()
-                        )
-                    })
-                    .to_owned()[timestep],
-                Err(_) => {
-                    print_warning!(
-                        "\tParticle {}: Could not find density in HDF5 file {}. \
-                        Using one instead.",
-                        particle_id,
-                        self.file.filename()
-                    );
-                    part_rad
-                }
-            };
-            print_debug!("\tParticle {}: Extracting time from HDF5.", particle_id);
-            let part_time = self
-                .file
-                .group(&format!("particle {}", 0))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not fine particle {} in file {}",
-                        0,
-                        self.file.filename()
-                    )
-                })
-                .dataset("time")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"time\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"time\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned()[timestep];
-
-            //position.slice_mut(s![particle_id]).assign(&part_pos);
-            position[particle_id] = part_pos[particle_id];
-            velocity.slice_mut(s![particle_id, ..]).assign(&part_vel);
-
-            //radius.slice_mut(s![particle_id]).assign(&part_rad);
-            radius[particle_id] = part_rad;
-            ptype[particle_id] = part_type;
-            //density.slice_mut(s![particle_id]).assign(&part_density);
-            density[particle_id] = part_density;
-            //time.slice_mut(s![particle_id]).assign(&part_time);
-            time[particle_id] = part_time;
-            //particleid.slice_mut(s![particle_id]).assign(&part_id);
-            particleid[particle_id] = part_id;
+    /// Read an optional per-particle dataset in `range`. If the dataset does not exist
+    /// it is replaced by zeros.
+    fn read_optional(
+        &self,
+        particle: &hdf5::Group,
+        name: &str,
+        range: (usize, usize),
+    ) -> Result<Array1<f64>> {
+        let filename = self.file.filename();
+        match particle.dataset(name) {
+            Ok(data) => read_rows(&data, range, name, &filename),
+            Err(_) => {
+                print_warning!(
+                    "\t{}: Could not find {} in HDF5 file {}. \
+                    Using zeros instead.",
+                    particle.name(),
+                    name,
+                    filename
+                );
+                Ok(Array1::<f64>::zeros(range.1 - range.0))
+            }
         }
+    }
 
-        print_debug!("PData: Data read. Saving new timestap and return reference.");
-        //let dt = Timestep::default();
-        //self.single_data = dt;
-        Timestep {
-            time: time[0],
-            position,
-            velocity,
-            radius,
-            particleid,
-            clouds,
-            density,
-            ptype,
+    /// Count the particles of each type. The type of a particle does not change, so only
+    /// the first entry of each particle is read.
+    fn count_particle_types(&self) -> Result<BTreeMap<usize, usize>> {
+        if *self.global_stats_.timesteps() == 0 {
+            return Ok(BTreeMap::new());
         }
+        let ptypes = (0..*self.global_stats_.nparticles())
+            .map(|particle_id| {
+                let particle = group(&self.file, &format!("particle {}", particle_id))?;
+                Ok(self.read_optional(&particle, "particletype", (0, 1))?[0])
+            })
+            .collect::<Result<Vec<f64>>>()?;
+        Ok(count_types(ptypes))
     }
 
-    pub fn reset_buffer() {
-        //TODO
-        unimplemented!("Buffer reset not implemented yet.")
-    }
+    /// Read the timesteps `range.0..range.1` of all particles. Only the requested
+    /// rows are read from the file.
     #[inline(never)]
-    pub fn update(&mut self, mut range: (usize, usize)) {
-        print_debug!("PData: Updating buffer");
-        let timesteps = *self.global_stats_.timesteps();
-        if range.1 >= timesteps {
-            range.1 = timesteps;
-        }
-        self.buffersize = range.1 - range.0;
+    fn read_range(&self, range: (usize, usize)) -> Result<Vec<Timestep>> {
+        let filename = self.file.filename();
+        let length = range.1 - range.0;
         let particles = *self.global_stats_.nparticles();
         let mut position: Array2<Position> =
-            Array2::<Position>::from_elem((self.buffersize, particles), [0.0, 0.0, 0.0]);
-        let mut velocity: Array3<f64> = Array3::<f64>::zeros((self.buffersize, particles, 3));
-        let mut radius: Array2<f64> = Array2::<f64>::zeros((self.buffersize, particles));
-        let mut ptype: Array2<f64> = Array2::<f64>::zeros((self.buffersize, particles));
-        let mut density: Array2<f64> = Array2::<f64>::zeros((self.buffersize, particles));
-        let mut particleid: Array2<f64> = Array2::<f64>::zeros((self.buffersize, particles));
-        let mut time: Array2<f64> = Array2::<f64>::zeros((self.buffersize, particles));
-        let clouds: Array2<f64> = Array2::<f64>::ones((self.buffersize, particles));
+            Array2::<Position>::from_elem((length, particles), [0.0, 0.0, 0.0]);
+        let mut velocity: Array3<f64> = Array3::<f64>::zeros((length, particles, 3));
+        let mut radius: Array2<f64> = Array2::<f64>::zeros((length, particles));
+        let mut ptype: Array2<f64> = Array2::<f64>::zeros((length, particles));
+        let mut density: Array2<f64> = Array2::<f64>::zeros((length, particles));
+        let mut particleid: Array2<f64> = Array2::<f64>::zeros((length, particles));
+        let clouds: Array2<f64> = Array2::<f64>::ones((length, particles));
+        // additional fields with the shape (timestep, particle, component)
+        let mut fields: BTreeMap<String, Array3<f64>> = BTreeMap::new();
+
+        print_debug!("PData: Extracting time from HDF5.");
+        let time = match &self.tracer_times {
+            // tracers with their own time are placed onto the time array of the file
+            Some(_) => self
+                .global_stats_
+                .time_array
+                .slice(s![range.0..range.1])
+                .to_owned(),
+            None => read_rows(
+                &dataset(&group(&self.file, "particle 0")?, "time")?,
+                range,
+                "time",
+                &filename,
+            )?,
+        };
+        let targets = time.to_vec();
+
         print_debug!("PData: Looping over all particles:");
         for particle_id in 0..particles {
-            // for each particle find the Position at the
-            print_debug!("\tParticle {}: Extracting position from HDF5.", particle_id);
-            let part_pos = self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| panic!("Can not find group \"particle {}\" in file {}",
-                    particle_id,
-                    self.file.filename()))
-                .dataset("position")
-                .unwrap_or_else(|_| panic!("Can not find dataset \"position\" in file {}",
-                    self.file.filename()))
-                .read_2d::<f64>()
-                .unwrap_or_else(|_| panic!("Can not read dataset \"position\" in file {}",
-                    self.file.filename()))
-                .slice(s![range.0..range.1, ..])
-                .to_owned()
-                .into_shape((range.1-range.0, 3))
-                .unwrap_or_else(|_| panic!("Can not shape dataset \"position\" in file {} into a ({}, 3) array, range: {:?}",
-                    self.file.filename(),
-                    range.1-range.0,
-                    range))
-                .axis_iter(Axis(0))
-                .map(|x| [x[0], x[1], x[2]])
-                .collect::<Array1<Position>>();
-
-            // for each particle find the velocuty at the
-            print_debug!("\tParticle {}: Extracting velocity from HDF5.", particle_id);
-            let part_vel = self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find group \"particle {}\" in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("velocity")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"velocity\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .read_2d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read dataset \"velocity\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .slice(s![range.0..range.1, ..])
-                .to_owned();
-
-            //Radius
-            print_debug!("\tParticle {}: Extracting radius from HDF5.", particle_id);
-            let part_rad = match self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find particle {} in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("radius")
-            {
-                Ok(s) => s
-                    .read_1d::<f64>()
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "Can not read data from \"radius\" dataset. \
-                        Data type or data format might be wrong. \
-                        Check creation of HDF5 file  \"{:?}\"",
-                            self.file.filename()
-                        )
-                    })
-                    .slice(s![range.0..range.1])
-                    .to_owned(),
-                Err(_) => {
-                    print_warning!(
-                        "\tParticle {}: Could not find radius in HDF5 file {}. \
-                        Using zeros instead.",
-                        particle_id,
-                        self.file.filename()
-                    );
-                    Array1::<f64>::zeros(part_vel.len() / 3)
+            let particle = group(&self.file, &format!("particle {}", particle_id))?;
+            // rows of this particle and, if it has its own time, their samples at the
+            // timesteps in `range`
+            let (rows, samples) = match &self.tracer_times {
+                Some(tracer_times) => {
+                    let (rows, samples) =
+                        self.tracer_sync.sample(&tracer_times[particle_id], &targets);
+                    (rows, Some(samples))
                 }
+                None => (range, None),
             };
-
-            let part_type = match self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find particle {} in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("particletype")
-            {
-                Ok(s) => s
-                    .read_1d::<f64>()
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "Can not read data from \"particletype\" dataset. \
-                        Data type or data format might be wrong. \
-                        Check creation of HDF5 file  \"{:?}\"",
-                            self.file.filename()
-                        )
-                    })
-                    .slice(s![range.0..range.1])
-                    .to_owned(),
-                Err(_) => {
-                    print_warning!(
-                        "\tParticle {}: Could not find particletype in HDF5 file {}. \
-                        Using zeros instead.",
-                        particle_id,
-                        self.file.filename()
-                    );
-                    Array1::<f64>::zeros(part_vel.len() / 3)
-                }
-            };
-            print_debug!("\tParticle {}: Extracting density from HDF5.", particle_id);
-            let part_density = match self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find particle {} in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("density")
-            {
-                Ok(s) => s
-                    .read_1d::<f64>()
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "Can not read data from \"density\" dataset. \
-                        Data type or data format might be wrong. \
-                        Check creation of HDF5 file  \"{:?}\"",
-                            self.file.filename()
-                        )
-                    })
-                    .slice(s![range.0..range.1])
-                    .to_owned(),
-                Err(_) => {
-                    print_warning!(
-                        "\tParticle {}: Could not find density in HDF5 file {}. \
-                        Using one instead.",
-                        particle_id,
-                        self.file.filename()
-                    );
-                    Array1::<f64>::zeros(part_vel.len() / 3)
-                }
+            let on_timesteps = |values: Array2<f64>| match &samples {
+                Some(samples) => resample(&values, samples),
+                None => values,
             };
-
-            print_debug!("\tParticle {}: Extracting time from HDF5.", particle_id);
-            let part_time = self
-                .file
-                .group(&format!("particle {}", 0))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not fine particle {} in file {}",
-                        0,
-                        self.file.filename()
-                    )
-                })
-                .dataset("time")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"time\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"time\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .slice(s![range.0..range.1])
-                .to_owned();
-
-            print_debug!("\tParticle {}: generating particle ID array.", particle_id);
-            let mut part_id: Array1<f64> = Array1::<f64>::zeros(self.buffersize);
-            part_id.fill(particle_id as f64);
-
-            print_debug!(
-                "\tParticle {}: Inserting all information in Arrays.",
-                particle_id
-            );
-            position.slice_mut(s![.., particle_id]).assign(&part_pos);
-            velocity
-                .slice_mut(s![.., particle_id, ..])
-                .assign(&part_vel);
-            radius.slice_mut(s![.., particle_id]).assign(&part_rad);
-            ptype.slice_mut(s![.., particle_id]).assign(&part_type);
-            density.slice_mut(s![.., particle_id]).assign(&part_density);
-            time.slice_mut(s![.., particle_id]).assign(&part_time);
-            particleid.slice_mut(s![.., particle_id]).assign(&part_id);
-            print_debug!("\tParticle {}: Finish.", particle_id);
-        }
-        //let mut  clouds = particleid.clone();
-        //clouds.fill(1.);
-
-        print_debug!("PData: Generating Timestep structs.");
-        for timestep in 0..range.1 - range.0 {
-            let dt = Timestep {
-                time: time[[timestep, 0]], //.slice(s![timestep,..]),
-                position: position.slice(s![timestep, ..]).to_owned(),
-                velocity: velocity.slice(s![timestep, .., ..]).to_owned(),
-                radius: radius.slice(s![timestep, ..]).to_owned(),
-                particleid: particleid.slice(s![timestep, ..]).to_owned(),
-                clouds: clouds.slice(s![timestep, ..]).to_owned(),
-                density: density.slice(s![timestep, ..]).to_owned(),
-                ptype: ptype.slice(s![timestep, ..]).to_owned(),
+            let on_timesteps_1d = |values: Array1<f64>| {
+                on_timesteps(values.insert_axis(Axis(1))).remove_axis(Axis(1))
             };
-
-            self.buffer[timestep] = dt;
-        }
-        self.range = range; //(range.0,range.1-1);
-        print_debug!("PData: Finished buffer update")
-    }
-
-    /// Update   of the extra buffers
-    #[inline(never)]
-    pub fn update_extra(&mut self, mut range: (usize, usize), buffer_id: usize) {
-        print_debug!("PData: Updating Extra buffer");
-        let timesteps = *self.global_stats_.timesteps();
-        if range.1 >= timesteps {
-            range.1 = timesteps;
-        }
-        self.buffersize_extra[buffer_id] = range.1 - range.0;
-        let particles = *self.global_stats_.nparticles();
-        let mut position: Array2<Position> = Array2::<Position>::from_elem(
-            (self.buffersize_extra[buffer_id], particles),
-            [0.0, 0.0, 0.0],
-        );
-        let mut velocity: Array3<f64> =
-            Array3::<f64>::zeros((self.buffersize_extra[buffer_id], particles, 3));
-        let mut radius: Array2<f64> =
-            Array2::<f64>::zeros((self.buffersize_extra[buffer_id], particles));
-        let mut ptype: Array2<f64> =
-            Array2::<f64>::zeros((self.buffersize_extra[buffer_id], particles));
-        let mut density: Array2<f64> =
-            Array2::<f64>::zeros((self.buffersize_extra[buffer_id], particles));
-        let mut particleid: Array2<f64> =
-            Array2::<f64>::zeros((self.buffersize_extra[buffer_id], particles));
-        let mut time: Array2<f64> =
-            Array2::<f64>::zeros((self.buffersize_extra[buffer_id], particles));
-        let clouds: Array2<f64> =
-            Array2::<f64>::ones((self.buffersize_extra[buffer_id], particles));
-        print_debug!("PData: Looping over all particles:");
-        for particle_id in 0..particles {
             // for each particle find the Position at the
             print_debug!("\tParticle {}: Extracting position from HDF5.", particle_id);
-            let part_pos = self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| panic!("Can not find group \"particle {}\" in file {}",
-                    particle_id,
-                    self.file.filename()))
-                .dataset("position")
-                .unwrap_or_else(|_| panic!("Can not find dataset \"position\" in file {}",
-                    self.file.filename()))
-                .read_2d::<f64>()
-                .unwrap_or_else(|_| panic!("Can not read dataset \"position\" in file {}",
-                    self.file.filename()))
-                .slice(s![range.0..range.1, ..])
-                .to_owned()
-                .into_shape((range.1-range.0, 3))
-                .unwrap_or_else(|_| panic!("Can not shape dataset \"position\" in file {} into a ({}, 3) array, range: {:?}",
-                    self.file.filename(),
-                    range.1-range.0,
-                    range))
-                .axis_iter(Axis(0))
-                .map(|x| [x[0], x[1], x[2]])
-                .collect::<Array1<Position>>();
+            let part_pos = on_timesteps(read_vector_rows(
+                &dataset(&particle, "position")?,
+                rows,
+                "position",
+                &filename,
+            )?)
+            .axis_iter(Axis(0))
+            .map(|x| [x[0], x[1], x[2]])
+            .collect::<Array1<Position>>();
 
             // for each particle find the velocuty at the
             print_debug!("\tParticle {}: Extracting velocity from HDF5.", particle_id);
-            let part_vel = self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find group \"particle {}\" in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("velocity")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"velocity\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .read_2d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read dataset \"velocity\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .slice(s![range.0..range.1, ..])
-                .to_owned();
+            let part_vel = on_timesteps(read_vector_rows(
+                &dataset(&particle, "velocity")?,
+                rows,
+                "velocity",
+                &filename,
+            )?);
 
-            //Radius
-            print_debug!("\tParticle {}: Extracting radius from HDF5.", particle_id);
-            let part_rad = match self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find particle {} in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("radius")
-            {
-                Ok(s) => s
-                    .read_1d::<f64>()
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "Can not read data from \"radius\" dataset. \
-                        Data type or data format might be wrong. \
-                        Check creation of HDF5 file  \"{:?}\"",
-                            self.file.filename()
-                        )
-                    })
-                    .slice(s![range.0..range.1])
-                    .to_owned(),
-                Err(_) => {
-                    print_warning!(
-                        "\tParticle {}: Could not find radius in HDF5 file {}. \
-                        Using zeros instead.",
-                        particle_id,
-                        self.file.filename()
-                    );
-                    Array1::<f64>::zeros(part_vel.len() / 3)
-                }
-            };
-
-            let part_type = match self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find particle {} in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("particletype")
-            {
-                Ok(s) => s
-                    .read_1d::<f64>()
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "Can not read data from \"particletype\" dataset. \
-                        Data type or data format might be wrong. \
-                        Check creation of HDF5 file  \"{:?}\"",
-                            self.file.filename()
-                        )
-                    })
-                    .slice(s![range.0..range.1])
-                    .to_owned(),
-                Err(_) => {
-                    print_warning!(
-                        "\tParticle {}: Could not find particletype in HDF5 file {}. \
-                        Using zeros instead.",
-                        particle_id,
-                        self.file.filename()
-                    );
-                    Array1::<f64>::zeros(part_vel.len() / 3)
-                }
-            };
-            print_debug!("\tParticle {}: Extracting density from HDF5.", particle_id);
-            let part_density = match self
-                .file
-                .group(&format!("particle {}", particle_id))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find particle {} in file {}",
-                        particle_id,
-                        self.file.filename()
-                    )
-                })
-                .dataset("density")
-            {
-                Ok(s) => s
-                    .read_1d::<f64>()
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "Can not read data from \"density\" dataset. \
-                        Data type or data format might be wrong. \
-                        Check creation of HDF5 file  \"{:?}\"",
-                            self.file.filename()
-                        )
-                    })
-                    .slice(s![range.0..range.1])
-                    .to_owned(),
-                Err(_) => {
-                    print_warning!(
-                        "\tParticle {}: Could not find density in HDF5 file {}. \
-                        Using one instead.",
-                        particle_id,
-                        self.file.filename()
-                    );
-                    Array1::<f64>::zeros(part_vel.len() / 3)
+            print_debug!(
+                "\tParticle {}: Extracting radius, type and density from HDF5.",
+                particle_id
+            );
+            let part_rad = on_timesteps_1d(self.read_optional(&particle, "radius", rows)?);
+            let part_type = on_timesteps_1d(self.read_optional(&particle, "particletype", rows)?);
+            let part_density = on_timesteps_1d(self.read_optional(&particle, "density", rows)?);
+
+            for name in field_names(&particle)? {
+                let field = on_timesteps(read_field(
+                    &dataset(&particle, &format!("fields/{}", name))?,
+                    Some(rows),
+                    &name,
+                    &filename,
+                )?);
+                // particles that do not carry this field are left at NaN
+                let all_particles = fields.entry(name.clone()).or_insert_with(|| {
+                    Array3::<f64>::from_elem((length, particles, field.ncols()), f64::NAN)
+                });
+                if all_particles.shape()[2] != field.ncols() {
+                    return Err(Up4Error::MalformedShape {
+                        name: format!("particle {}/fields/{}", particle_id, name),
+                        filename,
+                        expected: format!("{} components", all_particles.shape()[2]),
+                    });
                 }
-            };
-
-            print_debug!("\tParticle {}: Extracting time from HDF5.", particle_id);
-            let part_time = self
-                .file
-                .group(&format!("particle {}", 0))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not fine particle {} in file {}",
-                        0,
-                        self.file.filename()
-                    )
-                })
-                .dataset("time")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"time\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"time\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .slice(s![range.0..range.1])
-                .to_owned();
+                all_particles
+                    .slice_mut(s![.., particle_id, ..])
+                    .assign(&field);
+            }
 
             print_debug!("\tParticle {}: generating particle ID array.", particle_id);
-            let mut part_id: Array1<f64> = Array1::<f64>::zeros(self.buffersize_extra[buffer_id]);
-            part_id.fill(particle_id as f64);
+            let mut part_id: Array1<f64> = Array1::<f64>::zeros(length);
+            part_id.fill(self.particle_ids[particle_id]);
 
             print_debug!(
                 "\tParticle {}: Inserting all information in Arrays.",
@@ -832,17 +330,14 @@ impl PData {
             radius.slice_mut(s![.., particle_id]).assign(&part_rad);
             ptype.slice_mut(s![.., particle_id]).assign(&part_type);
             density.slice_mut(s![.., particle_id]).assign(&part_density);
-            time.slice_mut(s![.., particle_id]).assign(&part_time);
             particleid.slice_mut(s![.., particle_id]).assign(&part_id);
             print_debug!("\tParticle {}: Finish.", particle_id);
         }
-        //let mut  clouds = particleid.clone();
-        //clouds.fill(1.);
 
         print_debug!("PData: Generating Timestep structs.");
-        for timestep in 0..range.1 - range.0 {
-            let dt = Timestep {
-                time: time[[timestep, 0]], //.slice(s![timestep,..]),
+        let timesteps = (0..length)
+            .map(|timestep| Timestep {
+                time: time[timestep],
                 position: position.slice(s![timestep, ..]).to_owned(),
                 velocity: velocity.slice(s![timestep, .., ..]).to_owned(),
                 radius: radius.slice(s![timestep, ..]).to_owned(),
@@ -850,17 +345,62 @@ impl PData {
                 clouds: clouds.slice(s![timestep, ..]).to_owned(),
                 density: density.slice(s![timestep, ..]).to_owned(),
                 ptype: ptype.slice(s![timestep, ..]).to_owned(),
-            };
+                fields: fields
+                    .iter()
+                    .map(|(name, field)| {
+                        (name.clone(), field.slice(s![timestep, .., ..]).to_owned())
+                    })
+                    .collect(),
+            })
+            .map(|mut timestep| {
+                self.transform.apply(&mut timestep);
+                timestep
+            })
+            .collect();
+        Ok(timesteps)
+    }
+
+    /// Drop all timesteps kept in memory
+    pub fn reset_buffer(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Return true if the particles are located at different instants and are placed
+    /// onto the timesteps with the `TracerSync` of this dataset
+    pub fn has_tracer_times(&self) -> bool {
+        self.tracer_times.is_some()
+    }
 
-            //self.buffer[timestep] = dt;
-            self.extra_buffers[buffer_id][timestep] = dt;
+    /// Change how tracers with their own time are placed onto the timesteps. Files in which
+    /// all particles share one time are not affected.
+    pub fn set_tracer_sync(&mut self, tracer_sync: TracerSync) -> Result<()> {
+        if let TracerSync::Window(window) = tracer_sync {
+            if !(window > 0.0 && window.is_finite()) {
+                return Err(Up4Error::InvalidArgument(format!(
+                    "The time window must be positive, got {}",
+                    window
+                )));
+            }
         }
+        self.tracer_sync = tracer_sync;
+        // the cache holds timesteps placed with the previous setting
+        self.cache.clear();
+        Ok(())
+    }
 
-        self.range_extra[buffer_id] = range; //(range.0,range.1-1);
-        print_debug!("PData: Finished buffer update")
+    /// Return an error if `timestep` is not part of the dataset
+    fn check_timestep(&self, timestep: usize) -> Result<()> {
+        let timesteps = *self.global_stats_.timesteps();
+        if timestep >= timesteps {
+            return Err(Up4Error::TimestepOutOfRange {
+                timestep,
+                timesteps,
+            });
+        }
+        Ok(())
     }
 
-    pub fn test(&mut self) {
+    pub fn test(&mut self) -> Result<()> {
         let now = Instant::now();
         let _time = self.global_stats_.max_time();
         let mut v = 0.;
@@ -871,18 +411,25 @@ impl PData {
             vec![f64::MIN, f64::MAX],
             vec![f64::MIN, f64::MAX],
             vec![usize::MIN, usize::MAX],
+            vec![-1.],
         );
         for timestep in 0..*self.global_stats_.timesteps() {
-            let timestep_data = self.get_timestep(timestep);
+            let timestep_data = self.get_timestep(timestep)?;
             let _positions: &Array1<Position> = timestep_data.position();
             let velocity: &Array2<f64> = timestep_data.velocity();
             let radius: &Array1<f64> = timestep_data.radius();
             let particleid: &Array1<f64> = timestep_data.particleid();
             let clouds: &Array1<f64> = timestep_data.clouds();
+            let ptype: &Array1<f64> = timestep_data.ptype();
             let density: Array1<f64> = Array1::from_elem(clouds.len(), 1000.0);
             for (id, vel) in velocity.outer_iter().enumerate() {
-                if !selector.is_valid(radius[id], clouds[id], density[id], particleid[id] as usize)
-                {
+                if !selector.is_valid(
+                    radius[id],
+                    clouds[id],
+                    density[id],
+                    particleid[id] as usize,
+                    ptype[id],
+                ) {
                     continue;
                 }
                 if vel[0_usize].is_nan() {
@@ -897,176 +444,173 @@ impl PData {
         println!(
             "Vel from global STats: {}",
             self.global_stats_.velocity()[[0, 1]]
-        )
+        );
+        Ok(())
     }
 } //end impl
 
+/// Return the time of every particle if the particles are located at different instants.
+/// Particles share one time if their time datasets have the same length and the same
+/// first and last entry as the one of particle 0, only in the other case all times are
+/// read.
+fn read_tracer_times(file: &hdf5::File, particles: usize) -> Result<Option<Vec<Vec<f64>>>> {
+    let filename = file.filename();
+    let time_datasets = (0..particles)
+        .map(|particle_id| dataset(&group(file, &format!("particle {}", particle_id))?, "time"))
+        .collect::<Result<Vec<_>>>()?;
+    let bounds = |data: &hdf5::Dataset| -> Result<Option<(f64, f64)>> {
+        let rows = data.shape().first().copied().unwrap_or(0);
+        if rows == 0 {
+            return Ok(None);
+        }
+        let first = read_rows(data, (0, 1), "time", &filename)?[0];
+        let last = read_rows(data, (rows - 1, rows), "time", &filename)?[0];
+        Ok(Some((first, last)))
+    };
+    let reference = match time_datasets.first() {
+        Some(data) => (data.shape(), bounds(data)?),
+        None => return Ok(None),
+    };
+    let mut shared = true;
+    for data in time_datasets.iter().skip(1) {
+        if (data.shape(), bounds(data)?) != reference {
+            shared = false;
+            break;
+        }
+    }
+    if shared {
+        return Ok(None);
+    }
+    print_debug!("PData: Particles are located at different instants.");
+    time_datasets
+        .iter()
+        .map(|data| {
+            data.read_raw::<f64>()
+                .map_err(|e| Up4Error::read("time", &filename, e))
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(Some)
+}
+
+/// Return the ID of every particle. The ID does not change, so it is taken from the first
+/// entry of the "id" dataset of a particle. Particles without IDs, e.g. in files of older
+/// converters, are identified by the index of their group.
+fn read_particle_ids(file: &hdf5::File, particles: usize) -> Result<Vec<f64>> {
+    let filename = file.filename();
+    (0..particles)
+        .map(|particle_id| {
+            let particle = group(file, &format!("particle {}", particle_id))?;
+            let id = match particle.dataset("id") {
+                Ok(data) if data.shape().first().copied().unwrap_or(0) > 0 => {
+                    read_rows(&data, (0, 1), "id", &filename)?[0]
+                }
+                _ => f64::NAN,
+            };
+            Ok(if id.is_nan() { particle_id as f64 } else { id })
+        })
+        .collect()
+}
+
+/// Values of a tracer at the timesteps given by `samples`, timesteps without a location
+/// of the tracer are NaN
+fn resample(values: &Array2<f64>, samples: &[Sample]) -> Array2<f64> {
+    Array2::from_shape_fn((samples.len(), values.ncols()), |(row, column)| {
+        match samples[row] {
+            Some((before, after, weight)) => {
+                let before = values[[before, column]];
+                before + weight * (values[[after, column]] - before)
+            }
+            None => f64::NAN,
+        }
+    })
+}
+
+/// Make sure a per-particle dataset with `rows` entries covers `range`
+fn check_range(rows: usize, range: (usize, usize), name: &str, filename: &str) -> Result<()> {
+    if rows < range.1 {
+        return Err(Up4Error::MalformedShape {
+            name: name.to_string(),
+            filename: filename.to_string(),
+            expected: format!("at least {} entries", range.1),
+        });
+    }
+    Ok(())
+}
+
+/// Read the rows `range.0..range.1` of a per-particle scalar dataset
+fn read_rows(
+    data: &hdf5::Dataset,
+    range: (usize, usize),
+    name: &str,
+    filename: &str,
+) -> Result<Array1<f64>> {
+    let shape = data.shape();
+    if shape.len() != 1 {
+        return Err(Up4Error::MalformedShape {
+            name: name.to_string(),
+            filename: filename.to_string(),
+            expected: "(N,)".to_string(),
+        });
+    }
+    check_range(shape[0], range, name, filename)?;
+    data.read_slice_1d::<f64, _>(s![range.0..range.1])
+        .map_err(|e| Up4Error::read(name, filename, e))
+}
+
+/// Read the rows `range.0..range.1` of a per-particle vector dataset with the shape (N, 3)
+fn read_vector_rows(
+    data: &hdf5::Dataset,
+    range: (usize, usize),
+    name: &str,
+    filename: &str,
+) -> Result<Array2<f64>> {
+    let shape = data.shape();
+    if shape.len() != 2 || shape[1] != 3 {
+        return Err(Up4Error::MalformedShape {
+            name: name.to_string(),
+            filename: filename.to_string(),
+            expected: "(N, 3)".to_string(),
+        });
+    }
+    check_range(shape[0], range, name, filename)?;
+    data.read_slice_2d::<f64, _>(s![range.0..range.1, ..])
+        .map_err(|e| Up4Error::read(name, filename, e))
+}
+
 impl DataManager for PData {
     // return a array from a given dataset at a given timestep
-    fn get_timestep(&mut self, timestep: usize) -> &Timestep {
+    fn get_timestep(&mut self, timestep: usize) -> Result<&Timestep> {
         print_debug!("PData: Extracting timestep number {}", timestep);
-        // If a timestep is requested that is higher then the current range
-        // update buffer with new range
-        print_debug!(
-            "PData: Checking requested timestep {} in range {:?}",
-            timestep,
-            self.range
-        );
-        if timestep > self.range.1 - 1 {
-            self.update((timestep, timestep + self.buffersize));
-        } else if timestep < self.range.0 {
-            let chunck = ((timestep as f64) / (BUFFERSIZE as f64)).floor() as usize;
-            self.update((chunck * BUFFERSIZE, (chunck + 1) * BUFFERSIZE));
+        self.check_timestep(timestep)?;
+        if !self.cache.contains(timestep) {
+            let range = self.cache.chunk_range(timestep, *self.global_stats_.timesteps());
+            print_debug!("PData: Reading timesteps {:?}", range);
+            let timesteps = self.read_range(range)?;
+            self.cache.insert(range.0, timesteps);
         }
-
-        &self.buffer[timestep - self.range.0]
+        Ok(self
+            .cache
+            .get(timestep)
+            .expect("the chunk of the timestep was just cached"))
     }
 
     fn global_stats(&self) -> GlobalStats {
-        let dimensions = self
-            .file
-            .attr("dimensions")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find dataset \"dimensions\" in HDF5 file \"{:?}\"",
-                    self.file.filename()
-                )
-            })
-            .read_2d::<f64>()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read data from \"dimensions\" dataset. \
-                Data type or data format might be wrong. \
-                Check creation of HDF5 file  \"{:?}\"",
-                    self.file.filename()
-                )
-            });
-        let nparticles = self
-            .file
-            .attr("particle number")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"particle number\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .read_scalar()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read scalar from attribute \"particle number\" in file {}",
-                    self.file.filename()
-                )
-            });
-        let timesteps = self
-            .file
-            .attr("timesteps")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"timesteps\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .read_scalar()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read scalar from attribute \"timesteps\" in file {}",
-                    self.file.filename()
-                )
-            });
-        let time = self
-            .file
-            .attr("time")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"time\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .read_raw()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read vector from attribute \"time\" in file {}",
-                    self.file.filename()
-                )
-            });
-        let sample_rate = self
-            .file
-            .attr("sample rate")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"sample rate\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .read_scalar()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read scalar from attribute \"sample rate\" in file {}",
-                    self.file.filename()
-                )
-            });
-        let velocity = self
-            .file
-            .attr("velocity")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"velocity\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .read_2d()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read scalar from attribute \"velocity\" in file {}",
-                    self.file.filename()
-                )
-            });
-        let velocity_mag = self
-            .file
-            .attr("velocity magnitude")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"velocity magnitude\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .read_1d()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read scalar from attribute \"velocity magnitude\" in file {}",
-                    self.file.filename()
-                )
-            });
-        let time_array = self
-            .file
-            .dataset("time array")
-            .expect(
-                "Can not find attribute \"time array\" in file, \
-            You might have a old version of a HDF5 file. please include the \
-            time as an array in attributes",
-            )
-            .read_1d()
-            .expect("Can not read vector from attribute \"time array\" in file");
-        GlobalStats {
-            dimensions,
-            nparticles,
-            ntimesteps: timesteps,
-            min_time: time[0],
-            max_time: time[1],
-            sample_rate,
-            velocity,
-            velocity_mag,
-            time_array,
-        }
+        self.transform.apply_stats(&self.global_stats_)
+    }
+
+    fn particle_types(&mut self) -> Result<BTreeMap<usize, usize>> {
+        Ok(self.global_stats_.particle_types.clone())
     }
+
     fn stats(&self) {
-        let time = self.global_stats_.max_time();
-        let dim = self.global_stats_.dimensions();
+        let global_stats = self.global_stats();
+        let time = global_stats.max_time();
+        let dim = global_stats.dimensions();
         println!("Dimensions of the system:: {:?}", dim);
         println!("The max time of this set is : {:?}", time);
-        let part_num = self.global_stats_.nparticles();
+        let part_num = global_stats.nparticles();
         println!("Number of Particles: {}", part_num);
-        let vel_mag = self.global_stats_.velocity_mag();
+        let vel_mag = global_stats.velocity_mag();
         println!("Mean velocity of: {} m/s", vel_mag[1usize]);
         println!(
             "Minimum velocity {} m/s \nMaximum Velocity {} m/s",
@@ -1074,60 +618,16 @@ impl DataManager for PData {
         );
     }
 
-    fn get_timestep_unbuffered(&self, timestep: usize) -> Timestep {
+    fn get_timestep_unbuffered(&self, timestep: usize) -> Result<Timestep> {
+        self.check_timestep(timestep)?;
         self.read_single(timestep)
     }
 
-    /// setup a new buffer
-    fn setup_buffer(&mut self, external_buffer_id: usize) {
-        let range = (0usize, BUFFERSIZE);
-        let buffer_id;
-        if self.external_buffer_names.contains(&external_buffer_id) {
-            // buffer is already know and will be reset
-            buffer_id = self
-                .external_buffer_names
-                .iter()
-                .position(|x| x == &external_buffer_id)
-                .unwrap();
-            self.range_extra[buffer_id] = range
-        } else {
-            // new buffer. Allocate new memory and load the first bit of data
-            self.extra_buffers
-                .push(vec![Timestep::default(); BUFFERSIZE]);
-            buffer_id = self.extra_buffers.len() - 1;
-            self.range_extra.push(range);
-            self.buffersize_extra.push(BUFFERSIZE);
-            self.external_buffer_names.push(external_buffer_id)
-        }
-        self.update_extra(range, buffer_id)
+    fn set_cache_memory(&mut self, cache_memory: usize) {
+        self.cache.set_memory_budget(cache_memory);
     }
 
-    /// read from other buffer then the main one
-    fn get_timestep_buffer(&mut self, timestep: usize, buffer_id: usize) -> &Timestep {
-        print_debug!("PData: Extracting timestep number {}", timestep);
-        // If a timestep is requested that is higher then the current range
-        // update buffer with new range
-        print_debug!(
-            "PData: Checking requested timestep {} in range {:?}",
-            timestep,
-            self.range_extra[buffer_id]
-        );
-        if timestep > self.range_extra[buffer_id].1 - 1 {
-            self.update_extra((timestep, timestep + self.buffersize), buffer_id);
-        } else if self.range_extra[buffer_id].1 == self.global_stats_.ntimesteps
-            && timestep < self.range_extra[buffer_id].0
-        {
-            self.update_extra((0, BUFFERSIZE), buffer_id);
-        }
-        // If a timestep below the current range is requested, update buffer
-        if timestep < self.range_extra[buffer_id].0 {
-            self.update_extra((timestep, timestep + self.buffersize), buffer_id);
-        }
-
-        &self.buffer[timestep - self.range_extra[buffer_id].0]
-    }
-
-    fn info(&self) -> Result<String, &'static str> {
+    fn info(&self) -> Result<String> {
         let global_stats = self.global_stats();
         let time = global_stats.max_time();
         let timesteps = global_stats.timesteps();
@@ -1162,36 +662,29 @@ impl DataManager for PData {
             vel_mag[0usize],
             vel_mag[2usize]
         );
-        if self.rotation_is_set {
+        if self.tracer_times.is_some() {
             base_string.push_str(&format!(
-                "Rotation is set to: \n\
-                \t x: {:.2} degree\n\
-                \t y: {:.2} degree\n\
-                \t z: {:.2} degree\n",
-                self.rotation[0], self.rotation[1], self.rotation[2]
+                "Particles are located at different instants, placed onto the timesteps \
+                with {:?}\n",
+                self.tracer_sync
             ));
-            base_string.push_str(&format!(
-                "Rotation is performed around: \n\
-                \t x: {:.2} degree\n\
-                \t y: {:.2} degree\n\
-                \t z: {:.2} degree\n",
-                self.rotation_center[0], self.rotation_center[1], self.rotation_center[2]
-            ))
+        }
+        base_string.push_str(&global_stats.provenance().to_string());
+        if !self.transform.is_identity() {
+            base_string.push_str(&self.transform.to_string());
         }
         Ok(base_string)
     }
 
-    fn set_rotation_angle(&mut self, angle: f64, axis: f64) {
-        self.rotation_is_set = true;
-        self.rotation[axis as usize] = angle;
-        // check if now all elements are zero
-        if self.rotation.iter().all(|&x| x == 0.0) {
-            self.rotation_is_set = false;
-        }
+    fn set_transform(&mut self, transform: Transform) -> Result<()> {
+        self.transform = transform;
+        // the cache holds transformed timesteps, they are read again when requested
+        self.cache.clear();
+        Ok(())
     }
 
-    fn set_rotation_anker(&mut self, point: [f64; 3]) {
-        self.rotation_center = point;
+    fn transform(&self) -> Transform {
+        self.transform
     }
 }
 
@@ -1217,3 +710,43 @@ impl Drop for PData {
         //self.file.close();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolated_samples() {
+        let tracer_time = [0.0, 0.1, 0.3, 0.4, 0.8];
+        let (rows, samples) = TracerSync::Interpolate.sample(&tracer_time, &[0.2, 0.3, 0.35]);
+        assert_eq!(rows, (1, 4));
+        assert_eq!(samples[1], Some((1, 1, 0.0)));
+        let (before, after, weight) = samples[0].unwrap();
+        assert_eq!((before, after), (0, 1));
+        assert!((weight - 0.5).abs() < 1e-12);
+        // before the first location of the tracer there is nothing to interpolate
+        let (_, samples) = TracerSync::Interpolate.sample(&[1.0, 2.0], &[0.5, 1.5]);
+        assert_eq!(samples[0], None);
+        assert!(samples[1].is_some());
+    }
+
+    #[test]
+    fn window_samples() {
+        let tracer_time = [0.0, 0.1, 0.3, 0.4, 0.8];
+        let (rows, samples) = TracerSync::Window(0.1).sample(&tracer_time, &[0.28, 0.6]);
+        let times = &tracer_time[rows.0..rows.1];
+        let (row, _, _) = samples[0].unwrap();
+        assert_eq!(times[row], 0.3);
+        assert_eq!(samples[1], None);
+    }
+
+    #[test]
+    fn resampled_values() {
+        let values = array![[0.0, 1.0], [2.0, f64::NAN]];
+        let resampled = resample(&values, &[Some((0, 1, 0.25)), None, Some((1, 1, 0.0))]);
+        assert_eq!(resampled[[0, 0]], 0.5);
+        assert!(resampled[[1, 0]].is_nan());
+        assert_eq!(resampled[[2, 0]], 2.0);
+        assert!(resampled[[2, 1]].is_nan());
+    }
+}
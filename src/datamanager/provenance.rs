@@ -0,0 +1,219 @@
+//! Units and provenance of up4 HDF5 files
+//!
+//! The converters record where a file came from in root attributes next to the global
+//! stats: the unit of length ("length unit"), the converted files ("source files"), the
+//! converter and its settings ("converter", "converter settings", one "key=value" entry
+//! per setting) and the version of up4 that wrote the file ("up4 version"). All of them
+//! are optional, files written before they were introduced simply have no provenance.
+
+use super::utilities::attribute;
+use crate::error::{Result, Up4Error};
+use crate::units::LengthUnit;
+use derive_getters::Getters;
+use hdf5::types::VarLenUnicode;
+use std::collections::BTreeMap;
+use std::fmt;
+
+const LENGTH_UNIT: &str = "length unit";
+const SOURCE_FILES: &str = "source files";
+const CONVERTER: &str = "converter";
+const CONVERTER_SETTINGS: &str = "converter settings";
+const VERSION: &str = "up4 version";
+
+/// Units and origin of a dataset
+#[derive(Debug, Default, Getters, Clone, PartialEq)]
+pub struct Provenance {
+    length_unit: Option<LengthUnit>,
+    source_files: Vec<String>,
+    /// name of the converter that created the file, e.g. "csv"
+    converter: Option<String>,
+    converter_settings: BTreeMap<String, String>,
+    /// version of up4 that created the file
+    version: Option<String>,
+}
+
+impl Provenance {
+    /// Provenance of a file written now by the converter `converter`
+    ///
+    /// # Examples
+    /// ```ignore
+    /// Provenance::converter("csv")
+    ///     .add_source_files([filename])
+    ///     .add_setting("delimiter", delimiter)
+    ///     .write(&hdf5file)?;
+    /// ```
+    pub fn converter(converter: &str) -> Self {
+        Provenance {
+            converter: Some(converter.to_string()),
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            ..Provenance::default()
+        }
+    }
+
+    pub fn add_length_unit(self, length_unit: LengthUnit) -> Self {
+        Provenance {
+            length_unit: Some(length_unit),
+            ..self
+        }
+    }
+
+    pub fn add_source_files<I, S>(mut self, files: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.source_files
+            .extend(files.into_iter().map(|file| file.as_ref().to_string()));
+        self
+    }
+
+    pub fn add_setting<V: ToString>(mut self, name: &str, value: V) -> Self {
+        self.converter_settings
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Update the unit of length after all lengths were multiplied by `scale`, e.g. a
+    /// dataset in mm scaled by 0.001 is in m. If no unit matches the unit becomes unknown.
+    pub fn rescale_length(&mut self, scale: f64) {
+        self.length_unit = self
+            .length_unit
+            .and_then(|unit| LengthUnit::from_metres(unit.in_metres() / scale));
+    }
+
+    /// Combine the provenance of two datasets, e.g. of concatenated files. The unit of
+    /// length is only kept if both datasets agree on it.
+    pub fn merge(&mut self, other: &Provenance) {
+        if self.length_unit != other.length_unit {
+            self.length_unit = None;
+        }
+        self.source_files.extend(other.source_files.iter().cloned());
+        if self.converter != other.converter {
+            self.converter = None;
+            self.converter_settings.clear();
+        }
+    }
+
+    /// Read the provenance attributes of `file`, missing attributes are left empty
+    pub fn read(file: &hdf5::File) -> Result<Self> {
+        let filename = file.filename();
+        let read_string = |name: &str| -> Result<Option<String>> {
+            match file.attr(name) {
+                Ok(attr) => Ok(Some(
+                    attr.read_scalar::<VarLenUnicode>()
+                        .map_err(|e| Up4Error::read(name, &filename, e))?
+                        .to_string(),
+                )),
+                Err(_) => Ok(None),
+            }
+        };
+        let read_strings = |name: &str| -> Result<Vec<String>> {
+            match file.attr(name) {
+                Ok(attr) => Ok(attr
+                    .read_raw::<VarLenUnicode>()
+                    .map_err(|e| Up4Error::read(name, &filename, e))?
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect()),
+                Err(_) => Ok(vec![]),
+            }
+        };
+        let length_unit = read_string(LENGTH_UNIT)?
+            .map(|unit| unit.parse::<LengthUnit>())
+            .transpose()?;
+        let converter_settings = read_strings(CONVERTER_SETTINGS)?
+            .into_iter()
+            .map(|setting| match setting.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None => (setting, String::new()),
+            })
+            .collect();
+        Ok(Provenance {
+            length_unit,
+            source_files: read_strings(SOURCE_FILES)?,
+            converter: read_string(CONVERTER)?,
+            converter_settings,
+            version: read_string(VERSION)?,
+        })
+    }
+
+    /// Write all known attributes into the root of `file`
+    pub fn write(&self, file: &hdf5::File) -> Result<()> {
+        if let Some(length_unit) = self.length_unit {
+            write_string(file, LENGTH_UNIT, length_unit.symbol())?;
+        }
+        if !self.source_files.is_empty() {
+            write_strings(file, SOURCE_FILES, &self.source_files)?;
+        }
+        if let Some(converter) = &self.converter {
+            write_string(file, CONVERTER, converter)?;
+        }
+        if !self.converter_settings.is_empty() {
+            let settings: Vec<String> = self
+                .converter_settings
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            write_strings(file, CONVERTER_SETTINGS, &settings)?;
+        }
+        if let Some(version) = &self.version {
+            write_string(file, VERSION, version)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(length_unit) = self.length_unit {
+            writeln!(f, "Length unit: {}", length_unit)?;
+        }
+        if let Some(converter) = &self.converter {
+            writeln!(
+                f,
+                "Converted with the {} converter of up4 {} from {} file(s)",
+                converter,
+                self.version.as_deref().unwrap_or("(unknown version)"),
+                self.source_files.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Record the unit of length of an existing up4 file, e.g. of a file converted before
+/// units were recorded
+pub fn set_length_unit(filename: &str, length_unit: LengthUnit) -> Result<()> {
+    let file = hdf5::File::open_rw(filename).map_err(|e| Up4Error::FileOpen {
+        filename: filename.to_string(),
+        source: e,
+    })?;
+    // only up4 files carry the global attributes
+    attribute(&file, "dimensions")?;
+    if file.attr(LENGTH_UNIT).is_ok() {
+        file.delete_attr(LENGTH_UNIT)?;
+    }
+    write_string(&file, LENGTH_UNIT, length_unit.symbol())
+}
+
+pub(crate) fn unicode(value: &str) -> Result<VarLenUnicode> {
+    value.parse().map_err(|_| {
+        Up4Error::InvalidArgument(format!("{} can not be stored as an HDF5 string", value))
+    })
+}
+
+pub(crate) fn write_string(file: &hdf5::File, name: &str, value: &str) -> Result<()> {
+    file.new_attr::<VarLenUnicode>()
+        .create(name)?
+        .write_scalar(&unicode(value)?)?;
+    Ok(())
+}
+
+fn write_strings(file: &hdf5::File, name: &str, values: &[String]) -> Result<()> {
+    let values = values
+        .iter()
+        .map(|value| unicode(value))
+        .collect::<Result<Vec<_>>>()?;
+    file.new_attr_builder().with_data(&values).create(name)?;
+    Ok(())
+}
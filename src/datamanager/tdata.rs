@@ -2,10 +2,14 @@
 //!
 //! Implementation of reading + buffering functions.
 
-use super::{DataManager, GlobalStats, Manager, Timestep};
-use itertools::Chunk;
+use super::cache::{TimestepCache, DEFAULT_CACHE_MEMORY};
+use super::utilities::{
+    count_types, dataset, field_names, group, merge_type_counts, read_field, read_global_stats,
+};
+use super::{DataManager, FileLayout, GlobalStats, Manager, Timestep, Transform};
 use pyo3::prelude::*;
 extern crate ndarray;
+use crate::error::{Result, Up4Error};
 use crate::particleselector::Selector;
 use crate::types::*;
 use crate::ParticleSelector;
@@ -13,186 +17,111 @@ use crate::{print_debug, print_warning};
 #[cfg(feature = "blosc")]
 use hdf5::filters::blosc_set_nthreads;
 use ndarray::prelude::*;
+use std::collections::BTreeMap;
 use std::time::Instant;
+/// Number of timesteps read at once if no other buffer size is given
 const BUFFERSIZE: usize = 20000;
 #[pyclass]
 pub struct TData {
     // A data managing system for HDF5 files in the
     // H5Part format or H5TimePart information
     file: hdf5::File,
-    buffer: Vec<Timestep>,
-    buffer_extra: Vec<Vec<Timestep>>,
-    range: (usize, usize),
-    range_extra: Vec<(usize, usize)>,
+    /// chunks of timesteps read so far
+    cache: TimestepCache,
+    /// number of timesteps read from the file at once
     pub buffersize: usize,
-    pub buffersize_extra: Vec<usize>,
-    single_data: Timestep,
     global_stats_: GlobalStats,
-    rotation_is_set: bool,
-    rotation: [f64; 3],
-    rotation_center: [f64; 3],
-    external_buffer_names: Vec<usize>,
+    /// whether `global_stats_` holds the particle types, counting them reads the types of
+    /// every timestep
+    particle_types_counted: bool,
+    /// transform applied to every timestep when it is read
+    transform: Transform,
 }
 
 impl TData {
-    pub fn new(filename: &str) -> Self {
+    pub fn new(filename: &str) -> Result<Self> {
+        TData::with_buffersize(filename, BUFFERSIZE)
+    }
+
+    /// Open a file and read `buffersize` timesteps at once. Large timesteps, e.g. of DEM
+    /// simulations with many particles, need a small buffer size to limit the memory used.
+    pub fn with_buffersize(filename: &str, buffersize: usize) -> Result<Self> {
+        TData::with_cache(filename, buffersize, DEFAULT_CACHE_MEMORY)
+    }
+
+    /// Open a file, read `buffersize` timesteps at once and keep chunks of timesteps
+    /// using up to `cache_memory` bytes
+    pub fn with_cache(filename: &str, buffersize: usize, cache_memory: usize) -> Result<Self> {
         #[cfg(feature = "blosc")]
         blosc_set_nthreads(8);
         print_debug!(
             "TData: Generating new instance with file: {}\
             and buffersize: {}",
             &filename,
-            BUFFERSIZE
+            buffersize
         );
-        let file = hdf5::File::open(filename)
-            .unwrap_or_else(|_| panic!("Can not read HDF5 file {}. ", &filename));
-        let buffer = vec![Timestep::default(); BUFFERSIZE];
+        if buffersize == 0 {
+            return Err(Up4Error::InvalidArgument(
+                "The buffer size must be at least one timestep".to_string(),
+            ));
+        }
+        let file = hdf5::File::open(filename).map_err(|source| Up4Error::FileOpen {
+            filename: filename.to_string(),
+            source,
+        })?;
+        FileLayout::TData.check(&file)?;
+        let global_stats_ = read_global_stats(&file)?;
         let mut data = TData {
             file,
-            buffer,
-            buffer_extra: vec![],
-            range: (0, 0),
-            range_extra: vec![],
-            buffersize: BUFFERSIZE,
-            buffersize_extra: vec![],
-            single_data: Timestep::default(),
-            global_stats_: GlobalStats::default(),
-            rotation_is_set: false,
-            rotation: [0.0, 0.0, 0.0],
-            rotation_center: [0.0, 0.0, 0.0],
-
-            external_buffer_names: vec![],
+            cache: TimestepCache::new(buffersize, cache_memory),
+            buffersize,
+            particle_types_counted: !global_stats_.particle_types.is_empty(),
+            global_stats_,
+            transform: Transform::identity(),
         };
-        print_debug!(
-            "TData: Generation complete. First buffer update starting, buffer size: {}",
-            data.buffer.len()
-        );
-        let global_stats = data.global_stats();
-        data.global_stats_ = global_stats;
-        data.update((0, data.buffersize));
+        print_debug!("TData: Generation complete. First buffer update starting");
+        if *data.global_stats_.timesteps() > 0 {
+            data.get_timestep(0)?;
+        }
         print_debug!("TData: Buffer updated. Returning initiated TData.");
-        data
+        Ok(data)
     }
 
-    fn read_single(&self, timestep: usize) -> Timestep {
+    fn read_single(&self, timestep: usize) -> Result<Timestep> {
         print_debug!("TData: Reading a single timestep.");
-
-        print_debug!("TData: Looping over all particles.");
+        let filename = self.file.filename();
+        let timestep_group = group(&self.file, &format!("timestep {}", timestep))?;
 
         // for each particle find the Position at the
-        let position = self
-            .file
-            .group(&format!("timestep {}", timestep))
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find group \"timestep {}\" in file {}",
-                    timestep,
-                    self.file.filename()
-                )
-            })
-            .dataset("position")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find dataset \"position\" in file {}",
-                    self.file.filename()
-                )
-            })
+        print_debug!("Timestep {}: Extracting position from HDF5.", timestep);
+        let position = dataset(&timestep_group, "position")?
             .read_2d::<f64>()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read dataset \"position\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .to_owned()
+            .map_err(|e| Up4Error::read("position", &filename, e))?;
+        check_vectors(&position, "position", &filename)?;
+        let position = position
             .axis_iter(Axis(0))
             .map(|x| [x[0], x[1], x[2]])
             .collect::<Array1<Position>>();
 
         // for each particle find the velocuty at the
-        let velocity = self
-            .file
-            .group(&format!("timestep {}", timestep))
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find group \"timestep {}\" in file {}",
-                    timestep,
-                    self.file.filename()
-                )
-            })
-            .dataset("velocity")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find dataset \"velocity\" in file {}",
-                    self.file.filename()
-                )
-            })
+        let velocity = dataset(&timestep_group, "velocity")?
             .read_2d::<f64>()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read dataset \"velocity\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .to_owned();
-        //test
+            .map_err(|e| Up4Error::read("velocity", &filename, e))?;
+        check_vectors(&velocity, "velocity", &filename)?;
 
         //Radius
-        let radius = self
-            .file
-            .group(&format!("timestep {}", timestep))
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find timestep {} in file {}",
-                    timestep,
-                    self.file.filename()
-                )
-            })
-            .dataset("radius")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find dataset \"radius\" in HDF5 file \"{:?}\"",
-                    self.file.filename()
-                )
-            })
+        let radius = dataset(&timestep_group, "radius")?
             .read_1d::<f64>()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read data from \"radius\" dataset. \
-                Data type or data format might be wrong. \
-                Check creation of HDF5 file  \"{:?}\"",
-                    self.file.filename()
-                )
-            })
-            .to_owned();
-        let density = match self
-            .file
-            .group(&format!("timestep {}", timestep))
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find timestep {} in file {}",
-                    timestep,
-                    self.file.filename()
-                )
-            })
-            .dataset("density")
-        {
+            .map_err(|e| Up4Error::read("radius", &filename, e))?;
+        let density = match timestep_group.dataset("density") {
             Ok(s) => s
                 .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"density\" dataset. \
-                        Data type or data format might be wrong. \
-                        Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned(),
+                .map_err(|e| Up4Error::read("density", &filename, e))?,
             Err(_) => {
                 print_warning!(
                     "Can not find dataset \"density\" in file {} \
                     \n using ones instead ",
-                    self.file.filename()
+                    filename
                 );
                 let mut arr = radius.clone();
                 arr.fill(1.0);
@@ -200,120 +129,57 @@ impl TData {
             }
         };
 
-        let clouds = self
-            .file
-            .group(&format!("timestep {}", timestep))
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find timestep {} in file {}",
-                    timestep,
-                    self.file.filename()
-                )
-            })
-            .dataset("ppcloud")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find dataset \"ppcloud\" in HDF5 file \"{:?}\"",
-                    self.file.filename()
-                )
-            })
+        let clouds = dataset(&timestep_group, "ppcloud")?
             .read_1d::<f64>()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read data from \"ppcloud\" dataset. \
-                Data type or data format might be wrong. \
-                Check creation of HDF5 file  \"{:?}\"",
-                    self.file.filename()
-                )
-            })
-            .to_owned();
-
-        let time = self
-            .file
-            .group(&format!("timestep {}", timestep))
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not fine timestep {} in file {}",
-                    timestep,
-                    self.file.filename()
-                )
-            })
-            .dataset("time")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find dataset \"time\" in HDF5 file \"{:?}\"",
-                    self.file.filename()
-                )
-            })
+            .map_err(|e| Up4Error::read("ppcloud", &filename, e))?;
+        let time = dataset(&timestep_group, "time")?
             .read_scalar::<f64>()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read data from \"time\" dataset. \
-                Data type or data format might be wrong. \
-                Check creation of HDF5 file  \"{:?}\"",
-                    self.file.filename()
-                )
-            })
-            .to_owned();
-        let particletype = self
-            .file
-            .group(&format!("timestep {}", timestep))
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find timestep {} in file {}",
-                    timestep,
-                    self.file.filename()
-                )
-            })
-            .dataset("particletype")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find dataset \"particletype\" in HDF5 file \"{:?}\"",
-                    self.file.filename()
-                )
-            })
+            .map_err(|e| Up4Error::read("time", &filename, e))?;
+        let particleid = dataset(&timestep_group, "id")?
             .read_1d::<f64>()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read data from \"particletype\" dataset. \
-                Data type or data format might be wrong. \
-                Check creation of HDF5 file  \"{:?}\"",
-                    self.file.filename()
-                )
-            })
-            .to_owned();
-        let particleid = self
-            .file
-            .group(&format!("timestep {}", timestep))
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find timestep {} in file {}",
-                    timestep,
-                    self.file.filename()
-                )
-            })
-            .dataset("id")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find dataset \"id\" in HDF5 file \"{:?}\"",
-                    self.file.filename()
-                )
-            })
+            .map_err(|e| Up4Error::read("id", &filename, e))?;
+        let particletype = dataset(&timestep_group, "particletype")?
             .read_1d::<f64>()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read data from \"id\" dataset. \
-                Data type or data format might be wrong. \
-                Check creation of HDF5 file  \"{:?}\"",
-                    self.file.filename()
-                )
-            })
-            .to_owned();
+            .map_err(|e| Up4Error::read("particletype", &filename, e))?;
+
+        let mut fields = BTreeMap::new();
+        for name in field_names(&timestep_group)? {
+            let field = read_field(
+                &dataset(&timestep_group, &format!("fields/{}", name))?,
+                None,
+                &name,
+                &filename,
+            )?;
+            fields.insert(name, field);
+        }
+
+        // all per-particle datasets need to describe the same particles
+        let particles = position.len();
+        let field_lengths = fields
+            .iter()
+            .map(|(name, field)| (name.as_str(), field.nrows()));
+        for (name, len) in [
+            ("velocity", velocity.nrows()),
+            ("radius", radius.len()),
+            ("density", density.len()),
+            ("ppcloud", clouds.len()),
+            ("id", particleid.len()),
+            ("particletype", particletype.len()),
+        ]
+        .into_iter()
+        .chain(field_lengths)
+        {
+            if len != particles {
+                return Err(Up4Error::MalformedShape {
+                    name: format!("timestep {}/{}", timestep, name),
+                    filename,
+                    expected: format!("{} entries like \"position\"", particles),
+                });
+            }
+        }
 
         print_debug!("TData: Data read. Saving new timestap and return reference.");
-        //let dt = Timestep::default();
-        //self.single_data = dt;
-        Timestep {
+        let mut timestep = Timestep {
             time,
             position,
             velocity,
@@ -322,533 +188,54 @@ impl TData {
             clouds,
             density,
             ptype: particletype,
-        }
+            fields,
+        };
+        self.transform.apply(&mut timestep);
+        Ok(timestep)
     }
 
-    pub fn update(&mut self, mut range: (usize, usize)) {
-        print_debug!("TData: Updating buffer");
-        let timesteps = *self.global_stats_.timesteps();
-        if range.1 >= timesteps {
-            range.1 = timesteps;
+    /// Count the particles of each type, the largest number found in one timestep is kept
+    fn count_particle_types(&self) -> Result<BTreeMap<usize, usize>> {
+        let filename = self.file.filename();
+        let mut counts = BTreeMap::new();
+        for timestep in 0..*self.global_stats_.timesteps() {
+            let ptypes = dataset(
+                &group(&self.file, &format!("timestep {}", timestep))?,
+                "particletype",
+            )?
+            .read_1d::<f64>()
+            .map_err(|e| Up4Error::read("particletype", &filename, e))?;
+            merge_type_counts(&mut counts, &count_types(ptypes));
         }
-        self.buffersize = range.1 - range.0;
-
-        print_debug!("TData: Looping over all particles:");
-        for timestep in range.0..range.1 {
-            // for each particle find the Position at the
-            print_debug!("Timestep {}: Extracting position from HDF5.", timestep);
-            let position = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find group \"timestep {}\" in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("position")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"position\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .read_2d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read dataset \"position\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
-            let position = position
-                .axis_iter(Axis(0))
-                .map(|x| [x[0], x[1], x[2]])
-                .collect::<Array1<Position>>();
-            // for each particle find the velocuty at the
-            let velocity = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find group \"timestep {}\" in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("velocity")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"velocity\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .read_2d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read dataset \"velocity\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
-
-            //Radius
-            let radius = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find timestep {} in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("radius")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"radius\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"radius\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
-            let density = match self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find timestep {} in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("density")
-            {
-                Ok(s) => s
-                    .read_1d::<f64>()
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "Can not read data from \"density\" dataset. \
-                            Data type or data format might be wrong. \
-                            Check creation of HDF5 file  \"{:?}\"",
-                            self.file.filename()
-                        )
-                    })
-                    .to_owned(),
-                Err(_) => {
-                    print_warning!(
-                        "Can not find dataset \"density\" in file {} \
-                        \n using ones instead ",
-                        self.file.filename()
-                    );
-                    let mut arr = radius.clone();
-                    arr.fill(1.0);
-                    arr
-                }
-            };
-
-            let clouds = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find timestep {} in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("ppcloud")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"ppcloud\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"ppcloud\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
+        Ok(counts)
+    }
 
-            let time = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not fine timestep {} in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("time")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"time\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_scalar::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"time\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
-            let particleid = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find timestep {} in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("id")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"id\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"id\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
-            let particletype = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find timestep {} in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("particletype")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"particletype\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"particletype\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
+    /// Read the timesteps `range.0..range.1`
+    fn read_range(&self, range: (usize, usize)) -> Result<Vec<Timestep>> {
+        print_debug!("TData: Looping over all timesteps:");
+        (range.0..range.1)
+            .map(|timestep| self.read_single(timestep))
+            .collect()
+    }
 
-            let dt = Timestep {
-                time,
-                position,
-                velocity,
-                radius,
-                particleid,
-                clouds,
-                density,
-                ptype: particletype,
-            };
-            print_debug!(
-                "\tTimestep {}: Saving struct in buffer with len: {}.",
-                timestep,
-                self.buffer.len()
-            );
-            self.buffer[timestep - range.0] = dt;
-        }
-        self.range = range; //(range.0,range.1-1);
-        print_debug!("TData: Finished buffer update")
+    /// Drop all timesteps kept in memory
+    pub fn reset_buffer(&mut self) {
+        self.cache.clear();
     }
 
-    pub fn update_extra(&mut self, mut range: (usize, usize), buffer_id: usize) {
-        print_debug!("TData: Updating buffer");
+    /// Return an error if `timestep` is not part of the dataset
+    fn check_timestep(&self, timestep: usize) -> Result<()> {
         let timesteps = *self.global_stats_.timesteps();
-        if range.1 >= timesteps {
-            range.1 = timesteps;
-        }
-        self.buffersize_extra[buffer_id] = range.1 - range.0;
-
-        print_debug!("TData: Looping over all particles:");
-        for timestep in range.0..range.1 {
-            // for each particle find the Position at the
-            print_debug!("Timestep {}: Extracting position from HDF5.", timestep);
-            let position = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find group \"timestep {}\" in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("position")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"position\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .read_2d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read dataset \"position\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
-            let position = position
-                .axis_iter(Axis(0))
-                .map(|x| [x[0], x[1], x[2]])
-                .collect::<Array1<Position>>();
-            // for each particle find the velocuty at the
-            let velocity = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find group \"timestep {}\" in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("velocity")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"velocity\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .read_2d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read dataset \"velocity\" in file {}",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
-
-            //Radius
-            let radius = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find timestep {} in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("radius")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"radius\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"radius\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
-            let density = match self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find timestep {} in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("density")
-            {
-                Ok(s) => s
-                    .read_1d::<f64>()
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "Can not read data from \"density\" dataset. \
-                            Data type or data format might be wrong. \
-                            Check creation of HDF5 file  \"{:?}\"",
-                            self.file.filename()
-                        )
-                    })
-                    .to_owned(),
-                Err(_) => {
-                    print_warning!(
-                        "Can not find dataset \"density\" in file {} \
-                        \n using ones instead ",
-                        self.file.filename()
-                    );
-                    let mut arr = radius.clone();
-                    arr.fill(1.0);
-                    arr
-                }
-            };
-
-            let clouds = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find timestep {} in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("ppcloud")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"ppcloud\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"ppcloud\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
-
-            let time = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not fine timestep {} in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("time")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"time\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_scalar::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"time\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
-            let particleid = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find timestep {} in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("id")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"id\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"id\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
-            let particletype = self
-                .file
-                .group(&format!("timestep {}", timestep))
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find timestep {} in file {}",
-                        timestep,
-                        self.file.filename()
-                    )
-                })
-                .dataset("particletype")
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not find dataset \"particletype\" in HDF5 file \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .read_1d::<f64>()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Can not read data from \"particletype\" dataset. \
-                    Data type or data format might be wrong. \
-                    Check creation of HDF5 file  \"{:?}\"",
-                        self.file.filename()
-                    )
-                })
-                .to_owned();
-            let dt = Timestep {
-                time,
-                position,
-                velocity,
-                radius,
-                particleid,
-                clouds,
-                density,
-                ptype: particletype,
-            };
-            print_debug!(
-                "\tTimestep {}: Saving struct in buffer with len: {}.",
+        if timestep >= timesteps {
+            return Err(Up4Error::TimestepOutOfRange {
                 timestep,
-                self.buffer.len()
-            );
-            self.buffer_extra[buffer_id][timestep - range.0] = dt;
+                timesteps,
+            });
         }
-        self.range_extra[buffer_id] = range; //(range.0,range.1-1);
-        print_debug!("TData: Finished buffer update")
+        Ok(())
     }
 
-    pub fn test(&mut self) {
+    pub fn test(&mut self) -> Result<()> {
         let now = Instant::now();
         let _time = self.global_stats_.max_time();
         let mut x = 0.;
@@ -858,17 +245,24 @@ impl TData {
             vec![f64::MIN, f64::MAX],
             vec![f64::MIN, f64::MAX],
             vec![usize::MIN, usize::MAX],
+            vec![-1.],
         );
         for timestep in 0..*self.global_stats_.timesteps() {
-            let timestep_data = self.get_timestep(timestep);
+            let timestep_data = self.get_timestep(timestep)?;
             let positions: &Array1<Position> = timestep_data.position();
             let radius: &Array1<f64> = timestep_data.radius();
             let particleid: &Array1<f64> = timestep_data.particleid();
             let clouds: &Array1<f64> = timestep_data.clouds();
+            let ptype: &Array1<f64> = timestep_data.ptype();
             let density: Array1<f64> = Array1::from_elem(clouds.len(), 1000.0);
             for (id, _position) in positions.outer_iter().enumerate() {
-                if !selector.is_valid(radius[id], clouds[id], density[id], particleid[id] as usize)
-                {
+                if !selector.is_valid(
+                    radius[id],
+                    clouds[id],
+                    density[id],
+                    particleid[id] as usize,
+                    ptype[id],
+                ) {
                     continue;
                 }
                 x += positions[0][0];
@@ -876,167 +270,51 @@ impl TData {
         }
         println!("Elapsed time: {}", now.elapsed().as_millis());
         println!("x: {}", x);
+        Ok(())
     }
 } //end impl
 
+/// Make sure a per-particle vector dataset has the shape (N, 3)
+fn check_vectors(data: &Array2<f64>, name: &str, filename: &str) -> Result<()> {
+    if data.ncols() != 3 {
+        return Err(Up4Error::MalformedShape {
+            name: name.to_string(),
+            filename: filename.to_string(),
+            expected: "(N, 3)".to_string(),
+        });
+    }
+    Ok(())
+}
+
 impl DataManager for TData {
     // return a array from a given dataset at a given timestep
-    fn get_timestep(&mut self, timestep: usize) -> &Timestep {
+    fn get_timestep(&mut self, timestep: usize) -> Result<&Timestep> {
         print_debug!("TData: Extracting timestep number {}", timestep);
-        // If a timestep is requested that is higher then the current range
-        // update buffer with new range
-        print_debug!(
-            "TData: Checking requested timestep {} in range {:?}",
-            timestep,
-            self.range
-        );
-        if timestep > self.range.1 - 1 {
-            self.update((timestep, timestep + self.buffersize));
-        } else if timestep < self.range.0 {
-            let chunk = (timestep as f64 / BUFFERSIZE as f64).floor() as usize;
-            self.update((chunk * BUFFERSIZE, (chunk + 1) * BUFFERSIZE));
+        self.check_timestep(timestep)?;
+        if !self.cache.contains(timestep) {
+            let range = self.cache.chunk_range(timestep, *self.global_stats_.timesteps());
+            print_debug!("TData: Reading timesteps {:?}", range);
+            let timesteps = self.read_range(range)?;
+            self.cache.insert(range.0, timesteps);
         }
-
-        &self.buffer[timestep - self.range.0]
+        Ok(self
+            .cache
+            .get(timestep)
+            .expect("the chunk of the timestep was just cached"))
     }
 
     fn global_stats(&self) -> GlobalStats {
-        let dimensions = self
-            .file
-            .attr("dimensions")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find dataset \"dimensions\" in HDF5 file \"{:?}\"",
-                    self.file.filename()
-                )
-            })
-            .read_2d::<f64>()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read data from \"dimensions\" dataset. \
-                Data type or data format might be wrong. \
-                Check creation of HDF5 file  \"{:?}\"",
-                    self.file.filename()
-                )
-            });
-        let nparticles = self
-            .file
-            .attr("particle number")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"particle number\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .read_scalar()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read scalar from attribute \"particle number\" in file {}",
-                    self.file.filename()
-                )
-            });
-        let timesteps = self
-            .file
-            .attr("timesteps")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"timesteps\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .read_scalar()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read scalar from attribute \"timesteps\" in file {}",
-                    self.file.filename()
-                )
-            });
-        let time = self
-            .file
-            .attr("time")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"time\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .read_raw()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read vector from attribute \"time\" in file {}",
-                    self.file.filename()
-                )
-            });
-        let sample_rate = self
-            .file
-            .attr("sample rate")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"sample rate\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .read_scalar()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read scalar from attribute \"sample rate\" in file {}",
-                    self.file.filename()
-                )
-            });
-        let velocity = self
-            .file
-            .attr("velocity")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"velocity\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .read_2d()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read scalar from attribute \"velocity\" in file {}",
-                    self.file.filename()
-                )
-            });
-        let velocity_mag = self
-            .file
-            .attr("velocity magnitude")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"velocity magnitude\" in file {}",
-                    self.file.filename()
-                )
-            })
-            .read_1d()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read scalar from attribute \"velocity magnitude\" in file {}",
-                    self.file.filename()
-                )
-            });
-        let time_array = self
-            .file
-            .dataset("time array")
-            .expect(
-                "Can not find attribute \"time array\" in file, \
-            You might have a old version of a HDF5 file. please include the \
-            time as an array in attributes",
-            )
-            .read_1d()
-            .expect("Can not read vector from attribute \"time array\" in file");
-        GlobalStats {
-            dimensions,
-            nparticles,
-            ntimesteps: timesteps,
-            min_time: time[0],
-            max_time: time[1],
-            sample_rate,
-            velocity,
-            velocity_mag,
-            time_array,
+        self.transform.apply_stats(&self.global_stats_)
+    }
+
+    fn particle_types(&mut self) -> Result<BTreeMap<usize, usize>> {
+        if !self.particle_types_counted {
+            self.global_stats_.particle_types = self.count_particle_types()?;
+            self.particle_types_counted = true;
         }
+        Ok(self.global_stats_.particle_types.clone())
     }
+
     fn stats(&self) {
         let global_stats = self.global_stats();
         let time = global_stats.max_time();
@@ -1053,65 +331,17 @@ impl DataManager for TData {
         );
     }
 
-    fn get_timestep_unbuffered(&self, timestep: usize) -> Timestep {
+    fn get_timestep_unbuffered(&self, timestep: usize) -> Result<Timestep> {
         print_debug!("TData: Extracting timestep number {}", timestep);
+        self.check_timestep(timestep)?;
         self.read_single(timestep)
     }
-    /// setup a new buffer
-    fn setup_buffer(&mut self, external_buffer_id: usize) {
-        let range = (0usize, BUFFERSIZE);
-        let buffer_id;
-        if self.external_buffer_names.contains(&external_buffer_id) {
-            // buffer is already know and will be reset
-            buffer_id = self
-                .external_buffer_names
-                .iter()
-                .position(|x| x == &external_buffer_id)
-                .unwrap();
-            self.range_extra[buffer_id] = range
-        } else {
-            // new buffer. Allocate new memory and load the first bit of data
-            self.buffer_extra
-                .push(vec![Timestep::default(); BUFFERSIZE]);
-            buffer_id = self.buffer_extra.len() - 1;
-            self.range_extra.push(range);
-            self.buffersize_extra.push(BUFFERSIZE);
-            self.external_buffer_names.push(external_buffer_id)
-        }
-        self.update_extra(range, buffer_id)
-    }
 
-    /// read from other buffer then the main one
-    fn get_timestep_buffer(&mut self, timestep: usize, buffer_id: usize) -> &Timestep {
-        print_debug!("PData: Extracting timestep number {}", timestep);
-        // If a timestep is requested that is higher then the current range
-        // update buffer with new range
-        print_debug!(
-            "PData: Checking requested timestep {} in range {:?}",
-            timestep,
-            self.range_extra[buffer_id]
-        );
-        if timestep > self.range_extra[buffer_id].1 - 1 {
-            self.update_extra(
-                (timestep, timestep + self.buffersize_extra[buffer_id]),
-                buffer_id,
-            );
-        } else if self.range_extra[buffer_id].1 == self.global_stats_.ntimesteps
-            && timestep < self.range_extra[buffer_id].0
-        {
-            self.update_extra((0, BUFFERSIZE), buffer_id);
-        }
-        // If a timestep below the current range is requested, update buffer
-        if timestep < self.range.0 {
-            self.update_extra(
-                (timestep, timestep + self.buffersize_extra[buffer_id]),
-                buffer_id,
-            );
-        }
-        &self.buffer_extra[buffer_id][timestep - self.range_extra[buffer_id].0]
+    fn set_cache_memory(&mut self, cache_memory: usize) {
+        self.cache.set_memory_budget(cache_memory);
     }
 
-    fn info(&self) -> Result<String, &'static str> {
+    fn info(&self) -> Result<String> {
         let global_stats = self.global_stats();
         let time = global_stats.max_time();
         let timesteps = global_stats.timesteps();
@@ -1146,37 +376,22 @@ impl DataManager for TData {
             vel_mag[0usize],
             vel_mag[2usize]
         );
-        if self.rotation_is_set {
-            base_string.push_str(&format!(
-                "Rotation is set to: \n\
-                \t x: {:.2} degree\n\
-                \t y: {:.2} degree\n\
-                \t z: {:.2} degree\n",
-                self.rotation[0], self.rotation[1], self.rotation[2]
-            ));
-
-            base_string.push_str(&format!(
-                "Rotation is performed around: \n\
-                \t x: {:.2} degree\n\
-                \t y: {:.2} degree\n\
-                \t z: {:.2} degree\n",
-                self.rotation_center[0], self.rotation_center[1], self.rotation_center[2]
-            ))
+        base_string.push_str(&global_stats.provenance().to_string());
+        if !self.transform.is_identity() {
+            base_string.push_str(&self.transform.to_string());
         }
         Ok(base_string)
     }
 
-    fn set_rotation_angle(&mut self, angle: f64, axis: f64) {
-        self.rotation_is_set = true;
-        self.rotation[axis as usize] = angle;
-        // check if now all elements are zero
-        if self.rotation.iter().all(|&x| x == 0.0) {
-            self.rotation_is_set = false;
-        }
+    fn set_transform(&mut self, transform: Transform) -> Result<()> {
+        self.transform = transform;
+        // the cache holds transformed timesteps, they are read again when requested
+        self.cache.clear();
+        Ok(())
     }
 
-    fn set_rotation_anker(&mut self, point: [f64; 3]) {
-        self.rotation_center = point;
+    fn transform(&self) -> Transform {
+        self.transform
     }
 }
 
@@ -0,0 +1,252 @@
+//! Time based access to the timesteps of a dataset
+//!
+//! PEPT data is sampled irregularly, so a time rarely falls exactly onto a timestep.
+//! `GlobalStats::timestep_at` finds a timestep for a time with an explicit `TimeLookup`
+//! and `Timestep::interpolate` creates a synthetic timestep between two recorded ones.
+
+use super::{GlobalStats, Timestep};
+use crate::error::{Result, Up4Error};
+use ndarray::prelude::*;
+
+/// Relative tolerance below which two times are considered equal
+pub const TIME_TOLERANCE: f64 = 1e-9;
+
+/// How a time is matched to the timesteps of a dataset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeLookup {
+    /// only a timestep at exactly this time
+    Exact,
+    /// the timestep closest to the time
+    Nearest,
+    /// the last timestep at or before the time
+    Floor,
+    /// the first timestep at or after the time
+    Ceil,
+}
+
+impl std::str::FromStr for TimeLookup {
+    type Err = Up4Error;
+
+    fn from_str(lookup: &str) -> Result<Self> {
+        match lookup.to_lowercase().as_str() {
+            "exact" => Ok(TimeLookup::Exact),
+            "nearest" => Ok(TimeLookup::Nearest),
+            "floor" => Ok(TimeLookup::Floor),
+            "ceil" => Ok(TimeLookup::Ceil),
+            _ => Err(Up4Error::InvalidArgument(format!(
+                "Lookup {} is not valid. Valid lookups are: \"exact\", \"nearest\", \"floor\", \"ceil\"",
+                lookup
+            ))),
+        }
+    }
+}
+
+impl GlobalStats {
+    /// Return the timestep matching `seconds` according to `lookup`. Times outside of the
+    /// dataset are an error for every lookup.
+    pub fn timestep_at(&self, seconds: f64, lookup: TimeLookup) -> Result<usize> {
+        let times = &self.time_array;
+        let (first, last) = match (times.first(), times.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => {
+                return Err(Up4Error::InvalidArgument(
+                    "The dataset has no timesteps".to_string(),
+                ))
+            }
+        };
+        let tolerance = TIME_TOLERANCE * seconds.abs().max(1.0);
+        if !(seconds >= first - tolerance && seconds <= last + tolerance) {
+            return Err(Up4Error::TimeOutOfRange {
+                time: seconds,
+                min_time: first,
+                max_time: last,
+            });
+        }
+        // binary search for the first timestep that is not before `seconds`, it exists
+        // as `seconds` is not after the last timestep
+        let (mut low, mut high) = (0, times.len() - 1);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if times[mid] < seconds - tolerance {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        if (times[low] - seconds).abs() <= tolerance {
+            return Ok(low);
+        }
+        // no exact match: times[low] lies after `seconds` and, as `seconds` is not before
+        // the first timestep, `low` is at least one
+        match lookup {
+            TimeLookup::Exact => Err(Up4Error::InvalidArgument(format!(
+                "There is no timestep at {} s",
+                seconds
+            ))),
+            TimeLookup::Ceil => Ok(low),
+            TimeLookup::Floor => Ok(low - 1),
+            TimeLookup::Nearest => {
+                if seconds - times[low - 1] <= times[low] - seconds {
+                    Ok(low - 1)
+                } else {
+                    Ok(low)
+                }
+            }
+        }
+    }
+
+    /// Returns the timestep closest to this time in the dataset
+    pub fn timestep_at_seconds_closest(&self, seconds: f64) -> Result<usize> {
+        self.timestep_at(seconds, TimeLookup::Nearest)
+    }
+
+    /// Return the timesteps before and after `seconds` and the weight of the later one
+    /// for a linear interpolation. Both timesteps are the same if one matches exactly.
+    pub fn time_bracket(&self, seconds: f64) -> Result<(usize, usize, f64)> {
+        let floor = self.timestep_at(seconds, TimeLookup::Floor)?;
+        let ceil = self.timestep_at(seconds, TimeLookup::Ceil)?;
+        let span = self.time_array[ceil] - self.time_array[floor];
+        let weight = if span > 0.0 {
+            ((seconds - self.time_array[floor]) / span).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        Ok((floor, ceil, weight))
+    }
+}
+
+impl Timestep {
+    /// Interpolate linearly between this timestep and `next`, `weight` is 0 for this
+    /// timestep and 1 for `next`. Particles are matched by ID and particles missing in
+    /// `next` are dropped. Positions, velocities, time and additional fields are
+    /// interpolated, all other properties are taken from this timestep.
+    pub fn interpolate(&self, next: &Timestep, weight: f64) -> Timestep {
+        let lerp = |a: f64, b: f64| a + weight * (b - a);
+        let next_ids = next.id_map();
+        let pairs: Vec<(usize, usize)> = self
+            .particleid
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &id)| {
+                next_ids
+                    .get(&(id as usize))
+                    .map(|&other| (index, other))
+            })
+            .collect();
+        let rows: Vec<usize> = pairs.iter().map(|&(index, _)| index).collect();
+        let interpolate_rows = |this: &Array2<f64>, other: &Array2<f64>| {
+            Array2::from_shape_fn((pairs.len(), this.ncols()), |(row, column)| {
+                let (index, other_index) = pairs[row];
+                lerp(this[[index, column]], other[[other_index, column]])
+            })
+        };
+        Timestep {
+            time: lerp(self.time, next.time),
+            position: pairs
+                .iter()
+                .map(|&(index, other)| {
+                    let (a, b) = (self.position[index], next.position[other]);
+                    [lerp(a[0], b[0]), lerp(a[1], b[1]), lerp(a[2], b[2])]
+                })
+                .collect(),
+            velocity: interpolate_rows(&self.velocity, &next.velocity),
+            radius: self.radius.select(Axis(0), &rows),
+            particleid: self.particleid.select(Axis(0), &rows),
+            clouds: self.clouds.select(Axis(0), &rows),
+            density: self.density.select(Axis(0), &rows),
+            ptype: self.ptype.select(Axis(0), &rows),
+            fields: self
+                .fields
+                .iter()
+                .filter_map(|(name, field)| {
+                    let other = next.fields.get(name)?;
+                    (other.ncols() == field.ncols())
+                        .then(|| (name.clone(), interpolate_rows(field, other)))
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(times: &[f64]) -> GlobalStats {
+        GlobalStats {
+            time_array: Array1::from(times.to_vec()),
+            ntimesteps: times.len(),
+            ..GlobalStats::default()
+        }
+    }
+
+    #[test]
+    fn lookups() {
+        let stats = stats(&[0.0, 0.1, 0.25, 0.7]);
+        assert_eq!(stats.timestep_at(0.25, TimeLookup::Exact).unwrap(), 2);
+        assert!(stats.timestep_at(0.3, TimeLookup::Exact).is_err());
+        assert_eq!(stats.timestep_at(0.3, TimeLookup::Nearest).unwrap(), 2);
+        assert_eq!(stats.timestep_at(0.6, TimeLookup::Nearest).unwrap(), 3);
+        assert_eq!(stats.timestep_at(0.3, TimeLookup::Floor).unwrap(), 2);
+        assert_eq!(stats.timestep_at(0.3, TimeLookup::Ceil).unwrap(), 3);
+        assert_eq!(stats.timestep_at(0.0, TimeLookup::Floor).unwrap(), 0);
+        assert_eq!(stats.timestep_at(0.7, TimeLookup::Ceil).unwrap(), 3);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let stats = stats(&[0.0, 0.1]);
+        assert!(matches!(
+            stats.timestep_at(-0.1, TimeLookup::Nearest),
+            Err(Up4Error::TimeOutOfRange { .. })
+        ));
+        assert!(stats.timestep_at(0.2, TimeLookup::Floor).is_err());
+        assert!(stats.timestep_at(f64::NAN, TimeLookup::Nearest).is_err());
+        assert!(self::stats(&[]).timestep_at(0.0, TimeLookup::Nearest).is_err());
+    }
+
+    #[test]
+    fn single_timestep() {
+        let stats = stats(&[1.0]);
+        assert_eq!(stats.timestep_at(1.0, TimeLookup::Nearest).unwrap(), 0);
+        assert_eq!(stats.time_bracket(1.0).unwrap(), (0, 0, 0.0));
+    }
+
+    #[test]
+    fn interpolation() {
+        let stats = stats(&[0.0, 1.0, 3.0]);
+        let (floor, ceil, weight) = stats.time_bracket(2.5).unwrap();
+        assert_eq!((floor, ceil), (1, 2));
+        assert!((weight - 0.75).abs() < 1e-12);
+
+        let first = Timestep {
+            time: 1.0,
+            position: Array1::from(vec![[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]]),
+            velocity: Array2::zeros((2, 3)),
+            radius: array![0.1, 0.2],
+            particleid: array![0.0, 1.0],
+            clouds: array![1.0, 1.0],
+            density: array![1.0, 1.0],
+            ptype: array![0.0, 0.0],
+            ..Timestep::default()
+        };
+        // particle 1 moved to the front, particle 0 left the system
+        let second = Timestep {
+            time: 3.0,
+            position: Array1::from(vec![[3.0, 1.0, 1.0]]),
+            velocity: array![[2.0, 0.0, 0.0]],
+            radius: array![0.2],
+            particleid: array![1.0],
+            clouds: array![1.0],
+            density: array![1.0],
+            ptype: array![0.0],
+            ..Timestep::default()
+        };
+        let timestep = first.interpolate(&second, weight);
+        assert!((timestep.time - 2.5).abs() < 1e-12);
+        assert_eq!(timestep.particleid, array![1.0]);
+        assert!((timestep.position[0][0] - 2.5).abs() < 1e-12);
+        assert!((timestep.velocity[[0, 0]] - 1.5).abs() < 1e-12);
+        assert_eq!(timestep.radius, array![0.2]);
+    }
+}
@@ -0,0 +1,304 @@
+//! Affine transforms of positions and velocities
+//!
+//! Experimental and simulated data are usually recorded in different frames: the axes
+//! are swapped, the origin differs and one is given in mm while the other is given in
+//! m. A `Transform` is set on a `DataManager` and applied to every timestep when it is
+//! read, so all functions work on the aligned frame.
+//!
+//! Transforms are composed with `then`, the left transform is applied first:
+//!
+//! ```rust
+//! use upppp_rust::datamanager::Transform;
+//! // convert mm to m and move the origin to the bottom of the system
+//! let transform = Transform::scaling(0.001)
+//!     .unwrap()
+//!     .then(&Transform::translation([0.0, 0.0, 0.1]));
+//! let position = transform.apply_position([0.0, 0.0, -100.0]);
+//! assert!(position[2].abs() < 1e-12);
+//! ```
+
+use super::{GlobalStats, Timestep};
+use crate::error::{Result, Up4Error};
+use crate::types::*;
+use ndarray::prelude::*;
+use std::fmt;
+
+/// Affine transform `x' = matrix * x + translation` of positions. Velocities are only
+/// transformed by the matrix. The matrix is always a rotation or axis permutation
+/// multiplied by a uniform `scale`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: [[f64; 3]; 3],
+    translation: [f64; 3],
+    scale: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    /// Transform that leaves all data unchanged
+    pub fn identity() -> Self {
+        Transform {
+            matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            translation: [0.0; 3],
+            scale: 1.0,
+        }
+    }
+
+    /// Rotation by `angle` (radians) around `axis` through the point `center`. The
+    /// rotation follows the right hand rule.
+    pub fn rotation(axis: [f64; 3], angle: f64, center: [f64; 3]) -> Result<Self> {
+        let norm = axis.iter().map(|a| a * a).sum::<f64>().sqrt();
+        if !(norm > 0.0 && norm.is_finite()) {
+            return Err(Up4Error::InvalidArgument(format!(
+                "The rotation axis {:?} is not a valid direction",
+                axis
+            )));
+        }
+        let [x, y, z] = axis.map(|a| a / norm);
+        let (sin, cos) = angle.sin_cos();
+        let c = 1.0 - cos;
+        // Rodrigues' rotation formula
+        let matrix = [
+            [cos + x * x * c, x * y * c - z * sin, x * z * c + y * sin],
+            [y * x * c + z * sin, cos + y * y * c, y * z * c - x * sin],
+            [z * x * c - y * sin, z * y * c + x * sin, cos + z * z * c],
+        ];
+        let rotated_center = mul(&matrix, center);
+        Ok(Transform {
+            matrix,
+            translation: [
+                center[0] - rotated_center[0],
+                center[1] - rotated_center[1],
+                center[2] - rotated_center[2],
+            ],
+            scale: 1.0,
+        })
+    }
+
+    /// Translation of all positions by `offset`
+    pub fn translation(offset: [f64; 3]) -> Self {
+        Transform {
+            translation: offset,
+            ..Transform::identity()
+        }
+    }
+
+    /// Uniform scaling around the origin, e.g. 0.001 to convert mm to m. Velocities are
+    /// scaled by the same factor.
+    pub fn scaling(factor: f64) -> Result<Self> {
+        if !(factor > 0.0 && factor.is_finite()) {
+            return Err(Up4Error::InvalidArgument(format!(
+                "The scaling factor must be positive, got {}",
+                factor
+            )));
+        }
+        let mut transform = Transform::identity();
+        for (i, row) in transform.matrix.iter_mut().enumerate() {
+            row[i] = factor;
+        }
+        transform.scale = factor;
+        Ok(transform)
+    }
+
+    /// Swap the axes `first` and `second` (0: x, 1: y, 2: z)
+    pub fn swap_axes(first: usize, second: usize) -> Result<Self> {
+        if first > 2 || second > 2 {
+            return Err(Up4Error::InvalidArgument(format!(
+                "Axes must be 0, 1 or 2, got {} and {}",
+                first, second
+            )));
+        }
+        let mut transform = Transform::identity();
+        transform.matrix.swap(first, second);
+        Ok(transform)
+    }
+
+    /// Return the transform that applies `self` first and `next` afterwards
+    pub fn then(&self, next: &Transform) -> Transform {
+        let mut matrix = [[0.0; 3]; 3];
+        for (i, row) in matrix.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| next.matrix[i][k] * self.matrix[k][j]).sum();
+            }
+        }
+        let moved = mul(&next.matrix, self.translation);
+        Transform {
+            matrix,
+            translation: [
+                moved[0] + next.translation[0],
+                moved[1] + next.translation[1],
+                moved[2] + next.translation[2],
+            ],
+            scale: self.scale * next.scale,
+        }
+    }
+
+    /// Return true if the transform leaves all data unchanged
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::identity()
+    }
+
+    /// Linear part of the transform
+    pub fn matrix(&self) -> &[[f64; 3]; 3] {
+        &self.matrix
+    }
+
+    /// Translation applied after the linear part
+    pub fn offset(&self) -> &[f64; 3] {
+        &self.translation
+    }
+
+    /// Factor by which all lengths and speeds are scaled
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    pub fn apply_position(&self, position: Position) -> Position {
+        let moved = mul(&self.matrix, position);
+        [
+            moved[0] + self.translation[0],
+            moved[1] + self.translation[1],
+            moved[2] + self.translation[2],
+        ]
+    }
+
+    pub fn apply_velocity(&self, velocity: [f64; 3]) -> [f64; 3] {
+        mul(&self.matrix, velocity)
+    }
+
+    /// Transform positions and velocities of all particles in `timestep`. Additional
+    /// per-particle fields are left unchanged.
+    pub fn apply(&self, timestep: &mut Timestep) {
+        if self.is_identity() {
+            return;
+        }
+        timestep
+            .position
+            .mapv_inplace(|position| self.apply_position(position));
+        for mut velocity in timestep.velocity.outer_iter_mut() {
+            let new = self.apply_velocity([velocity[0], velocity[1], velocity[2]]);
+            velocity.assign(&aview1(&new));
+        }
+    }
+
+    /// Return `stats` in the transformed frame. Dimensions and velocity ranges are the
+    /// bounding box of the transformed box, which is exact for axis swaps and scaling and
+    /// encloses the data for rotations.
+    pub fn apply_stats(&self, stats: &GlobalStats) -> GlobalStats {
+        if self.is_identity() {
+            return stats.clone();
+        }
+        let mut stats = stats.clone();
+        let mut dimensions = Array2::<f64>::zeros((2, 3));
+        let mut velocity = Array2::<f64>::zeros((3, 3));
+        for i in 0..3 {
+            dimensions[[0, i]] = self.translation[i];
+            dimensions[[1, i]] = self.translation[i];
+            for j in 0..3 {
+                let factor = self.matrix[i][j];
+                let (low, high) = min_max(
+                    factor * stats.dimensions[[0, j]],
+                    factor * stats.dimensions[[1, j]],
+                );
+                dimensions[[0, i]] += low;
+                dimensions[[1, i]] += high;
+                let (low, high) = min_max(
+                    factor * stats.velocity[[j, 0]],
+                    factor * stats.velocity[[j, 2]],
+                );
+                velocity[[i, 0]] += low;
+                velocity[[i, 1]] += factor * stats.velocity[[j, 1]];
+                velocity[[i, 2]] += high;
+            }
+        }
+        stats.dimensions = dimensions;
+        stats.velocity = velocity;
+        stats.velocity_mag.mapv_inplace(|v| v * self.scale);
+        stats.provenance.rescale_length(self.scale);
+        stats
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Transform: x' = M x + t")?;
+        for (row, t) in self.matrix.iter().zip(self.translation.iter()) {
+            writeln!(
+                f,
+                "\t| {:>8.4} {:>8.4} {:>8.4} |   | {:>8.4} |",
+                row[0], row[1], row[2], t
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn mul(matrix: &[[f64; 3]; 3], vector: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+fn min_max(a: f64, b: f64) -> (f64, f64) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: [f64; 3], b: [f64; 3]) -> bool {
+        a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-12)
+    }
+
+    #[test]
+    fn rotation_around_center() {
+        let transform =
+            Transform::rotation([0.0, 0.0, 1.0], std::f64::consts::FRAC_PI_2, [1.0, 0.0, 0.0])
+                .unwrap();
+        assert!(close(transform.apply_position([2.0, 0.0, 3.0]), [1.0, 1.0, 3.0]));
+        assert!(close(transform.apply_velocity([1.0, 0.0, 0.0]), [0.0, 1.0, 0.0]));
+    }
+
+    #[test]
+    fn composition_order() {
+        let transform = Transform::scaling(0.001)
+            .unwrap()
+            .then(&Transform::translation([1.0, 0.0, 0.0]))
+            .then(&Transform::swap_axes(0, 2).unwrap());
+        assert!(close(transform.apply_position([1000.0, 0.0, 0.0]), [0.0, 0.0, 2.0]));
+        assert!(close(transform.apply_velocity([0.0, 0.0, 1000.0]), [1.0, 0.0, 0.0]));
+        assert_eq!(transform.scale(), 0.001);
+    }
+
+    #[test]
+    fn stats_dimensions() {
+        let stats = GlobalStats {
+            dimensions: array![[0.0, -1.0, 0.0], [2.0, 1.0, 5.0]],
+            velocity: array![[-1.0, 0.0, 1.0], [-2.0, 0.0, 2.0], [0.0, 0.5, 1.0]],
+            velocity_mag: array![0.0, 1.0, 2.0],
+            ..GlobalStats::default()
+        };
+        let transform = Transform::swap_axes(0, 2)
+            .unwrap()
+            .then(&Transform::scaling(2.0).unwrap());
+        let stats = transform.apply_stats(&stats);
+        assert_eq!(stats.dimensions, array![[0.0, -2.0, 0.0], [10.0, 2.0, 4.0]]);
+        assert_eq!(stats.velocity.row(0), aview1(&[0.0, 1.0, 2.0]));
+        assert_eq!(stats.velocity_mag, array![0.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(Transform::rotation([0.0; 3], 1.0, [0.0; 3]).is_err());
+        assert!(Transform::scaling(0.0).is_err());
+        assert!(Transform::swap_axes(0, 3).is_err());
+    }
+}
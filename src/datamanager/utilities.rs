@@ -1,4 +1,285 @@
-use crate::types::*;
+use super::{GlobalStats, Provenance, Timestep};
+use crate::error::{Result, Up4Error};
+use ndarray::prelude::*;
+use std::collections::BTreeMap;
+
+/// Open the group `name` in `file`
+pub(super) fn group(file: &hdf5::File, name: &str) -> Result<hdf5::Group> {
+    file.group(name).map_err(|_| Up4Error::MissingGroup {
+        group: name.to_string(),
+        filename: file.filename(),
+    })
+}
+
+/// Open the dataset `name` in `group`
+pub(super) fn dataset(group: &hdf5::Group, name: &str) -> Result<hdf5::Dataset> {
+    group.dataset(name).map_err(|_| Up4Error::MissingDataset {
+        dataset: name.to_string(),
+        filename: group.filename(),
+    })
+}
+
+/// Open the attribute `name` attached to the root of `file`
+pub(super) fn attribute(file: &hdf5::File, name: &str) -> Result<hdf5::Attribute> {
+    file.attr(name).map_err(|_| Up4Error::MissingAttribute {
+        attribute: name.to_string(),
+        filename: file.filename(),
+    })
+}
+
+/// Read an additional per-particle field, scalar fields are returned with a single
+/// column. If `rows` is given only these rows are read from the file.
+pub(super) fn read_field(
+    data: &hdf5::Dataset,
+    rows: Option<(usize, usize)>,
+    name: &str,
+    filename: &str,
+) -> Result<Array2<f64>> {
+    let shape = data.shape();
+    let nrows = shape.first().copied().unwrap_or(0);
+    let (start, end) = rows.unwrap_or((0, nrows));
+    if end > nrows {
+        return Err(Up4Error::MalformedShape {
+            name: name.to_string(),
+            filename: filename.to_string(),
+            expected: format!("at least {} entries", end),
+        });
+    }
+    match shape.len() {
+        1 => Ok(data
+            .read_slice_1d::<f64, _>(s![start..end])
+            .map_err(|e| Up4Error::read(name, filename, e))?
+            .insert_axis(Axis(1))),
+        2 => data
+            .read_slice_2d::<f64, _>(s![start..end, ..])
+            .map_err(|e| Up4Error::read(name, filename, e)),
+        _ => Err(Up4Error::MalformedShape {
+            name: name.to_string(),
+            filename: filename.to_string(),
+            expected: "(N,) or (N, components)".to_string(),
+        }),
+    }
+}
+
+/// Return the names of all additional fields stored in the "fields" subgroup of
+/// `parent`. Files without additional fields have no such subgroup.
+pub(super) fn field_names(parent: &hdf5::Group) -> Result<Vec<String>> {
+    match parent.group("fields") {
+        Ok(fields) => Ok(fields.member_names()?),
+        Err(_) => Ok(vec![]),
+    }
+}
+
+/// Read the global attributes every up4 file carries in its root
+pub(super) fn read_global_stats(file: &hdf5::File) -> Result<GlobalStats> {
+    let filename = file.filename();
+    let dimensions = attribute(file, "dimensions")?
+        .read_2d::<f64>()
+        .map_err(|e| Up4Error::read("dimensions", &filename, e))?;
+    if dimensions.shape() != [2, 3] {
+        return Err(Up4Error::MalformedShape {
+            name: "dimensions".to_string(),
+            filename,
+            expected: "(2, 3)".to_string(),
+        });
+    }
+    let nparticles = attribute(file, "particle number")?
+        .read_scalar()
+        .map_err(|e| Up4Error::read("particle number", &filename, e))?;
+    let timesteps = attribute(file, "timesteps")?
+        .read_scalar()
+        .map_err(|e| Up4Error::read("timesteps", &filename, e))?;
+    let time = attribute(file, "time")?
+        .read_raw::<f64>()
+        .map_err(|e| Up4Error::read("time", &filename, e))?;
+    if time.len() != 2 {
+        return Err(Up4Error::MalformedShape {
+            name: "time".to_string(),
+            filename,
+            expected: "[min_time, max_time]".to_string(),
+        });
+    }
+    let sample_rate = attribute(file, "sample rate")?
+        .read_scalar()
+        .map_err(|e| Up4Error::read("sample rate", &filename, e))?;
+    let velocity = attribute(file, "velocity")?
+        .read_2d()
+        .map_err(|e| Up4Error::read("velocity", &filename, e))?;
+    let velocity_mag = attribute(file, "velocity magnitude")?
+        .read_1d()
+        .map_err(|e| Up4Error::read("velocity magnitude", &filename, e))?;
+    // the time array is required, files written by very old converters do not carry it
+    // and have to be converted again
+    let time_array: Array1<f64> = dataset(file, "time array")?
+        .read_1d()
+        .map_err(|e| Up4Error::read("time array", &filename, e))?;
+    // (type, count) rows, files written by older converters do not list the particle types
+    // and the managers count them from the data
+    let particle_types = match file.attr("particle types") {
+        Ok(attr) => attr
+            .read_2d::<u64>()
+            .map_err(|e| Up4Error::read("particle types", &filename, e))?
+            .outer_iter()
+            .filter(|row| row.len() == 2)
+            .map(|row| (row[0] as usize, row[1] as usize))
+            .collect(),
+        Err(_) => BTreeMap::new(),
+    };
+    Ok(GlobalStats {
+        dimensions,
+        nparticles,
+        ntimesteps: timesteps,
+        min_time: time[0],
+        max_time: time[1],
+        sample_rate,
+        velocity,
+        velocity_mag,
+        time_array,
+        particle_types,
+        provenance: Provenance::read(file)?,
+    })
+}
+
+/// Count the particles of each type, types are truncated to integers and NaN types are
+/// skipped
+pub(crate) fn count_types(ptypes: impl IntoIterator<Item = f64>) -> BTreeMap<usize, usize> {
+    let mut counts = BTreeMap::new();
+    for ptype in ptypes.into_iter().filter(|ptype| !ptype.is_nan()) {
+        *counts.entry(ptype as usize).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Keep the larger count of each type, e.g. to combine the counts of several timesteps
+pub(crate) fn merge_type_counts(
+    counts: &mut BTreeMap<usize, usize>,
+    other: &BTreeMap<usize, usize>,
+) {
+    for (&ptype, &count) in other.iter() {
+        let entry = counts.entry(ptype).or_insert(0);
+        *entry = (*entry).max(count);
+    }
+}
+/// Accumulates the global attributes timestep by timestep, in the same layout the
+/// converters write into the root of a file
+pub(super) struct StatsBuilder {
+    dimensions: Array2<f64>,
+    velocity: Array2<f64>,
+    velocity_mag: Array1<f64>,
+    mean_counter: usize,
+    nparticles: usize,
+    time_array: Vec<f64>,
+    particle_types: BTreeMap<usize, usize>,
+}
+
+impl StatsBuilder {
+    pub(super) fn new() -> Self {
+        let mut dimensions: Array2<f64> = Array2::<f64>::zeros((2, 3)); // [min:[x,y,z],max:[x,y,z]]
+        dimensions.slice_mut(s![0_usize, ..]).fill(f64::MAX);
+        dimensions.slice_mut(s![1_usize, ..]).fill(f64::MIN);
+        //velocity: [x:[min, mean, max],y:[min,mean,max],z:[min,mean,max]]
+        let mut velocity: Array2<f64> = Array2::<f64>::zeros((3, 3));
+        velocity.slice_mut(s![.., 0_usize]).fill(f64::MAX);
+        velocity.slice_mut(s![.., 2_usize]).fill(f64::MIN);
+        // vel mag = [min,mean,max]
+        let mut velocity_mag: Array1<f64> = Array1::<f64>::zeros(3);
+        velocity_mag[0] = f64::MAX;
+        velocity_mag[2] = f64::MIN;
+        StatsBuilder {
+            dimensions,
+            velocity,
+            velocity_mag,
+            mean_counter: 0,
+            nparticles: 0,
+            time_array: vec![],
+            particle_types: BTreeMap::new(),
+        }
+    }
+
+    /// Add all particles of a timestep whose entry in `mask` is true
+    pub(super) fn add(&mut self, timestep: &Timestep, mask: &[bool]) {
+        self.time_array.push(timestep.time);
+        self.nparticles = self.nparticles.max(mask.iter().filter(|&&m| m).count());
+        let types = count_types(
+            timestep
+                .ptype
+                .iter()
+                .zip(mask)
+                .filter(|(_, &m)| m)
+                .map(|(&ptype, _)| ptype),
+        );
+        merge_type_counts(&mut self.particle_types, &types);
+        for ((pos, vel), _) in timestep
+            .position
+            .iter()
+            .zip(timestep.velocity.outer_iter())
+            .zip(mask)
+            .filter(|(_, &m)| m)
+        {
+            for i in 0..3 {
+                if pos[i].is_nan() {
+                    continue;
+                }
+                self.dimensions[[0, i]] = self.dimensions[[0, i]].min(pos[i]);
+                self.dimensions[[1, i]] = self.dimensions[[1, i]].max(pos[i]);
+            }
+            if vel.iter().any(|v| v.is_nan()) {
+                continue;
+            }
+            for i in 0..3 {
+                self.velocity[[i, 0]] = self.velocity[[i, 0]].min(vel[i]);
+                self.velocity[[i, 2]] = self.velocity[[i, 2]].max(vel[i]);
+                self.velocity[[i, 1]] += vel[i];
+            }
+            let vel_mag: f64 = vel.iter().map(|v| v * v).sum::<f64>().sqrt();
+            self.velocity_mag[0] = self.velocity_mag[0].min(vel_mag);
+            self.velocity_mag[2] = self.velocity_mag[2].max(vel_mag);
+            self.velocity_mag[1] += vel_mag;
+            self.mean_counter += 1;
+        }
+    }
+
+    pub(super) fn finish(mut self) -> GlobalStats {
+        if self.mean_counter > 0 {
+            let mean_counter = self.mean_counter as f64;
+            self.velocity
+                .slice_mut(s![.., 1_usize])
+                .mapv_inplace(|v| v / mean_counter);
+            self.velocity_mag[1] /= mean_counter;
+        }
+        let time_array = Array1::from(self.time_array);
+        let min_time = time_array.first().copied().unwrap_or(0.0);
+        let max_time = time_array.last().copied().unwrap_or(0.0);
+        let sample_rate = if time_array.len() > 1 {
+            (max_time - min_time) / (time_array.len() - 1) as f64
+        } else {
+            0.0
+        };
+        GlobalStats {
+            dimensions: self.dimensions,
+            nparticles: self.nparticles,
+            ntimesteps: time_array.len(),
+            min_time,
+            max_time,
+            sample_rate,
+            velocity: self.velocity,
+            velocity_mag: self.velocity_mag,
+            time_array,
+            particle_types: self.particle_types,
+            provenance: Provenance::default(),
+        }
+    }
+}
+
+/// Calculate the global attributes for a set of timesteps held in memory
+pub(super) fn compute_global_stats(timesteps: &[Timestep]) -> GlobalStats {
+    let mut builder = StatsBuilder::new();
+    for timestep in timesteps {
+        builder.add(timestep, &vec![true; timestep.position.len()]);
+    }
+    builder.finish()
+}
+
 /***
 pub fn dimensions(file: &hdf5::File)->(Array1<f64>,Array1<f64>){
         let array = file
@@ -66,59 +347,3 @@ pub fn find_type(file: &hdf5::File)-> String{
 
 
 */
-
-fn rotate_position(position: Position, angles: [f64; 3], anchor: [f64; 3]) -> Position {
-    let mut new_position = [
-        position[0] - anchor[0],
-        position[1] - anchor[1],
-        position[2] - anchor[2],
-    ];
-    for (axis, angle) in angles.iter().enumerate() {
-        let (sin, cos) = angle.sin_cos();
-        let (x, y, z) = (new_position[0], new_position[1], new_position[2]);
-        match axis {
-            0 => {
-                new_position[1] = y * cos - z * sin;
-                new_position[2] = y * sin + z * cos;
-            }
-            1 => {
-                new_position[0] = x * cos + z * sin;
-                new_position[2] = -x * sin + z * cos;
-            }
-            2 => {
-                new_position[0] = x * cos - y * sin;
-                new_position[1] = x * sin + y * cos;
-            }
-            _ => unreachable!(),
-        }
-    }
-    [
-        new_position[0] + anchor[0],
-        new_position[1] + anchor[1],
-        new_position[2] + anchor[2],
-    ]
-}
-
-fn rotate_velocity(velocity: [f64; 3], angles: [f64; 3]) -> [f64; 3] {
-    let mut new_velocity = [velocity[0], velocity[1], velocity[2]];
-    for (axis, angle) in angles.iter().enumerate() {
-        let (sin, cos) = angle.sin_cos();
-        let (x, y, z) = (new_velocity[0], new_velocity[1], new_velocity[2]);
-        match axis {
-            0 => {
-                new_velocity[1] = y * cos - z * sin;
-                new_velocity[2] = y * sin + z * cos;
-            }
-            1 => {
-                new_velocity[0] = x * cos + z * sin;
-                new_velocity[2] = -x * sin + z * cos;
-            }
-            2 => {
-                new_velocity[0] = x * cos - y * sin;
-                new_velocity[1] = x * sin + y * cos;
-            }
-            _ => unreachable!(),
-        }
-    }
-    [new_velocity[0], new_velocity[1], new_velocity[2]]
-}
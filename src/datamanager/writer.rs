@@ -0,0 +1,377 @@
+//! Writer for up4 HDF5 files
+//!
+//! Writes any `DataManager` back into the PData or TData layout, restricted to the
+//! timesteps and particles a `Selector` accepts.
+
+use super::format::{write_format, FileLayout};
+use super::utilities::{count_types, StatsBuilder};
+use super::{DataManager, GlobalStats, Timestep};
+use crate::error::{Result, Up4Error};
+use crate::particleselector::Selector;
+use crate::{check_signals, print_debug};
+use ndarray::prelude::*;
+use std::collections::BTreeMap;
+
+/// Write all timesteps and particles of `data` accepted by `selector` into a new HDF5
+/// file `outname`. The file carries the same attributes the converters write and can be
+/// opened again with `PData` or `TData`, depending on `layout`.
+///
+/// # Examples
+/// ```ignore
+/// let mut data = PData::new("experiment.hdf5")?;
+/// let mut selector = ParticleSelector::default();
+/// selector.set_time(1.0, 2.0);
+/// write_hdf5(&mut data, &selector, "trimmed.hdf5", FileLayout::PData)?;
+/// ```
+pub fn write_hdf5<T>(
+    data: &mut T,
+    selector: &dyn Selector,
+    outname: &str,
+    layout: FileLayout,
+) -> Result<()>
+where
+    T: DataManager + ?Sized,
+{
+    print_debug!("Writer: Writing {:?} file {}", layout, outname);
+    // check the selection before an existing file is overwritten
+    let selected = data
+        .global_stats()
+        .time_array
+        .iter()
+        .any(|&time| selector.timestep_valid(time));
+    if !selected {
+        return Err(nothing_selected(outname));
+    }
+    let file = hdf5::File::create(outname)?;
+    let written = write_file(data, selector, &file, layout, outname);
+    if written.is_err() {
+        // do not leave a partially written file behind
+        drop(file);
+        let _ = std::fs::remove_file(outname);
+    }
+    written
+}
+
+fn write_file<T>(
+    data: &mut T,
+    selector: &dyn Selector,
+    file: &hdf5::File,
+    layout: FileLayout,
+    outname: &str,
+) -> Result<()>
+where
+    T: DataManager + ?Sized,
+{
+    let stats = match layout {
+        FileLayout::TData => write_tdata(data, selector, file)?,
+        FileLayout::PData => write_pdata(data, selector, file)?,
+    };
+    if stats.ntimesteps == 0 {
+        return Err(nothing_selected(outname));
+    }
+    write_attributes(file, &stats, layout)?;
+    // the written file keeps the units and origin of the source dataset
+    data.global_stats().provenance().write(file)?;
+    print_debug!("Writer: Finished writing {}", outname);
+    Ok(())
+}
+
+fn nothing_selected(outname: &str) -> Up4Error {
+    Up4Error::InvalidArgument(format!(
+        "No timestep of the dataset is selected, nothing to write to {}",
+        outname
+    ))
+}
+
+/// Return for each particle in `timestep` whether it is accepted by `selector`
+fn valid_particles(timestep: &Timestep, selector: &dyn Selector) -> Vec<bool> {
+    (0..timestep.position.len())
+        .map(|particle| {
+            selector.is_valid(
+                timestep.radius[particle],
+                timestep.clouds[particle],
+                timestep.density[particle],
+                timestep.particleid[particle] as usize,
+                timestep.ptype[particle],
+            )
+        })
+        .collect()
+}
+
+fn write_dataset<D: ndarray::Dimension>(
+    group: &hdf5::Group,
+    name: &str,
+    data: &Array<f64, D>,
+) -> Result<()> {
+    group.new_dataset_builder().with_data(data).create(name)?;
+    Ok(())
+}
+
+/// One group per timestep, only the selected particles are written
+fn write_tdata<T>(data: &mut T, selector: &dyn Selector, file: &hdf5::File) -> Result<GlobalStats>
+where
+    T: DataManager + ?Sized,
+{
+    let timesteps = *data.global_stats().timesteps();
+    let mut stats = StatsBuilder::new();
+    let mut written = 0;
+    for timestep in 0..timesteps {
+        let timestep_data = data.get_timestep(timestep)?;
+        if !selector.timestep_valid(timestep_data.time) {
+            continue;
+        }
+        let mask = valid_particles(timestep_data, selector);
+        let indices: Vec<usize> = (0..mask.len()).filter(|&id| mask[id]).collect();
+        let position = timestep_data.position.select(Axis(0), &indices);
+        let position = Array2::from_shape_fn((position.len(), 3), |(id, dim)| position[id][dim]);
+
+        let group = file.create_group(&format!("timestep {}", written))?;
+        group
+            .new_dataset::<f64>()
+            .create("time")?
+            .write_scalar(&timestep_data.time)?;
+        write_dataset(&group, "position", &position)?;
+        write_dataset(
+            &group,
+            "velocity",
+            &timestep_data.velocity.select(Axis(0), &indices),
+        )?;
+        write_dataset(&group, "radius", &timestep_data.radius.select(Axis(0), &indices))?;
+        write_dataset(&group, "id", &timestep_data.particleid.select(Axis(0), &indices))?;
+        write_dataset(&group, "ppcloud", &timestep_data.clouds.select(Axis(0), &indices))?;
+        write_dataset(&group, "density", &timestep_data.density.select(Axis(0), &indices))?;
+        write_dataset(
+            &group,
+            "particletype",
+            &timestep_data.ptype.select(Axis(0), &indices),
+        )?;
+        if !timestep_data.fields.is_empty() {
+            let fields = group.create_group("fields")?;
+            for (name, field) in timestep_data.fields.iter() {
+                write_dataset(&fields, name, &field.select(Axis(0), &indices))?;
+            }
+        }
+        stats.add(timestep_data, &mask);
+        written += 1;
+        check_signals!();
+    }
+    Ok(stats.finish())
+}
+
+/// Trajectory of a single particle while collecting the data for the PData layout
+struct Trajectory {
+    // (index of the written timestep, position, velocity)
+    points: Vec<(usize, [f64; 3], [f64; 3])>,
+    // additional fields: name -> (index of the written timestep, value)
+    fields: BTreeMap<String, Vec<(usize, Array1<f64>)>>,
+    particleid: f64,
+    radius: f64,
+    cloud: f64,
+    density: f64,
+    ptype: f64,
+}
+
+/// One group per particle, identified by the particle ID. Timesteps in which a
+/// particle is missing or not selected are filled with NaN.
+fn write_pdata<T>(data: &mut T, selector: &dyn Selector, file: &hdf5::File) -> Result<GlobalStats>
+where
+    T: DataManager + ?Sized,
+{
+    let timesteps = *data.global_stats().timesteps();
+    let mut stats = StatsBuilder::new();
+    let mut trajectories: BTreeMap<usize, Trajectory> = BTreeMap::new();
+    let mut time = vec![];
+    for timestep in 0..timesteps {
+        let timestep_data = data.get_timestep(timestep)?;
+        if !selector.timestep_valid(timestep_data.time) {
+            continue;
+        }
+        let mask = valid_particles(timestep_data, selector);
+        for particle in (0..mask.len()).filter(|&id| mask[id]) {
+            let velocity = timestep_data.velocity.row(particle);
+            let trajectory = trajectories
+                .entry(timestep_data.particleid[particle] as usize)
+                .or_insert_with(|| Trajectory {
+                    points: vec![],
+                    fields: BTreeMap::new(),
+                    particleid: timestep_data.particleid[particle],
+                    radius: timestep_data.radius[particle],
+                    cloud: timestep_data.clouds[particle],
+                    density: timestep_data.density[particle],
+                    ptype: timestep_data.ptype[particle],
+                });
+            trajectory.points.push((
+                time.len(),
+                timestep_data.position[particle],
+                [velocity[0], velocity[1], velocity[2]],
+            ));
+            for (name, field) in timestep_data.fields.iter() {
+                trajectory
+                    .fields
+                    .entry(name.clone())
+                    .or_default()
+                    .push((time.len(), field.row(particle).to_owned()));
+            }
+        }
+        stats.add(timestep_data, &mask);
+        time.push(timestep_data.time);
+        check_signals!();
+    }
+
+    let time = Array1::from(time);
+    let length = time.len();
+    for (particle, trajectory) in trajectories.values().enumerate() {
+        let mut position = Array2::<f64>::from_elem((length, 3), f64::NAN);
+        let mut velocity = Array2::<f64>::from_elem((length, 3), f64::NAN);
+        for (timestep, pos, vel) in trajectory.points.iter() {
+            position
+                .row_mut(*timestep)
+                .assign(&ArrayView1::from(&pos[..]));
+            velocity
+                .row_mut(*timestep)
+                .assign(&ArrayView1::from(&vel[..]));
+        }
+        let group = file.create_group(&format!("particle {}", particle))?;
+        write_dataset(&group, "time", &time)?;
+        write_dataset(&group, "position", &position)?;
+        write_dataset(&group, "velocity", &velocity)?;
+        write_dataset(&group, "id", &Array1::from_elem(length, trajectory.particleid))?;
+        write_dataset(&group, "radius", &Array1::from_elem(length, trajectory.radius))?;
+        write_dataset(&group, "ppcloud", &Array1::from_elem(length, trajectory.cloud))?;
+        write_dataset(&group, "density", &Array1::from_elem(length, trajectory.density))?;
+        write_dataset(
+            &group,
+            "particletype",
+            &Array1::from_elem(length, trajectory.ptype),
+        )?;
+        if !trajectory.fields.is_empty() {
+            let fields = group.create_group("fields")?;
+            for (name, points) in trajectory.fields.iter() {
+                let mut field = Array2::<f64>::from_elem((length, points[0].1.len()), f64::NAN);
+                for (timestep, value) in points.iter() {
+                    field.row_mut(*timestep).assign(value);
+                }
+                write_dataset(&fields, name, &field)?;
+            }
+        }
+    }
+    let mut stats = stats.finish();
+    stats.nparticles = trajectories.len();
+    stats.particle_types = count_types(trajectories.values().map(|trajectory| trajectory.ptype));
+    Ok(stats)
+}
+
+/// Write the root attributes every up4 file carries
+fn write_attributes(file: &hdf5::File, stats: &GlobalStats, layout: FileLayout) -> Result<()> {
+    write_format(file, layout)?;
+    file.new_attr_builder()
+        .with_data(&stats.dimensions)
+        .create("dimensions")?;
+    file.new_attr::<u64>()
+        .create("particle number")?
+        .write_scalar(&(stats.nparticles as u64))?;
+    file.new_attr::<u64>()
+        .create("timesteps")?
+        .write_scalar(&(stats.ntimesteps as u64))?;
+    file.new_attr::<f64>()
+        .create("sample rate")?
+        .write_scalar(&stats.sample_rate)?;
+    file.new_attr_builder()
+        .with_data(&array![stats.min_time, stats.max_time])
+        .create("time")?;
+    file.new_attr_builder()
+        .with_data(&stats.velocity)
+        .create("velocity")?;
+    file.new_attr_builder()
+        .with_data(&stats.velocity_mag)
+        .create("velocity magnitude")?;
+    file.new_dataset_builder()
+        .with_data(&stats.time_array)
+        .create("time array")?;
+    write_particle_types(file, &stats.particle_types)
+}
+
+/// Write the number of particles of each type as one (type, count) row per type, so the
+/// managers do not have to count them when opening the file
+pub(crate) fn write_particle_types(
+    file: &hdf5::File,
+    particle_types: &BTreeMap<usize, usize>,
+) -> Result<()> {
+    if particle_types.is_empty() {
+        return Ok(());
+    }
+    let rows: Array2<u64> = particle_types
+        .iter()
+        .map(|(&ptype, &count)| [ptype as u64, count as u64])
+        .collect::<Vec<_>>()
+        .into();
+    file.new_attr_builder()
+        .with_data(&rows)
+        .create("particle types")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamanager::{MData, PData, TData};
+    use crate::particleselector::ParticleSelector;
+    use std::path::Path;
+
+    /// Two particles with the IDs 7 and 3 moving along x
+    fn data() -> MData {
+        let time = Array1::linspace(0.0, 0.3, 4);
+        let position =
+            Array3::from_shape_fn((4, 2, 3), |(t, p, d)| if d == 0 { (t + p) as f64 } else { 0.0 });
+        let particleid = Array2::from_shape_fn((4, 2), |(_, p)| [7.0, 3.0][p]);
+        MData::from_arrays(time, position, None, None, Some(particleid), None, None).unwrap()
+    }
+
+    fn filename(name: &str) -> String {
+        let filename = std::env::temp_dir().join(format!("up4_writer_{}.hdf5", name));
+        filename.to_str().unwrap().to_string()
+    }
+
+    /// Position along x and ID of each particle in the timestep `timestep`, sorted by ID
+    fn particles(data: &mut dyn DataManager, timestep: usize) -> Vec<(f64, f64)> {
+        let timestep = data.get_timestep(timestep).unwrap();
+        let mut particles: Vec<(f64, f64)> = (0..timestep.position.len())
+            .map(|particle| (timestep.particleid[particle], timestep.position[particle][0]))
+            .collect();
+        particles.sort_by(|a, b| a.0.total_cmp(&b.0));
+        particles
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut source = data();
+        let expected = particles(&mut source, 2);
+        assert_eq!(expected, vec![(3.0, 3.0), (7.0, 2.0)]);
+        let selector = ParticleSelector::default();
+        for layout in [FileLayout::TData, FileLayout::PData] {
+            let filename = filename(&format!("{:?}", layout));
+            write_hdf5(&mut source, &selector, &filename, layout).unwrap();
+            let mut written: Box<dyn DataManager> = match layout {
+                FileLayout::TData => Box::new(TData::new(&filename).unwrap()),
+                FileLayout::PData => Box::new(PData::new(&filename).unwrap()),
+            };
+            assert_eq!(*written.global_stats().timesteps(), 4);
+            assert_eq!(particles(written.as_mut(), 2), expected);
+            // the types are read from the attribute and not counted when opening the file
+            let particle_types = source.particle_types().unwrap();
+            assert_eq!(written.global_stats().particle_types(), &particle_types);
+            drop(written);
+            std::fs::remove_file(&filename).unwrap();
+        }
+    }
+
+    #[test]
+    fn nothing_selected() {
+        let mut source = data();
+        let mut selector = ParticleSelector::default();
+        selector.set_time(10.0, 20.0);
+        let filename = filename("empty");
+        assert!(write_hdf5(&mut source, &selector, &filename, FileLayout::PData).is_err());
+        assert!(!Path::new(&filename).exists());
+    }
+}
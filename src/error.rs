@@ -0,0 +1,90 @@
+//! Crate wide error type.
+//!
+//! All fallible functions in the `DataManager` implementations and the analysis functions
+//! built on top of them return `error::Result`, so that a broken or unexpected file can be
+//! reported to the caller instead of aborting the whole process.
+use thiserror::Error;
+
+/// Errors that can occur while reading or processing an up4 dataset.
+#[derive(Debug, Error)]
+pub enum Up4Error {
+    /// The HDF5 file could not be opened at all
+    #[error("Can not open HDF5 file {filename}. Check if the file exists. ({source})")]
+    FileOpen {
+        filename: String,
+        source: hdf5::Error,
+    },
+
+    /// A group such as `particle 0` or `timestep 0` is missing
+    #[error("Can not find group \"{group}\" in file {filename}")]
+    MissingGroup { group: String, filename: String },
+
+    /// A dataset such as `position` or `time array` is missing
+    #[error("Can not find dataset \"{dataset}\" in file {filename}")]
+    MissingDataset { dataset: String, filename: String },
+
+    /// A root attribute such as `dimensions` or `timesteps` is missing
+    #[error("Can not find attribute \"{attribute}\" in file {filename}")]
+    MissingAttribute { attribute: String, filename: String },
+
+    /// A dataset or attribute exists but can not be read with the expected type
+    #[error(
+        "Can not read data from \"{name}\" in file {filename}. \
+        Data type or data format might be wrong. ({source})"
+    )]
+    Read {
+        name: String,
+        filename: String,
+        source: hdf5::Error,
+    },
+
+    /// A dataset or attribute was read but does not have the expected shape
+    #[error("Data \"{name}\" in file {filename} has a wrong shape, expected {expected}")]
+    MalformedShape {
+        name: String,
+        filename: String,
+        expected: String,
+    },
+
+    /// The file is not of the kind the reader expects (e.g. a TData file opened as PData)
+    #[error("File {filename} is of the wrong kind: expected {expected}, found {found}")]
+    WrongFileKind {
+        filename: String,
+        expected: String,
+        found: String,
+    },
+
+    /// A timestep outside of the dataset was requested
+    #[error("Timestep {timestep} is out of range, the dataset has {timesteps} timesteps")]
+    TimestepOutOfRange { timestep: usize, timesteps: usize },
+
+    /// A time outside of the dataset was requested
+    #[error("Time {time} is out of range, the dataset covers {min_time} to {max_time}")]
+    TimeOutOfRange {
+        time: f64,
+        min_time: f64,
+        max_time: f64,
+    },
+
+    /// A function was called with an argument it can not handle (e.g. an unknown mode)
+    #[error("{0}")]
+    InvalidArgument(String),
+
+    /// Any other error raised by the HDF5 library
+    #[error(transparent)]
+    Hdf5(#[from] hdf5::Error),
+}
+
+impl Up4Error {
+    /// Shorthand for a `Up4Error::Read` error
+    pub fn read(name: &str, filename: &str, source: hdf5::Error) -> Self {
+        Up4Error::Read {
+            name: name.to_string(),
+            filename: filename.to_string(),
+            source,
+        }
+    }
+}
+
+/// `Result` type used throughout the crate
+pub type Result<T> = std::result::Result<T, Up4Error>;
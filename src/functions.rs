@@ -1,7 +1,12 @@
 pub mod conditional;
 pub mod extractions;
 pub mod mixing;
-use crate::datamanager::DataManager;
+pub mod parallel;
+pub mod property;
+use crate::datamanager::{DataManager, GlobalStats, TimeLookup, Timestep};
+use parallel::{empty_grid, fold_timesteps, fold_windows};
+use property::{CellAccumulator, Property, Reducer};
+use crate::error::{Result, Up4Error};
 //use crate::utilities::print_debug;
 use crate::{check_signals, print_debug, print_warning};
 extern crate ndarray;
@@ -9,10 +14,34 @@ extern crate ndarray;
 extern crate numpy;
 
 use crate::{
-    grid::{GridFunctions3D, VectorGrid},
+    grid::{GridFunctions3D, GridUnits, VectorGrid},
+    units::LengthUnit,
     ParticleSelector, Selector,
 };
 use ndarray::prelude::*;
+use std::collections::BTreeMap;
+/// Return the timestep at `time`, lagged timesteps are served from the cache of `data`.
+/// Without `interpolate` the recorded timestep closest to `time` is returned, otherwise a
+/// timestep is interpolated linearly between the recorded timesteps around `time`.
+pub(crate) fn timestep_at_time<T: DataManager + ?Sized>(
+    data: &mut T,
+    global_stats: &GlobalStats,
+    time: f64,
+    interpolate: bool,
+) -> Result<Timestep> {
+    if !interpolate {
+        let timestep = global_stats.timestep_at(time, TimeLookup::Nearest)?;
+        return Ok(data.get_timestep(timestep)?.clone());
+    }
+    let (floor, ceil, weight) = global_stats.time_bracket(time)?;
+    let before = data.get_timestep(floor)?.clone();
+    if floor == ceil {
+        return Ok(before);
+    }
+    let after = data.get_timestep(ceil)?;
+    Ok(before.interpolate(after, weight))
+}
+
 pub trait Granular: DataManager {
     /// Calculate a 2D velocity vectorfield across `grid`, optionally normalising values to 1.
     /// The 3D data is projected in 2D according to `axis`.
@@ -26,65 +55,61 @@ pub trait Granular: DataManager {
         &mut self,
         gridbox: Box<dyn GridFunctions3D>,
         selector: &ParticleSelector,
-    ) -> VectorGrid {
-        let global_stats = self.global_stats();
-        let timesteps: &usize = global_stats.timesteps();
-        let mut vectorgrid = VectorGrid::new(gridbox);
-        for timestep in 0..=timesteps - 1 {
-            let timestep_data = self.get_timestep(timestep);
-            let current_time = *timestep_data.time();
-            // check if timestep is in the timeframe given
-            if !selector.timestep_valid(current_time) {
-                print_debug!("Timestep {} is not valid", timestep);
-                continue;
-            }
-            print_debug!("Timestep {} is valid", timestep);
-            let positions = timestep_data.position();
-            let velocities = timestep_data.velocity();
-            let particle_ids = timestep_data.particleid();
-            let rad_array = timestep_data.radius();
-            let clouds = timestep_data.clouds();
-            let density = timestep_data.density();
-            let particles = positions.len();
-            // loop over all particles in this timestep, calculate the velocity vector and add it to the
-            // vectorfield array
-            print_debug!(
-                "velocityfield: looping over particles form 0 to {}",
-                particles
-            );
-            for particle in 0..particles {
-                if !selector.is_valid(
-                    rad_array[particle],
-                    clouds[particle],
-                    density[particle],
-                    particle_ids[particle] as usize,
-                ) {
-                    print_debug!("Particle {} is not valid", particle);
-                    continue;
-                }
-                print_debug!("Particle {} is valid", particle);
-                let position = positions[particle];
-                if position[0].is_nan() || position[1].is_nan() || position[2].is_nan() {
-                    print_debug!("Position is NaN");
-                    continue;
-                }
-                let velocity = velocities.slice(s![particle, ..]).to_owned();
-                //reset the position. the lowest value should be at 0,0,0
-                if !vectorgrid.is_inside(position) {
-                    // the particle is out of the field of view
-                    print_debug!("Particle {} is out of FoV", particle);
-                    continue;
-                }
-                let velocity = vectorgrid.velocity_calculation(position, velocity);
+    ) -> Result<VectorGrid> {
+        let empty = VectorGrid::new(empty_grid(gridbox.as_ref()));
+        let mut vectorgrid = fold_timesteps(
+            self,
+            selector,
+            || empty.clone(),
+            |vectorgrid, timestep_data| {
+                let positions = timestep_data.position();
+                let velocities = timestep_data.velocity();
+                let particle_ids = timestep_data.particleid();
+                let rad_array = timestep_data.radius();
+                let clouds = timestep_data.clouds();
+                let density = timestep_data.density();
+                let particle_type = timestep_data.ptype();
+                let particles = positions.len();
+                // loop over all particles in this timestep, calculate the velocity vector and add it to the
+                // vectorfield array
+                print_debug!(
+                    "velocityfield: looping over particles form 0 to {}",
+                    particles
+                );
+                for particle in 0..particles {
+                    if !selector.is_valid(
+                        rad_array[particle],
+                        clouds[particle],
+                        density[particle],
+                        particle_ids[particle] as usize,
+                        particle_type[particle],
+                    ) {
+                        print_debug!("Particle {} is not valid", particle);
+                        continue;
+                    }
+                    print_debug!("Particle {} is valid", particle);
+                    let position = positions[particle];
+                    if position[0].is_nan() || position[1].is_nan() || position[2].is_nan() {
+                        print_debug!("Position is NaN");
+                        continue;
+                    }
+                    let velocity = velocities.slice(s![particle, ..]).to_owned();
+                    if !vectorgrid.is_inside(position) {
+                        // the particle is out of the field of view
+                        print_debug!("Particle {} is out of FoV", particle);
+                        continue;
+                    }
+                    let velocity = vectorgrid.velocity_calculation(position, velocity);
 
-                vectorgrid.data[0].add_value(position, velocity[0]);
-                vectorgrid.data[1].add_value(position, velocity[1]);
-                vectorgrid.data[2].add_value(position, velocity[2]);
-            }
-            check_signals!();
-        }
+                    vectorgrid.data[0].add_value(position, velocity[0]);
+                    vectorgrid.data[1].add_value(position, velocity[1]);
+                    vectorgrid.data[2].add_value(position, velocity[2]);
+                }
+            },
+        )?;
         vectorgrid.divide_by_weight();
-        vectorgrid
+        vectorgrid.set_units(GridUnits::velocity(length_unit(self)));
+        Ok(vectorgrid)
     }
 
     fn velocityfield(
@@ -94,7 +119,7 @@ pub trait Granular: DataManager {
         mode: &str,
         min_vel: f64,
         max_vel: f64,
-    ) -> Box<dyn GridFunctions3D> {
+    ) -> Result<Box<dyn GridFunctions3D>> {
         //read the number of timesteps inside this hdf5file
         type FnPointer = fn(Array1<f64>) -> f64;
         let velocity_calc: FnPointer;
@@ -147,158 +172,128 @@ pub trait Granular: DataManager {
             }
             velocity_calc = velocity_calculation;
         } else {
-            panic!(
+            return Err(Up4Error::InvalidArgument(format!(
                 "Mode {} is not valid. Valid modes are: \"absolute\", \"x\" \"r\", \"y\", \"theta\" \"z\"",
                 mode
-            );
+            )));
         }
-        let global_stats = self.global_stats();
-        let timesteps: &usize = global_stats.timesteps();
-        let mut velocity_grid = grid.new_zeros();
         print_debug!("velocityfield: Initiation over, entering time loop");
-        for timestep in 0..=timesteps - 1 {
-            let timestep_data = self.get_timestep(timestep);
-            let current_time = *timestep_data.time();
-            // check if timestep is in the timeframe given
-            if !selector.timestep_valid(current_time) {
-                print_debug!("Timestep {} is not valid", timestep);
-                continue;
-            }
-            print_debug!("Timestep {} is valid", timestep);
-            let positions = timestep_data.position();
-            let velocities = timestep_data.velocity();
-            let particle_ids = timestep_data.particleid();
-            let rad_array = timestep_data.radius();
-            let clouds = timestep_data.clouds();
-            let density = timestep_data.density();
-            let particles = positions.len();
-            // loop over all particles in this timestep, calculate the velocity vector and add it to the
-            // vectorfield array
-            print_debug!(
-                "velocityfield: looping over particles form 0 to {}",
-                particles
-            );
-            for particle in 0..particles {
-                if !selector.is_valid(
-                    rad_array[particle],
-                    clouds[particle],
-                    density[particle],
-                    particle_ids[particle] as usize,
-                ) {
-                    print_debug!("Particle {} is not valid", particle);
-                    continue;
-                }
-                print_debug!("Particle {} is valid", particle);
-                let position = positions[particle];
-                if position[0].is_nan() || position[1].is_nan() || position[2].is_nan() {
-                    print_debug!("Position is NaN");
-                    continue;
-                }
-                let velocity = velocities.slice(s![particle, ..]).to_owned();
-                //reset the position. the lowest value should be at 0,0,0
+        let mut velocity_grid = fold_timesteps(
+            self,
+            selector,
+            || empty_grid(grid.as_ref()),
+            |velocity_grid, timestep_data| {
+                let positions = timestep_data.position();
+                let velocities = timestep_data.velocity();
+                let particle_ids = timestep_data.particleid();
+                let rad_array = timestep_data.radius();
+                let clouds = timestep_data.clouds();
+                let density = timestep_data.density();
+                let particle_type = timestep_data.ptype();
+                let particles = positions.len();
+                // loop over all particles in this timestep, calculate the velocity vector and add it to the
+                // vectorfield array
+                print_debug!(
+                    "velocityfield: looping over particles form 0 to {}",
+                    particles
+                );
+                for particle in 0..particles {
+                    if !selector.is_valid(
+                        rad_array[particle],
+                        clouds[particle],
+                        density[particle],
+                        particle_ids[particle] as usize,
+                        particle_type[particle],
+                    ) {
+                        print_debug!("Particle {} is not valid", particle);
+                        continue;
+                    }
+                    print_debug!("Particle {} is valid", particle);
+                    let position = positions[particle];
+                    if position[0].is_nan() || position[1].is_nan() || position[2].is_nan() {
+                        print_debug!("Position is NaN");
+                        continue;
+                    }
+                    let velocity = velocities.slice(s![particle, ..]).to_owned();
 
-                //velocities
-                let abs_vel = velocity_calc(velocity);
+                    //velocities
+                    let abs_vel = velocity_calc(velocity);
 
-                if abs_vel.is_nan() {
-                    print_debug!("Particle {} has no velocity", particle);
-                    continue;
-                }
+                    if abs_vel.is_nan() {
+                        print_debug!("Particle {} has no velocity", particle);
+                        continue;
+                    }
 
-                if !grid.is_inside(position) {
-                    // the particle is out of the field of view
-                    print_debug!("Particle {} is out of FoV", particle);
-                    continue;
-                }
-                if abs_vel < min_vel || abs_vel > max_vel {
-                    print_debug!("Particle {} is out of range", particle);
-                    continue;
+                    if !grid.is_inside(position) {
+                        // the particle is out of the field of view
+                        print_debug!("Particle {} is out of FoV", particle);
+                        continue;
+                    }
+                    if abs_vel < min_vel || abs_vel > max_vel {
+                        print_debug!("Particle {} is out of range", particle);
+                        continue;
+                    }
+                    print_debug!("Particle {} is in the grid", particle);
+                    print_debug!("Cell ids: {:?}", grid.cell_id(position));
+                    velocity_grid.add_value(position, abs_vel);
                 }
-                print_debug!("Particle {} is in the grid", particle);
-                print_debug!("Cell ids: {:?}", grid.cell_id(position));
-                print_debug!(
-                    "Grid Positions \n{:?},\n{:?},\n{:?}",
-                    grid.get_xpositions(),
-                    grid.get_ypositions(),
-                    grid.get_zpositions()
-                );
-                velocity_grid.add_value(position, abs_vel);
-            }
-            // checking for kill signals after each timestep
-            check_signals!();
-        }
+            },
+        )?;
         velocity_grid.divide_by_weight();
-        velocity_grid
+        velocity_grid.set_units(GridUnits::velocity(length_unit(self)));
+        Ok(velocity_grid)
     }
 
     fn numberfield(
         &mut self,
         grid: Box<dyn GridFunctions3D>,
         selector: &ParticleSelector,
-    ) -> Box<dyn GridFunctions3D> {
-        //read the number of timesteps inside this hdf5file
-        let global_stats = self.global_stats();
-        let timesteps: &usize = global_stats.timesteps();
-        let mut number_grid = grid.new_zeros();
-        print_debug!("velocityfield: Initiation over, entering time loop");
-        for timestep in 0..=timesteps - 1 {
-            let timestep_data = self.get_timestep(timestep);
-            let current_time = *timestep_data.time();
-            // check if timestep is in the timeframe given
-            if !selector.timestep_valid(current_time) {
-                print_debug!("Timestep {} is not valid", timestep);
-                continue;
-            }
-            print_debug!("Timestep {} is valid", timestep);
-            let positions = timestep_data.position();
-
-            let particle_ids = timestep_data.particleid();
-            let rad_array = timestep_data.radius();
-            let clouds = timestep_data.clouds();
-            let density = timestep_data.density();
-            let particles = positions.len();
-            // loop over all particles in this timestep, calculate the velocity vector and add it to the
-            // vectorfield array
-            print_debug!(
-                "velocityfield: looping over particles form 0 to {}",
-                particles
-            );
-
-            for particle in 0..particles {
-                if !selector.is_valid(
-                    rad_array[particle],
-                    clouds[particle],
-                    density[particle],
-                    particle_ids[particle] as usize,
-                ) {
-                    print_debug!("Particle {} is not valid", particle);
-                    continue;
-                }
-
-                print_debug!("Particle {} is valid", particle);
-                let position = positions[particle];
-
-                if !grid.is_inside(position) {
-                    // the particle is out of the field of view
-                    print_debug!("Particle {} is out of FoV", particle);
-                    continue;
-                }
-                print_debug!("Particle {} is in the grid", particle);
-                print_debug!("Cell ids: {:?}", grid.cell_id(position));
+    ) -> Result<Box<dyn GridFunctions3D>> {
+        print_debug!("numberfield: Initiation over, entering time loop");
+        let mut number_grid = fold_timesteps(
+            self,
+            selector,
+            || empty_grid(grid.as_ref()),
+            |number_grid, timestep_data| {
+                let positions = timestep_data.position();
+                let particle_ids = timestep_data.particleid();
+                let rad_array = timestep_data.radius();
+                let clouds = timestep_data.clouds();
+                let density = timestep_data.density();
+                let particle_type = timestep_data.ptype();
+                let particles = positions.len();
                 print_debug!(
-                    "Grid Positions \n{:?},\n{:?},\n{:?}",
-                    grid.get_xpositions(),
-                    grid.get_ypositions(),
-                    grid.get_zpositions()
+                    "numberfield: looping over particles form 0 to {}",
+                    particles
                 );
-                //here bug already
+                for particle in 0..particles {
+                    if !selector.is_valid(
+                        rad_array[particle],
+                        clouds[particle],
+                        density[particle],
+                        particle_ids[particle] as usize,
+                        particle_type[particle],
+                    ) {
+                        print_debug!("Particle {} is not valid", particle);
+                        continue;
+                    }
 
-                number_grid.add_value(position, 1.0);
-            }
-            // checking for kill signals after each timestep
-            check_signals!();
-        }
-        number_grid
+                    print_debug!("Particle {} is valid", particle);
+                    let position = positions[particle];
+
+                    if !grid.is_inside(position) {
+                        // the particle is out of the field of view
+                        print_debug!("Particle {} is out of FoV", particle);
+                        continue;
+                    }
+                    print_debug!("Particle {} is in the grid", particle);
+                    print_debug!("Cell ids: {:?}", grid.cell_id(position));
+                    number_grid.add_value(position, 1.0);
+                }
+            },
+        )?;
+        number_grid.set_units(GridUnits::new(length_unit(self), None));
+        Ok(number_grid)
     }
 
     /// Occupancy field
@@ -307,94 +302,84 @@ pub trait Granular: DataManager {
         grid: Box<dyn GridFunctions3D>,
         selector: &ParticleSelector,
         min_vel: f64,
-    ) -> Box<dyn GridFunctions3D> {
-        //read the number of timesteps inside this hdf5file
-        let global_stats = self.global_stats();
-        let timesteps: &usize = global_stats.timesteps();
-        let mut occupancy_grid = grid.new_zeros();
-        let mut complete_time = 0.0;
-        self.setup_buffer(0); //setup another buffer
-        for timestep in 0..timesteps - 3 {
-            let timestep_data = self.get_timestep(timestep).clone();
-            //BUG this is not working, try to acces a point in the buffer that doesnt excist
-            let next_timestep_data = self.get_timestep_buffer(timestep + 1, 0);
-            let current_time = *timestep_data.time();
-            let next_time = *next_timestep_data.time();
-            // check if timestep is in the timeframe given
-            if !selector.timestep_valid(current_time) {
-                print_debug!("Timestep {} is not valid", timestep);
-                continue;
-            }
-            print_debug!("Timestep {} is valid", timestep);
-            let positions = timestep_data.position();
-            let next_positions = next_timestep_data.position();
-            let velocities = timestep_data.velocity();
-            let particle_ids = timestep_data.particleid();
-            let rad_array = timestep_data.radius();
-            let clouds = timestep_data.clouds();
-            let density = timestep_data.density();
-            let particles = positions.len();
-            // loop over all particles in this timestep, calculate the velocity vector and add it to the
-            // vectorfield array
-            print_debug!(
-                "velocityfield: looping over particles form 0 to {}",
-                particles
-            );
-
-            for particle in 0..particles {
-                if !selector.is_valid(
-                    rad_array[particle],
-                    clouds[particle],
-                    density[particle],
-                    particle_ids[particle] as usize,
-                ) {
-                    print_debug!("Particle {} is not valid", particle);
-                    continue;
-                }
-
-                print_debug!("Particle {} is valid", particle);
-                let position = positions[particle];
-                if !grid.is_inside(position) {
-                    // the particle is out of the field of view
-                    print_debug!("Particle {} is out of FoV", particle);
-                    continue;
-                }
-                print_debug!("Particle {} is in the grid", particle);
-                print_debug!("Cell ids: {:?}", grid.cell_id(position));
+    ) -> Result<Box<dyn GridFunctions3D>> {
+        // every pair of consecutive timesteps adds the time between them along the
+        // trajectory of each particle
+        let (mut occupancy_grid, complete_time) = fold_windows(
+            self,
+            selector,
+            2,
+            || (empty_grid(grid.as_ref()), 0.0),
+            |(occupancy_grid, complete_time), window| {
+                let (timestep_data, next_timestep_data) = (&window[0], &window[1]);
+                let current_time = *timestep_data.time();
+                let next_time = *next_timestep_data.time();
+                let positions = timestep_data.position();
+                let next_positions = next_timestep_data.position();
+                let next_ids = next_timestep_data.id_map();
+                let velocities = timestep_data.velocity();
+                let particle_ids = timestep_data.particleid();
+                let rad_array = timestep_data.radius();
+                let clouds = timestep_data.clouds();
+                let density = timestep_data.density();
+                let particle_type = timestep_data.ptype();
+                let particles = positions.len();
                 print_debug!(
-                    "Grid Positions \n{:?},\n{:?},\n{:?}",
-                    grid.get_xpositions(),
-                    grid.get_ypositions(),
-                    grid.get_zpositions()
+                    "occupancyfield: looping over particles form 0 to {}",
+                    particles
                 );
-                if (velocities
-                    .slice(s![particle, ..])
-                    .iter()
-                    .fold(0.0, |res, x| res + x * x))
-                .sqrt()
-                    < min_vel
-                {
-                    continue;
-                }
-                //here bug already
                 let time_spent = next_time - current_time;
                 if time_spent < 0.0 {
                     print_warning!("Occupyfield: timestep is negative");
-                    continue;
+                    return;
                 }
-                //TODO BUG this is not valit code. we must check which particle is at which position
-                if next_positions.len() <= particle {
-                    print_warning!("Occupyfield: next timestep has less particles");
-                    continue;
+                for particle in 0..particles {
+                    if !selector.is_valid(
+                        rad_array[particle],
+                        clouds[particle],
+                        density[particle],
+                        particle_ids[particle] as usize,
+                        particle_type[particle],
+                    ) {
+                        print_debug!("Particle {} is not valid", particle);
+                        continue;
+                    }
+
+                    print_debug!("Particle {} is valid", particle);
+                    let position = positions[particle];
+                    if !grid.is_inside(position) {
+                        // the particle is out of the field of view
+                        print_debug!("Particle {} is out of FoV", particle);
+                        continue;
+                    }
+                    if (velocities
+                        .slice(s![particle, ..])
+                        .iter()
+                        .fold(0.0, |res, x| res + x * x))
+                    .sqrt()
+                        < min_vel
+                    {
+                        continue;
+                    }
+                    let next_index = match next_ids.get(&(particle_ids[particle] as usize)) {
+                        Some(&index) => index,
+                        None => {
+                            print_debug!("Occupyfield: particle left the system");
+                            continue;
+                        }
+                    };
+                    occupancy_grid.add_trajectory_value(
+                        position,
+                        next_positions[next_index],
+                        time_spent,
+                    );
+                    *complete_time += time_spent;
                 }
-                occupancy_grid.add_trajectory_value(position, next_positions[particle], time_spent);
-                complete_time += time_spent;
-            }
-            // checking for kill signals after each timestep
-            check_signals!();
-        }
+            },
+        )?;
         occupancy_grid.divide_by_scalar(complete_time);
-        occupancy_grid
+        occupancy_grid.set_units(GridUnits::new(length_unit(self), None));
+        Ok(occupancy_grid)
     }
 
     /// Calculate the mean velocity of the valid particles within the system.
@@ -405,13 +390,13 @@ pub trait Granular: DataManager {
     ///’’’
     ///mean_velocity = data.mean_velocity_showcase(particleselector)
     ///'''
-    fn mean_velocity_showcase(&mut self, selector: &ParticleSelector) -> f64 {
+    fn mean_velocity_showcase(&mut self, selector: &ParticleSelector) -> Result<f64> {
         let global_stats = self.global_stats();
         let timesteps = global_stats.timesteps();
         let mut mean_velocity = 0.0;
         let mut num_counts = 0.0;
         for timestep in 0..=timesteps - 1 {
-            let timestep_data = self.get_timestep(timestep);
+            let timestep_data = self.get_timestep(timestep)?;
             let current_time = *timestep_data.time();
             // check if timestep is in the timeframe given
             if !selector.timestep_valid(current_time) {
@@ -431,7 +416,7 @@ pub trait Granular: DataManager {
         }
         mean_velocity /= num_counts;
 
-        mean_velocity
+        Ok(mean_velocity)
     }
 
     /// Return the dispersion of the particles in the system.
@@ -443,6 +428,9 @@ pub trait Granular: DataManager {
     ///    Grid class containing the grid layout.
     /// time_for_dispersion : float
     ///   Time for which the dispersion is calculated.
+    /// interpolate : bool
+    ///   Interpolate the positions exactly `time_for_dispersion` later instead of using
+    ///   the closest recorded timestep.
     ///
     /// returns
     /// -------
@@ -458,12 +446,12 @@ pub trait Granular: DataManager {
         grid: Box<dyn GridFunctions3D>,
         selector: &ParticleSelector,
         time_for_dispersion: f64,
-    ) -> (Box<dyn GridFunctions3D>, f64) {
+        interpolate: bool,
+    ) -> Result<(Box<dyn GridFunctions3D>, f64)> {
         let global_stats = self.global_stats();
         let timesteps = global_stats.timesteps();
-        self.setup_buffer(0); // add another buffer to the system
-                              // Allocate arrays needed for calculation
-                              // those arrays are needed for the calculation of the variance with a special algorithm
+        // Allocate arrays needed for calculation
+        // those arrays are needed for the calculation of the variance with a special algorithm
         let mut squared_sum_x = grid.get_data().clone();
         let mut squared_sum_y = grid.get_data().clone();
         let mut squared_sum_z = grid.get_data().clone();
@@ -474,9 +462,8 @@ pub trait Granular: DataManager {
         let mut dispersion_grid = grid.new_zeros();
         print_debug!("Dispersion: Initiation over, entering time loop");
 
-        let mut next_timestep;
         for timestep in 0..=timesteps - 1 {
-            let timestep_data = self.get_timestep(timestep).clone();
+            let timestep_data = self.get_timestep(timestep)?.clone();
             let current_time = *timestep_data.time();
             // check if timestep is in the timeframe given
             if !selector.timestep_valid(current_time) {
@@ -490,33 +477,26 @@ pub trait Granular: DataManager {
             let rad_array = timestep_data.radius();
             let clouds = timestep_data.clouds();
             let density = timestep_data.density();
+            let particle_type = timestep_data.ptype();
             let particles = positions.len();
             print_debug!("Dispersion: looping over particles form 0 to {}", particles);
-            next_timestep = match global_stats
-                .timestep_at_seconds_closest(current_time + time_for_dispersion)
-            {
-                Ok(x) => x,
-                Err(s) => {
-                    print_warning!(
-                        "Dispersion: No timestep found for time {}, \n error: {}",
-                        current_time + time_for_dispersion,
-                        s
-                    );
-                    continue;
-                }
-            };
-            //println!("This timestep is {}", timestep);
-            //println!("Dt is {}", time_for_dispersion);
-            //println!("Next timestep is {}", next_timestep);
-            //println!("Current time is {}", current_time);
-
-            //   global_stats.timestep_at_seconds(current_time + time_for_dispersion);
-
-            print_debug!("Next timestep is {}", next_timestep);
-            let timestep_future = self.get_timestep_buffer(next_timestep, 0);
-            //println!("Next time is {}", *timestep_future.time());
+            let future_time = current_time + time_for_dispersion;
+            let timestep_future =
+                match timestep_at_time(self, &global_stats, future_time, interpolate) {
+                    Ok(x) => x,
+                    Err(s) => {
+                        print_warning!(
+                            "Dispersion: No timestep found for time {}, \n error: {}",
+                            future_time,
+                            s
+                        );
+                        continue;
+                    }
+                };
+            print_debug!("Future time is {}", *timestep_future.time());
             print_debug!("extracting position");
             let position_future = timestep_future.position();
+            let future_ids = timestep_future.id_map();
             print_debug!("Starting particle loop");
 
             for particle in 0..particles {
@@ -525,6 +505,7 @@ pub trait Granular: DataManager {
                     clouds[particle],
                     density[particle],
                     particle_ids[particle] as usize,
+                    particle_type[particle],
                 ) {
                     print_debug!("Particle {} is not valid", particle);
                     continue;
@@ -545,7 +526,12 @@ pub trait Granular: DataManager {
                         continue;
                     }
                 };
-                let position_future_particle = position_future[particle];
+                // the particle may have left the system or moved in the arrays
+                let future_index = match future_ids.get(&(particle_ids[particle] as usize)) {
+                    Some(&index) => index,
+                    None => continue,
+                };
+                let position_future_particle = position_future[future_index];
                 squared_sum_x[cell_id] += position_future_particle[0] * position_future_particle[0];
                 squared_sum_y[cell_id] += position_future_particle[1] * position_future_particle[1];
                 squared_sum_z[cell_id] += position_future_particle[2] * position_future_particle[2];
@@ -585,7 +571,8 @@ pub trait Granular: DataManager {
         let mixing_effectifness = (dispersion_grid.get_weights() * dispersion_grid.get_data())
             .sum()
             / dispersion_grid.get_weights().sum();
-        (dispersion_grid, mixing_effectifness)
+        dispersion_grid.set_units(GridUnits::new(length_unit(self), None));
+        Ok((dispersion_grid, mixing_effectifness))
     }
 
     /// Calculate the mean velocity of the valid particles within the system.
@@ -648,19 +635,16 @@ pub trait Granular: DataManager {
         property: &str,
         max_limit: f64,
         bins: usize,
-    ) -> (Array1<f64>, Array1<f64>) {
+    ) -> Result<(Array1<f64>, Array1<f64>)> {
         let property_list = vec!["velocity"];
         if !property_list.contains(&property) {
-            panic!(
+            return Err(Up4Error::InvalidArgument(format!(
                 "Property {} is not available. Available properties are: {:?}",
                 property, property_list
-            );
+            )));
         }
 
-        //read the number of timesteps inside this hdf5file
         let global_stats = self.global_stats();
-        let timesteps: &usize = global_stats.timesteps();
-        let mut histogram = ndarray::Array1::<f64>::zeros(bins);
         // make bin edges
 
         let mut bin_edges = ndarray::Array1::<f64>::zeros(bins + 1);
@@ -678,61 +662,58 @@ pub trait Granular: DataManager {
             }
             bin_edges[bins] = max;
         } else {
-            panic!(
+            return Err(Up4Error::InvalidArgument(format!(
                 "Property {} is not available. Available properties are: {:?}",
                 property, property_list
-            );
+            )));
         }
-        print_debug!("velocityfield: Initiation over, entering time loop");
-        for timestep in 0..=timesteps - 1 {
-            let timestep_data = self.get_timestep(timestep);
-            let current_time = *timestep_data.time();
-            // check if timestep is in the timeframe given
-            if !selector.timestep_valid(current_time) {
-                print_debug!("Timestep {} is not valid", timestep);
-                continue;
-            }
-            print_debug!("Timestep {} is valid", timestep);
-            let positions = timestep_data.position();
-            let velocities = timestep_data.velocity();
-            let particle_ids = timestep_data.particleid();
-            let rad_array = timestep_data.radius();
-            let clouds = timestep_data.clouds();
-            let density = timestep_data.density();
-            let particles = positions.len();
-            for particle in 0..particles {
-                if !selector.is_valid(
-                    rad_array[particle],
-                    clouds[particle],
-                    density[particle],
-                    particle_ids[particle] as usize,
-                ) {
-                    print_debug!("Particle {} is not valid", particle);
-                    continue;
-                }
+        print_debug!("histogram: Initiation over, entering time loop");
+        let histogram = fold_timesteps(
+            self,
+            selector,
+            || Array1::<f64>::zeros(bins),
+            |histogram, timestep_data| {
+                let positions = timestep_data.position();
+                let velocities = timestep_data.velocity();
+                let particle_ids = timestep_data.particleid();
+                let rad_array = timestep_data.radius();
+                let clouds = timestep_data.clouds();
+                let density = timestep_data.density();
+                let particle_type = timestep_data.ptype();
+                let particles = positions.len();
+                for particle in 0..particles {
+                    if !selector.is_valid(
+                        rad_array[particle],
+                        clouds[particle],
+                        density[particle],
+                        particle_ids[particle] as usize,
+                        particle_type[particle],
+                    ) {
+                        print_debug!("Particle {} is not valid", particle);
+                        continue;
+                    }
 
-                print_debug!("Particle {} is valid", particle);
-                let position = positions[particle];
-                if !grid.is_inside(position) {
-                    continue;
-                }
-                let velocity = velocities.slice(s![particle, ..]);
-                let velocity_mag = (velocity[0] * velocity[0]
-                    + velocity[1] * velocity[1]
-                    + velocity[2] * velocity[2])
-                    .sqrt();
-                let bin = ((velocity_mag - bin_edges[0]) / (bin_edges[1] - bin_edges[0])).floor()
-                    as usize;
-                // BUG: bin is sometimes out of bounds
-                if bin > bins - 1 {
-                    continue;
+                    print_debug!("Particle {} is valid", particle);
+                    let position = positions[particle];
+                    if !grid.is_inside(position) {
+                        continue;
+                    }
+                    let velocity = velocities.slice(s![particle, ..]);
+                    let velocity_mag = (velocity[0] * velocity[0]
+                        + velocity[1] * velocity[1]
+                        + velocity[2] * velocity[2])
+                        .sqrt();
+                    let bin = ((velocity_mag - bin_edges[0]) / (bin_edges[1] - bin_edges[0]))
+                        .floor() as usize;
+                    // BUG: bin is sometimes out of bounds
+                    if bin > bins - 1 {
+                        continue;
+                    }
+                    histogram[bin] += 1.0;
                 }
-                histogram[bin] += 1.0;
-            }
-            // checking for kill signals after each timestep
-            check_signals!();
-        }
-        (histogram, bin_edges)
+            },
+        )?;
+        Ok((histogram, bin_edges))
     }
 
     /// Calculate the granular temperature of the system.
@@ -756,90 +737,78 @@ pub trait Granular: DataManager {
         grid: Box<dyn GridFunctions3D>,
         selector: &ParticleSelector,
         mode: &str,
-    ) -> Box<dyn GridFunctions3D> {
-        //read the number of timesteps inside this hdf5file
-        let global_stats = self.global_stats();
-        let timesteps: &usize = global_stats.timesteps();
+    ) -> Result<Box<dyn GridFunctions3D>> {
         // calculating the mean fluicitaing velcity using a algroythm that allows
-        // to only use one loop over the data
-        let mut squared_sum_x = grid.get_data().clone();
-        let mut squared_sum_y = grid.get_data().clone();
-        let mut squared_sum_z = grid.get_data().clone();
-        let mut sum_x = grid.get_data().clone();
-        let mut sum_y = grid.get_data().clone();
-        let mut sum_z = grid.get_data().clone();
-        let mut num_counts = grid.get_data().clone();
-        let mut grantemp = grid.new_zeros();
-        print_debug!("velocityfield: Initiation over, entering time loop");
-        for timestep in 0..=timesteps - 1 {
-            let timestep_data = self.get_timestep(timestep);
-            let current_time = *timestep_data.time();
-            // check if timestep is in the timeframe given
-            if !selector.timestep_valid(current_time) {
-                print_debug!("Timestep {} is not valid", timestep);
-                continue;
-            }
-            print_debug!("Timestep {} is valid", timestep);
-            let positions = timestep_data.position();
-            let velocities = timestep_data.velocity();
-            let particle_ids = timestep_data.particleid();
-            let rad_array = timestep_data.radius();
-            let clouds = timestep_data.clouds();
-            let density = timestep_data.density();
-            let particles = positions.len();
-            // loop over all particles in this timestep, calculate the velocity vector and add it to the
-            // vectorfield array
-            print_debug!(
-                "velocityfield: looping over particles form 0 to {}",
-                particles
-            );
-
-            for particle in 0..particles {
-                if !selector.is_valid(
-                    rad_array[particle],
-                    clouds[particle],
-                    density[particle],
-                    particle_ids[particle] as usize,
-                ) {
-                    print_debug!("Particle {} is not valid", particle);
-                    continue;
-                }
-
-                print_debug!("Particle {} is valid", particle);
-                let position = positions[particle];
-                if !grid.is_inside(position) {
-                    // the particle is out of the field of view
-                    print_debug!("Particle {} is out of FoV", particle);
-                    continue;
-                }
-                print_debug!("Particle {} is in the grid", particle);
-                print_debug!("Cell ids: {:?}", grid.cell_id(position));
+        // to only use one loop over the data. The sums of all three velocity
+        // components are stored along the first axis.
+        let cells = *grid.get_cells();
+        let moments = fold_timesteps(
+            self,
+            selector,
+            || {
+                (
+                    Array3::<f64>::zeros(cells),
+                    (
+                        Array4::<f64>::zeros((3, cells[0], cells[1], cells[2])),
+                        Array4::<f64>::zeros((3, cells[0], cells[1], cells[2])),
+                    ),
+                )
+            },
+            |(num_counts, (sum, squared_sum)), timestep_data| {
+                let positions = timestep_data.position();
+                let velocities = timestep_data.velocity();
+                let particle_ids = timestep_data.particleid();
+                let rad_array = timestep_data.radius();
+                let clouds = timestep_data.clouds();
+                let density = timestep_data.density();
+                let particle_type = timestep_data.ptype();
+                let particles = positions.len();
                 print_debug!(
-                    "Grid Positions \n{:?},\n{:?},\n{:?}",
-                    grid.get_xpositions(),
-                    grid.get_ypositions(),
-                    grid.get_zpositions()
+                    "granular_temperature_field: looping over particles form 0 to {}",
+                    particles
                 );
-                let velocity = velocities.slice(s![particle, ..]);
-                let cell_id = match grid.cell_id(position) {
-                    Ok(cell_id) => cell_id,
-                    Err(_) => continue,
-                };
-                num_counts[cell_id] += 1.0;
-                sum_x[cell_id] += velocity[0];
-                sum_y[cell_id] += velocity[1];
-                sum_z[cell_id] += velocity[2];
-                squared_sum_x[cell_id] += velocity[0] * velocity[0];
-                squared_sum_y[cell_id] += velocity[1] * velocity[1];
-                squared_sum_z[cell_id] += velocity[2] * velocity[2];
-            }
-            // checking for kill signals after each timestep
-            check_signals!();
-        }
 
-        let fluct_x = &squared_sum_x - &sum_x * &sum_x / &num_counts;
-        let fluct_y = &squared_sum_y - &sum_y * &sum_y / &num_counts;
-        let fluct_z = &squared_sum_z - &sum_z * &sum_z / &num_counts;
+                for particle in 0..particles {
+                    if !selector.is_valid(
+                        rad_array[particle],
+                        clouds[particle],
+                        density[particle],
+                        particle_ids[particle] as usize,
+                        particle_type[particle],
+                    ) {
+                        print_debug!("Particle {} is not valid", particle);
+                        continue;
+                    }
+
+                    print_debug!("Particle {} is valid", particle);
+                    let position = positions[particle];
+                    if !grid.is_inside(position) {
+                        // the particle is out of the field of view
+                        print_debug!("Particle {} is out of FoV", particle);
+                        continue;
+                    }
+                    print_debug!("Particle {} is in the grid", particle);
+                    let velocity = velocities.slice(s![particle, ..]);
+                    let cell_id = match grid.cell_id(position) {
+                        Ok(cell_id) => cell_id,
+                        Err(_) => continue,
+                    };
+                    num_counts[cell_id] += 1.0;
+                    for dim in 0..3 {
+                        let index = [dim, cell_id[0], cell_id[1], cell_id[2]];
+                        sum[index] += velocity[dim];
+                        squared_sum[index] += velocity[dim] * velocity[dim];
+                    }
+                }
+            },
+        )?;
+        let (num_counts, (sum, squared_sum)) = moments;
+        let fluct = |dim: usize| {
+            let sum = sum.index_axis(Axis(0), dim);
+            &squared_sum.index_axis(Axis(0), dim) - &(&sum * &sum / &num_counts)
+        };
+        let (fluct_x, fluct_y, fluct_z) = (fluct(0), fluct(1), fluct(2));
+        let mut grantemp = grid.new_zeros();
         let mut temp;
 
         if mode == "x" {
@@ -851,13 +820,17 @@ pub trait Granular: DataManager {
         } else if mode == "xyz" {
             temp = (&fluct_x + &fluct_y + &fluct_z) / 3.0;
         } else {
-            panic!("Mode {} is not supported", mode);
+            return Err(Up4Error::InvalidArgument(format!(
+                "Mode {} is not supported",
+                mode
+            )));
         }
         temp /= &num_counts;
         temp.mapv_inplace(|x| x.sqrt());
         grantemp.set_data(temp);
         grantemp.set_weights(num_counts);
-        grantemp
+        grantemp.set_units(GridUnits::velocity(length_unit(self)));
+        Ok(grantemp)
     }
 
     fn homogenity_index(
@@ -865,8 +838,8 @@ pub trait Granular: DataManager {
         grid: Box<dyn GridFunctions3D>,
         selector: &ParticleSelector,
         min_velocity: f64,
-    ) -> f64 {
-        let field = self.occupancyfield(grid, selector, min_velocity);
+    ) -> Result<f64> {
+        let field = self.occupancyfield(grid, selector, min_velocity)?;
         let field = field.collapse_two(0, 1);
         let mean = field.iter().fold(
             0.0,
@@ -880,10 +853,161 @@ pub trait Granular: DataManager {
             ih += (value - mean) * (value - mean);
         }
         ih /= field.len() as f64;
-        ih.sqrt()
+        Ok(ih.sqrt())
+    }
+
+    /// Bin any per-particle `property` onto `grid` and reduce the values in each cell with
+    /// `reducer`. Particles with a NaN value are ignored, empty cells are NaN unless the
+    /// reducer is `Reducer::Count`. The weights of the returned grid hold the number of values
+    /// in each cell.
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let property: Property = "kinetic_energy".parse()?;
+    /// let field = data.property_field(grid, &selector, &property, Reducer::Median)?;
+    /// ```
+    fn property_field(
+        &mut self,
+        grid: Box<dyn GridFunctions3D>,
+        selector: &ParticleSelector,
+        property: &Property,
+        reducer: Reducer,
+    ) -> Result<Box<dyn GridFunctions3D>> {
+        let cells = *grid.get_cells();
+        let accumulator = bin_property(self, grid.as_ref(), selector, property, reducer, false)?
+            .remove(&0)
+            .unwrap_or_else(|| CellAccumulator::new(cells, reducer));
+        let mut property_grid = reduced_grid(grid.as_ref(), accumulator);
+        property_grid.set_units(property.units(length_unit(self), reducer));
+        Ok(property_grid)
+    }
+
+    /// Bin `property` onto `grid` like `property_field`, but reduce the values of each
+    /// particle type separately. All types are binned in a single pass over the data and
+    /// every type listed in the global stats gets a grid, even if it has no valid particles.
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let property: Property = "vz".parse()?;
+    /// let fields = data.property_field_by_type(grid, &selector, &property, Reducer::Mean)?;
+    /// let small_particles = &fields[&0];
+    /// ```
+    fn property_field_by_type(
+        &mut self,
+        grid: Box<dyn GridFunctions3D>,
+        selector: &ParticleSelector,
+        property: &Property,
+        reducer: Reducer,
+    ) -> Result<BTreeMap<usize, Box<dyn GridFunctions3D>>> {
+        let accumulators = bin_property(self, grid.as_ref(), selector, property, reducer, true)?;
+        let units = property.units(length_unit(self), reducer);
+        Ok(accumulators
+            .into_iter()
+            .map(|(ptype, accumulator)| {
+                let mut property_grid = reduced_grid(grid.as_ref(), accumulator);
+                property_grid.set_units(units.clone());
+                (ptype, property_grid)
+            })
+            .collect())
     }
 } //End Granular trait
 
+/// Bin `property` of all valid particles onto `grid`. With `by_type` the values of each
+/// particle type are collected in their own accumulator, otherwise all values are
+/// collected under type 0.
+fn bin_property<T: DataManager + ?Sized>(
+    data: &mut T,
+    grid: &dyn GridFunctions3D,
+    selector: &ParticleSelector,
+    property: &Property,
+    reducer: Reducer,
+    by_type: bool,
+) -> Result<BTreeMap<usize, CellAccumulator>> {
+    let global_stats = data.global_stats();
+    let timesteps: &usize = global_stats.timesteps();
+    let cells = *grid.get_cells();
+    let mut accumulators: BTreeMap<usize, CellAccumulator> = BTreeMap::new();
+    if by_type {
+        for &ptype in data.particle_types()?.keys() {
+            accumulators.insert(ptype, CellAccumulator::new(cells, reducer));
+        }
+    }
+    print_debug!("property_field: Initiation over, entering time loop");
+    for timestep in 0..*timesteps {
+        let timestep_data = data.get_timestep(timestep)?;
+        let current_time = *timestep_data.time();
+        // check if timestep is in the timeframe given
+        if !selector.timestep_valid(current_time) {
+            print_debug!("Timestep {} is not valid", timestep);
+            continue;
+        }
+        let positions = timestep_data.position();
+        let particle_ids = timestep_data.particleid();
+        let rad_array = timestep_data.radius();
+        let clouds = timestep_data.clouds();
+        let density = timestep_data.density();
+        let particle_type = timestep_data.ptype();
+        for particle in 0..positions.len() {
+            if !selector.is_valid(
+                rad_array[particle],
+                clouds[particle],
+                density[particle],
+                particle_ids[particle] as usize,
+                particle_type[particle],
+            ) {
+                print_debug!("Particle {} is not valid", particle);
+                continue;
+            }
+            let position = positions[particle];
+            if !grid.is_inside(position) {
+                print_debug!("Particle {} is out of FoV", particle);
+                continue;
+            }
+            let cell_id = match grid.cell_id(position) {
+                Ok(cell_id) => cell_id,
+                Err(_) => continue,
+            };
+            if (0..3).any(|dim| cell_id[dim] >= cells[dim]) {
+                continue;
+            }
+            let value = property.value(timestep_data, particle)?;
+            if value.is_nan() {
+                continue;
+            }
+            let ptype = if by_type {
+                particle_type[particle] as usize
+            } else {
+                0
+            };
+            accumulators
+                .entry(ptype)
+                .or_insert_with(|| CellAccumulator::new(cells, reducer))
+                .add(cell_id, value);
+        }
+        // checking for kill signals after each timestep
+        check_signals!();
+    }
+    Ok(accumulators)
+}
+
+/// Return a grid like `grid` holding the reduced values of `accumulator`, the weights
+/// hold the number of values in each cell
+fn reduced_grid(
+    grid: &dyn GridFunctions3D,
+    accumulator: CellAccumulator,
+) -> Box<dyn GridFunctions3D> {
+    let (data, weights) = accumulator.finish();
+    let mut property_grid = grid.new_zeros();
+    property_grid.set_data(data);
+    property_grid.set_weights(weights);
+    property_grid
+}
+
+/// Unit of length of `data`, `None` if the dataset does not record it
+pub(crate) fn length_unit<T: DataManager + ?Sized>(data: &T) -> Option<LengthUnit> {
+    *data.global_stats().provenance().length_unit()
+}
+
 impl<T> Granular for T where T: DataManager {}
 impl<T> extractions::Extraction for T where T: DataManager {}
 impl<T> mixing::Mixing for T where T: DataManager {}
@@ -1,28 +1,39 @@
 use super::*;
+use std::collections::HashMap;
+
+/// Circulation state of a single particle, kept by particle ID
+#[derive(Default)]
+struct CirculationState {
+    start: usize,
+    mid: usize,
+    time: f64,
+    mid_time: f64,
+}
+
 pub trait Conditional: DataManager {
     fn circulation_time(
         &mut self,
         selector: &ParticleSelector,
         axis: usize,
         boundary_position: f64,
-    ) -> Vec<f64> {
+    ) -> Result<Vec<f64>> {
         //read the number of timesteps inside this hdf5file
 
         let global_stats = self.global_stats();
         let dim = global_stats.dimensions();
         if boundary_position > dim[[1, axis]] || boundary_position < dim[[0, axis]] {
-            panic!("Boundary position is outside of the system");
+            return Err(Up4Error::InvalidArgument(
+                "Boundary position is outside of the system".to_string(),
+            ));
         }
-        let particle_number = global_stats.nparticles();
         let timesteps: &usize = global_stats.timesteps();
         print_debug!("velocityfield: Initiation over, entering time loop");
         let mut circulation_time = Vec::<f64>::new();
-        let mut start_flag_array = Array1::<usize>::zeros(particle_number + 1);
-        let mut time_flag_array = Array1::<f64>::zeros(particle_number + 1);
+        let mut states: HashMap<usize, CirculationState> = HashMap::new();
 
-        let mut old_timestep = self.get_timestep(0).to_owned();
+        let mut old_timestep = self.get_timestep(0)?.to_owned();
         for timestep in 1..timesteps - 1 {
-            let timestep_data = self.get_timestep(timestep);
+            let timestep_data = self.get_timestep(timestep)?;
             let current_time = *timestep_data.time();
             // check if timestep is in the timeframe given
             if !selector.timestep_valid(current_time) {
@@ -33,11 +44,11 @@ pub trait Conditional: DataManager {
             let positions = timestep_data.position();
             let old_positions = old_timestep.position();
             let particle_ids = timestep_data.particleid();
-            let old_particle_ids = old_timestep.particleid();
+            let old_ids = old_timestep.id_map();
             let rad_array = timestep_data.radius();
             let clouds = timestep_data.clouds();
             let density = timestep_data.density();
-            //let particle_type = timestep_data.ptype();
+            let particle_type = timestep_data.ptype();
             let particles = positions.len();
             // loop over all particles in this timestep, calculate the velocity vector and add it to the
             // vectorfield array
@@ -52,27 +63,34 @@ pub trait Conditional: DataManager {
                     clouds[particle],
                     density[particle],
                     particle_ids[particle] as usize,
+                    particle_type[particle],
                 ) {
                     print_debug!("Particle {} is not valid", particle);
                     continue;
                 }
-                if particle_ids[particle] != old_particle_ids[particle] {
-                    print_debug!("Particle {} is not valid", particle);
-                    continue;
-                }
+                // match the particle by ID, it may not exist in the last timestep
+                let id = particle_ids[particle] as usize;
+                let old_index = match old_ids.get(&id) {
+                    Some(&index) => index,
+                    None => {
+                        print_debug!("Particle {} is not in the last timestep", id);
+                        continue;
+                    }
+                };
+                let state = states.entry(id).or_default();
                 let pos = positions[particle];
-                let old_pos = old_positions[particle];
+                let old_pos = old_positions[old_index];
                 // check if particle crossed boundary
                 if (pos[axis] - boundary_position) * (old_pos[axis] - boundary_position) < 0.0 {
                     // check if particle is already in the list
-                    if start_flag_array[particle] == 0 {
-                        start_flag_array[particle] = 1;
-                        time_flag_array[particle] = current_time;
-                    } else if start_flag_array[particle] == 1 {
-                        start_flag_array[particle] = 2;
+                    if state.start == 0 {
+                        state.start = 1;
+                        state.time = current_time;
+                    } else if state.start == 1 {
+                        state.start = 2;
                     } else {
-                        circulation_time.push(current_time - time_flag_array[particle]);
-                        start_flag_array[particle] = 0;
+                        circulation_time.push(current_time - state.time);
+                        state.start = 0;
                     }
                 }
             }
@@ -80,7 +98,7 @@ pub trait Conditional: DataManager {
             old_timestep = timestep_data.to_owned();
             check_signals!()
         }
-        circulation_time
+        Ok(circulation_time)
     }
 
     fn circulation_time_two_boundary(
@@ -88,30 +106,27 @@ pub trait Conditional: DataManager {
         selector: &ParticleSelector,
         axis: usize,
         boundary_position: (f64,f64),
-    ) -> Vec<(f64,f64)> {
+    ) -> Result<Vec<(f64,f64)>> {
         //read the number of timesteps inside this hdf5file
 
         let global_stats = self.global_stats();
         let dim = global_stats.dimensions();
-        if boundary_position.0 > dim[[1, axis]] || boundary_position.0 < dim[[0, axis]] {
-            panic!("Boundary position is outside of the system");
-        }
-        if boundary_position.1 > dim[[1, axis]] || boundary_position.1 < dim[[0, axis]] {
-            panic!("Boundary position is outside of the system");
+        for boundary in [boundary_position.0, boundary_position.1] {
+            if boundary > dim[[1, axis]] || boundary < dim[[0, axis]] {
+                return Err(Up4Error::InvalidArgument(
+                    "Boundary position is outside of the system".to_string(),
+                ));
+            }
         }
-        let particle_number = global_stats.nparticles();
         let timesteps: &usize = global_stats.timesteps();
         print_debug!("velocityfield: Initiation over, entering time loop");
         let mut circulation_time = Vec::<(f64,f64)>::new();
-        let mut start_flag_array = Array1::<usize>::zeros(particle_number + 1);
-        let mut mid_flag_array = Array1::<usize>::zeros(particle_number + 1);
-        let mut time_flag_array = Array1::<f64>::zeros(particle_number + 1);
-        let mut mid_time_flag_array = Array1::<f64>::zeros(particle_number + 1);
+        let mut states: HashMap<usize, CirculationState> = HashMap::new();
      
-        let mut old_timestep = self.get_timestep(0).to_owned();
+        let mut old_timestep = self.get_timestep(0)?.to_owned();
         let mut count = 0;
         for timestep in 1..timesteps - 1 {
-            let timestep_data = self.get_timestep(timestep);
+            let timestep_data = self.get_timestep(timestep)?;
             let current_time = *timestep_data.time();
             // check if timestep is in the timeframe given
             if !selector.timestep_valid(current_time) {
@@ -122,11 +137,11 @@ pub trait Conditional: DataManager {
             let positions = timestep_data.position();
             let old_positions = old_timestep.position();
             let particle_ids = timestep_data.particleid();
-            let old_particle_ids = old_timestep.particleid();
+            let old_ids = old_timestep.id_map();
             let rad_array = timestep_data.radius();
             let clouds = timestep_data.clouds();
             let density = timestep_data.density();
-            //let particle_type = timestep_data.ptype();
+            let particle_type = timestep_data.ptype();
             let particles = positions.len();
             // loop over all particles in this timestep, calculate the velocity vector and add it to the
             // vectorfield array
@@ -141,29 +156,36 @@ pub trait Conditional: DataManager {
                     clouds[particle],
                     density[particle],
                     particle_ids[particle] as usize,
+                    particle_type[particle],
                 ) {
                     print_debug!("Particle {} is not valid", particle);
                     continue;
                 }
-                if particle_ids[particle] != old_particle_ids[particle] {
-                    print_debug!("Particle {} is not valid", particle);
-                    continue;
-                }
+                // match the particle by ID, it may not exist in the last timestep
+                let id = particle_ids[particle] as usize;
+                let old_index = match old_ids.get(&id) {
+                    Some(&index) => index,
+                    None => {
+                        print_debug!("Particle {} is not in the last timestep", id);
+                        continue;
+                    }
+                };
+                let state = states.entry(id).or_default();
                 let pos = positions[particle][axis];
-                let old_pos = old_positions[particle][axis];
+                let old_pos = old_positions[old_index][axis];
                 if pos > boundary_position.1 && old_pos < boundary_position.1 {
                     count += 1;
                     //println!("particle inside upper ");
     
                     // if particle before was below
-                    if start_flag_array[particle] == 2 {
+                    if state.start == 2 {
                         // say " particle was in the other region"
-                        if mid_flag_array[particle] == 0 {
-                            //down_times.push(current_time - time_flag_array[particle]);
+                        if state.mid == 0 {
+                            //down_times.push(current_time - state.time);
                         }
-                        mid_flag_array[particle] = 1;
-                    } else if start_flag_array[particle] == 1 && mid_flag_array[particle] == 1 {
-                        //up_times.push(current_time - mid_time_flag_array[particle]);
+                        state.mid = 1;
+                    } else if state.start == 1 && state.mid == 1 {
+                        //up_times.push(current_time - state.mid_time);
                         // One more flag to make shure up time only counted once
                     }
                 }
@@ -173,21 +195,21 @@ pub trait Conditional: DataManager {
                     //println!("particle out of upper");
                     //println!("{}->{},{},{},{}",old_pos,pos,boundary[1],boundary[0],pos  < boundary[0] && old_pos  > boundary[0]);
                     // if particle before was nowhere
-                    if start_flag_array[particle] == 0 {
+                    if state.start == 0 {
                         // start clock
-                        time_flag_array[particle] = current_time;
-                        start_flag_array[particle] = 1;
-                    } else if start_flag_array[particle] == 1 && mid_flag_array[particle] == 1 {
+                        state.time = current_time;
+                        state.start = 1;
+                    } else if state.start == 1 && state.mid == 1 {
                         // end of one circulation
-                        circulation_time.push((time_flag_array[particle],current_time));
-                        //if current_time - time_flag_array[particle] <= 0.0 {println!("Out of higher")};
+                        circulation_time.push((state.time,current_time));
+                        //if current_time - state.time <= 0.0 {println!("Out of higher")};
                         // restart the run
-                        mid_flag_array[particle] = 0;
-                        time_flag_array[particle] = current_time;
-                    } else if start_flag_array[particle] == 1 {
-                        time_flag_array[particle] = current_time;
-                    } else if start_flag_array[particle] == 2 {
-                        mid_time_flag_array[particle] = current_time;
+                        state.mid = 0;
+                        state.time = current_time;
+                    } else if state.start == 1 {
+                        state.time = current_time;
+                    } else if state.start == 2 {
+                        state.mid_time = current_time;
                     }
                     continue;
                 }
@@ -199,34 +221,34 @@ pub trait Conditional: DataManager {
                     //println!("particle in lower");
                     //println!("{}->{},{},{},{}",old_pos,pos,boundary[1],boundary[0],pos  < boundary[0] && old_pos  > boundary[0]);
                     // if particle before was below
-                    if start_flag_array[particle] == 1 {
+                    if state.start == 1 {
                         // say " particle was in the other region"
-                        if mid_flag_array[particle] == 0 {
-                            //down_times.push(current_time - time_flag_array[particle]);
+                        if state.mid == 0 {
+                            //down_times.push(current_time - state.time);
                         }
-                        mid_flag_array[particle] = 1;
-                    } else if start_flag_array[particle] == 2 && mid_flag_array[particle] == 1 {
-                        //down_times.push(current_time - mid_time_flag_array[particle]);
+                        state.mid = 1;
+                    } else if state.start == 2 && state.mid == 1 {
+                        //down_times.push(current_time - state.mid_time);
                     }
                 }
                 // particle crosses out of lower border
                 else if pos > boundary_position.0 && old_pos < boundary_position.0 {
                     // if particle before was nowhere
-                    if start_flag_array[particle] == 0 {
+                    if state.start == 0 {
                         // start clock
-                        time_flag_array[particle] = current_time;
-                        start_flag_array[particle] = 2;
-                    } else if start_flag_array[particle] == 2 && mid_flag_array[particle] == 1 {
+                        state.time = current_time;
+                        state.start = 2;
+                    } else if state.start == 2 && state.mid == 1 {
                         // end of one circulation
-                        circulation_time.push((time_flag_array[particle],current_time));
+                        circulation_time.push((state.time,current_time));
                         // restart the run
-                        //if current_time - time_flag_array[particle] <= 0.0 {println!("Out of lower")};
-                        mid_flag_array[particle] = 0;
-                        time_flag_array[particle] = current_time;
-                    } else if start_flag_array[particle] == 2 {
-                        time_flag_array[particle] = current_time;
-                    } else if start_flag_array[particle] == 1 {
-                        mid_time_flag_array[particle] = current_time;
+                        //if current_time - state.time <= 0.0 {println!("Out of lower")};
+                        state.mid = 0;
+                        state.time = current_time;
+                    } else if state.start == 2 {
+                        state.time = current_time;
+                    } else if state.start == 1 {
+                        state.mid_time = current_time;
                     }
                 }
                 //println!("check done");
@@ -235,7 +257,7 @@ pub trait Conditional: DataManager {
             old_timestep = timestep_data.to_owned();
             check_signals!()
         }
-        circulation_time
+        Ok(circulation_time)
     }
 
 }
@@ -1,30 +1,48 @@
 use crate::datamanager::DataManager;
+use crate::error::{Result, Up4Error};
 //use crate::utilities::print_debug;
 use crate::check_signals;
 extern crate ndarray;
 //extern crate ndarray_linalg;
 extern crate numpy;
-use core::panic;
 use ndarray::prelude::*;
 
 pub trait Extraction: DataManager {
-    /// Extract the particle information over a given time period
-    fn extract(&mut self, particle_id: usize, timestep: (usize, usize)) -> ndarray::Array2<f64> {
+    /// Extract the particle information over a given time period. The particle is found
+    /// by its ID in every timestep, timesteps in which it does not exist are NaN.
+    fn extract(
+        &mut self,
+        particle_id: usize,
+        timestep: (usize, usize),
+    ) -> Result<ndarray::Array2<f64>> {
         let global_stats = self.global_stats();
         let timesteps: &usize = global_stats.timesteps();
-        if &timestep.0 >= timesteps || &timestep.1 >= timesteps {
-            panic!("Timestep out of bounds");
+        for requested in [timestep.0, timestep.1] {
+            if &requested >= timesteps {
+                return Err(Up4Error::TimestepOutOfRange {
+                    timestep: requested,
+                    timesteps: *timesteps,
+                });
+            }
         }
         let mut return_data: ndarray::Array2<f64> =
             ndarray::Array2::zeros((timestep.1 - timestep.0, 7));
         for (id, timestep) in (timestep.0..timestep.1).enumerate() {
             let mut mut_return = return_data.slice_mut(s![id, ..]);
-            let timestep_data = self.get_timestep(timestep);
+            let timestep_data = self.get_timestep(timestep)?;
             let current_time = *timestep_data.time();
+            let index = match timestep_data.index_of(particle_id) {
+                Some(index) => index,
+                None => {
+                    mut_return.fill(f64::NAN);
+                    mut_return[0] = current_time;
+                    continue;
+                }
+            };
             let positions = timestep_data.position();
             let velocities = timestep_data.velocity();
-            let position = positions[particle_id];
-            let velocity = velocities.slice(s![particle_id, ..]).to_owned();
+            let position = positions[index];
+            let velocity = velocities.slice(s![index, ..]).to_owned();
             let x = array![
                 current_time,
                 position[0],
@@ -38,6 +56,6 @@ pub trait Extraction: DataManager {
             check_signals!();
         }
 
-        return_data
+        Ok(return_data)
     }
 }
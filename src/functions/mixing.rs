@@ -12,7 +12,7 @@ pub trait Mixing: DataManager {
         type_a: usize,
         type_b: usize,
         threshhold: usize,
-    ) -> (Array1<f64>, Array1<f64>) {
+    ) -> Result<(Array1<f64>, Array1<f64>)> {
         //read the number of timesteps inside this hdf5file
         let global_stats = self.global_stats();
         let timesteps: &usize = global_stats.timesteps();
@@ -21,7 +21,7 @@ pub trait Mixing: DataManager {
         let mut time = ndarray::Array1::<f64>::zeros(*timesteps - 1);
 
         for timestep in 0..timesteps - 1 {
-            let timestep_data = self.get_timestep(timestep);
+            let timestep_data = self.get_timestep(timestep)?;
             let current_time = *timestep_data.time();
             // check if timestep is in the timeframe given
             if !selector.timestep_valid(current_time) {
@@ -51,6 +51,7 @@ pub trait Mixing: DataManager {
                     clouds[particle],
                     density[particle],
                     particle_ids[particle] as usize,
+                    particle_type[particle],
                 ) {
                     print_debug!("Particle {} is not valid", particle);
                     continue;
@@ -114,7 +115,7 @@ pub trait Mixing: DataManager {
             time[timestep] = current_time;
             check_signals!();
         }
-        (time, mixing)
+        Ok((time, mixing))
     }
 
     fn concentration_field(
@@ -123,14 +124,14 @@ pub trait Mixing: DataManager {
         selector: &ParticleSelector,
         type_a: usize,
         type_b: usize,
-    ) -> Box<dyn GridFunctions3D> {
+    ) -> Result<Box<dyn GridFunctions3D>> {
         //read the number of timesteps inside this hdf5file
         let global_stats = self.global_stats();
         let timesteps: &usize = global_stats.timesteps();
         let mut type_a_grid = grid.new_zeros();
         let mut type_b_grid = grid.new_zeros();
         for timestep in 0..timesteps - 1 {
-            let timestep_data = self.get_timestep(timestep);
+            let timestep_data = self.get_timestep(timestep)?;
             let current_time = *timestep_data.time();
             // check if timestep is in the timeframe given
             if !selector.timestep_valid(current_time) {
@@ -157,6 +158,7 @@ pub trait Mixing: DataManager {
                     clouds[particle],
                     density[particle],
                     particle_ids[particle] as usize,
+                    particle_type[particle],
                 ) {
                     print_debug!("Particle {} is not valid", particle);
                     continue;
@@ -194,7 +196,8 @@ pub trait Mixing: DataManager {
 
         grid.set_data(type_a_grid.get_data() / (type_a_grid.get_data() + type_b_grid.get_data()));
         grid.set_weights(type_a_grid.get_weights() + type_b_grid.get_weights());
-        grid
+        grid.set_units(GridUnits::new(length_unit(self), None));
+        Ok(grid)
     }
 
     fn msd_field(
@@ -202,18 +205,17 @@ pub trait Mixing: DataManager {
         mut grid: Box<dyn GridFunctions3D>,
         selector: &ParticleSelector,
         time_for_msd: f64,
-    ) -> Box<dyn GridFunctions3D> {
+        interpolate: bool,
+    ) -> Result<Box<dyn GridFunctions3D>> {
         let global_stats = self.global_stats();
         let timesteps = global_stats.timesteps();
-        self.setup_buffer(0); // add another buffer to the system
-                              // Allocate arrays needed for calculation
-                              // those arrays are needed for the calculation of the variance with a special algorithm
+        // Allocate arrays needed for calculation
+        // those arrays are needed for the calculation of the variance with a special algorithm
         let mut distance = grid.new_zeros();
         print_debug!("Dispersion: Initiation over, entering time loop");
 
-        let mut next_timestep;
         for timestep in 0..timesteps - 1 {
-            let timestep_data = self.get_timestep(timestep).clone();
+            let timestep_data = self.get_timestep(timestep)?.clone();
             let current_time = *timestep_data.time();
             // check if timestep is in the timeframe given
             if !selector.timestep_valid(current_time) {
@@ -226,27 +228,24 @@ pub trait Mixing: DataManager {
             let rad_array = timestep_data.radius();
             let clouds = timestep_data.clouds();
             let density = timestep_data.density();
+            let particle_type = timestep_data.ptype();
             let particles = positions.len();
-            next_timestep =
-                match global_stats.timestep_at_seconds_closest(current_time + time_for_msd) {
+            let future_time = current_time + time_for_msd;
+            let timestep_future =
+                match timestep_at_time(self, &global_stats, future_time, interpolate) {
                     Ok(x) => x,
                     Err(s) => {
-                        let _message = s.to_string();
                         print_warning!(
-                            "Dispersion: No timestep found for time {}, \n error: {}",
-                            current_time + time_for_msd,
-                            message
+                            "MSD: No timestep found for time {}, \n error: {}",
+                            future_time,
+                            s
                         );
                         continue;
                     }
                 };
-
-            //   global_stats.timestep_at_seconds(current_time + time_for_dispersion);
-
-            print_debug!("Next timestep is {}", next_timestep);
-            let timestep_future = self.get_timestep_buffer(next_timestep, 0);
             print_debug!("extracting position");
             let position_future = timestep_future.position();
+            let future_ids = timestep_future.id_map();
             print_debug!("Starting particle loop");
 
             for particle in 0..particles {
@@ -255,6 +254,7 @@ pub trait Mixing: DataManager {
                     clouds[particle],
                     density[particle],
                     particle_ids[particle] as usize,
+                    particle_type[particle],
                 ) {
                     print_debug!("Particle {} is not valid", particle);
                     continue;
@@ -264,7 +264,12 @@ pub trait Mixing: DataManager {
                     print_debug!("Particle {} is out of FoV", particle);
                     continue;
                 }
-                let position_future_particle = position_future[particle];
+                // the particle may have left the system or moved in the arrays
+                let future_index = match future_ids.get(&(particle_ids[particle] as usize)) {
+                    Some(&index) => index,
+                    None => continue,
+                };
+                let position_future_particle = position_future[future_index];
                 let dist = ((positions[particle][0] - position_future_particle[0]).powi(2)
                     + (positions[particle][1] - position_future_particle[1]).powi(2)
                     + (positions[particle][2] - position_future_particle[2]).powi(2))
@@ -288,7 +293,8 @@ pub trait Mixing: DataManager {
         let data = ndarray::Array::from_shape_vec((cells[0], cells[1], cells[2]), data).unwrap();
         grid.set_data(data);
         grid.set_weights(weights);
-        grid
+        grid.set_units(GridUnits::new(length_unit(self), None));
+        Ok(grid)
     }
 
     fn msd(
@@ -298,11 +304,12 @@ pub trait Mixing: DataManager {
         min_time: f64,
         max_time: f64,
         steps: usize,
-    ) -> (Array1<f64>, Array1<f64>) {
+        interpolate: bool,
+    ) -> Result<(Array1<f64>, Array1<f64>)> {
         let time = Array1::linspace(min_time, max_time, steps);
         let mut msd = Array1::zeros(time.len());
         for (i, t) in time.iter().enumerate() {
-            let msd_field = self.msd_field(grid.clone(), selector, *t);
+            let msd_field = self.msd_field(grid.clone(), selector, *t, interpolate)?;
             let data = msd_field.get_data();
             let weights = msd_field.get_weights();
             let msd_at_time = data
@@ -312,6 +319,6 @@ pub trait Mixing: DataManager {
                 / weights.iter().fold(0.0, |acc, x| acc + x);
             msd[i] = msd_at_time;
         }
-        (msd, time)
+        Ok((msd, time))
     }
 }
@@ -0,0 +1,133 @@
+//! Parallel processing of timesteps
+//!
+//! Reading from the HDF5 file is serial, so timesteps are read in chunks through the
+//! `DataManager` buffer and every chunk is processed in parallel with rayon. Each thread
+//! folds its timesteps into its own accumulator and the partial accumulators are merged
+//! once the thread is done.
+
+use crate::check_signals;
+use crate::datamanager::{DataManager, Timestep};
+use crate::error::Result;
+use crate::grid::{GridFunctions3D, VectorGrid};
+use crate::particleselector::Selector;
+use ndarray::prelude::*;
+use rayon::prelude::*;
+
+/// Number of timesteps read before they are processed in parallel
+pub(crate) const CHUNKSIZE: usize = 256;
+
+/// Partial result of a computation over timesteps. Partial results of different threads
+/// are combined with `merge`.
+pub(crate) trait Accumulator: Send {
+    fn merge(&mut self, other: Self);
+}
+
+impl Accumulator for f64 {
+    fn merge(&mut self, other: Self) {
+        *self += other;
+    }
+}
+
+impl<D: Dimension> Accumulator for Array<f64, D> {
+    fn merge(&mut self, other: Self) {
+        *self += &other;
+    }
+}
+
+/// Grids are merged by adding their data and weights, so values must be added with
+/// `add_value` or `add_trajectory_value` and normalised after merging.
+impl Accumulator for Box<dyn GridFunctions3D> {
+    fn merge(&mut self, other: Self) {
+        let data = self.get_data() + other.get_data();
+        let weights = self.get_weights() + other.get_weights();
+        self.set_data(data);
+        self.set_weights(weights);
+    }
+}
+
+impl Accumulator for VectorGrid {
+    fn merge(&mut self, other: Self) {
+        for (component, other) in self.data.iter_mut().zip(other.data) {
+            component.merge(other);
+        }
+    }
+}
+
+impl<A: Accumulator, B: Accumulator> Accumulator for (A, B) {
+    fn merge(&mut self, other: Self) {
+        self.0.merge(other.0);
+        self.1.merge(other.1);
+    }
+}
+
+/// Return an empty copy of `grid` with zero data and zero weights
+pub(crate) fn empty_grid(grid: &dyn GridFunctions3D) -> Box<dyn GridFunctions3D> {
+    let mut empty = grid.new_zeros();
+    empty.set_weights(Array3::zeros(*grid.get_cells()));
+    empty
+}
+
+/// Fold every timestep accepted by `selector` into an accumulator, in parallel.
+/// `init` creates the empty accumulator of each thread.
+pub(crate) fn fold_timesteps<T, A, I, F>(
+    data: &mut T,
+    selector: &(dyn Selector + Sync),
+    init: I,
+    fold: F,
+) -> Result<A>
+where
+    T: DataManager + ?Sized,
+    A: Accumulator,
+    I: Fn() -> A + Sync + Send,
+    F: Fn(&mut A, &Timestep) + Sync + Send,
+{
+    fold_windows(data, selector, 1, init, |accumulator, window| {
+        fold(accumulator, &window[0])
+    })
+}
+
+/// Fold every window of `window` consecutive timesteps into an accumulator, in parallel.
+/// A window is used if `selector` accepts the time of its first timestep.
+pub(crate) fn fold_windows<T, A, I, F>(
+    data: &mut T,
+    selector: &(dyn Selector + Sync),
+    window: usize,
+    init: I,
+    fold: F,
+) -> Result<A>
+where
+    T: DataManager + ?Sized,
+    A: Accumulator,
+    I: Fn() -> A + Sync + Send,
+    F: Fn(&mut A, &[Timestep]) + Sync + Send,
+{
+    let timesteps = *data.global_stats().timesteps();
+    let mut result = init();
+    if timesteps < window {
+        return Ok(result);
+    }
+    let windows = timesteps - window + 1;
+    for start in (0..windows).step_by(CHUNKSIZE) {
+        // read the timesteps of CHUNKSIZE windows, the last window - 1 timesteps are
+        // read again for the next chunk
+        let end = (start + CHUNKSIZE + window - 1).min(timesteps);
+        let chunk = (start..end)
+            .map(|timestep| Ok(data.get_timestep(timestep)?.clone()))
+            .collect::<Result<Vec<Timestep>>>()?;
+        let partial = chunk
+            .par_windows(window)
+            .filter(|window| selector.timestep_valid(*window[0].time()))
+            .fold(&init, |mut accumulator, window| {
+                fold(&mut accumulator, window);
+                accumulator
+            })
+            .reduce(&init, |mut accumulator, other| {
+                accumulator.merge(other);
+                accumulator
+            });
+        result.merge(partial);
+        // checking for kill signals after each chunk
+        check_signals!();
+    }
+    Ok(result)
+}
@@ -0,0 +1,281 @@
+//! Per-particle quantities and the reducers used to bin them onto a grid
+//! with `Granular::property_field`.
+
+use crate::datamanager::Timestep;
+use crate::error::{Result, Up4Error};
+use crate::types::CellId;
+use crate::units::{GridUnits, LengthUnit};
+use ndarray::prelude::*;
+use std::f64::consts::PI;
+
+/// Function returning a derived value for the particle with the index `particle`
+pub type PropertyFn = dyn Fn(&Timestep, usize) -> f64 + Send + Sync;
+
+/// A scalar per-particle quantity of a `Timestep`
+pub enum Property {
+    Radius,
+    Density,
+    ParticleType,
+    ParticleId,
+    Cloud,
+    /// Single velocity component, 0: x, 1: y, 2: z
+    Velocity(usize),
+    /// Magnitude of the velocity
+    Speed,
+    /// Kinetic energy 0.5 * m * |v|^2 of a sphere with the particle radius and density
+    KineticEnergy,
+    /// Component of an additional per-particle field
+    Field { name: String, component: usize },
+    /// Any quantity derived from the timestep
+    Custom(Box<PropertyFn>),
+}
+
+impl Property {
+    /// Return the property of the particle with the index `particle` in `timestep`
+    pub fn value(&self, timestep: &Timestep, particle: usize) -> Result<f64> {
+        let velocity = || timestep.velocity().row(particle);
+        let value = match self {
+            Property::Radius => timestep.radius()[particle],
+            Property::Density => timestep.density()[particle],
+            Property::ParticleType => timestep.ptype()[particle],
+            Property::ParticleId => timestep.particleid()[particle],
+            Property::Cloud => timestep.clouds()[particle],
+            Property::Velocity(component) => velocity()[*component],
+            Property::Speed => velocity().dot(&velocity()).sqrt(),
+            Property::KineticEnergy => {
+                let radius = timestep.radius()[particle];
+                let mass = timestep.density()[particle] * 4. / 3. * PI * radius.powi(3);
+                0.5 * mass * velocity().dot(&velocity())
+            }
+            Property::Field { name, component } => {
+                let field = timestep.field(name).ok_or_else(|| {
+                    Up4Error::InvalidArgument(format!(
+                        "Field {} does not exist. Available fields are: {:?}",
+                        name,
+                        timestep.field_names()
+                    ))
+                })?;
+                if *component >= field.ncols() {
+                    return Err(Up4Error::InvalidArgument(format!(
+                        "Field {} has {} components, component {} does not exist",
+                        name,
+                        field.ncols(),
+                        component
+                    )));
+                }
+                field[[particle, *component]]
+            }
+            Property::Custom(function) => function(timestep, particle),
+        };
+        Ok(value)
+    }
+
+    /// Units of a grid of this property reduced with `reducer` for a dataset with the
+    /// unit of length `length`. Values without a known unit are `None`.
+    pub fn units(&self, length: Option<LengthUnit>, reducer: Reducer) -> GridUnits {
+        let value = match (self, reducer) {
+            (_, Reducer::Count) => None,
+            (Property::Radius, _) => length.map(|unit| unit.symbol().to_string()),
+            (Property::Velocity(_) | Property::Speed, _) => {
+                length.map(|unit| unit.velocity_symbol())
+            }
+            _ => None,
+        };
+        GridUnits::new(length, value)
+    }
+}
+
+impl std::str::FromStr for Property {
+    type Err = Up4Error;
+
+    /// Parse a property name. Names that are not built in refer to additional fields,
+    /// a component of a vector field is selected with `name[component]`, e.g. `omega[2]`.
+    fn from_str(property: &str) -> Result<Self> {
+        let parsed = match property {
+            "radius" => Property::Radius,
+            "density" => Property::Density,
+            "type" | "ptype" | "particletype" => Property::ParticleType,
+            "id" | "particleid" => Property::ParticleId,
+            "ppcloud" => Property::Cloud,
+            "vx" => Property::Velocity(0),
+            "vy" => Property::Velocity(1),
+            "vz" => Property::Velocity(2),
+            "speed" | "velocity" => Property::Speed,
+            "kinetic_energy" => Property::KineticEnergy,
+            _ => match property.strip_suffix(']').and_then(|name| name.split_once('[')) {
+                Some((name, component)) => Property::Field {
+                    name: name.to_string(),
+                    component: component.parse().map_err(|_| {
+                        Up4Error::InvalidArgument(format!(
+                            "Property {} has an invalid component",
+                            property
+                        ))
+                    })?,
+                },
+                None => Property::Field {
+                    name: property.to_string(),
+                    component: 0,
+                },
+            },
+        };
+        Ok(parsed)
+    }
+}
+
+/// Reduction applied to all values that fall into one grid cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reducer {
+    Mean,
+    Median,
+    Min,
+    Max,
+    Std,
+    Count,
+}
+
+impl std::str::FromStr for Reducer {
+    type Err = Up4Error;
+
+    fn from_str(reducer: &str) -> Result<Self> {
+        match reducer.to_lowercase().as_str() {
+            "mean" => Ok(Reducer::Mean),
+            "median" => Ok(Reducer::Median),
+            "min" => Ok(Reducer::Min),
+            "max" => Ok(Reducer::Max),
+            "std" => Ok(Reducer::Std),
+            "count" => Ok(Reducer::Count),
+            _ => Err(Up4Error::InvalidArgument(format!(
+                "Reducer {} is not valid. Valid reducers are: \"mean\", \"median\", \"min\", \"max\", \"std\", \"count\"",
+                reducer
+            ))),
+        }
+    }
+}
+
+/// Running statistics for each cell of a grid. Single values are only kept for the median.
+pub(crate) struct CellAccumulator {
+    reducer: Reducer,
+    count: Array3<f64>,
+    mean: Array3<f64>,
+    // sum of squared differences from the mean (Welford)
+    m2: Array3<f64>,
+    min: Array3<f64>,
+    max: Array3<f64>,
+    values: Array3<Vec<f64>>,
+}
+
+impl CellAccumulator {
+    pub(crate) fn new(cells: CellId, reducer: Reducer) -> Self {
+        let values = if reducer == Reducer::Median {
+            Array3::from_elem(cells, Vec::new())
+        } else {
+            Array3::from_elem((0, 0, 0), Vec::new())
+        };
+        CellAccumulator {
+            reducer,
+            count: Array3::zeros(cells),
+            mean: Array3::zeros(cells),
+            m2: Array3::zeros(cells),
+            min: Array3::from_elem(cells, f64::INFINITY),
+            max: Array3::from_elem(cells, f64::NEG_INFINITY),
+            values,
+        }
+    }
+
+    pub(crate) fn add(&mut self, cell: CellId, value: f64) {
+        let cell = (cell[0], cell[1], cell[2]);
+        self.count[cell] += 1.;
+        let delta = value - self.mean[cell];
+        self.mean[cell] += delta / self.count[cell];
+        self.m2[cell] += delta * (value - self.mean[cell]);
+        self.min[cell] = self.min[cell].min(value);
+        self.max[cell] = self.max[cell].max(value);
+        if self.reducer == Reducer::Median {
+            self.values[cell].push(value);
+        }
+    }
+
+    /// Return the reduced value and the number of values of each cell. Empty cells are NaN,
+    /// except for `Reducer::Count`.
+    pub(crate) fn finish(self) -> (Array3<f64>, Array3<f64>) {
+        let mut data = match self.reducer {
+            Reducer::Mean => self.mean,
+            Reducer::Min => self.min,
+            Reducer::Max => self.max,
+            Reducer::Count => return (self.count.clone(), self.count),
+            Reducer::Std => (&self.m2 / &self.count).mapv(f64::sqrt),
+            Reducer::Median => self.values.mapv(|mut values| median(&mut values)),
+        };
+        data.zip_mut_with(&self.count, |value, &count| {
+            if count == 0. {
+                *value = f64::NAN
+            }
+        });
+        (data, self.count)
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    if values.is_empty() {
+        return f64::NAN;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        0.5 * (values[mid - 1] + values[mid])
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reduced(reducer: Reducer, values: &[f64]) -> (Array3<f64>, Array3<f64>) {
+        let mut accumulator = CellAccumulator::new([2, 1, 1], reducer);
+        for &value in values {
+            accumulator.add([0, 0, 0], value);
+        }
+        accumulator.finish()
+    }
+
+    #[test]
+    fn welford_std() {
+        let (data, count) = reduced(Reducer::Std, &[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert!((data[[0, 0, 0]] - 2.0).abs() < 1e-12);
+        assert_eq!(count[[0, 0, 0]], 8.0);
+    }
+
+    #[test]
+    fn even_and_odd_median() {
+        let (odd, _) = reduced(Reducer::Median, &[5.0, 1.0, 3.0]);
+        assert_eq!(odd[[0, 0, 0]], 3.0);
+        let (even, _) = reduced(Reducer::Median, &[4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(even[[0, 0, 0]], 2.5);
+    }
+
+    #[test]
+    fn empty_cells() {
+        for reducer in [Reducer::Mean, Reducer::Median, Reducer::Min, Reducer::Max, Reducer::Std] {
+            let (data, count) = reduced(reducer, &[1.0, 2.0]);
+            assert!(data[[0, 0, 0]].is_finite());
+            assert!(data[[1, 0, 0]].is_nan());
+            assert_eq!(count[[1, 0, 0]], 0.0);
+        }
+        let (data, _) = reduced(Reducer::Count, &[1.0, 2.0]);
+        assert_eq!(data[[1, 0, 0]], 0.0);
+    }
+
+    #[test]
+    fn field_component() {
+        let property: Property = "omega[2]".parse().unwrap();
+        assert!(
+            matches!(property, Property::Field { name, component: 2 } if name == "omega")
+        );
+        let property: Property = "temperature".parse().unwrap();
+        assert!(matches!(property, Property::Field { component: 0, .. }));
+        assert!(matches!("vz".parse::<Property>(), Ok(Property::Velocity(2))));
+        assert!("omega[z]".parse::<Property>().is_err());
+    }
+}
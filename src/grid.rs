@@ -2,20 +2,182 @@
 //! Grids implement ndarrays which hold data. This grid wrapper allows easy grid operations such as
 //! finding a special cell
 
-//TODO Rewrite grids to make dimensionality a generic instead of 3 structs
-
 extern crate ndarray;
 use crate::types::*;
+pub use crate::units::GridUnits;
 use derive_getters::Getters;
 use dyn_clone::{clone_trait_object, DynClone};
 use ndarray::prelude::*;
 use num_traits;
 
 use std::any::Any;
+
+/// Implement `GridFunctions3D` (and `Debug`/`Display`) for a grid of fewer dimensions that
+/// wraps a `CartesianGrid3D` with a single cell along its unresolved axes in the field
+/// `grid`. Positions are moved onto the unresolved axes with `self.project` first, so
+/// their coordinates along these axes do not matter.
+macro_rules! reduced_grid_functions {
+    ($grid:ty, $name:literal) => {
+        impl std::fmt::Debug for $grid {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "{}", self)
+            }
+        }
+
+        impl std::fmt::Display for $grid {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                let grid = self.grid.to_string();
+                write!(f, "{}{}", $name, grid.strip_prefix("Grid3D").unwrap_or(&grid))
+            }
+        }
+
+        impl $crate::grid::GridFunctions3D for $grid {
+            fn get_value(&self, pos: $crate::types::Position) -> f64 {
+                self.grid.get_value(self.project(pos))
+            }
+            fn add_value(&mut self, pos: $crate::types::Position, value: f64) {
+                let pos = self.project(pos);
+                self.grid.add_value(pos, value)
+            }
+            fn add_trajectory_value(
+                &mut self,
+                pos1: $crate::types::Position,
+                pos2: $crate::types::Position,
+                value: f64,
+            ) {
+                let (pos1, pos2) = (self.project(pos1), self.project(pos2));
+                self.grid.add_trajectory_value(pos1, pos2, value)
+            }
+            fn divide_by_array(&mut self, other: &::ndarray::Array3<f64>) {
+                self.grid.divide_by_array(other)
+            }
+            fn divide_by_scalar(&mut self, other: f64) {
+                self.grid.divide_by_scalar(other)
+            }
+            fn divide_by_weight(&mut self) {
+                self.grid.divide_by_weight()
+            }
+            fn insert(&mut self, pos: $crate::types::Position, value: f64) {
+                let pos = self.project(pos);
+                self.grid.insert(pos, value)
+            }
+            fn is_inside(&self, pos: $crate::types::Position) -> bool {
+                self.grid.is_inside(self.project(pos))
+            }
+            fn cell_id(
+                &self,
+                pos: $crate::types::Position,
+            ) -> ::anyhow::Result<$crate::types::CellId> {
+                self.grid.cell_id(self.project(pos))
+            }
+            fn cell_ids_in_trajectory(
+                &self,
+                pos1: $crate::types::Position,
+                pos2: $crate::types::Position,
+            ) -> ::anyhow::Result<(Vec<$crate::types::CellId>, Vec<f64>)> {
+                self.grid
+                    .cell_ids_in_trajectory(self.project(pos1), self.project(pos2))
+            }
+            fn add_to_cell(&mut self, cell_id: $crate::types::CellId, value: f64) {
+                self.grid.add_to_cell(cell_id, value)
+            }
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+            fn new_zeros(&self) -> Box<dyn $crate::grid::GridFunctions3D> {
+                let mut grid = self.clone();
+                grid.grid
+                    .set_data(::ndarray::Array3::zeros(*self.grid.get_cells()));
+                Box::new(grid)
+            }
+            fn collapse(&self, axis: usize) -> ::ndarray::Array2<f64> {
+                self.grid.collapse(axis)
+            }
+            fn collapse_weight(&self, axis: usize) -> ::ndarray::Array2<f64> {
+                self.grid.collapse_weight(axis)
+            }
+            fn collapse_two(&self, axis1: usize, axis2: usize) -> ::ndarray::Array1<f64> {
+                self.grid.collapse_two(axis1, axis2)
+            }
+            fn collapse_two_weight(&self, axis1: usize, axis2: usize) -> ::ndarray::Array1<f64> {
+                self.grid.collapse_two_weight(axis1, axis2)
+            }
+            fn slice(&self, axis: usize, position: f64) -> ::ndarray::Array2<f64> {
+                self.grid.slice(axis, position)
+            }
+            fn slice_idx(&self, axis: usize, index: usize) -> ::ndarray::Array2<f64> {
+                self.grid.slice_idx(axis, index)
+            }
+            fn get_xpositions(&self) -> &::ndarray::Array1<f64> {
+                self.grid.get_xpositions()
+            }
+            fn get_ypositions(&self) -> &::ndarray::Array1<f64> {
+                self.grid.get_ypositions()
+            }
+            fn get_zpositions(&self) -> &::ndarray::Array1<f64> {
+                self.grid.get_zpositions()
+            }
+            fn get_limits(&self) -> &$crate::types::ThreeD {
+                self.grid.get_limits()
+            }
+            fn get_cells(&self) -> &$crate::types::CellId {
+                self.grid.get_cells()
+            }
+            fn get_data(&self) -> &::ndarray::Array3<f64> {
+                self.grid.get_data()
+            }
+            fn get_weights(&self) -> &::ndarray::Array3<f64> {
+                self.grid.get_weights()
+            }
+            fn is_cylindrical(&self) -> bool {
+                false
+            }
+            fn get_units(&self) -> &$crate::grid::GridUnits {
+                self.grid.get_units()
+            }
+            fn set_units(&mut self, units: $crate::grid::GridUnits) {
+                self.grid.set_units(units)
+            }
+            fn set_data(&mut self, data: ::ndarray::Array3<f64>) {
+                self.grid.set_data(data)
+            }
+            fn set_weights(&mut self, weights: ::ndarray::Array3<f64>) {
+                self.grid.set_weights(weights)
+            }
+            fn outlier_removal(&mut self, threshold: f64, mode: usize) {
+                self.grid.outlier_removal(threshold, mode)
+            }
+        }
+    };
+}
+
+/// Range of an axis that a reduced grid does not resolve. Positions are projected onto its
+/// centre, so any range works as long as the single cell along the axis has a size. Ranges
+/// of zero width are widened to one unit around their start.
+fn unresolved_range(lim: [f64; 2]) -> [f64; 2] {
+    if lim[1] > lim[0] {
+        lim
+    } else {
+        [lim[0] - 0.5, lim[0] + 0.5]
+    }
+}
+
+pub mod arithmetic;
+pub use arithmetic::GridOperation;
 pub mod cartesian_grid;
 pub use cartesian_grid::CartesianGrid3D;
 pub mod cylindrical_grid;
-pub use cylindrical_grid::CylindricalGrid3D;
+pub use cylindrical_grid::{CylindricalGrid3D, RadialSpacing};
+pub mod spherical_grid;
+pub use spherical_grid::SphericalGrid3D;
+pub mod storage;
+pub use storage::GridState;
+pub mod vtk;
+pub use vtk::write_vtk;
+pub mod grid1d;
+pub use grid1d::Grid1D;
+pub mod grid2d;
+pub use grid2d::Grid2D;
 pub mod vector_grid;
 pub use vector_grid::VectorGrid;
 
@@ -42,7 +204,7 @@ pub enum Dim {
 }
 
 //pub trait Grid: Debug {}
-pub trait GridFunctions3D: DynClone + std::fmt::Display + std::fmt::Debug + Send {
+pub trait GridFunctions3D: DynClone + std::fmt::Display + std::fmt::Debug + Send + Sync {
     //get value at this cell id
     fn get_value(&self, pos: Position) -> f64;
 
@@ -108,6 +270,13 @@ pub trait GridFunctions3D: DynClone + std::fmt::Display + std::fmt::Debug + Send
     fn get_data(&self) -> &Array3<f64>;
     fn get_weights(&self) -> &Array3<f64>;
     fn is_cylindrical(&self) -> bool;
+    // r, theta (polar angle) and phi (azimuth) instead of x, y, z
+    fn is_spherical(&self) -> bool {
+        false
+    }
+    // units of the cell positions and the values, used to label plots
+    fn get_units(&self) -> &GridUnits;
+    fn set_units(&mut self, units: GridUnits);
 
     fn set_data(&mut self, data: Array3<f64>);
 
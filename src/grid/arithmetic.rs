@@ -0,0 +1,335 @@
+//! Element-wise operations between grids of the same geometry
+//!
+//! Two grids are compatible if they are of the same kind (e.g. both `CylindricalGrid3D`) and
+//! have the same cells, limits and further geometry, such as the centre, axis and radial
+//! edges of a cylinder. The result keeps the geometry of the first grid, so a simulated field
+//! can be subtracted from an experimental one without losing the weights or the cell
+//! positions. Besides the arithmetic operations, compatible grids can be compared cell by
+//! cell with `GridOperation::Minimum` and `Maximum` or merged into one grid with `merge`.
+
+use super::{
+    CylindricalGrid3D, Grid1D, Grid2D, GridFunctions3D, GridUnits, SphericalGrid3D, VectorGrid,
+};
+use anyhow::{anyhow, Result};
+use ndarray::{Array3, Zip};
+
+/// Operation applied to the values of two grids, or a grid and a scalar, cell by cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridOperation {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    /// the smaller value, NaN values are ignored
+    Minimum,
+    /// the larger value, NaN values are ignored
+    Maximum,
+}
+
+impl GridOperation {
+    pub fn apply(&self, left: f64, right: f64) -> f64 {
+        match self {
+            GridOperation::Add => left + right,
+            GridOperation::Subtract => left - right,
+            GridOperation::Multiply => left * right,
+            GridOperation::Divide => left / right,
+            GridOperation::Minimum => left.min(right),
+            GridOperation::Maximum => left.max(right),
+        }
+    }
+
+    /// Unit of the values of the result, `None` if it is unknown
+    fn value_unit(&self, left: &Option<String>, right: &Option<String>) -> Option<String> {
+        match (self, left, right) {
+            (GridOperation::Multiply, Some(left), Some(right)) => {
+                Some(format!("{}*{}", left, right))
+            }
+            (GridOperation::Divide, Some(left), Some(right)) if left == right => None,
+            (GridOperation::Divide, Some(left), Some(right)) => {
+                Some(format!("{}/{}", left, right))
+            }
+            (GridOperation::Multiply | GridOperation::Divide, _, _) => None,
+            _ => left.clone(),
+        }
+    }
+}
+
+/// Check that `other` has the same kind, cells, limits and geometry as `grid`
+pub fn check_compatible(grid: &dyn GridFunctions3D, other: &dyn GridFunctions3D) -> Result<()> {
+    if grid.as_any().type_id() != other.as_any().type_id()
+        || grid.is_cylindrical() != other.is_cylindrical()
+        || grid.is_spherical() != other.is_spherical()
+    {
+        return Err(anyhow!("Grids of different kinds can not be combined"));
+    }
+    if grid.get_cells() != other.get_cells() {
+        return Err(anyhow!(
+            "Grids with {:?} and {:?} cells can not be combined",
+            grid.get_cells(),
+            other.get_cells()
+        ));
+    }
+    let same_limits = grid
+        .get_limits()
+        .iter()
+        .flatten()
+        .zip(other.get_limits().iter().flatten())
+        .all(|(&a, &b)| close(a, b));
+    if !same_limits {
+        return Err(anyhow!(
+            "Grids with the limits {:?} and {:?} can not be combined",
+            grid.get_limits(),
+            other.get_limits()
+        ));
+    }
+    for ((name, values), (_, other_values)) in geometry(grid).iter().zip(geometry(other).iter()) {
+        if !values.iter().zip(other_values).all(|(&a, &b)| close(a, b)) {
+            return Err(anyhow!(
+                "Grids with the {} {:?} and {:?} can not be combined",
+                name,
+                values,
+                other_values
+            ));
+        }
+    }
+    let (units, other_units) = (grid.get_units(), other.get_units());
+    if let (Some(length), Some(other_length)) = (units.length, other_units.length) {
+        if length != other_length {
+            return Err(anyhow!(
+                "Grids in {} and {} can not be combined",
+                length,
+                other_length
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Equal up to rounding errors, relative to the size of the values
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0)
+}
+
+/// Geometry of `grid` that is not given by its cells and limits: the centre, axis and
+/// radial edges of cylinders and spheres and the resolved axes of reduced cartesian grids
+fn geometry(grid: &dyn GridFunctions3D) -> Vec<(&'static str, Vec<f64>)> {
+    let base = match grid.as_any().downcast_ref::<VectorGrid>() {
+        Some(vector_grid) => vector_grid.data[0].as_any(),
+        None => grid.as_any(),
+    };
+    if let Some(cylinder) = base.downcast_ref::<CylindricalGrid3D>() {
+        vec![
+            ("centre", cylinder.center().to_vec()),
+            ("axis", cylinder.axis().to_vec()),
+            ("radial edges", cylinder.radial_edges()),
+        ]
+    } else if let Some(sphere) = base.downcast_ref::<SphericalGrid3D>() {
+        vec![
+            ("centre", sphere.center().to_vec()),
+            ("radial edges", sphere.radial_edges()),
+        ]
+    } else if let Some(grid1d) = base.downcast_ref::<Grid1D>() {
+        vec![("resolved axis", vec![grid1d.axis() as f64])]
+    } else if let Some(grid2d) = base.downcast_ref::<Grid2D>() {
+        vec![("normal", vec![grid2d.normal() as f64])]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Apply `operation` to the values of `grid` and `other` cell by cell. The weights of the
+/// result are the sum of both weights.
+pub fn combine(
+    grid: &dyn GridFunctions3D,
+    other: &dyn GridFunctions3D,
+    operation: GridOperation,
+) -> Result<Box<dyn GridFunctions3D>> {
+    check_compatible(grid, other)?;
+    if let Some(vector_grid) = grid.as_any().downcast_ref::<VectorGrid>() {
+        let other = other.as_any().downcast_ref::<VectorGrid>().unwrap();
+        return Ok(Box::new(vector_grid.combine(other, operation)?));
+    }
+    let (value, other_value) = (&grid.get_units().value, &other.get_units().value);
+    // only values of the same quantity can be added, subtracted or compared
+    if !matches!(operation, GridOperation::Multiply | GridOperation::Divide) {
+        if let (Some(value), Some(other_value)) = (value, other_value) {
+            if value != other_value {
+                return Err(anyhow!(
+                    "Values in {} and {} can not be combined",
+                    value,
+                    other_value
+                ));
+            }
+        }
+    }
+    let data = Zip::from(grid.get_data())
+        .and(other.get_data())
+        .map_collect(|&left, &right| operation.apply(left, right));
+    let weights = grid.get_weights() + other.get_weights();
+    let units = GridUnits::new(
+        grid.get_units().length.or(other.get_units().length),
+        operation.value_unit(value, other_value),
+    );
+    Ok(with_values(grid, data, weights, units))
+}
+
+/// Apply `function` to the value of every cell of `grid`, keeping the weights and units,
+/// e.g. `|value| GridOperation::Divide.apply(value, 2.0)`
+pub fn map_values<F>(grid: &dyn GridFunctions3D, function: F) -> Box<dyn GridFunctions3D>
+where
+    F: Fn(f64) -> f64,
+{
+    if let Some(vector_grid) = grid.as_any().downcast_ref::<VectorGrid>() {
+        return Box::new(vector_grid.map_values(function));
+    }
+    let data = grid.get_data().mapv(function);
+    with_values(grid, data, grid.get_weights().clone(), grid.get_units().clone())
+}
+
+/// Merge two partial results of the same quantity, e.g. of two experiments, into their
+/// weighted mean. Cells without weight or with NaN values in one grid take the value of
+/// the other one, cells without any weight become NaN.
+pub fn merge(
+    grid: &dyn GridFunctions3D,
+    other: &dyn GridFunctions3D,
+) -> Result<Box<dyn GridFunctions3D>> {
+    check_compatible(grid, other)?;
+    if let Some(vector_grid) = grid.as_any().downcast_ref::<VectorGrid>() {
+        let other = other.as_any().downcast_ref::<VectorGrid>().unwrap();
+        return Ok(Box::new(vector_grid.merge(other)?));
+    }
+    if grid.get_units().value.is_some()
+        && other.get_units().value.is_some()
+        && grid.get_units().value != other.get_units().value
+    {
+        return Err(anyhow!(
+            "Values in {:?} and {:?} can not be merged",
+            grid.get_units().value,
+            other.get_units().value
+        ));
+    }
+    // NaN values carry no information, so they do not count towards the weight
+    let weight_of = |value: f64, weight: f64| {
+        if value.is_nan() || weight.is_nan() {
+            0.0
+        } else {
+            weight
+        }
+    };
+    let mut data = Array3::zeros(grid.get_data().raw_dim());
+    let mut weights = Array3::zeros(grid.get_data().raw_dim());
+    Zip::from(&mut data)
+        .and(&mut weights)
+        .and(grid.get_data())
+        .and(grid.get_weights())
+        .and(other.get_data())
+        .and(other.get_weights())
+        .for_each(|value, weight, &value1, &weight1, &value2, &weight2| {
+            let (weight1, weight2) = (weight_of(value1, weight1), weight_of(value2, weight2));
+            *weight = weight1 + weight2;
+            *value = if *weight > 0.0 {
+                let sum1 = if weight1 > 0.0 { value1 * weight1 } else { 0.0 };
+                let sum2 = if weight2 > 0.0 { value2 * weight2 } else { 0.0 };
+                (sum1 + sum2) / *weight
+            } else {
+                f64::NAN
+            };
+        });
+    let units = GridUnits::new(
+        grid.get_units().length.or(other.get_units().length),
+        grid.get_units()
+            .value
+            .clone()
+            .or_else(|| other.get_units().value.clone()),
+    );
+    Ok(with_values(grid, data, weights, units))
+}
+
+/// Copy of `grid` with other values, weights and units
+fn with_values(
+    grid: &dyn GridFunctions3D,
+    data: Array3<f64>,
+    weights: Array3<f64>,
+    units: GridUnits,
+) -> Box<dyn GridFunctions3D> {
+    let mut result = dyn_clone::clone_box(grid);
+    result.set_data(data);
+    result.set_weights(weights);
+    result.set_units(units);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{CartesianGrid3D, Dim, RadialSpacing};
+
+    fn grid(values: [f64; 2], weights: [f64; 2]) -> Box<dyn GridFunctions3D> {
+        let mut grid = CartesianGrid3D::new([2, 1, 1], Dim::ThreeD([[0.0, 1.0]; 3]));
+        grid.set_data(Array3::from_shape_vec((2, 1, 1), values.to_vec()).unwrap());
+        grid.set_weights(Array3::from_shape_vec((2, 1, 1), weights.to_vec()).unwrap());
+        Box::new(grid)
+    }
+
+    #[test]
+    fn operations() {
+        let (a, b) = (grid([4.0, 1.0], [1.0, 1.0]), grid([2.0, 3.0], [1.0, 2.0]));
+        let difference = combine(a.as_ref(), b.as_ref(), GridOperation::Subtract).unwrap();
+        assert_eq!(difference.get_data().as_slice().unwrap(), &[2.0, -2.0]);
+        assert_eq!(difference.get_weights().as_slice().unwrap(), &[2.0, 3.0]);
+        let maximum = combine(a.as_ref(), b.as_ref(), GridOperation::Maximum).unwrap();
+        assert_eq!(maximum.get_data().as_slice().unwrap(), &[4.0, 3.0]);
+        let half = map_values(a.as_ref(), |x| GridOperation::Divide.apply(x, 2.0));
+        assert_eq!(half.get_data().as_slice().unwrap(), &[2.0, 0.5]);
+    }
+
+    #[test]
+    fn merging() {
+        let a = grid([4.0, f64::NAN], [1.0, 0.0]);
+        let b = grid([1.0, 3.0], [3.0, 2.0]);
+        let merged = merge(a.as_ref(), b.as_ref()).unwrap();
+        assert_eq!(merged.get_data().as_slice().unwrap(), &[1.75, 3.0]);
+        assert_eq!(merged.get_weights().as_slice().unwrap(), &[4.0, 2.0]);
+    }
+
+    #[test]
+    fn incompatible() {
+        let a = grid([1.0, 1.0], [1.0, 1.0]);
+        let other_cells = CartesianGrid3D::new([1, 2, 1], Dim::ThreeD([[0.0, 1.0]; 3]));
+        assert!(combine(a.as_ref(), &other_cells, GridOperation::Add).is_err());
+        let other_limits = CartesianGrid3D::new([2, 1, 1], Dim::ThreeD([[0.0, 2.0]; 3]));
+        assert!(merge(a.as_ref(), &other_limits).is_err());
+        let cylinder = CylindricalGrid3D::new([2, 1, 1], Dim::ThreeD([[0.0, 1.0]; 3]), "volume");
+        assert!(check_compatible(a.as_ref(), &cylinder).is_err());
+    }
+
+    #[test]
+    fn different_geometry() {
+        let limits = || Dim::ThreeD([[-1.0, 1.0]; 3]);
+        let cylinder = |axis, spacing| {
+            CylindricalGrid3D::along_axis([2, 4, 2], limits(), axis, spacing, 0.0).unwrap()
+        };
+        let volume = cylinder(2, RadialSpacing::Volume);
+        assert!(check_compatible(&volume, &cylinder(2, RadialSpacing::Volume)).is_ok());
+        assert!(check_compatible(&volume, &cylinder(2, RadialSpacing::Radius)).is_err());
+        assert!(check_compatible(&volume, &cylinder(0, RadialSpacing::Volume)).is_err());
+        let shifted = CylindricalGrid3D::oriented(
+            [2, 4, 2],
+            [0.5, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            1.0,
+            [-1.0, 1.0],
+            RadialSpacing::Volume,
+            0.0,
+        )
+        .unwrap();
+        assert!(check_compatible(&volume, &shifted).is_err());
+        let sphere = |center| {
+            SphericalGrid3D::around([2, 2, 2], center, 1.0, RadialSpacing::Volume).unwrap()
+        };
+        assert!(check_compatible(&sphere([0.0; 3]), &sphere([0.0; 3])).is_ok());
+        assert!(check_compatible(&sphere([0.0; 3]), &sphere([0.5, 0.0, 0.0])).is_err());
+        let profile = |axis| Grid1D::new(axis, 1, Dim::ThreeD([[0.0, 1.0]; 3])).unwrap();
+        assert!(check_compatible(&profile(0), &profile(2)).is_err());
+    }
+}
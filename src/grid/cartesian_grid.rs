@@ -1,5 +1,5 @@
 extern crate ndarray;
-use super::{CellId, Dim, GridFunctions3D, Position, ThreeD};
+use super::{CellId, Dim, GridFunctions3D, GridUnits, Position, ThreeD};
 use crate::{print_debug, print_warning};
 use crate::utilities::{nan_mean, nan_std};
 use derive_getters::Getters;
@@ -18,6 +18,7 @@ pub struct CartesianGrid3D {
     limits: ThreeD,
     data: Array3<f64>,
     weight: Array3<f64>,
+    units: GridUnits,
     // attrs: HashMap<String, >,
 }
 
@@ -52,6 +53,7 @@ impl CartesianGrid3D {
             limits: lim,
             data: Array3::zeros(cells),
             weight: Array3::zeros(cells),
+            units: GridUnits::default(),
         }
     }
     /*
@@ -463,6 +465,14 @@ impl GridFunctions3D for CartesianGrid3D {
         false
     }
 
+    fn get_units(&self) -> &GridUnits {
+        &self.units
+    }
+
+    fn set_units(&mut self, units: GridUnits) {
+        self.units = units;
+    }
+
     fn set_data(&mut self, data: Array3<f64>) {
         if self.data.shape() == data.shape() {
             self.data = data;
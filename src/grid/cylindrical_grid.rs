@@ -1,5 +1,5 @@
 extern crate ndarray;
-use super::{CellId, Dim, GridFunctions3D, Position, ThreeD};
+use super::{CellId, Dim, GridFunctions3D, GridUnits, Position, ThreeD};
 use crate::{print_debug, print_warning};
 use crate::utilities::{nan_mean, nan_std};
 use derive_getters::Getters;
@@ -7,9 +7,12 @@ use ndarray::{prelude::*, RemoveAxis};
 use ndarray_stats::QuantileExt;
 use std::any::Any;
 
-use anyhow::Result;
-// TODO
-// - Currently the cylinder is always vertical, but this should be configurable.
+use anyhow::{anyhow, Result};
+
+/// Grid in cylindrical coordinates r, theta and the height along the axis of the cylinder.
+/// The axis is the z axis through the centre of the limits by default, `along_axis` and
+/// `oriented` create cylinders along x or y (e.g. horizontal rotating drums) or along any
+/// direction through any point (e.g. inclined mixers).
 #[derive(Getters, Clone, Default)]
 pub struct CylindricalGrid3D {
     cells: CellId,
@@ -17,32 +20,206 @@ pub struct CylindricalGrid3D {
     rmeanpositions: Array1<f64>,  // midpoint radius for cell
     opositions: Array1<f64>,      // omega (angle)
     zpositions: Array1<f64>,      // height
-    center: [f64; 3],
+    center: [f64; 3], // point on the axis
+    /// unit vector along the axis of the cylinder, the height of a position is its
+    /// projection onto this vector
+    axis: [f64; 3],
+    /// unit vectors perpendicular to the axis, theta is measured from the first towards the
+    /// second one
+    basis: [[f64; 3]; 2],
     radius: f64,
+    /// radius of the central shaft, positions closer to the axis are outside of the grid
+    inner_radius: f64,
     limits: ThreeD,
     data: Array3<f64>,
     weight: Array3<f64>,
+    units: GridUnits,
     // attrs: HashMap<String, >,
 }
 
+/// Radial spacing of the cells of a `CylindricalGrid3D` or a `SphericalGrid3D`
+#[derive(Debug, Clone, PartialEq)]
+pub enum RadialSpacing {
+    /// all cells of a ring (or shell) have the same volume, the cells get thinner towards the
+    /// wall
+    Volume,
+    /// all cells have the same radial width
+    Radius,
+    /// the radial cell boundaries given by the user, from the inner to the outer radius
+    Edges(Vec<f64>),
+}
+
+impl std::str::FromStr for RadialSpacing {
+    type Err = anyhow::Error;
+
+    fn from_str(mode: &str) -> Result<Self> {
+        match mode {
+            "volume" => Ok(RadialSpacing::Volume),
+            "radius" => Ok(RadialSpacing::Radius),
+            _ => Err(anyhow!(
+                "Radial mode {} is not valid. Valid modes are: \"volume\", \"radius\" and \
+                \"edges\" with user-supplied radial edges",
+                mode
+            )),
+        }
+    }
+}
+
+impl RadialSpacing {
+    /// Radial boundaries of `cells` cells between `inner_radius` and `outer_radius`. The
+    /// volume of a shell grows with the radius to the power of `power`, 2 for cylinders and
+    /// 3 for spheres.
+    pub(super) fn edges(
+        &self,
+        cells: usize,
+        inner_radius: f64,
+        outer_radius: f64,
+        power: i32,
+    ) -> Result<Vec<f64>> {
+        if let RadialSpacing::Edges(edges) = self {
+            if edges.len() != cells + 1 {
+                return Err(anyhow!(
+                    "{} radial cells need {} radial edges, got {}",
+                    cells,
+                    cells + 1,
+                    edges.len()
+                ));
+            }
+            if edges[0] < 0.0 || edges.windows(2).any(|pair| pair[1] <= pair[0]) {
+                return Err(anyhow!(
+                    "Radial edges must be positive and increasing, got {:?}",
+                    edges
+                ));
+            }
+            return Ok(edges.clone());
+        }
+        if inner_radius < 0.0 || inner_radius >= outer_radius {
+            return Err(anyhow!(
+                "Inner radius {} must be positive and smaller than the outer radius {}",
+                inner_radius,
+                outer_radius
+            ));
+        }
+        let mut edges = vec![inner_radius];
+        for cellidx in 0..cells {
+            let edge = match self {
+                // radial positions should be in a distance so that all cells have the same
+                // volume, the volume of a cell is pi*h*(r_o**2-r_i**2)*alpha/360, therefore
+                // the next outer radius is (V + r_before**2).sqrt() with V the area of the
+                // annulus divided by the number of cells. For spheres the squares are cubes.
+                RadialSpacing::Volume => (edges[cellidx].powi(power)
+                    + (outer_radius.powi(power) - inner_radius.powi(power)) / cells as f64)
+                    .powf(1.0 / power as f64),
+                _ => {
+                    inner_radius
+                        + (cellidx + 1) as f64 * (outer_radius - inner_radius) / cells as f64
+                }
+            };
+            edges.push(edge);
+        }
+        Ok(edges)
+    }
+}
+
 impl CylindricalGrid3D {
+    /// Create a grid without an inner radius, with cells of equal volume for the `mode`
+    /// "volume" or of equal radial width for "radius", see `RadialSpacing`.
     pub fn new(cells: [usize; 3], limit: Dim, mode: &str) -> Self {
-        if mode != "volume" {
-            panic!("CylindricalGrid3D currently only supports \"volume\" mode.");
-        }
-        print_debug!("Grid3D: Generating new grid");
+        let spacing = mode.parse().unwrap_or_else(|e| panic!("{}", e));
+        CylindricalGrid3D::with_spacing(cells, limit, spacing, 0.0)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Create a grid with the radial spacing `spacing` between `inner_radius` and the outer
+    /// radius given by `limit`, e.g. for annular vessels such as Couette cells or drums with
+    /// a central shaft. Positions inside the inner radius are outside of the grid. With
+    /// `RadialSpacing::Edges` the edges define the inner and outer radius.
+    pub fn with_spacing(
+        cells: [usize; 3],
+        limit: Dim,
+        spacing: RadialSpacing,
+        inner_radius: f64,
+    ) -> Result<Self> {
+        CylindricalGrid3D::along_axis(cells, limit, 2, spacing, inner_radius)
+    }
 
+    /// Create a grid like `with_spacing`, but with the axis of the cylinder along the
+    /// cartesian axis `axis` (0: x, 1: y, 2: z) through the centre of `limit`. The height
+    /// limits are the limits along `axis` and the outer radius is the largest half width
+    /// of the limits of the other two axes.
+    pub fn along_axis(
+        cells: [usize; 3],
+        limit: Dim,
+        axis: usize,
+        spacing: RadialSpacing,
+        inner_radius: f64,
+    ) -> Result<Self> {
         let lim = match limit {
             Dim::ThreeD(x) => x,
-            _ => panic!("Grid3D got limits for other then three dimensions."),
+            _ => return Err(anyhow!("A cylindrical grid needs limits for three dimensions")),
         };
+        if axis > 2 {
+            return Err(anyhow!("Axis {} does not exist, valid axes are 0, 1 and 2", axis));
+        }
+        // center in cartesian coords!!!
+        let center = [
+            (lim[0][0] + lim[0][1]) / 2.0,
+            (lim[1][0] + lim[1][1]) / 2.0,
+            (lim[2][0] + lim[2][1]) / 2.0,
+        ];
+        let outer_radius = (0..3)
+            .filter(|&dim| dim != axis)
+            .map(|dim| {
+                (lim[dim][1] - center[dim])
+                    .abs()
+                    .max((lim[dim][0] - center[dim]).abs())
+            })
+            .fold(0.0, f64::max);
+        let mut direction = [0.0; 3];
+        direction[axis] = 1.0;
+        CylindricalGrid3D::oriented(
+            cells,
+            center,
+            direction,
+            outer_radius,
+            lim[axis],
+            spacing,
+            inner_radius,
+        )
+    }
+
+    /// Create a grid around the axis through `center` along `axis`, which does not need to
+    /// be normalised. The height of a position is its projection onto the normalised axis,
+    /// so `height` limits positions along the axis in the same coordinates, e.g. the x
+    /// limits for an axis along x.
+    pub fn oriented(
+        cells: [usize; 3],
+        center: [f64; 3],
+        axis: [f64; 3],
+        radius: f64,
+        height: [f64; 2],
+        spacing: RadialSpacing,
+        inner_radius: f64,
+    ) -> Result<Self> {
+        print_debug!("Grid3D: Generating new grid");
+        if cells.contains(&0) {
+            return Err(anyhow!(
+                "A cylindrical grid needs at least one cell along every axis, got {:?}",
+                cells
+            ));
+        }
+
+        let length = (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2]).sqrt();
+        if length == 0.0 || !length.is_finite() {
+            return Err(anyhow!("The axis of a cylinder can not be {:?}", axis));
+        }
+        let axis = [axis[0] / length, axis[1] / length, axis[2] / length];
+        let basis = perpendicular_basis(axis);
         const PI: f64 = std::f64::consts::PI;
         // the distance beween two angles is constant therefore the cell size in omega dimension is
         let ocellsize = (2.0 * PI) / cells[1] as f64;
         // height distance is also easy to calculate
-        let zcellsize = (lim[2][1] - lim[2][0]) / cells[2] as f64;
-        // The radial positions depend on the mode
-        let mut rpositions = Array::from_elem(cells[0], [0.0, 0.0]);
+        let zcellsize = (height[1] - height[0]) / cells[2] as f64;
         let mut opositions = Array::from_elem(cells[1], 0.);
         let mut zpositions = Array::from_elem(cells[2], 0.);
 
@@ -52,76 +229,117 @@ impl CylindricalGrid3D {
         }
 
         for cellidz in 0..cells[2] {
-            zpositions[cellidz] = cellidz as f64 * zcellsize + zcellsize + lim[2][0];
+            zpositions[cellidz] = cellidz as f64 * zcellsize + zcellsize + height[0];
         }
-        // center in cartesian coords!!!
-        let center = [
-            (lim[0][0] + lim[0][1]) / 2.0,
-            (lim[1][0] + lim[1][1]) / 2.0,
-            (lim[2][0] + lim[2][1]) / 2.0,
-        ];
-        let outer_radius = {
-            let x = if (lim[0][1] - center[0]).abs() > (lim[0][0] - center[0]).abs() {
-                (lim[0][1] - center[0]).abs()
-            } else {
-                (lim[0][0] - center[0]).abs()
-            };
-            let y = if (lim[1][1] - center[1]).abs() > (lim[1][0] - center[1]).abs() {
-                (lim[1][1] - center[1]).abs()
-            } else {
-                (lim[1][0] - center[1]).abs()
-            };
-            if x > y {
-                x
-            } else {
-                y
-            }
-        };
 
-        let mut inner_radius = 0.0;
-        for cellidx in 0..cells[0] {
-            if mode == "volume" {
-                // radial positions should be in a distance so that all cells have the same volume
-                // volume of a cell is pi*h*(r_o**2-r_i**2)*alpha/360
-                // new is the next outer radius calculated by
-                // new = (V +r_before**2).sqrt()
-                // V is the volume of a cell which is siply the volume defided by the num of cells
-
-                let new = (
-                    outer_radius * outer_radius / cells[0] as f64 // volume
-                    + inner_radius * inner_radius
-                    // radius of cell before
-                )
-                    .sqrt();
-                rpositions[cellidx] = [inner_radius, new]; // inside, outside
-                inner_radius = new;
-            }
-        }
+        // The radial positions depend on the mode
+        let edges = spacing.edges(cells[0], inner_radius, radius, 2)?;
+        let rpositions = edges
+            .windows(2)
+            .map(|pair| [pair[0], pair[1]]) // inside, outside
+            .collect::<Array1<[f64; 2]>>();
+        let (inner_radius, outer_radius) = (edges[0], edges[cells[0]]);
 
         let lim = [
-            [0.0, outer_radius],    // radius
-            [-PI, PI],              // omega
-            [lim[2][0], lim[2][1]], // height4
+            [inner_radius, outer_radius], // radius
+            [-PI, PI],                    // omega
+            height,                       // height
         ];
         let rmeanpositions = rpositions
             .iter()
             .map(|x| (x[0] + x[1]) / 2.)
             .collect::<Array1<f64>>();
-        CylindricalGrid3D {
+        Ok(CylindricalGrid3D {
             cells,
             rpositions,
             rmeanpositions,
             opositions,
             zpositions,
             center,
+            axis,
+            basis,
             radius: outer_radius,
+            inner_radius,
             limits: lim,
             data: Array3::zeros(cells),
             weight: Array3::zeros(cells),
+            units: GridUnits::default(),
+        })
+    }
+
+    /// Cartesian coordinates of a position in the frame of the cylinder: the components
+    /// along the two basis vectors relative to the axis, and the height along the axis
+    fn to_local(&self, pos: Position) -> Position {
+        let relative = [
+            pos[0] - self.center[0],
+            pos[1] - self.center[1],
+            pos[2] - self.center[2],
+        ];
+        [
+            dot(relative, self.basis[0]),
+            dot(relative, self.basis[1]),
+            dot(pos, self.axis),
+        ]
+    }
+
+    /// Parameters t in [0, 1] at which the path pos1 + t * (pos2 - pos1) crosses a cell
+    /// boundary, sorted and including both ends of the path
+    fn trajectory_crossings(&self, pos1: Position, pos2: Position) -> Vec<f64> {
+        // the transformation into the frame of the cylinder is affine, so the parameters t
+        // are the same in both frames
+        let (local1, local2) = (self.to_local(pos1), self.to_local(pos2));
+        let (x0, y0, z0) = (local1[0], local1[1], local1[2]);
+        let (dx, dy, dz) = (
+            local2[0] - local1[0],
+            local2[1] - local1[1],
+            local2[2] - local1[2],
+        );
+        let mut crossings = vec![0.0, 1.0];
+        // cylinders r = edge: |(x0, y0) + t (dx, dy)|^2 = edge^2
+        let a = dx * dx + dy * dy;
+        let b = 2.0 * (x0 * dx + y0 * dy);
+        if a > 0.0 {
+            let edges = self.rpositions.iter().map(|r| r[0]).chain([self.radius]);
+            for edge in edges {
+                let c = x0 * x0 + y0 * y0 - edge * edge;
+                let discriminant = b * b - 4.0 * a * c;
+                if discriminant < 0.0 {
+                    continue;
+                }
+                let root = discriminant.sqrt();
+                crossings.push((-b - root) / (2.0 * a));
+                crossings.push((-b + root) / (2.0 * a));
+            }
         }
+        // half planes theta = angle, the boundary at -pi and pi is the same half plane, the
+        // branch cut of atan2
+        for &angle in &self.angular_edges()[1..] {
+            let (sin, cos) = angle.sin_cos();
+            let denominator = dx * sin - dy * cos;
+            if denominator == 0.0 {
+                continue;
+            }
+            let t = -(x0 * sin - y0 * cos) / denominator;
+            // only the half of the line on the side of the angle
+            if (x0 + t * dx) * cos + (y0 + t * dy) * sin > 0.0 {
+                crossings.push(t);
+            }
+        }
+        // planes z = height
+        if dz != 0.0 {
+            for height in self.height_edges() {
+                crossings.push((height - z0) / dz);
+            }
+        }
+        crossings.retain(|t| (0.0..=1.0).contains(t));
+        crossings.sort_by(|a, b| a.total_cmp(b));
+        crossings.dedup();
+        crossings
     }
+
+    /// Transform a cartesian position into [r, theta, height] around the axis of the grid
     pub fn to_cylindrical(&self, pos: Position) -> Position {
-        let pos = [pos[0] - self.center[0], pos[1] - self.center[1], pos[2]];
+        let pos = self.to_local(pos);
         let r = (pos[0] * pos[0] + pos[1] * pos[1]).sqrt();
         let theta = pos[1].atan2(pos[0]);
         let z = pos[2];
@@ -129,6 +347,112 @@ impl CylindricalGrid3D {
         print_debug!("Cart to cyl: {:?}-->{:?}", pos, [r, theta, z]);
         [r, theta, z]
     }
+
+    /// Decompose the cartesian velocity `vel` of a particle at `pos` into the radial
+    /// velocity, the angular velocity around the axis and the axial velocity
+    pub fn velocity(&self, pos: Position, vel: [f64; 3]) -> [f64; 3] {
+        let pos = self.to_local(pos);
+        let vel = [
+            dot(vel, self.basis[0]),
+            dot(vel, self.basis[1]),
+            dot(vel, self.axis),
+        ];
+        let r2 = pos[0] * pos[0] + pos[1] * pos[1];
+        let vel_radial = (pos[0] * vel[0] + pos[1] * vel[1]) / r2.sqrt();
+        let vel_omega = (pos[0] * vel[1] - pos[1] * vel[0]) / r2;
+        [vel_radial, vel_omega, vel[2]]
+    }
+
+    /// Cartesian position of the cylindrical position `pos` ([r, theta, height]), the inverse
+    /// of `to_cylindrical`
+    pub fn to_cartesian(&self, pos: Position) -> Position {
+        let (x, y) = (pos[1].cos() * pos[0], pos[1].sin() * pos[0]);
+        let height = pos[2] - dot(self.center, self.axis);
+        [0, 1, 2].map(|dim| {
+            self.center[dim]
+                + x * self.basis[0][dim]
+                + y * self.basis[1][dim]
+                + height * self.axis[dim]
+        })
+    }
+
+    /// Cartesian velocity of a particle at the cylindrical position `pos` moving with the
+    /// radial, angular and axial velocity `vel`, the inverse of `velocity`
+    pub fn cartesian_velocity(&self, pos: Position, vel: [f64; 3]) -> [f64; 3] {
+        let (cos, sin) = (pos[1].cos(), pos[1].sin());
+        // velocity along the two basis vectors, the tangential velocity is omega * r
+        let tangential = vel[1] * pos[0];
+        let (vel_x, vel_y) = (
+            vel[0] * cos - tangential * sin,
+            vel[0] * sin + tangential * cos,
+        );
+        [0, 1, 2].map(|dim| {
+            vel_x * self.basis[0][dim] + vel_y * self.basis[1][dim] + vel[2] * self.axis[dim]
+        })
+    }
+
+    /// Radial boundaries of all cells, from the inner to the outer radius
+    pub fn radial_edges(&self) -> Vec<f64> {
+        self.rpositions
+            .iter()
+            .map(|r| r[0])
+            .chain(self.rpositions.iter().last().map(|r| r[1]))
+            .collect()
+    }
+
+    /// Angular boundaries of all cells from -pi to pi. Positions are assigned to the closest
+    /// entry of `opositions`, so the inner boundaries lie halfway between two entries.
+    pub fn angular_edges(&self) -> Vec<f64> {
+        midpoint_edges(&self.opositions, self.limits[1])
+    }
+
+    /// Boundaries of all cells along the axis, from the lower to the upper height limit. Like
+    /// the angles, heights are assigned to the closest entry of `zpositions`.
+    pub fn height_edges(&self) -> Vec<f64> {
+        midpoint_edges(&self.zpositions, self.limits[2])
+    }
+}
+
+/// The two `limits` and the midpoints between all neighbouring `positions`
+fn midpoint_edges(positions: &Array1<f64>, limits: [f64; 2]) -> Vec<f64> {
+    let midpoints = positions
+        .windows(2)
+        .into_iter()
+        .map(|pair| 0.5 * (pair[0] + pair[1]));
+    std::iter::once(limits[0])
+        .chain(midpoints)
+        .chain([limits[1]])
+        .collect()
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// Two unit vectors perpendicular to the unit vector `axis` forming a right-handed system
+/// with it. For the cartesian axes these are the next two axes in cyclic order, so a
+/// cylinder along z measures theta in the xy plane from the x axis as before.
+fn perpendicular_basis(axis: [f64; 3]) -> [[f64; 3]; 2] {
+    let largest = (0..3)
+        .max_by(|&a, &b| axis[a].abs().total_cmp(&axis[b].abs()))
+        .unwrap_or(2);
+    let mut reference = [0.0; 3];
+    reference[(largest + 1) % 3] = 1.0;
+    // remove the part of the reference along the axis
+    let along = dot(reference, axis);
+    let first = [
+        reference[0] - along * axis[0],
+        reference[1] - along * axis[1],
+        reference[2] - along * axis[2],
+    ];
+    let length = dot(first, first).sqrt();
+    let first = [first[0] / length, first[1] / length, first[2] / length];
+    let second = [
+        axis[1] * first[2] - axis[2] * first[1],
+        axis[2] * first[0] - axis[0] * first[2],
+        axis[0] * first[1] - axis[1] * first[0],
+    ];
+    [first, second]
 }
 
 impl std::fmt::Debug for CylindricalGrid3D {
@@ -179,7 +503,7 @@ impl GridFunctions3D for CylindricalGrid3D {
     fn is_inside(&self, pos: Position) -> bool {
         let pos = self.to_cylindrical(pos);
         print_debug!("Grid3D: Checking if {:?} is in grid", pos);
-        let in_rad = pos[0] <= self.radius;
+        let in_rad = pos[0] >= self.inner_radius && pos[0] <= self.radius;
         let in_omega = pos[1] >= self.limits[1][0] && pos[1] <= self.limits[1][1];
         let in_height = pos[2] >= self.limits[2][0] && pos[2] <= self.limits[2][1];
         in_rad && in_omega && in_height
@@ -195,35 +519,70 @@ impl GridFunctions3D for CylindricalGrid3D {
                 cell_idr = Some(idx)
             }
         }
-        let cell_idr = cell_idr.unwrap_or_else(|| {
-            panic!(
+        let cell_idr = cell_idr.ok_or_else(|| {
+            anyhow!(
                 "Unable to find radial cell id \n pos r:{}\nlen {:?}",
-                posr, self.rpositions
+                posr,
+                self.rpositions
             )
-        });
+        })?;
         let poso = pos[1];
         let cell_ido = (&self.opositions - poso)
             .iter()
             .map(|x| x.abs())
             .collect::<Array1<f64>>()
             .argmin()
-            .unwrap_or_else(|_| panic!("Can not find min of {:?} in Gri3D", pos));
+            .map_err(|e| anyhow!("Can not find min of {:?} in Grid3D: {}", pos, e))?;
         let posz = pos[2];
         let cell_idz = (&self.zpositions - posz)
             .iter()
             .map(|z| z.abs())
             .collect::<Array1<f64>>()
             .argmin()
-            .unwrap_or_else(|_| panic!("Can not find min of {:?} in Gri3D", pos));
+            .map_err(|e| anyhow!("Can not find min of {:?} in Grid3D: {}", pos, e))?;
         Ok([cell_idr, cell_ido, cell_idz])
     }
-    #[allow(unused_variables)]
+    // Exact ray traversal: the straight path is split at every radial, angular and height
+    // boundary it crosses and each piece is assigned to the cell containing its midpoint.
+    // Pieces outside of the grid (e.g. through the shaft) are left out, so the fractions
+    // only add up to one if the whole path lies inside.
     fn cell_ids_in_trajectory(
         &self,
         pos1: Position,
         pos2: Position,
     ) -> Result<(Vec<CellId>, Vec<f64>)> {
-        unimplemented!("Not implemented for cylindrical grid")
+        let crossings = self.trajectory_crossings(pos1, pos2);
+        let mut cell_ids: Vec<CellId> = Vec::new();
+        let mut fractions: Vec<f64> = Vec::new();
+        for pair in crossings.windows(2) {
+            let t = 0.5 * (pair[0] + pair[1]);
+            let midpoint = [
+                pos1[0] + t * (pos2[0] - pos1[0]),
+                pos1[1] + t * (pos2[1] - pos1[1]),
+                pos1[2] + t * (pos2[2] - pos1[2]),
+            ];
+            if !self.is_inside(midpoint) {
+                continue;
+            }
+            let cell_id = self.cell_id(midpoint)?;
+            let fraction = pair[1] - pair[0];
+            match cell_ids.last() {
+                // extra boundaries may split the path inside of a single cell
+                Some(last) if *last == cell_id => *fractions.last_mut().unwrap() += fraction,
+                _ => {
+                    cell_ids.push(cell_id);
+                    fractions.push(fraction);
+                }
+            }
+        }
+        if cell_ids.is_empty() {
+            return Err(anyhow!(
+                "Trajectory from {:?} to {:?} does not cross the grid",
+                pos1,
+                pos2
+            ));
+        }
+        Ok((cell_ids, fractions))
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -251,13 +610,20 @@ impl GridFunctions3D for CylindricalGrid3D {
         self.data[(cell_id[0], cell_id[1], cell_id[2])] += value;
         self.weight[(cell_id[0], cell_id[1], cell_id[2])] += 1.;
     }
-    #[allow(unused_variables)]
+    // Between two points of a trajectory, add the value to all crossed cells weighted by the
+    // fraction of the path that lies in each cell
     fn add_trajectory_value(&mut self, pos1: Position, pos2: Position, value: f64) {
-        print_warning!(
-            "This feature is not yet implemented for cylindrical grid\
-        Falling back to add_value"
-        );
-        self.add_value(pos1, value);
+        let (cell_ids, fractions) = match self.cell_ids_in_trajectory(pos1, pos2) {
+            Ok((cell_ids, fractions)) => (cell_ids, fractions),
+            Err(_) => {
+                print_warning!("Error in cell_ids_in_trajectory calculation, cell id not found");
+                return;
+            }
+        };
+        for (cell_id, fraction) in cell_ids.iter().zip(fractions.iter()) {
+            self.data[(cell_id[0], cell_id[1], cell_id[2])] += value * fraction;
+            self.weight[(cell_id[0], cell_id[1], cell_id[2])] += fraction;
+        }
     }
 
     fn divide_by_array(&mut self, other: &Array3<f64>) {
@@ -426,6 +792,14 @@ impl GridFunctions3D for CylindricalGrid3D {
         true
     }
 
+    fn get_units(&self) -> &GridUnits {
+        &self.units
+    }
+
+    fn set_units(&mut self, units: GridUnits) {
+        self.units = units;
+    }
+
     fn set_data(&mut self, data: Array3<f64>) {
         if self.data.shape() == data.shape() {
             self.data = data;
@@ -513,3 +887,153 @@ impl GridFunctions3D for CylindricalGrid3D {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> Dim {
+        Dim::ThreeD([[-1.0, 1.0], [-1.0, 1.0], [0.0, 1.0]])
+    }
+
+    #[test]
+    fn equal_radius() {
+        let grid =
+            CylindricalGrid3D::with_spacing([4, 1, 1], limits(), RadialSpacing::Radius, 0.2)
+                .unwrap();
+        let edges: Vec<f64> = grid.rpositions().iter().map(|r| r[1]).collect();
+        assert!(edges
+            .iter()
+            .zip([0.4, 0.6, 0.8, 1.0])
+            .all(|(edge, expected)| (edge - expected).abs() < 1e-12));
+        // inside of the shaft
+        assert!(!grid.is_inside([0.1, 0.0, 0.5]));
+        assert_eq!(grid.cell_id([0.0, 0.5, 0.5]).unwrap()[0], 1);
+    }
+
+    #[test]
+    fn equal_volume() {
+        let grid =
+            CylindricalGrid3D::with_spacing([3, 1, 1], limits(), RadialSpacing::Volume, 0.5)
+                .unwrap();
+        let areas: Vec<f64> = grid
+            .rpositions()
+            .iter()
+            .map(|r| r[1] * r[1] - r[0] * r[0])
+            .collect();
+        assert!(areas.iter().all(|area| (area - 0.25).abs() < 1e-12));
+    }
+
+    #[test]
+    fn trajectory() {
+        let grid =
+            CylindricalGrid3D::with_spacing([2, 4, 1], limits(), RadialSpacing::Radius, 0.0)
+                .unwrap();
+        // along the x axis towards the centre, half of the path in each radial cell
+        let (cell_ids, fractions) = grid
+            .cell_ids_in_trajectory([0.9, 0.0, 0.5], [0.1, 0.0, 0.5])
+            .unwrap();
+        assert_eq!(cell_ids.len(), 2);
+        assert_eq!(cell_ids[0][0], 1);
+        assert_eq!(cell_ids[1][0], 0);
+        assert!((fractions[0] - 0.5).abs() < 1e-12);
+        assert!((fractions[1] - 0.5).abs() < 1e-12);
+
+        // a quarter circle chord crosses the angular boundary at 45 degrees
+        let (cell_ids, fractions) = grid
+            .cell_ids_in_trajectory([0.8, 0.0, 0.5], [0.0, 0.8, 0.5])
+            .unwrap();
+        assert_eq!(cell_ids.len(), 2);
+        assert_ne!(cell_ids[0][1], cell_ids[1][1]);
+        assert!((fractions.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+
+        // a path leaving the grid only adds the part inside
+        let (_, fractions) = grid
+            .cell_ids_in_trajectory([0.0, 0.0, 0.5], [2.0, 0.0, 0.5])
+            .unwrap();
+        assert!((fractions.iter().sum::<f64>() - 0.5).abs() < 1e-12);
+        assert!(grid
+            .cell_ids_in_trajectory([2.0, 2.0, 0.5], [3.0, 2.0, 0.5])
+            .is_err());
+    }
+
+    #[test]
+    fn horizontal_axis() {
+        let limits = Dim::ThreeD([[0.0, 1.0], [-1.0, 1.0], [-1.0, 1.0]]);
+        let grid =
+            CylindricalGrid3D::along_axis([2, 4, 2], limits, 0, RadialSpacing::Radius, 0.0)
+                .unwrap();
+        let cyl = grid.to_cylindrical([0.5, 0.0, 0.8]);
+        assert!((cyl[0] - 0.8).abs() < 1e-12);
+        assert!((cyl[1] - 0.5 * std::f64::consts::PI).abs() < 1e-12);
+        assert!((cyl[2] - 0.5).abs() < 1e-12);
+        assert!(grid.is_inside([0.9, 0.5, -0.5]));
+        assert!(!grid.is_inside([1.5, 0.0, 0.0]));
+        // rotating around the x axis while moving along it
+        let vel = grid.velocity([0.5, 0.0, 0.8], [0.2, -1.0, 0.0]);
+        assert!(vel[0].abs() < 1e-12);
+        assert!((vel[1] - 1.25).abs() < 1e-12);
+        assert!((vel[2] - 0.2).abs() < 1e-12);
+        let flat = Dim::TwoD([[0.0, 1.0], [-1.0, 1.0]]);
+        assert!(
+            CylindricalGrid3D::along_axis([2, 4, 2], flat, 0, RadialSpacing::Radius, 0.0).is_err()
+        );
+    }
+
+    #[test]
+    fn oriented_axis() {
+        let grid = CylindricalGrid3D::oriented(
+            [2, 4, 2],
+            [0.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            1.0,
+            [-1.0, 1.0],
+            RadialSpacing::Radius,
+            0.0,
+        )
+        .unwrap();
+        let cyl = grid.to_cylindrical([0.5, -0.5, 0.0]);
+        assert!((cyl[0] - 0.5f64.sqrt()).abs() < 1e-12);
+        assert!(cyl[2].abs() < 1e-12);
+        assert!(grid.is_inside([0.7, 0.7, 0.0]));
+        assert!(!grid.is_inside([0.7, -0.7, 0.8]));
+        let pos = [0.3, -0.2, 0.6];
+        let back = grid.to_cartesian(grid.to_cylindrical(pos));
+        assert!((0..3).all(|dim| (back[dim] - pos[dim]).abs() < 1e-12));
+        let vel = [0.5, 1.0, -2.0];
+        let back = grid.cartesian_velocity(grid.to_cylindrical(pos), grid.velocity(pos, vel));
+        assert!((0..3).all(|dim| (back[dim] - vel[dim]).abs() < 1e-12));
+        let no_axis = CylindricalGrid3D::oriented(
+            [2, 4, 2],
+            [0.0; 3],
+            [0.0; 3],
+            1.0,
+            [0.0, 1.0],
+            RadialSpacing::Volume,
+            0.0,
+        );
+        assert!(no_axis.is_err());
+        for cells in [[0, 4, 2], [2, 0, 2], [2, 4, 0]] {
+            let no_cells = CylindricalGrid3D::oriented(
+                cells,
+                [0.0; 3],
+                [0.0, 0.0, 1.0],
+                1.0,
+                [0.0, 1.0],
+                RadialSpacing::Volume,
+                0.0,
+            );
+            assert!(no_cells.is_err());
+        }
+    }
+
+    #[test]
+    fn edges() {
+        let spacing = RadialSpacing::Edges(vec![0.1, 0.5, 0.6]);
+        let grid = CylindricalGrid3D::with_spacing([2, 1, 1], limits(), spacing, 0.0).unwrap();
+        assert_eq!(grid.limits()[0], [0.1, 0.6]);
+        assert!(!grid.is_inside([0.8, 0.0, 0.5]));
+        let wrong = RadialSpacing::Edges(vec![0.5, 0.1, 0.6]);
+        assert!(CylindricalGrid3D::with_spacing([2, 1, 1], limits(), wrong, 0.0).is_err());
+        assert!("edges".parse::<RadialSpacing>().is_err());
+    }
+}
@@ -1,111 +1,128 @@
 extern crate ndarray;
+use super::{unresolved_range, CartesianGrid3D, Dim, GridFunctions3D, Position, ThreeD};
 use crate::print_debug;
-use derive_getters::Getters;
+use anyhow::{anyhow, Result};
 use ndarray::prelude::*;
-use ndarray_stats::QuantileExt;
-use std::any::Any;
 
-use super::{Dim, GridFunctions, OneD};
-
-/// One dimensional grid that allowes storage of `cells[0]` datapoints of type `<T: Clone + Debug>`
-/// in the equally spaced range of `xlim`, such as velocity distributions.
+/// One dimensional grid resolving the positions along `axis` only, such as a height profile.
+/// Positions along the other two axes are ignored, every particle with a position inside
+/// the limits of `axis` is binned.
+///
+/// The grid implements `GridFunctions3D`, so it can be used by all field functions. Its
+/// data has a single cell along the unresolved axes.
 /// ```rust
-/// let grid = Grid1D::new(
-///             arr![10.],              // 10 cells
-///             Dim::OneD((0.,100.)),   // dimensions range from 0 to 100
-///             0.0,                    // Initiate with zeros
-/// };
+/// use upppp_rust::{Dim, Grid1D, GridFunctions3D};
+/// // 10 cells between 0 and 100 along z
+/// let grid = Grid1D::new(2, 10, Dim::OneD([[0., 100.]])).unwrap();
+/// assert_eq!(grid.get_cells(), &[1, 1, 10]);
+/// assert!(grid.is_inside([1e6, -1e6, 12.5]));
 /// ```
-#[derive(Getters, Clone)]
+#[derive(Clone)]
 pub struct Grid1D {
-    cells: Array1<usize>,
-    positions: Array1<f64>, // midpoint of each cell
-    limits: OneD,
-    // attrs: HashMap<String, >,
+    axis: usize,
+    grid: CartesianGrid3D,
 }
 
 impl Grid1D {
-    /// Struct constructor.
-    /// ```rust
-    /// let grid = Grid1D::new(
-    ///             arr![10.],              // 10 cells
-    ///             Dim::OneD((0.,100.)),   // dimensions range from 0 to 100
-    ///             0.0,                    // Initiate with zeros
-    /// };
-    /// ```
-    pub fn new(cells: Array1<usize>, limit: Dim) -> Self {
+    /// Create a grid with `cells` cells along `axis`. `limit` is either the range along
+    /// `axis` (`Dim::OneD`) or the ranges of all three axes (`Dim::ThreeD`), where the
+    /// ranges of the unresolved axes are only kept for reference. Unresolved axes without a
+    /// range of their own span one unit, see `unresolved_range`.
+    pub fn new(axis: usize, cells: usize, limit: Dim) -> Result<Self> {
         print_debug!("Grid1D: Generating new grid");
-        if cells.shape()[0] != 1 {
-            panic!(
-                "Grid1D got wrong Cell-shape.\\
-                    Array should only hold a single number. Not {:?}",
-                cells.shape()
-            )
+        if axis > 2 {
+            return Err(anyhow!("Axis {} does not exist, valid axes are 0, 1 and 2", axis));
+        }
+        if cells == 0 {
+            return Err(anyhow!("Grid1D needs at least one cell"));
         }
-        let xlim = match limit {
-            Dim::OneD(s) => s,
-            _ => panic!("Grid1D got limits for other then one dimension."),
+        let mut limits: ThreeD = match limit {
+            Dim::OneD(lim) => {
+                let mut limits = [[0.0, 0.0]; 3];
+                limits[axis] = lim[0];
+                limits
+            }
+            Dim::ThreeD(lim) => lim,
+            _ => return Err(anyhow!("Grid1D got limits for two dimensions")),
         };
-        let cellsize = (xlim[0][1] - xlim[0][0]) / cells[0] as f64;
-        let mut positions = Array1::<f64>::zeros(cells[0]);
-        for cellid in 0..cells[0] as usize {
-            positions[cellid] = cellid as f64 * cellsize + cellsize
+        if limits[axis][1] <= limits[axis][0] {
+            return Err(anyhow!(
+                "The limits {:?} of axis {} are not increasing",
+                limits[axis],
+                axis
+            ));
         }
-        print_debug!(
-            "Grid1D:\n\tCells: {:?} \n\tpositions: {:?} \n\tlim: {:?}",
-            cells,
-            positions,
-            xlim
-        );
+        for dim in (0..3).filter(|&dim| dim != axis) {
+            limits[dim] = unresolved_range(limits[dim]);
+        }
+        let mut cell_numbers = [1; 3];
+        cell_numbers[axis] = cells;
+        Ok(Grid1D {
+            axis,
+            grid: CartesianGrid3D::new(cell_numbers, Dim::ThreeD(limits)),
+        })
+    }
 
-        Grid1D {
-            cells,
-            positions,
-            limits: xlim,
+    /// Axis resolved by the grid, 0: x, 1: y, 2: z
+    pub fn axis(&self) -> usize {
+        self.axis
+    }
+
+    /// Cell centres along the resolved axis
+    pub fn positions(&self) -> &Array1<f64> {
+        match self.axis {
+            0 => self.grid.xpositions(),
+            1 => self.grid.ypositions(),
+            _ => self.grid.zpositions(),
         }
     }
 
-    fn data_array<T: Default + Clone>(&self) -> Array1<T> {
-        Array1::from_elem(self.cells[0] as usize, T::default())
+    /// Values of all cells along the resolved axis
+    pub fn values(&self) -> Array1<f64> {
+        self.grid.data().iter().copied().collect()
     }
-}
 
-impl GridFunctions for Grid1D {
-    /// Check if a position is inside cell and return a bool
-    /// ```rust
-    /// let grid = Grid1D::new(
-    ///             arr![10],              // 10 cells
-    ///             Dim::OneD((0.,100.)),   // dimensions range from 0 to 100
-    ///             0.0,                    // Initiate with zeros
-    /// };
-    /// assert_eq!(grid.is_inside(12.5), true);
-    /// ```
-    fn is_inside(&self, num: Vec<f64>) -> bool {
-        print_debug!("Grid1D: Checking if {:?} is in grid", num);
-        let pos = num[0];
-        pos > self.limits[0][0] && pos < self.limits[0][1]
+    /// Move a position onto the centre of the unresolved axes
+    fn project(&self, pos: Position) -> Position {
+        let mut projected = pos;
+        for dim in (0..3).filter(|&dim| dim != self.axis) {
+            let lim = self.grid.limits()[dim];
+            if !pos[dim].is_nan() {
+                projected[dim] = 0.5 * (lim[0] + lim[1]);
+            }
+        }
+        projected
     }
-    /// Return cell id of cell that holds a position
-    /// ```rust
-    /// let grid = Grid1D::new(
-    ///             arr![10.],              // 10 cells
-    ///             Dim::OneD((0.,100.)),   // dimensions range from 0 to 100
-    ///             0.0,                    // Initiate with zeros
-    /// }
-    /// let cellid = grid.cell_id()
-    /// ```
-    fn cell_id(&self, num: Vec<f64>) -> Array1<usize> {
-        print_debug!("Grid1D: Checking if {:?} is in grid", num);
-        let pos = num[0];
-        let cell_id = (&self.positions - pos)
-            .iter()
-            .map(|x| x.abs())
-            .collect::<Array1<f64>>()
-            .argmin()
-            .expect("lol");
-        array![cell_id]
+}
+
+reduced_grid_functions!(Grid1D, "Grid1D");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile() {
+        let mut grid = Grid1D::new(2, 5, Dim::OneD([[0.0, 1.0]])).unwrap();
+        grid.add_value([10.0, -3.0, 0.0], 1.0);
+        grid.add_value([0.5, 0.5, 1.0], 2.0);
+        grid.add_value([0.5, 0.5, 2.0], 4.0);
+        grid.add_value([f64::NAN, 0.5, 0.5], 8.0);
+        assert_eq!(grid.get_cells(), &[1, 1, 5]);
+        let values = grid.values();
+        assert_eq!(values.len(), 5);
+        assert_eq!(values.sum(), 3.0);
+        assert!(!grid.is_inside([0.0, 0.0, 2.0]));
+        // the unresolved axes have a size, so their cell index is never NaN
+        assert_eq!(grid.cell_id([0.0, 0.0, 0.5]).unwrap(), [0, 0, 2]);
+        assert!(grid.get_limits()[0][1] > grid.get_limits()[0][0]);
     }
-    fn as_any(&self) -> &dyn Any {
-        self
+
+    #[test]
+    fn invalid() {
+        assert!(Grid1D::new(3, 5, Dim::OneD([[0.0, 1.0]])).is_err());
+        assert!(Grid1D::new(2, 0, Dim::OneD([[0.0, 1.0]])).is_err());
+        assert!(Grid1D::new(2, 5, Dim::OneD([[1.0, 1.0]])).is_err());
+        assert!(Grid1D::new(2, 5, Dim::TwoD([[0.0, 1.0]; 2])).is_err());
     }
 }
@@ -1,111 +1,127 @@
 extern crate ndarray;
+use super::{unresolved_range, CartesianGrid3D, Dim, GridFunctions3D, Position, ThreeD};
 use crate::print_debug;
-use derive_getters::Getters;
+use anyhow::{anyhow, Result};
 use ndarray::prelude::*;
-use ndarray_stats::QuantileExt;
-use std::any::Any;
 
-use super::{Dim, GridFunctions, TwoD};
-#[derive(Getters, Clone)]
+/// Two dimensional grid resolving the plane normal to `normal`, such as a depth averaged
+/// map. Positions along `normal` are ignored, every particle with a position inside the
+/// limits of the plane is binned.
+///
+/// The grid implements `GridFunctions3D`, so it can be used by all field functions. Its
+/// data has a single cell along `normal`.
+/// ```rust
+/// use upppp_rust::{Dim, Grid2D, GridFunctions3D};
+/// // 10 x 20 cells in the xz plane
+/// let grid = Grid2D::new(1, [10, 20], Dim::TwoD([[0., 1.], [0., 2.]])).unwrap();
+/// assert_eq!(grid.get_cells(), &[10, 1, 20]);
+/// assert!(grid.is_inside([0.5, 1e6, 1.5]));
+/// ```
+#[derive(Clone)]
 pub struct Grid2D {
-    cells: Array1<usize>,
-    xpositions: Array1<f64>,
-    ypositions: Array1<f64>,
-    limits: TwoD,
-    // attrs: HashMap<String, >,
+    normal: usize,
+    grid: CartesianGrid3D,
 }
 
 impl Grid2D {
-    pub fn new(cells: Array1<usize>, limit: Dim) -> Self {
+    /// Create a grid with `cells` cells along the two axes of the plane normal to `normal`,
+    /// in increasing order of the axes. `limit` is either the ranges of these two axes
+    /// (`Dim::TwoD`) or the ranges of all three axes (`Dim::ThreeD`), where the range along
+    /// `normal` is only kept for reference. Without a range of its own `normal` spans one
+    /// unit, see `unresolved_range`.
+    pub fn new(normal: usize, cells: [usize; 2], limit: Dim) -> Result<Self> {
         print_debug!("Grid2D: Generating new grid");
-        if cells.shape()[0] != 2 {
-            panic!(
-                "Grid2D got wrong Arrayshape.\\
-                    Array should only hold a single number."
-            )
+        if normal > 2 {
+            return Err(anyhow!("Axis {} does not exist, valid axes are 0, 1 and 2", normal));
         }
-
-        let lim = match limit {
-            Dim::TwoD(s) => (s),
-            _ => panic!("Grid2D got limits for other then two dimensions."),
-        };
-        let xcellsize = (lim[0][1] - lim[0][0]) / cells[0] as f64;
-        let ycellsize = (lim[1][1] - lim[1][0]) / cells[1] as f64;
-        let mut xpositions = Array::from_elem(cells[0], 0.);
-        let mut ypositions = Array::from_elem(cells[1], 0.);
-        for cellidx in 0..cells[0] {
-            xpositions[cellidx as usize] = cellidx as f64 * xcellsize + lim[0][0];
+        if cells.contains(&0) {
+            return Err(anyhow!("Grid2D needs at least one cell along both axes, got {:?}", cells));
         }
-        for cellidy in 0..cells[1] {
-            ypositions[cellidy as usize] = cellidy as f64 * ycellsize + lim[1][0];
+        let plane = in_plane(normal);
+        let mut limits: ThreeD = match limit {
+            Dim::TwoD(lim) => {
+                let mut limits = [[0.0, 0.0]; 3];
+                limits[plane[0]] = lim[0];
+                limits[plane[1]] = lim[1];
+                limits
+            }
+            Dim::ThreeD(lim) => lim,
+            _ => return Err(anyhow!("Grid2D got limits for one dimension")),
+        };
+        if let Some(&axis) = plane.iter().find(|&&axis| limits[axis][1] <= limits[axis][0]) {
+            return Err(anyhow!(
+                "The limits {:?} of axis {} are not increasing",
+                limits[axis],
+                axis
+            ));
         }
-        print_debug!(
-            "Grid2D:\n\tCells: {:?} \n\txpositions: {:?}\\
-             \n\typositions: {:?} \n\txlim: {:?} \n\tylim: {:?}",
-            cells,
-            xpositions,
-            ypositions,
-            xlim,
-            ylim
-        );
+        limits[normal] = unresolved_range(limits[normal]);
+        let mut cell_numbers = [1; 3];
+        cell_numbers[plane[0]] = cells[0];
+        cell_numbers[plane[1]] = cells[1];
+        Ok(Grid2D {
+            normal,
+            grid: CartesianGrid3D::new(cell_numbers, Dim::ThreeD(limits)),
+        })
+    }
 
-        Grid2D {
-            cells,
-            xpositions,
-            ypositions,
-            limits: lim,
-        }
+    /// Axis normal to the resolved plane, 0: x, 1: y, 2: z
+    pub fn normal(&self) -> usize {
+        self.normal
+    }
+
+    /// Values of all cells of the resolved plane
+    pub fn values(&self) -> Array2<f64> {
+        self.grid.data().index_axis(Axis(self.normal), 0).to_owned()
     }
 
-    pub fn data_array<T: Default + Clone>(&self) -> Array2<T> {
-        Array2::from_elem(
-            (self.cells[0] as usize, self.cells[1] as usize),
-            T::default(),
-        )
+    /// Move a position onto the centre of the axis normal to the plane
+    fn project(&self, pos: Position) -> Position {
+        let mut projected = pos;
+        let lim = self.grid.limits()[self.normal];
+        if !pos[self.normal].is_nan() {
+            projected[self.normal] = 0.5 * (lim[0] + lim[1]);
+        }
+        projected
     }
 }
 
-impl GridFunctions for Grid2D {
-    fn is_inside(&self, num: Vec<f64>) -> bool {
-        print_debug!("Grid2D: Checking if {:?} is in grid", num);
-        let posx = num[0];
-        let posy = num[1];
-        posx > self.limits[0][0]
-            && posx < self.limits[0][1]
-            && posy > self.limits[1][0]
-            && posy < self.limits[1][1]
+/// The two axes spanning the plane normal to `normal`
+fn in_plane(normal: usize) -> [usize; 2] {
+    match normal {
+        0 => [1, 2],
+        1 => [0, 2],
+        _ => [0, 1],
     }
+}
 
-    fn cell_id(&self, num: Vec<f64>) -> Array1<usize> {
-        let posx = num[0];
-        print_debug!(
-            "Checking array {:?} with position {:?}",
-            &self.xpositions,
-            posx
-        );
-        let cell_idx = (&self.xpositions - posx)
-            .iter()
-            .map(|x| x.abs())
-            .collect::<Array1<f64>>()
-            .argmin()
-            .expect(&format!("Can not find min of {:?} in Gri2D", num));
-        print_debug!("Result: {}", cell_idx);
-        let posy = num[1];
-        print_debug!(
-            "Checking array {:?} with position {:?}",
-            &self.ypositions,
-            posy
-        );
-        let cell_idy = (&self.ypositions - posy)
-            .iter()
-            .map(|x| x.abs())
-            .collect::<Array1<f64>>()
-            .argmin()
-            .expect(&format!("Can not find min of {:?} in Gri2D", num));
-        print_debug!("Result: {}", cell_idy);
-        array![cell_idx, cell_idy]
+reduced_grid_functions!(Grid2D, "Grid2D");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plane() {
+        let mut grid = Grid2D::new(2, [2, 3], Dim::TwoD([[0.0, 1.0], [0.0, 1.0]])).unwrap();
+        grid.add_value([0.0, 0.0, 100.0], 1.0);
+        grid.add_value([1.0, 1.0, -100.0], 2.0);
+        grid.add_value([1.0, 2.0, 0.0], 4.0);
+        let values = grid.values();
+        assert_eq!(values.dim(), (2, 3));
+        assert_eq!(values[[0, 0]], 1.0);
+        assert_eq!(values[[1, 2]], 2.0);
+        assert_eq!(values.sum(), 3.0);
+        assert_eq!(grid.cell_id([1.0, 1.0, 100.0]).unwrap(), [1, 2, 0]);
+        assert!(grid.get_limits()[2][1] > grid.get_limits()[2][0]);
     }
-    fn as_any(&self) -> &dyn Any {
-        self
+
+    #[test]
+    fn invalid() {
+        let limit = Dim::TwoD([[0.0, 1.0], [0.0, 1.0]]);
+        assert!(Grid2D::new(3, [2, 3], limit.clone()).is_err());
+        assert!(Grid2D::new(2, [2, 0], limit).is_err());
+        assert!(Grid2D::new(2, [2, 3], Dim::TwoD([[0.0, 1.0], [1.0, 0.0]])).is_err());
+        assert!(Grid2D::new(2, [2, 3], Dim::OneD([[0.0, 1.0]])).is_err());
     }
 }
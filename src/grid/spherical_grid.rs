@@ -0,0 +1,679 @@
+extern crate ndarray;
+use super::{CellId, Dim, GridFunctions3D, GridUnits, Position, RadialSpacing, ThreeD};
+use crate::utilities::{nan_mean, nan_std};
+use crate::{print_debug, print_warning};
+use derive_getters::Getters;
+use ndarray::{prelude::*, RemoveAxis};
+use ndarray_stats::QuantileExt;
+use std::any::Any;
+use std::f64::consts::PI;
+
+use anyhow::{anyhow, Result};
+
+/// Grid in spherical coordinates around the centre of its limits, e.g. for spherical mixers,
+/// ball mills or spouted-bed domes. The three axes are the radius r, the polar angle theta
+/// measured from the positive z axis in [0, pi] and the azimuth phi in [-pi, pi].
+///
+/// ```rust
+/// use upppp_rust::{Dim, GridFunctions3D, SphericalGrid3D};
+/// let limits = Dim::ThreeD([[-1., 1.], [-1., 1.], [-1., 1.]]);
+/// let grid = SphericalGrid3D::new([4, 6, 8], limits, "volume");
+/// assert!(grid.is_inside([0.5, 0.5, 0.5]));
+/// assert!(!grid.is_inside([0.8, 0.8, 0.8]));
+/// ```
+#[derive(Getters, Clone, Default)]
+pub struct SphericalGrid3D {
+    cells: CellId,
+    rpositions: Array1<[f64; 2]>, // radius min and max for cell, needed because non equal distance
+    rmeanpositions: Array1<f64>,  // midpoint radius for cell
+    tpositions: Array1<f64>,      // theta (polar angle), centre of cell
+    ppositions: Array1<f64>,      // phi (azimuth), centre of cell
+    center: [f64; 3],
+    radius: f64,
+    limits: ThreeD,
+    data: Array3<f64>,
+    weight: Array3<f64>,
+    units: GridUnits,
+}
+
+impl SphericalGrid3D {
+    /// Create a grid of `cells` cells in r, theta and phi. `mode` must be "volume" for cells
+    /// of equal volume in each shell or "radius" for cells of equal radial width.
+    pub fn new(cells: [usize; 3], limit: Dim, mode: &str) -> Self {
+        let spacing = mode.parse().unwrap_or_else(|e| panic!("{}", e));
+        SphericalGrid3D::with_spacing(cells, limit, spacing).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Create a grid with the radial spacing `spacing`. The sphere is centred in the middle
+    /// of `limit` and its radius is the largest half width of the limits. With
+    /// `RadialSpacing::Edges` the edges define the inner and outer radius.
+    pub fn with_spacing(cells: [usize; 3], limit: Dim, spacing: RadialSpacing) -> Result<Self> {
+        print_debug!("SphericalGrid3D: Generating new grid");
+
+        let lim = match limit {
+            Dim::ThreeD(x) => x,
+            _ => return Err(anyhow!("A spherical grid needs limits for three dimensions")),
+        };
+        // center in cartesian coords!!!
+        let center = [
+            (lim[0][0] + lim[0][1]) / 2.0,
+            (lim[1][0] + lim[1][1]) / 2.0,
+            (lim[2][0] + lim[2][1]) / 2.0,
+        ];
+        let outer_radius = lim
+            .iter()
+            .map(|range| 0.5 * (range[1] - range[0]).abs())
+            .fold(0.0, f64::max);
+        SphericalGrid3D::around(cells, center, outer_radius, spacing)
+    }
+
+    /// Create a grid with the radial spacing `spacing` around `center` with the outer radius
+    /// `radius`, e.g. to rebuild a saved grid. With `RadialSpacing::Edges` the edges define
+    /// the inner and outer radius.
+    pub fn around(
+        cells: [usize; 3],
+        center: [f64; 3],
+        radius: f64,
+        spacing: RadialSpacing,
+    ) -> Result<Self> {
+        if cells.contains(&0) {
+            return Err(anyhow!(
+                "A spherical grid needs at least one cell along every axis, got {:?}",
+                cells
+            ));
+        }
+        // the radial positions depend on the mode, the volume of a shell grows with r**3
+        let edges = spacing.edges(cells[0], 0.0, radius, 3)?;
+        let rpositions = edges
+            .windows(2)
+            .map(|pair| [pair[0], pair[1]]) // inside, outside
+            .collect::<Array1<[f64; 2]>>();
+        let rmeanpositions = rpositions
+            .iter()
+            .map(|x| (x[0] + x[1]) / 2.)
+            .collect::<Array1<f64>>();
+        let (inner_radius, outer_radius) = (edges[0], edges[cells[0]]);
+
+        // the angles are spaced equally
+        let tcellsize = PI / cells[1] as f64;
+        let pcellsize = 2.0 * PI / cells[2] as f64;
+        let tpositions = Array1::from_shape_fn(cells[1], |idx| (idx as f64 + 0.5) * tcellsize);
+        let ppositions =
+            Array1::from_shape_fn(cells[2], |idx| -PI + (idx as f64 + 0.5) * pcellsize);
+
+        Ok(SphericalGrid3D {
+            cells,
+            rpositions,
+            rmeanpositions,
+            tpositions,
+            ppositions,
+            center,
+            radius: outer_radius,
+            limits: [
+                [inner_radius, outer_radius], // radius
+                [0.0, PI],                    // theta
+                [-PI, PI],                    // phi
+            ],
+            data: Array3::zeros(cells),
+            weight: Array3::zeros(cells),
+            units: GridUnits::default(),
+        })
+    }
+
+    /// Transform a cartesian position into [r, theta, phi] around the centre of the grid
+    pub fn to_spherical(&self, pos: Position) -> Position {
+        let pos = [
+            pos[0] - self.center[0],
+            pos[1] - self.center[1],
+            pos[2] - self.center[2],
+        ];
+        let r = (pos[0] * pos[0] + pos[1] * pos[1] + pos[2] * pos[2]).sqrt();
+        let theta = if r > 0.0 {
+            (pos[2] / r).clamp(-1.0, 1.0).acos()
+        } else {
+            0.0
+        };
+        let phi = pos[1].atan2(pos[0]);
+
+        print_debug!("Cart to sph: {:?}-->{:?}", pos, [r, theta, phi]);
+        [r, theta, phi]
+    }
+
+    /// Decompose the cartesian velocity `vel` of a particle at `pos` into the radial
+    /// velocity and the rates of change of theta and phi, in the same way as the angular
+    /// velocity of a `CylindricalGrid3D`
+    pub fn velocity(&self, pos: Position, vel: [f64; 3]) -> [f64; 3] {
+        let (x, y, z) = (
+            pos[0] - self.center[0],
+            pos[1] - self.center[1],
+            pos[2] - self.center[2],
+        );
+        let rho2 = x * x + y * y;
+        let r = (rho2 + z * z).sqrt();
+        let vel_radial = (x * vel[0] + y * vel[1] + z * vel[2]) / r;
+        // d/dt acos(z / r) = (z * dr/dt - r * dz/dt) / (r * rho)
+        let vel_theta = (z * vel_radial - r * vel[2]) / (r * rho2.sqrt());
+        let vel_phi = (x * vel[1] - y * vel[0]) / rho2;
+        [vel_radial, vel_theta, vel_phi]
+    }
+
+    /// Cartesian position of the spherical position `pos` ([r, theta, phi]), the inverse of
+    /// `to_spherical`
+    pub fn to_cartesian(&self, pos: Position) -> Position {
+        let (r, theta, phi) = (pos[0], pos[1], pos[2]);
+        [
+            self.center[0] + r * theta.sin() * phi.cos(),
+            self.center[1] + r * theta.sin() * phi.sin(),
+            self.center[2] + r * theta.cos(),
+        ]
+    }
+
+    /// Cartesian velocity of a particle at the spherical position `pos` moving with the
+    /// radial velocity and the rates of change of theta and phi `vel`, the inverse of
+    /// `velocity`
+    pub fn cartesian_velocity(&self, pos: Position, vel: [f64; 3]) -> [f64; 3] {
+        let (r, theta, phi) = (pos[0], pos[1], pos[2]);
+        let (sin_t, cos_t, sin_p, cos_p) = (theta.sin(), theta.cos(), phi.sin(), phi.cos());
+        let unit_r = [sin_t * cos_p, sin_t * sin_p, cos_t];
+        let unit_theta = [cos_t * cos_p, cos_t * sin_p, -sin_t];
+        let unit_phi = [-sin_p, cos_p, 0.0];
+        let (vel_theta, vel_phi) = (r * vel[1], r * sin_t * vel[2]);
+        [0, 1, 2].map(|dim| {
+            vel[0] * unit_r[dim] + vel_theta * unit_theta[dim] + vel_phi * unit_phi[dim]
+        })
+    }
+
+    /// Radial boundaries of all shells, from the centre to the outer radius
+    pub fn radial_edges(&self) -> Vec<f64> {
+        self.rpositions
+            .iter()
+            .map(|r| r[0])
+            .chain(self.rpositions.iter().last().map(|r| r[1]))
+            .collect()
+    }
+
+    /// Parameters t in [0, 1] at which the path pos1 + t * (pos2 - pos1) crosses a cell
+    /// boundary, sorted and including both ends of the path. A few of them may lie on the
+    /// mirror image of a boundary, which only splits a piece of the path inside of a cell.
+    fn trajectory_crossings(&self, pos1: Position, pos2: Position) -> Vec<f64> {
+        let (x0, y0, z0) = (
+            pos1[0] - self.center[0],
+            pos1[1] - self.center[1],
+            pos1[2] - self.center[2],
+        );
+        let (dx, dy, dz) = (pos2[0] - pos1[0], pos2[1] - pos1[1], pos2[2] - pos1[2]);
+        let mut crossings = vec![0.0, 1.0];
+        // spheres r = edge: |p0 + t d|^2 = edge^2
+        let edges = self.rpositions.iter().map(|r| r[0]).chain([self.radius]);
+        for edge in edges {
+            crossings.extend(quadratic_roots(
+                dx * dx + dy * dy + dz * dz,
+                2.0 * (x0 * dx + y0 * dy + z0 * dz),
+                x0 * x0 + y0 * y0 + z0 * z0 - edge * edge,
+            ));
+        }
+        // cones theta = angle: (x^2 + y^2) cos^2 - z^2 sin^2 = 0, which includes the
+        // mirrored cone
+        let tcellsize = PI / self.cells[1] as f64;
+        for idx in 1..self.cells[1] {
+            let (sin, cos) = (idx as f64 * tcellsize).sin_cos();
+            let (sin2, cos2) = (sin * sin, cos * cos);
+            crossings.extend(quadratic_roots(
+                (dx * dx + dy * dy) * cos2 - dz * dz * sin2,
+                2.0 * ((x0 * dx + y0 * dy) * cos2 - z0 * dz * sin2),
+                (x0 * x0 + y0 * y0) * cos2 - z0 * z0 * sin2,
+            ));
+        }
+        // half planes phi = angle, including the branch cut of atan2
+        let pcellsize = 2.0 * PI / self.cells[2] as f64;
+        for idx in 1..=self.cells[2] {
+            let (sin, cos) = (-PI + idx as f64 * pcellsize).sin_cos();
+            let denominator = dx * sin - dy * cos;
+            if denominator == 0.0 {
+                continue;
+            }
+            let t = -(x0 * sin - y0 * cos) / denominator;
+            // only the half of the plane on the side of the angle
+            if (x0 + t * dx) * cos + (y0 + t * dy) * sin > 0.0 {
+                crossings.push(t);
+            }
+        }
+        crossings.retain(|t| (0.0..=1.0).contains(t));
+        crossings.sort_by(|a, b| a.total_cmp(b));
+        crossings.dedup();
+        crossings
+    }
+}
+
+/// Real roots of a * t^2 + b * t + c = 0, falling back to the linear equation if a is zero.
+/// Double roots, e.g. of the cone theta = pi / 2 which is the plane z = 0, are kept even if
+/// rounding makes the discriminant slightly negative.
+fn quadratic_roots(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return if b == 0.0 { vec![] } else { vec![-c / b] };
+    }
+    let mut discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        if discriminant < -1e-12 * b * b {
+            return vec![];
+        }
+        discriminant = 0.0;
+    }
+    let root = discriminant.sqrt();
+    vec![(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+}
+
+impl std::fmt::Debug for SphericalGrid3D {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::fmt::Display for SphericalGrid3D {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "SphericalGrid3D: \n\tCells: {:?} \n\trlim: {:?} \
+            \n\tthetalim: {:?} \n\tphilim: {:?}\n\tData information:\n\t\tMean: {:?}\
+            \n\t\tStd: {:?}\n\t\tMin: {:?}\n\t\tMax: {:?}",
+            self.cells,
+            self.limits[0],
+            self.limits[1],
+            self.limits[2],
+            nan_mean(&self.data),
+            nan_std(&self.data),
+            self.data.min_skipnan(),
+            self.data.max_skipnan()
+        )
+    }
+}
+
+impl std::ops::Index<CellId> for SphericalGrid3D {
+    type Output = f64;
+    fn index(&self, index: CellId) -> &Self::Output {
+        &self.data[index]
+    }
+}
+
+impl GridFunctions3D for SphericalGrid3D {
+    fn is_inside(&self, pos: Position) -> bool {
+        let pos = self.to_spherical(pos);
+        print_debug!("SphericalGrid3D: Checking if {:?} is in grid", pos);
+        pos[0] >= self.limits[0][0] && pos[0] <= self.limits[0][1]
+    }
+
+    fn cell_id(&self, pos: Position) -> Result<CellId> {
+        let pos = self.to_spherical(pos);
+        let cell_idr = self
+            .rpositions
+            .iter()
+            .position(|radius| pos[0] >= radius[0] && pos[0] <= radius[1])
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unable to find radial cell id \n pos r:{}\nlen {:?}",
+                    pos[0],
+                    self.rpositions
+                )
+            })?;
+        // theta = pi and phi = pi belong to the last cell
+        let angle_id = |angle: f64, dim: usize| {
+            let range = self.limits[dim][1] - self.limits[dim][0];
+            let idx = ((angle - self.limits[dim][0]) / range * self.cells[dim] as f64) as usize;
+            idx.min(self.cells[dim] - 1)
+        };
+        Ok([cell_idr, angle_id(pos[1], 1), angle_id(pos[2], 2)])
+    }
+
+    // Exact ray traversal: the straight path is split at every spherical, conical and
+    // azimuthal boundary it crosses and each piece is assigned to the cell containing its
+    // midpoint. Pieces outside of the grid are left out.
+    fn cell_ids_in_trajectory(
+        &self,
+        pos1: Position,
+        pos2: Position,
+    ) -> Result<(Vec<CellId>, Vec<f64>)> {
+        let crossings = self.trajectory_crossings(pos1, pos2);
+        let mut cell_ids: Vec<CellId> = Vec::new();
+        let mut fractions: Vec<f64> = Vec::new();
+        for pair in crossings.windows(2) {
+            let t = 0.5 * (pair[0] + pair[1]);
+            let midpoint = [
+                pos1[0] + t * (pos2[0] - pos1[0]),
+                pos1[1] + t * (pos2[1] - pos1[1]),
+                pos1[2] + t * (pos2[2] - pos1[2]),
+            ];
+            if !self.is_inside(midpoint) {
+                continue;
+            }
+            let cell_id = self.cell_id(midpoint)?;
+            let fraction = pair[1] - pair[0];
+            match cell_ids.last() {
+                Some(last) if *last == cell_id => *fractions.last_mut().unwrap() += fraction,
+                _ => {
+                    cell_ids.push(cell_id);
+                    fractions.push(fraction);
+                }
+            }
+        }
+        if cell_ids.is_empty() {
+            return Err(anyhow!(
+                "Trajectory from {:?} to {:?} does not cross the grid",
+                pos1,
+                pos2
+            ));
+        }
+        Ok((cell_ids, fractions))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn add_to_cell(&mut self, cell_id: CellId, value: f64) {
+        self.data[(cell_id[0], cell_id[1], cell_id[2])] += value;
+        self.weight[(cell_id[0], cell_id[1], cell_id[2])] += 1.;
+    }
+
+    fn get_value(&self, pos: Position) -> f64 {
+        match self.cell_id(pos) {
+            Ok(cell_id) => self.data[(cell_id[0], cell_id[1], cell_id[2])],
+            Err(_) => f64::NAN,
+        }
+    }
+
+    fn add_value(&mut self, pos: Position, value: f64) {
+        if let Ok(cell_id) = self.cell_id(pos) {
+            self.add_to_cell(cell_id, value);
+        }
+    }
+
+    // Between two points of a trajectory, add the value to all crossed cells weighted by the
+    // fraction of the path that lies in each cell
+    fn add_trajectory_value(&mut self, pos1: Position, pos2: Position, value: f64) {
+        let (cell_ids, fractions) = match self.cell_ids_in_trajectory(pos1, pos2) {
+            Ok((cell_ids, fractions)) => (cell_ids, fractions),
+            Err(_) => {
+                print_warning!("Error in cell_ids_in_trajectory calculation, cell id not found");
+                return;
+            }
+        };
+        for (cell_id, fraction) in cell_ids.iter().zip(fractions.iter()) {
+            self.data[(cell_id[0], cell_id[1], cell_id[2])] += value * fraction;
+            self.weight[(cell_id[0], cell_id[1], cell_id[2])] += fraction;
+        }
+    }
+
+    fn divide_by_array(&mut self, other: &Array3<f64>) {
+        self.data = &self.data / other;
+    }
+
+    fn divide_by_scalar(&mut self, other: f64) {
+        self.data = &self.data / other;
+    }
+
+    fn divide_by_weight(&mut self) {
+        self.data = &self.data / &self.weight;
+    }
+
+    fn insert(&mut self, pos: Position, value: f64) {
+        if let Ok(cell_id) = self.cell_id(pos) {
+            self.data[(cell_id[0], cell_id[1], cell_id[2])] = value;
+        }
+    }
+
+    fn new_zeros(&self) -> Box<dyn GridFunctions3D> {
+        let mut grid = self.clone();
+        grid.data = Array::zeros(self.cells);
+        Box::new(grid)
+    }
+
+    fn collapse(&self, axis: usize) -> Array2<f64> {
+        let axis = Axis(axis);
+        let mut result: Array2<f64> = Array::zeros(self.data.raw_dim().remove_axis(axis));
+        let mut result_weight: Array2<f64> = Array::zeros(self.data.raw_dim().remove_axis(axis));
+
+        for (data_arr, weight) in self.data.axis_iter(axis).zip(self.weight.axis_iter(axis)) {
+            // check for nans
+            let data_arr = data_arr.mapv(|x| if x.is_nan() { 0. } else { x });
+            let weight = weight.mapv(|x| if x.is_nan() { 0. } else { x });
+            result = result + &data_arr * &weight;
+            result_weight += &weight;
+        }
+        result /= &result_weight;
+        result
+    }
+
+    fn collapse_weight(&self, axis: usize) -> Array2<f64> {
+        let axis = Axis(axis);
+        let mut result_weight: Array2<f64> = Array::zeros(self.data.raw_dim().remove_axis(axis));
+
+        for weight in self.weight.axis_iter(axis) {
+            result_weight += &weight;
+        }
+        result_weight
+    }
+
+    fn collapse_two(&self, axis1: usize, axis2: usize) -> Array1<f64> {
+        if axis1 == axis2 {
+            panic!("axis1 and axis2 must be different");
+        }
+        let (axis1, axis2) = (axis1.min(axis2), axis1.max(axis2));
+        let axis2 = Axis(axis2 - 1); // we removed axis1 so axis2 is now one smaller
+        let first_collaps = self.collapse(axis1);
+        let first_collaps_weight = self.collapse_weight(axis1);
+        let mut result: Array1<f64> = Array::zeros(first_collaps.raw_dim().remove_axis(axis2));
+        let mut result_weight: Array1<f64> =
+            Array::zeros(first_collaps.raw_dim().remove_axis(axis2));
+
+        for (data_arr, weight) in first_collaps
+            .axis_iter(axis2)
+            .zip(first_collaps_weight.axis_iter(axis2))
+        {
+            // check for nans
+            let data_arr = data_arr.mapv(|x| if x.is_nan() { 0. } else { x });
+            result = result + &data_arr * &weight;
+            result_weight += &weight;
+        }
+        result /= &result_weight;
+        result
+    }
+
+    fn collapse_two_weight(&self, axis1: usize, axis2: usize) -> Array1<f64> {
+        if axis1 == axis2 {
+            panic!("axis1 and axis2 must be different");
+        }
+        let (axis1, axis2) = (axis1.min(axis2), axis1.max(axis2));
+        let axis2 = Axis(axis2 - 1);
+        let first_collaps_weight = self.collapse_weight(axis1);
+        let mut result_weight: Array1<f64> =
+            Array::zeros(first_collaps_weight.raw_dim().remove_axis(axis2));
+
+        for weight in first_collaps_weight.axis_iter(axis2) {
+            result_weight += &weight;
+        }
+        result_weight
+    }
+
+    fn slice(&self, axis: usize, position: f64) -> Array2<f64> {
+        let cell_id = if axis == 0 {
+            self.rpositions
+                .iter()
+                .position(|radius| position >= radius[0] && position <= radius[1])
+                .unwrap_or(self.cells[0] - 1)
+        } else {
+            ((position - self.limits[axis][0]) / (self.limits[axis][1] - self.limits[axis][0])
+                * self.cells[axis] as f64) as usize
+        };
+        self.slice_idx(axis, cell_id)
+    }
+
+    fn slice_idx(&self, axis: usize, cell_id: usize) -> Array2<f64> {
+        if axis > 2 {
+            panic!("Spherical Grid: Axis {:?} not supported in 3D array", axis);
+        }
+        self.data.index_axis(Axis(axis), cell_id).to_owned()
+    }
+
+    fn get_xpositions(&self) -> &Array1<f64> {
+        print_warning!("SphericalGrid3D: get_xpositions is returning midpoint of cell!");
+        &self.rmeanpositions
+    }
+    fn get_ypositions(&self) -> &Array1<f64> {
+        &self.tpositions
+    }
+    fn get_zpositions(&self) -> &Array1<f64> {
+        &self.ppositions
+    }
+    fn get_limits(&self) -> &ThreeD {
+        &self.limits
+    }
+    fn get_cells(&self) -> &CellId {
+        &self.cells
+    }
+    fn get_weights(&self) -> &Array3<f64> {
+        &self.weight
+    }
+    fn get_data(&self) -> &Array3<f64> {
+        &self.data
+    }
+
+    fn is_cylindrical(&self) -> bool {
+        false
+    }
+
+    fn is_spherical(&self) -> bool {
+        true
+    }
+
+    fn get_units(&self) -> &GridUnits {
+        &self.units
+    }
+
+    fn set_units(&mut self, units: GridUnits) {
+        self.units = units;
+    }
+
+    fn set_data(&mut self, data: Array3<f64>) {
+        if self.data.shape() == data.shape() {
+            self.data = data;
+        } else {
+            panic!("SphericalGrid3D: set_data: shape of data does not match shape of grid");
+        }
+    }
+
+    fn set_weights(&mut self, weights: Array3<f64>) {
+        if self.weight.shape() == weights.shape() {
+            self.weight = weights;
+        } else {
+            panic!("SphericalGrid3D: set_weights: shape of data does not match shape of grid");
+        }
+    }
+
+    // mode 0: set all values above threshold to zero, mode 1: set all values above threshold
+    // to threshold, mode 2: set all values above threshold to the mean of the surrounding
+    // cells
+    fn outlier_removal(&mut self, threshold: f64, mode: usize) {
+        match mode {
+            0 => self.data.mapv_inplace(|x| if x > threshold { 0. } else { x }),
+            1 => self.data.mapv_inplace(|x| x.min(threshold)),
+            2 => {
+                let data = self.data.clone();
+                let weight = self.weight.clone();
+                let shape = self.data.raw_dim();
+                for ((i, j, k), value) in data.indexed_iter() {
+                    if *value <= threshold {
+                        continue;
+                    }
+                    // the up to 26 surrounding cells
+                    let range = |idx: usize, len: usize| idx.saturating_sub(1)..(idx + 2).min(len);
+                    let (mut sum, mut weight_sum, mut counter) = (0.0, 0.0, 0.0);
+                    for ni in range(i, shape[0]) {
+                        for nj in range(j, shape[1]) {
+                            for nk in range(k, shape[2]) {
+                                if (ni, nj, nk) != (i, j, k) {
+                                    sum += data[(ni, nj, nk)];
+                                    weight_sum += weight[(ni, nj, nk)];
+                                    counter += 1.0;
+                                }
+                            }
+                        }
+                    }
+                    self.data[(i, j, k)] = sum / counter;
+                    self.weight[(i, j, k)] = weight_sum / counter;
+                }
+            }
+            _ => panic!("Spherical Grid: Mode {:?} not supported", mode),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> Dim {
+        Dim::ThreeD([[-1.0, 1.0], [-1.0, 1.0], [-1.0, 1.0]])
+    }
+
+    #[test]
+    fn cells() {
+        let grid = SphericalGrid3D::new([2, 2, 4], limits(), "radius");
+        assert_eq!(grid.limits()[0], [0.0, 1.0]);
+        assert_eq!(grid.cell_id([0.2, 0.0, 0.1]).unwrap(), [0, 0, 2]);
+        assert_eq!(grid.cell_id([0.0, -0.7, -0.1]).unwrap(), [1, 1, 1]);
+        assert!(!grid.is_inside([0.8, 0.0, 0.8]));
+        assert!(grid.cell_id([0.8, 0.0, 0.8]).is_err());
+        let flat = Dim::TwoD([[-1.0, 1.0], [-1.0, 1.0]]);
+        assert!(SphericalGrid3D::with_spacing([2, 2, 4], flat, RadialSpacing::Radius).is_err());
+        for cells in [[0, 2, 4], [2, 0, 4], [2, 2, 0]] {
+            assert!(SphericalGrid3D::with_spacing(cells, limits(), RadialSpacing::Radius).is_err());
+        }
+    }
+
+    #[test]
+    fn equal_volume() {
+        let grid = SphericalGrid3D::new([3, 1, 1], limits(), "volume");
+        let volumes: Vec<f64> = grid
+            .rpositions()
+            .iter()
+            .map(|r| r[1].powi(3) - r[0].powi(3))
+            .collect();
+        assert!(volumes.iter().all(|volume| (volume - 1.0 / 3.0).abs() < 1e-12));
+    }
+
+    #[test]
+    fn velocity() {
+        let grid = SphericalGrid3D::new([2, 2, 4], limits(), "volume");
+        // moving outwards on the x axis
+        let vel = grid.velocity([0.5, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        assert!((vel[0] - 1.0).abs() < 1e-12 && vel[1].abs() < 1e-12 && vel[2].abs() < 1e-12);
+        // moving downwards on the x axis increases theta, circling around z increases phi
+        let vel = grid.velocity([0.5, 0.0, 0.0], [0.0, 1.0, -1.0]);
+        assert!(vel[0].abs() < 1e-12);
+        assert!((vel[1] - 2.0).abs() < 1e-12);
+        assert!((vel[2] - 2.0).abs() < 1e-12);
+        let (pos, vel) = ([0.3, -0.2, 0.6], [0.5, 1.0, -2.0]);
+        let back = grid.to_cartesian(grid.to_spherical(pos));
+        assert!((0..3).all(|dim| (back[dim] - pos[dim]).abs() < 1e-12));
+        let back = grid.cartesian_velocity(grid.to_spherical(pos), grid.velocity(pos, vel));
+        assert!((0..3).all(|dim| (back[dim] - vel[dim]).abs() < 1e-12));
+    }
+
+    #[test]
+    fn trajectory() {
+        let grid = SphericalGrid3D::new([2, 2, 4], limits(), "radius");
+        // past the centre, parallel to the z axis
+        let (cell_ids, fractions) = grid
+            .cell_ids_in_trajectory([0.05, 0.1, 0.9], [0.05, 0.1, -0.9])
+            .unwrap();
+        assert_eq!(cell_ids.len(), 4);
+        assert_eq!(cell_ids[0][1], 0);
+        assert_eq!(cell_ids[3][1], 1);
+        assert!((fractions.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        // leaving the sphere
+        let (_, fractions) = grid
+            .cell_ids_in_trajectory([0.0, 0.0, 0.0], [2.0, 0.0, 0.0])
+            .unwrap();
+        assert!((fractions.iter().sum::<f64>() - 0.5).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,435 @@
+//! Saving grids to and loading them from files
+//!
+//! A `GridState` holds everything needed to rebuild a grid: its kind, the geometry it was
+//! created with, the values and weights of all components, the units and the parameters of
+//! the analysis that filled it. HDF5 grid files store the values and weights in the datasets
+//! "data" and "weights" with the shape (components, cells...) and everything else in root
+//! attributes. Vector grids have three components, all other grids one.
+
+use super::{
+    CartesianGrid3D, CylindricalGrid3D, Dim, Grid1D, Grid2D, GridFunctions3D, GridUnits,
+    RadialSpacing, SphericalGrid3D, VectorGrid,
+};
+use crate::datamanager::provenance::{unicode, write_string};
+use crate::error::{Result, Up4Error};
+use crate::types::ThreeD;
+use crate::units::LengthUnit;
+use hdf5::types::VarLenUnicode;
+use ndarray::{stack, Array4, Axis, Ix4};
+use std::collections::BTreeMap;
+
+const KIND: &str = "grid kind";
+const LENGTH_UNIT: &str = "length unit";
+const VALUE_UNIT: &str = "value unit";
+const PARAMETERS: &str = "parameters";
+const VERSION: &str = "up4 version";
+
+/// Kind of a stored grid, the names are used in the "grid kind" attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridKind {
+    Cartesian,
+    Cylindrical,
+    Spherical,
+    Cartesian1D,
+    Cartesian2D,
+}
+
+impl GridKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GridKind::Cartesian => "cartesian",
+            GridKind::Cylindrical => "cylindrical",
+            GridKind::Spherical => "spherical",
+            GridKind::Cartesian1D => "cartesian1d",
+            GridKind::Cartesian2D => "cartesian2d",
+        }
+    }
+}
+
+impl std::str::FromStr for GridKind {
+    type Err = Up4Error;
+
+    fn from_str(kind: &str) -> Result<Self> {
+        [
+            GridKind::Cartesian,
+            GridKind::Cylindrical,
+            GridKind::Spherical,
+            GridKind::Cartesian1D,
+            GridKind::Cartesian2D,
+        ]
+        .into_iter()
+        .find(|grid_kind| grid_kind.name() == kind)
+        .ok_or_else(|| Up4Error::InvalidArgument(format!("Unknown grid kind {}", kind)))
+    }
+}
+
+/// Everything needed to rebuild a grid
+#[derive(Debug, Clone)]
+pub struct GridState {
+    pub kind: GridKind,
+    /// the geometry the grid was created with, always "cells" and "limits" and depending on
+    /// the kind "center", "axis", "radial edges", "resolved axis" or "normal"
+    pub geometry: BTreeMap<String, Vec<f64>>,
+    /// values with the shape (components, cells...)
+    pub data: Array4<f64>,
+    pub weights: Array4<f64>,
+    pub units: GridUnits,
+    /// parameters of the analysis that filled the grid, e.g. "function" = "vectorfield"
+    pub parameters: BTreeMap<String, String>,
+}
+
+impl GridState {
+    /// Record the state of `grid` together with the analysis `parameters`
+    pub fn new(grid: &dyn GridFunctions3D, parameters: BTreeMap<String, String>) -> Result<Self> {
+        let components: Vec<&dyn GridFunctions3D> =
+            match grid.as_any().downcast_ref::<VectorGrid>() {
+                Some(vector_grid) => vector_grid.data.iter().map(|c| c.as_ref()).collect(),
+                None => vec![grid],
+            };
+        let base = components[0].as_any();
+        let mut geometry = BTreeMap::new();
+        geometry.insert(
+            "cells".to_string(),
+            grid.get_cells().iter().map(|&cells| cells as f64).collect(),
+        );
+        geometry.insert(
+            "limits".to_string(),
+            grid.get_limits().iter().flatten().copied().collect(),
+        );
+        let kind = if base.is::<CartesianGrid3D>() {
+            GridKind::Cartesian
+        } else if let Some(cylinder) = base.downcast_ref::<CylindricalGrid3D>() {
+            geometry.insert("center".to_string(), cylinder.center().to_vec());
+            geometry.insert("axis".to_string(), cylinder.axis().to_vec());
+            geometry.insert("radial edges".to_string(), cylinder.radial_edges());
+            GridKind::Cylindrical
+        } else if let Some(sphere) = base.downcast_ref::<SphericalGrid3D>() {
+            geometry.insert("center".to_string(), sphere.center().to_vec());
+            geometry.insert("radial edges".to_string(), sphere.radial_edges());
+            GridKind::Spherical
+        } else if let Some(grid1d) = base.downcast_ref::<Grid1D>() {
+            geometry.insert("resolved axis".to_string(), vec![grid1d.axis() as f64]);
+            GridKind::Cartesian1D
+        } else if let Some(grid2d) = base.downcast_ref::<Grid2D>() {
+            geometry.insert("normal".to_string(), vec![grid2d.normal() as f64]);
+            GridKind::Cartesian2D
+        } else {
+            return Err(Up4Error::InvalidArgument(format!(
+                "Grids of this kind can not be saved: {}",
+                grid
+            )));
+        };
+        let data: Vec<_> = components.iter().map(|c| c.get_data().view()).collect();
+        let weights: Vec<_> = components.iter().map(|c| c.get_weights().view()).collect();
+        Ok(GridState {
+            kind,
+            geometry,
+            data: stack(Axis(0), &data).expect("components of a grid have the same shape"),
+            weights: stack(Axis(0), &weights).expect("components of a grid have the same shape"),
+            units: grid.get_units().clone(),
+            parameters,
+        })
+    }
+
+    /// Rebuild the grid, a `VectorGrid` if the state has three components
+    pub fn to_grid(&self) -> Result<Box<dyn GridFunctions3D>> {
+        let cells = self.geometry_values("cells", 3)?;
+        let cells = [cells[0] as usize, cells[1] as usize, cells[2] as usize];
+        let limits = self.geometry_values("limits", 6)?;
+        let limits: ThreeD = [
+            [limits[0], limits[1]],
+            [limits[2], limits[3]],
+            [limits[4], limits[5]],
+        ];
+        let invalid = |e: anyhow::Error| Up4Error::InvalidArgument(e.to_string());
+        let mut base: Box<dyn GridFunctions3D> = match self.kind {
+            GridKind::Cartesian => Box::new(CartesianGrid3D::new(cells, Dim::ThreeD(limits))),
+            GridKind::Cylindrical => {
+                let center = self.geometry_values("center", 3)?;
+                let axis = self.geometry_values("axis", 3)?;
+                let edges = self.geometry_values("radial edges", cells[0] + 1)?;
+                Box::new(
+                    CylindricalGrid3D::oriented(
+                        cells,
+                        [center[0], center[1], center[2]],
+                        [axis[0], axis[1], axis[2]],
+                        edges[cells[0]],
+                        limits[2],
+                        RadialSpacing::Edges(edges.to_vec()),
+                        0.0,
+                    )
+                    .map_err(invalid)?,
+                )
+            }
+            GridKind::Spherical => {
+                let center = self.geometry_values("center", 3)?;
+                let edges = self.geometry_values("radial edges", cells[0] + 1)?;
+                Box::new(
+                    SphericalGrid3D::around(
+                        cells,
+                        [center[0], center[1], center[2]],
+                        edges[cells[0]],
+                        RadialSpacing::Edges(edges.to_vec()),
+                    )
+                    .map_err(invalid)?,
+                )
+            }
+            GridKind::Cartesian1D => {
+                let axis = self.axis_index("resolved axis")?;
+                Box::new(Grid1D::new(axis, cells[axis], Dim::ThreeD(limits)).map_err(invalid)?)
+            }
+            GridKind::Cartesian2D => {
+                let normal = self.axis_index("normal")?;
+                let plane: Vec<usize> = (0..3).filter(|&dim| dim != normal).collect();
+                let plane_cells = [cells[plane[0]], cells[plane[1]]];
+                Box::new(Grid2D::new(normal, plane_cells, Dim::ThreeD(limits)).map_err(invalid)?)
+            }
+        };
+        let shape = [self.data.shape()[0], cells[0], cells[1], cells[2]];
+        if !matches!(shape[0], 1 | 3)
+            || self.data.shape() != shape
+            || self.weights.shape() != shape
+        {
+            return Err(Up4Error::InvalidArgument(format!(
+                "The values and weights of a grid with {:?} cells must have the shape (1, ...) \
+                or (3, ...), got {:?} and {:?}",
+                cells,
+                self.data.shape(),
+                self.weights.shape()
+            )));
+        }
+        if shape[0] == 1 {
+            base.set_data(self.data.index_axis(Axis(0), 0).to_owned());
+            base.set_weights(self.weights.index_axis(Axis(0), 0).to_owned());
+            base.set_units(self.units.clone());
+            return Ok(base);
+        }
+        let mut vector_grid = VectorGrid::new(base);
+        for (idx, component) in vector_grid.data.iter_mut().enumerate() {
+            component.set_data(self.data.index_axis(Axis(0), idx).to_owned());
+            component.set_weights(self.weights.index_axis(Axis(0), idx).to_owned());
+        }
+        vector_grid.set_units(self.units.clone());
+        Ok(Box::new(vector_grid))
+    }
+
+    /// Write the state into the new HDF5 file `filename`, an existing file is overwritten
+    pub fn write_hdf5(&self, filename: &str) -> Result<()> {
+        let file = hdf5::File::create(filename).map_err(|e| Up4Error::FileOpen {
+            filename: filename.to_string(),
+            source: e,
+        })?;
+        write_string(&file, KIND, self.kind.name())?;
+        for (name, values) in &self.geometry {
+            file.new_attr_builder()
+                .with_data(values.as_slice())
+                .create(name.as_str())?;
+        }
+        file.new_dataset_builder()
+            .with_data(&self.data)
+            .create("data")?;
+        file.new_dataset_builder()
+            .with_data(&self.weights)
+            .create("weights")?;
+        if let Some(length) = self.units.length {
+            write_string(&file, LENGTH_UNIT, length.symbol())?;
+        }
+        if let Some(value) = &self.units.value {
+            write_string(&file, VALUE_UNIT, value)?;
+        }
+        if !self.parameters.is_empty() {
+            let parameters = self
+                .parameters
+                .iter()
+                .map(|(name, value)| unicode(&format!("{}={}", name, value)))
+                .collect::<Result<Vec<_>>>()?;
+            file.new_attr_builder()
+                .with_data(&parameters)
+                .create(PARAMETERS)?;
+        }
+        write_string(&file, VERSION, env!("CARGO_PKG_VERSION"))?;
+        Ok(())
+    }
+
+    /// Read a state written by `write_hdf5`
+    pub fn read_hdf5(filename: &str) -> Result<Self> {
+        let file = hdf5::File::open(filename).map_err(|e| Up4Error::FileOpen {
+            filename: filename.to_string(),
+            source: e,
+        })?;
+        let kind = match file.attr(KIND) {
+            Ok(attr) => attr
+                .read_scalar::<VarLenUnicode>()
+                .map_err(|e| Up4Error::read(KIND, filename, e))?
+                .to_string()
+                .parse()?,
+            Err(_) => {
+                return Err(Up4Error::WrongFileKind {
+                    filename: filename.to_string(),
+                    expected: "up4 grid".to_string(),
+                    found: format!("file without a \"{}\" attribute", KIND),
+                })
+            }
+        };
+        let read_string = |name: &str| -> Result<Option<String>> {
+            match file.attr(name) {
+                Ok(attr) => Ok(Some(
+                    attr.read_scalar::<VarLenUnicode>()
+                        .map_err(|e| Up4Error::read(name, filename, e))?
+                        .to_string(),
+                )),
+                Err(_) => Ok(None),
+            }
+        };
+        let mut geometry = BTreeMap::new();
+        for name in file.attr_names()? {
+            if [KIND, LENGTH_UNIT, VALUE_UNIT, PARAMETERS, VERSION].contains(&name.as_str()) {
+                continue;
+            }
+            let values = file
+                .attr(&name)?
+                .read_raw::<f64>()
+                .map_err(|e| Up4Error::read(&name, filename, e))?;
+            geometry.insert(name, values);
+        }
+        let read_array = |name: &str| -> Result<Array4<f64>> {
+            file.dataset(name)
+                .map_err(|_| Up4Error::MissingDataset {
+                    dataset: name.to_string(),
+                    filename: filename.to_string(),
+                })?
+                .read::<f64, Ix4>()
+                .map_err(|e| Up4Error::read(name, filename, e))
+        };
+        let parameters = match file.attr(PARAMETERS) {
+            Ok(attr) => attr
+                .read_raw::<VarLenUnicode>()
+                .map_err(|e| Up4Error::read(PARAMETERS, filename, e))?
+                .iter()
+                .map(|parameter| match parameter.as_str().split_once('=') {
+                    Some((name, value)) => (name.to_string(), value.to_string()),
+                    None => (parameter.to_string(), String::new()),
+                })
+                .collect(),
+            Err(_) => BTreeMap::new(),
+        };
+        let length = read_string(LENGTH_UNIT)?
+            .map(|unit| unit.parse::<LengthUnit>())
+            .transpose()?;
+        Ok(GridState {
+            kind,
+            geometry,
+            data: read_array("data")?,
+            weights: read_array("weights")?,
+            units: GridUnits::new(length, read_string(VALUE_UNIT)?),
+            parameters,
+        })
+    }
+
+    /// Values of the geometry entry `name`, which must have `len` values
+    fn geometry_values(&self, name: &str, len: usize) -> Result<&[f64]> {
+        match self.geometry.get(name) {
+            Some(values) if values.len() == len => Ok(values),
+            Some(values) => Err(Up4Error::InvalidArgument(format!(
+                "The grid geometry \"{}\" must have {} values, got {}",
+                name,
+                len,
+                values.len()
+            ))),
+            None => Err(Up4Error::InvalidArgument(format!(
+                "The {} grid is missing its geometry \"{}\"",
+                self.kind.name(),
+                name
+            ))),
+        }
+    }
+
+    fn axis_index(&self, name: &str) -> Result<usize> {
+        let axis = self.geometry_values(name, 1)?[0];
+        match axis as usize {
+            axis @ 0..=2 => Ok(axis),
+            _ => Err(Up4Error::InvalidArgument(format!(
+                "The grid geometry \"{}\" must be 0, 1 or 2, got {}",
+                name, axis
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Array3;
+
+    fn filled<G: GridFunctions3D>(mut grid: G) -> G {
+        let cells = *grid.get_cells();
+        grid.set_data(Array3::from_shape_fn(cells, |(i, j, k)| (i + 10 * j + 100 * k) as f64));
+        grid.set_weights(Array3::from_elem(cells, 2.0));
+        grid
+    }
+
+    fn roundtrip(grid: &dyn GridFunctions3D, name: &str) -> Box<dyn GridFunctions3D> {
+        let filename = std::env::temp_dir().join(format!("up4_grid_{}.hdf5", name));
+        let filename = filename.to_str().unwrap();
+        let mut parameters = BTreeMap::new();
+        parameters.insert("function".to_string(), "test".to_string());
+        GridState::new(grid, parameters.clone())
+            .unwrap()
+            .write_hdf5(filename)
+            .unwrap();
+        let state = GridState::read_hdf5(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+        assert_eq!(state.parameters, parameters);
+        let loaded = state.to_grid().unwrap();
+        assert_eq!(loaded.get_cells(), grid.get_cells());
+        assert_eq!(loaded.get_limits(), grid.get_limits());
+        assert_eq!(loaded.get_data(), grid.get_data());
+        assert_eq!(loaded.get_weights(), grid.get_weights());
+        assert_eq!(loaded.get_units(), grid.get_units());
+        loaded
+    }
+
+    #[test]
+    fn cartesian() {
+        let mut grid = filled(CartesianGrid3D::new(
+            [2, 3, 4],
+            Dim::ThreeD([[0.0, 1.0], [0.0, 2.0], [-1.0, 1.0]]),
+        ));
+        grid.set_units(GridUnits::velocity(Some(LengthUnit::Millimetre)));
+        let loaded = roundtrip(&grid, "cartesian");
+        assert_eq!(loaded.get_xpositions(), grid.get_xpositions());
+    }
+
+    #[test]
+    fn cylindrical() {
+        let grid = CylindricalGrid3D::along_axis(
+            [3, 4, 2],
+            Dim::ThreeD([[0.0, 1.0], [-1.0, 1.0], [-1.0, 1.0]]),
+            0,
+            RadialSpacing::Volume,
+            0.1,
+        )
+        .unwrap();
+        let grid = filled(grid);
+        let loaded = roundtrip(&grid, "cylindrical");
+        let loaded = loaded
+            .as_any()
+            .downcast_ref::<CylindricalGrid3D>()
+            .unwrap();
+        assert_eq!(loaded.rpositions(), grid.rpositions());
+        assert_eq!(loaded.axis(), grid.axis());
+        assert_eq!(loaded.center(), grid.center());
+    }
+
+    #[test]
+    fn vector() {
+        let base = CartesianGrid3D::new([2, 2, 2], Dim::ThreeD([[0.0, 1.0]; 3]));
+        let mut grid = VectorGrid::new(Box::new(base));
+        for (idx, component) in grid.data.iter_mut().enumerate() {
+            component.set_data(Array3::from_elem([2, 2, 2], idx as f64));
+        }
+        let loaded = roundtrip(&grid, "vector");
+        let loaded = loaded.as_any().downcast_ref::<VectorGrid>().unwrap();
+        assert_eq!(loaded.data[2].get_data(), &Array3::from_elem([2, 2, 2], 2.0));
+    }
+}
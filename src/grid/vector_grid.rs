@@ -1,6 +1,7 @@
 extern crate ndarray;
-use super::{CellId, GridFunctions3D, Position};
-use crate::{grid, CylindricalGrid3D};
+use super::arithmetic::{self, GridOperation};
+use super::{CellId, GridFunctions3D, GridUnits, Position};
+use crate::{grid, CylindricalGrid3D, SphericalGrid3D};
 use derive_getters::Getters;
 use ndarray::prelude::*;
 use std::any::Any;
@@ -10,6 +11,7 @@ use anyhow::Result;
 pub struct VectorGrid {
     pub data: [Box<dyn grid::GridFunctions3D>; 3],
     cyl_grid: CylindricalGrid3D,
+    sph_grid: SphericalGrid3D,
 }
 
 impl VectorGrid {
@@ -22,28 +24,63 @@ impl VectorGrid {
         } else {
             CylindricalGrid3D::default()
         };
+        let sph_grid = grid
+            .as_any()
+            .downcast_ref::<SphericalGrid3D>()
+            .cloned()
+            .unwrap_or_default();
         VectorGrid {
             data: [grid.clone(), grid.clone(), grid.clone()],
             cyl_grid,
+            sph_grid,
         }
     }
 
     pub fn velocity_calculation(&self, pos: Position, vel: Array1<f64>) -> [f64; 3] {
         let mut vel = [vel[0], vel[1], vel[2]];
+        if self.data[0].is_spherical() {
+            // radial velocity and the rates of change of theta and phi
+            return self.sph_grid.velocity(pos, vel);
+        }
         if !self.data[0].is_cylindrical() {
             // it is cartesian, so just return the velocity
             return [vel[0], vel[1], vel[2]];
         } else {
-            // downcast a data grid to a cylindrical grid
-            let cyl_pos = self.cyl_grid.to_cylindrical(pos);
-            let vel_radial = (pos[0] * vel[0] + pos[1] * vel[1]) / cyl_pos[0];
-            let vel_omega =
-                (pos[0] * vel[1] - pos[1] * vel[0]) / (pos[0] * pos[0] + pos[1] * pos[1]);
-            let vel_z = vel[2];
-            vel = [vel_radial, vel_omega, vel_z];
+            // radial, angular and axial velocity around the axis of the cylinder
+            vel = self.cyl_grid.velocity(pos, vel);
         }
         vel
     }
+
+    /// Apply `operation` to each component of this and another vector grid cell by cell
+    pub fn combine(&self, other: &VectorGrid, operation: GridOperation) -> Result<VectorGrid> {
+        let mut result = self.clone();
+        for (component, other) in result.data.iter_mut().zip(other.data.iter()) {
+            *component = arithmetic::combine(component.as_ref(), other.as_ref(), operation)?;
+        }
+        Ok(result)
+    }
+
+    /// Merge each component with the weighted mean of this and another vector grid
+    pub fn merge(&self, other: &VectorGrid) -> Result<VectorGrid> {
+        let mut result = self.clone();
+        for (component, other) in result.data.iter_mut().zip(other.data.iter()) {
+            *component = arithmetic::merge(component.as_ref(), other.as_ref())?;
+        }
+        Ok(result)
+    }
+
+    /// Apply `function` to the value of every cell of each component
+    pub fn map_values<F>(&self, function: F) -> VectorGrid
+    where
+        F: Fn(f64) -> f64,
+    {
+        let mut result = self.clone();
+        for component in result.data.iter_mut() {
+            *component = arithmetic::map_values(component.as_ref(), &function);
+        }
+        result
+    }
 }
 
 impl std::fmt::Debug for VectorGrid {
@@ -227,6 +264,17 @@ impl GridFunctions3D for VectorGrid {
     fn is_cylindrical(&self) -> bool {
         self.data[0].is_cylindrical()
     }
+    fn is_spherical(&self) -> bool {
+        self.data[0].is_spherical()
+    }
+    fn get_units(&self) -> &GridUnits {
+        self.data[0].get_units()
+    }
+    fn set_units(&mut self, units: GridUnits) {
+        self.data[0].set_units(units.clone());
+        self.data[1].set_units(units.clone());
+        self.data[2].set_units(units);
+    }
 
     #[allow(unused_variables)]
     fn set_data(&mut self, data: Array3<f64>) {
@@ -0,0 +1,281 @@
+//! Export of grids to VTK files, e.g. to post-process them in ParaView
+//!
+//! Cartesian grids (also `Grid1D` and `Grid2D`) are written as rectilinear grids, cylindrical
+//! and spherical grids as structured grids whose points are the corners of their curved cells.
+//! The values and weights are cell arrays named "data" and "weights". The three components
+//! of a `VectorGrid` are written as one vector array with cartesian components, so glyphs
+//! point along the velocity in cylindrical and spherical grids as well.
+
+use super::{CylindricalGrid3D, GridFunctions3D, SphericalGrid3D, VectorGrid};
+use crate::types::Position;
+use anyhow::{anyhow, Result};
+use ndarray::Array3;
+use std::any::Any;
+use std::path::Path;
+use vtkio::model::*;
+
+/// Write `grid` to `filename`. Files ending in ".vtk" are written in the legacy format, all
+/// others in the XML format matching the grid: ".vtr" for cartesian grids and ".vts" for
+/// cylindrical and spherical grids.
+pub fn write_vtk(grid: &dyn GridFunctions3D, filename: &str) -> Result<()> {
+    let vector_grid = grid.as_any().downcast_ref::<VectorGrid>();
+    let base = match vector_grid {
+        Some(vector_grid) => vector_grid.data[0].as_any(),
+        None => grid.as_any(),
+    };
+    let cells = *grid.get_cells();
+    let extent = Extent::Ranges([
+        0..=cells[0] as i32,
+        0..=cells[1] as i32,
+        0..=cells[2] as i32,
+    ]);
+    let cell = match vector_grid {
+        Some(vector_grid) => vec![
+            cell_array("data", ElementType::Vectors, cartesian_vectors(vector_grid)),
+            cell_array(
+                "weights",
+                ElementType::Generic(3),
+                interleaved(vector_grid.data.iter().map(|c| c.get_weights())),
+            ),
+        ],
+        None => vec![
+            cell_array("data", scalars(), cell_values(grid.get_data())),
+            cell_array("weights", scalars(), cell_values(grid.get_weights())),
+        ],
+    };
+    let attributes = Attributes {
+        point: Vec::new(),
+        cell,
+    };
+    let curvilinear = |points: Vec<f64>| DataSet::StructuredGrid {
+        extent: extent.clone(),
+        meta: None,
+        pieces: vec![Piece::Inline(Box::new(StructuredGridPiece {
+            extent: extent.clone(),
+            points: points.into(),
+            data: attributes.clone(),
+        }))],
+    };
+    let limits = grid.get_limits();
+    let (data, extension) = if let Some(cylinder) = base.downcast_ref::<CylindricalGrid3D>() {
+        let points = corner_points(curved_edges(base), |pos| cylinder.to_cartesian(pos));
+        (curvilinear(points), "vts")
+    } else if let Some(sphere) = base.downcast_ref::<SphericalGrid3D>() {
+        let points = corner_points(curved_edges(base), |pos| sphere.to_cartesian(pos));
+        (curvilinear(points), "vts")
+    } else {
+        let coords = Coordinates {
+            x: edges(limits[0], cells[0]).into(),
+            y: edges(limits[1], cells[1]).into(),
+            z: edges(limits[2], cells[2]).into(),
+        };
+        let data = DataSet::RectilinearGrid {
+            extent: extent.clone(),
+            meta: None,
+            pieces: vec![Piece::Inline(Box::new(RectilinearGridPiece {
+                extent: extent.clone(),
+                coords,
+                data: attributes.clone(),
+            }))],
+        };
+        (data, "vtr")
+    };
+    let file_extension = Path::new(filename)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    if file_extension != "vtk" && file_extension != extension {
+        return Err(anyhow!(
+            "This grid can only be written to a \".vtk\" or \".{}\" file, got {}",
+            extension,
+            filename
+        ));
+    }
+    let vtk = Vtk {
+        version: Version::new((1, 0)),
+        title: format!("up4 {}", grid.get_units().value_label("grid")),
+        byte_order: ByteOrder::BigEndian,
+        file_path: None,
+        data,
+    };
+    vtk.export(filename)
+        .map_err(|e| anyhow!("Can not write VTK file {}: {:?}", filename, e))
+}
+
+fn scalars() -> ElementType {
+    ElementType::Scalars {
+        num_comp: 1,
+        lookup_table: None,
+    }
+}
+
+fn cell_array(name: &str, elem: ElementType, values: Vec<f64>) -> Attribute {
+    Attribute::DataArray(DataArray {
+        name: name.to_string(),
+        elem,
+        data: values.into(),
+    })
+}
+
+/// Values of all cells in the order of VTK, where the first axis changes fastest
+fn cell_values(array: &Array3<f64>) -> Vec<f64> {
+    array.t().iter().copied().collect()
+}
+
+/// Values of several arrays per cell in the order of VTK
+fn interleaved<'a>(arrays: impl Iterator<Item = &'a Array3<f64>>) -> Vec<f64> {
+    let arrays: Vec<Vec<f64>> = arrays.map(cell_values).collect();
+    (0..arrays[0].len())
+        .flat_map(|idx| arrays.iter().map(move |values| values[idx]))
+        .collect()
+}
+
+/// Cartesian components of the vectors of all cells in the order of VTK. The components of
+/// cylindrical and spherical grids are converted at the centre of each cell.
+fn cartesian_vectors(vector_grid: &VectorGrid) -> Vec<f64> {
+    let base = vector_grid.data[0].as_any();
+    let cells = *vector_grid.get_cells();
+    let edges = curved_edges(base);
+    let center = |idx: [usize; 3]| {
+        [0, 1, 2].map(|dim| 0.5 * (edges[dim][idx[dim]] + edges[dim][idx[dim] + 1]))
+    };
+    let mut vectors = Vec::with_capacity(3 * cells.iter().product::<usize>());
+    for k in 0..cells[2] {
+        for j in 0..cells[1] {
+            for i in 0..cells[0] {
+                let vector = [0, 1, 2].map(|dim| vector_grid.data[dim].get_data()[[i, j, k]]);
+                let vector = if let Some(cylinder) = base.downcast_ref::<CylindricalGrid3D>() {
+                    cylinder.cartesian_velocity(center([i, j, k]), vector)
+                } else if let Some(sphere) = base.downcast_ref::<SphericalGrid3D>() {
+                    sphere.cartesian_velocity(center([i, j, k]), vector)
+                } else {
+                    vector
+                };
+                vectors.extend(vector);
+            }
+        }
+    }
+    vectors
+}
+
+/// `cells` + 1 equally spaced boundaries between the two `limits`
+fn edges(limits: [f64; 2], cells: usize) -> Vec<f64> {
+    (0..=cells)
+        .map(|idx| limits[0] + idx as f64 * (limits[1] - limits[0]) / cells as f64)
+        .collect()
+}
+
+/// Cell boundaries of a cylindrical or spherical grid along its three axes, the same
+/// boundaries its `cell_id` assigns positions by. All other grids have no boundaries.
+fn curved_edges(base: &dyn Any) -> [Vec<f64>; 3] {
+    if let Some(cylinder) = base.downcast_ref::<CylindricalGrid3D>() {
+        [
+            cylinder.radial_edges(),
+            cylinder.angular_edges(),
+            cylinder.height_edges(),
+        ]
+    } else if let Some(sphere) = base.downcast_ref::<SphericalGrid3D>() {
+        let (cells, limits) = (sphere.get_cells(), sphere.get_limits());
+        [
+            sphere.radial_edges(),
+            edges(limits[1], cells[1]),
+            edges(limits[2], cells[2]),
+        ]
+    } else {
+        Default::default()
+    }
+}
+
+/// Cartesian positions of the corners of all cells of a cylindrical or spherical grid with
+/// the boundaries `edges` in the order of VTK
+fn corner_points<F>(edges: [Vec<f64>; 3], to_cartesian: F) -> Vec<f64>
+where
+    F: Fn(Position) -> Position,
+{
+    let [first, second, third] = edges;
+    let mut points = Vec::with_capacity(3 * first.len() * second.len() * third.len());
+    for &z in &third {
+        for &y in &second {
+            for &x in &first {
+                points.extend(to_cartesian([x, y, z]));
+            }
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{CartesianGrid3D, Dim};
+
+    fn exported(grid: &dyn GridFunctions3D, name: &str) -> Vtk {
+        let filename = std::env::temp_dir().join(format!("up4_grid_{}", name));
+        let filename = filename.to_str().unwrap();
+        write_vtk(grid, filename).unwrap();
+        let vtk = Vtk::import(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+        vtk
+    }
+
+    fn cell_arrays(data: DataSet) -> Vec<(String, usize)> {
+        let attributes = match data {
+            DataSet::RectilinearGrid { pieces, .. } => match &pieces[0] {
+                Piece::Inline(piece) => piece.data.cell.clone(),
+                _ => panic!("piece is not inline"),
+            },
+            DataSet::StructuredGrid { pieces, .. } => match &pieces[0] {
+                Piece::Inline(piece) => piece.data.cell.clone(),
+                _ => panic!("piece is not inline"),
+            },
+            _ => panic!("unexpected data set"),
+        };
+        attributes
+            .into_iter()
+            .map(|attribute| match attribute {
+                Attribute::DataArray(array) => (array.name, array.data.len()),
+                _ => panic!("unexpected attribute"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cartesian() {
+        let grid = CartesianGrid3D::new([2, 3, 4], Dim::ThreeD([[0.0, 1.0]; 3]));
+        let vtk = exported(&grid, "cartesian.vtr");
+        assert!(matches!(vtk.data, DataSet::RectilinearGrid { .. }));
+        let arrays = cell_arrays(vtk.data);
+        assert_eq!(
+            arrays,
+            vec![("data".to_string(), 24), ("weights".to_string(), 24)]
+        );
+        assert!(write_vtk(&grid, "grid.vts").is_err());
+    }
+
+    #[test]
+    fn cylindrical_vectors() {
+        let base = CylindricalGrid3D::new([2, 4, 3], Dim::ThreeD([[-1.0, 1.0]; 3]), "volume");
+        let mut grid = VectorGrid::new(Box::new(base.clone()));
+        // rotation around the axis with the angular velocity 1
+        grid.data[1].set_data(Array3::from_elem([2, 4, 3], 1.0));
+        let vectors = cartesian_vectors(&grid);
+        // cell_id assigns theta up to -pi / 4 to the first cell, so its centre is at
+        // theta = -5/8 pi and the velocity there is tangential
+        let (r, theta) = (base.rmeanpositions()[0], -0.625 * std::f64::consts::PI);
+        assert_eq!(base.angular_edges()[1], -0.25 * std::f64::consts::PI);
+        assert!((vectors[0] + r * theta.sin()).abs() < 1e-12);
+        assert!((vectors[1] - r * theta.cos()).abs() < 1e-12);
+        assert!(vectors[2].abs() < 1e-12);
+        // the centre of the lowest cell is halfway between -1 and the boundary at 0
+        let position = base.to_cartesian([r, theta, -0.5]);
+        assert_eq!(base.cell_id(position).unwrap(), [0, 0, 0]);
+        assert_eq!(base.height_edges(), vec![-1.0, 0.0, 2.0 / 3.0, 1.0]);
+        let vtk = exported(&grid, "cylinder.vts");
+        assert!(matches!(vtk.data, DataSet::StructuredGrid { .. }));
+        let arrays = cell_arrays(vtk.data);
+        assert_eq!(
+            arrays,
+            vec![("data".to_string(), 72), ("weights".to_string(), 72)]
+        );
+    }
+}
@@ -30,6 +30,7 @@ pub use converter::*;
 
 pub mod comparison;
 pub mod datamanager;
+pub mod error;
 mod functions;
 
 // This module is essentially python written in Rust
@@ -39,6 +40,7 @@ mod functions;
 #[allow(clippy::too_many_arguments, clippy::needless_lifetimes)]
 pub mod pylib;
 pub mod types;
+pub mod units;
 pub mod utilities;
 
 pub mod plotting;
@@ -1,5 +1,6 @@
 #![allow(dead_code, unused_variables, unused_imports)]
 mod datamanager;
+pub mod error;
 //use std::time::{Duration, Instant};
 /// Module that implements nD grids and basic functionality on them.
 pub mod grid;
@@ -22,6 +23,7 @@ use functions::Granular;
 use upppp_rust::plotting::plot;
 use upppp_rust::quiver::QuiverPlot;
 pub mod types;
+pub mod units;
 
 //pub mod comparison;
 
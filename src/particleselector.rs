@@ -6,9 +6,18 @@ use crate::print_debug;
 use std::any::Any;
 
 pub trait Selector {
-    fn is_valid(&self, radius: f64, clouds: f64, density: f64, particleid: usize) -> bool;
+    fn is_valid(
+        &self,
+        radius: f64,
+        clouds: f64,
+        density: f64,
+        particleid: usize,
+        ptype: f64,
+    ) -> bool;
     fn timestep_valid(&self, timestep: f64) -> bool;
     fn set_time(&mut self, min_time: f64, max_time: f64);
+    /// Only accept particles of the types in `ptypes`, an empty list accepts all types
+    fn set_particle_types(&mut self, ptypes: Vec<f64>);
     fn as_any(&self) -> &dyn Any;
 }
 
@@ -18,6 +27,8 @@ pub struct ParticleSelector {
     clouds: Vec<f64>,
     density: Vec<f64>,
     particleid: Vec<usize>,
+    /// particle types are categories, a particle is valid if its type is in the list
+    ptype: Vec<f64>,
 }
 impl Default for ParticleSelector {
     fn default() -> Self {
@@ -27,6 +38,7 @@ impl Default for ParticleSelector {
             clouds: vec![-1.],
             density: vec![-1.],
             particleid: vec![0],
+            ptype: vec![-1.],
         }
     }
 }
@@ -37,6 +49,7 @@ impl ParticleSelector {
         clouds: Vec<f64>,
         density: Vec<f64>,
         particleid: Vec<usize>,
+        ptype: Vec<f64>,
     ) -> Self {
         ParticleSelector {
             time,
@@ -44,6 +57,7 @@ impl ParticleSelector {
             clouds,
             density,
             particleid,
+            ptype,
         }
     }
 
@@ -100,16 +114,31 @@ impl ParticleSelector {
             self.particleid.contains(&particleid)
         }
     }
+    fn is_ptype_valid(&self, ptype: f64) -> bool {
+        // if first element is -1 one does not want to check this
+        if self.ptype[0] == -1. {
+            return true;
+        }
+        self.ptype.contains(&ptype)
+    }
 }
 
 impl Selector for ParticleSelector {
-    fn is_valid(&self, radius: f64, clouds: f64, density: f64, particleid: usize) -> bool {
+    fn is_valid(
+        &self,
+        radius: f64,
+        clouds: f64,
+        density: f64,
+        particleid: usize,
+        ptype: f64,
+    ) -> bool {
         print_debug!("ParticleSelector: Checking if particle is valid.");
 
         self.is_radius_valid(radius)
             && self.is_cloud_valid(clouds)
             && self.is_density_valid(density)
             && self.is_particleid_valid(particleid)
+            && self.is_ptype_valid(ptype)
     }
 
     fn timestep_valid(&self, time: f64) -> bool {
@@ -120,6 +149,10 @@ impl Selector for ParticleSelector {
         self.time = (min_time, max_time);
     }
 
+    fn set_particle_types(&mut self, ptypes: Vec<f64>) {
+        self.ptype = if ptypes.is_empty() { vec![-1.] } else { ptypes };
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -7,18 +7,85 @@ extern crate ndarray;
 extern crate plotly;
 use crate::particleselector::*;
 
+use crate::error;
 use crate::print_debug;
-use numpy::{IntoPyArray, PyArray2};
+use numpy::{IntoPyArray, PyArray2, PyReadonlyArray1, PyReadonlyArray2, PyReadonlyArray3};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use std::collections::{BTreeMap, HashMap};
 pub mod libcomp;
 pub mod libconv;
 pub mod libgrid;
 pub mod libplot;
-use crate::datamanager::{Manager, PData, TData};
+use crate::functions::property::{Property, Reducer};
+use crate::datamanager::{
+    self,
+    pdata::{TracerSync, DEFAULT_BUFFERSIZE},
+    inspect, write_hdf5, ConcatData, FileLayout, InspectOptions, MData, Manager, PData, TData,
+    TimeLookup, Transform,
+};
+use crate::units::LengthUnit;
 
 use libconv::*;
 use libgrid::*;
 use libplot::*;
 
+create_exception!(
+    upppp_rust,
+    Up4Error,
+    PyException,
+    "Base class for all errors raised while reading or processing an up4 dataset."
+);
+create_exception!(
+    upppp_rust,
+    FileOpenError,
+    Up4Error,
+    "The HDF5 file could not be opened."
+);
+create_exception!(
+    upppp_rust,
+    MissingDataError,
+    Up4Error,
+    "A group, dataset or attribute is missing in the HDF5 file."
+);
+create_exception!(
+    upppp_rust,
+    MalformedDataError,
+    Up4Error,
+    "Data in the HDF5 file has a wrong type or shape."
+);
+create_exception!(
+    upppp_rust,
+    FileKindError,
+    Up4Error,
+    "The HDF5 file is not of the expected up4 kind."
+);
+create_exception!(
+    upppp_rust,
+    TimestepError,
+    Up4Error,
+    "A timestep or time outside of the dataset was requested."
+);
+
+impl From<error::Up4Error> for PyErr {
+    fn from(err: error::Up4Error) -> PyErr {
+        let message = err.to_string();
+        match err {
+            error::Up4Error::FileOpen { .. } => FileOpenError::new_err(message),
+            error::Up4Error::MissingGroup { .. }
+            | error::Up4Error::MissingDataset { .. }
+            | error::Up4Error::MissingAttribute { .. } => MissingDataError::new_err(message),
+            error::Up4Error::Read { .. }
+            | error::Up4Error::MalformedShape { .. }
+            | error::Up4Error::Hdf5(_) => MalformedDataError::new_err(message),
+            error::Up4Error::WrongFileKind { .. } => FileKindError::new_err(message),
+            error::Up4Error::TimestepOutOfRange { .. } | error::Up4Error::TimeOutOfRange { .. } => {
+                TimestepError::new_err(message)
+            }
+            error::Up4Error::InvalidArgument(_) => PyValueError::new_err(message),
+        }
+    }
+}
 
 // pyO3 helper:
 #[derive(FromPyObject)]
@@ -43,15 +110,42 @@ enum SliceIntOrVec<'a> {
 /// from_tdata:
 ///     Constructor for up4.Data using data that has low numbers of particles, typically from experiment.
 ///
+/// from_numpy:
+///     Constructor for up4.Data using numpy arrays that are kept in memory.
+///
+/// concat:
+///     Constructor for up4.Data chaining several files into one timeline.
+///
 /// stats:
 ///     Calculate statistics of the dataset such as dimensions, mean velocity and number of particles.
 ///
 /// set_time:
 ///     Select the dataset between two times.
 ///
+/// save:
+///     Save the selected part of the dataset into a new HDF5 file.
+///
+/// timestep_at:
+///     Return the index of the timestep at a given time.
+///
+/// field_names:
+///     Return the names of the additional per-particle fields.
+///
+/// field:
+///     Return an additional per-particle field for a single timestep.
+///
+/// rotate, translate, scale, swap_axes:
+///     Transform positions and velocities, e.g. to align experimental and simulated frames.
+///
+/// reset_transform:
+///     Remove all transforms.
+///
 /// vectorfield:
 ///     Return vector data as a vector field.
 ///
+/// property_field:
+///     Return any per-particle property binned onto a grid with a mean/median/min/max/std/count reducer.
+///
 /// velocityfield:
 ///     Return the velocity data as a velocity field.
 ///
@@ -108,40 +202,31 @@ impl PyData {
     /// ----------
     /// filename : str
     ///     Filename of hdf5 dataset.
+    /// buffersize : int, optional
+    ///     Number of timesteps read from the file at once. Chunks of this size are cached
+    ///     up to the memory set with `set_cache_memory`, so files with many particles per
+    ///     timestep need a small buffersize to limit the memory used.
     ///
     /// Returns
     /// -------
     /// up4.Data
     ///     Data class.
+    ///
+    /// Raises
+    /// ------
+    /// up4.FileKindError
+    ///     If the file is not an up4 dataset or was written by an incompatible up4 version.
+    /// up4.Up4Error
+    ///     If the file can not be opened or is not a valid up4 dataset.
     #[new]
-    fn constructor(filename: &str) -> Self {
-        let file = hdf5::File::open(filename)
-            .unwrap_or_else(|_| panic!("Unable to open file {}. Check if file exists.", filename));
-        let hdf5type: i32 = file
-            .attr("hdf5_up4_type")
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not find attribute \"hdf5_up4_type\" in file {}",
-                    filename
-                )
-            })
-            .read_scalar()
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Can not read scalar from attribute \"hdf5_up4_type\" in file {}",
-                    filename
-                )
-            });
-        file.close().expect("Unable to close file");
-        let data;
-        if hdf5type == 0x1_i32 {
-            data = PyData::from_tdata(filename)
-        } else if hdf5type == 0x2_i32 {
-            data = PyData::from_pdata(filename)
-        } else {
-            panic!("Unknown hdf5 type {}", hdf5type);
-        }
-        data
+    #[pyo3(signature = (filename, buffersize = DEFAULT_BUFFERSIZE))]
+    fn constructor(filename: &str, buffersize: usize) -> PyResult<Self> {
+        let data = datamanager::open(filename, buffersize)?;
+        let selector = ParticleSelector::default();
+        Ok(PyData {
+            data,
+            selector: Box::new(selector),
+        })
     }
 
     /// Create new instance of the up4.Data class. This method assumes a particle oriented hdf5 file.
@@ -150,19 +235,35 @@ impl PyData {
     /// ----------
     /// filename : str
     ///     Filename of hdf5 dataset.
+    /// buffersize : int, optional
+    ///     Number of timesteps read from the file at once. Chunks of this size are cached
+    ///     up to the memory set with `set_cache_memory`.
+    /// tracer_window : float, optional
+    ///     Only used if every tracer has its own time, e.g. in multi-tracer PEPT. By default
+    ///     the tracers are interpolated onto the timesteps of the file. With a window in
+    ///     seconds each timestep uses the closest location of a tracer that is at most half
+    ///     the window away, tracers without such a location are NaN.
     ///
     /// Returns
     /// -------
     /// up4.Data
     ///     Data class
     #[staticmethod]
-    fn from_pdata(filename: &str) -> Self {
-        let pdata = PData::new(filename);
+    #[pyo3(signature = (filename, buffersize = DEFAULT_BUFFERSIZE, tracer_window = None))]
+    fn from_pdata(
+        filename: &str,
+        buffersize: usize,
+        tracer_window: Option<f64>,
+    ) -> PyResult<Self> {
+        let mut pdata = PData::with_buffersize(filename, buffersize)?;
+        if let Some(window) = tracer_window {
+            pdata.set_tracer_sync(TracerSync::Window(window))?;
+        }
         let selector = ParticleSelector::default();
-        PyData {
+        Ok(PyData {
             data: Box::new(pdata),
             selector: Box::new(selector),
-        }
+        })
     }
 
     /// Create new instance of the Data class. This method assumes a time oriented hdf5 file.
@@ -171,19 +272,130 @@ impl PyData {
     /// ----------
     /// filename : str
     ///     Filename of hdf5 dataset.
+    /// buffersize : int, optional
+    ///     Number of timesteps read from the file at once. Chunks of this size are cached
+    ///     up to the memory set with `set_cache_memory`.
     ///
     /// Returns
     /// -------
     /// up4.Data
     ///     Data class
     #[staticmethod]
-    fn from_tdata(filename: &str) -> Self {
-        let tdata = TData::new(filename);
+    #[pyo3(signature = (filename, buffersize = DEFAULT_BUFFERSIZE))]
+    fn from_tdata(filename: &str, buffersize: usize) -> PyResult<Self> {
+        let tdata = TData::with_buffersize(filename, buffersize)?;
         let selector = ParticleSelector::default();
-        PyData {
+        Ok(PyData {
             data: Box::new(tdata),
             selector: Box::new(selector),
+        })
+    }
+
+    /// Create new instance of the Data class that chains several files into one timeline,
+    /// e.g. a restarted simulation or a PEPT experiment recorded in several chunks.
+    ///
+    /// Parameters
+    /// ----------
+    /// filenames : list[str]
+    ///     Filenames of the hdf5 datasets in chronological order. Time or particle oriented
+    ///     formats are parsed automatically.
+    /// buffersize : int, optional
+    ///     Number of timesteps read from each file at once.
+    /// time_offsets : list[float], optional
+    ///     Time added to each file. By default a file that does not start after the end of
+    ///     the previous one is shifted to start one sample after it.
+    ///
+    /// Returns
+    /// -------
+    /// up4.Data
+    ///     Data class
+    #[staticmethod]
+    #[pyo3(signature = (filenames, buffersize = DEFAULT_BUFFERSIZE, time_offsets = None))]
+    fn concat(
+        filenames: Vec<&str>,
+        buffersize: usize,
+        time_offsets: Option<Vec<f64>>,
+    ) -> PyResult<Self> {
+        let data = match time_offsets {
+            Some(time_offsets) => {
+                let parts = filenames
+                    .iter()
+                    .map(|filename| datamanager::open(filename, buffersize))
+                    .collect::<error::Result<Vec<_>>>()?;
+                ConcatData::with_time_offsets(parts, time_offsets)?
+            }
+            None => ConcatData::from_files(&filenames, buffersize)?,
+        };
+        let selector = ParticleSelector::default();
+        Ok(PyData {
+            data: Box::new(data),
+            selector: Box::new(selector),
+        })
+    }
+
+    /// Create new instance of the Data class from numpy arrays. The data is kept in memory,
+    /// no file is written.
+    ///
+    /// Parameters
+    /// ----------
+    /// time : numpy.ndarray
+    ///     Time of each timestep with shape (T,). Must be sorted.
+    /// position : numpy.ndarray
+    ///     Particle positions with shape (T, N, 3).
+    /// velocity : numpy.ndarray, optional
+    ///     Particle velocities with shape (T, N, 3). Calculated from the positions with
+    ///     finite differences if not given.
+    /// radius : numpy.ndarray, optional
+    ///     Particle radii with shape (T, N). Defaults to zeros.
+    /// particle_id : numpy.ndarray, optional
+    ///     Particle IDs with shape (T, N). Defaults to the particle index.
+    /// particle_type : numpy.ndarray, optional
+    ///     Particle types with shape (T, N). Defaults to zeros.
+    /// density : numpy.ndarray, optional
+    ///     Particle densities with shape (T, N). Defaults to ones.
+    /// fields : dict[str, numpy.ndarray], optional
+    ///     Additional per-particle fields with shape (T, N, components), e.g. the temperature
+    ///     with shape (T, N, 1).
+    /// length_unit : str, optional
+    ///     Unit of the positions and radii: "m", "cm", "mm" or "um". Unknown if not given.
+    ///
+    /// Returns
+    /// -------
+    /// up4.Data
+    ///     Data class
+    #[staticmethod]
+    #[pyo3(signature = (time, position, velocity = None, radius = None, particle_id = None, particle_type = None, density = None, fields = None, length_unit = None))]
+    fn from_numpy(
+        time: PyReadonlyArray1<f64>,
+        position: PyReadonlyArray3<f64>,
+        velocity: Option<PyReadonlyArray3<f64>>,
+        radius: Option<PyReadonlyArray2<f64>>,
+        particle_id: Option<PyReadonlyArray2<f64>>,
+        particle_type: Option<PyReadonlyArray2<f64>>,
+        density: Option<PyReadonlyArray2<f64>>,
+        fields: Option<HashMap<String, PyReadonlyArray3<f64>>>,
+        length_unit: Option<&str>,
+    ) -> PyResult<Self> {
+        let mut mdata = MData::from_arrays(
+            time.as_array().to_owned(),
+            position.as_array().to_owned(),
+            velocity.map(|array| array.as_array().to_owned()),
+            radius.map(|array| array.as_array().to_owned()),
+            particle_id.map(|array| array.as_array().to_owned()),
+            particle_type.map(|array| array.as_array().to_owned()),
+            density.map(|array| array.as_array().to_owned()),
+        )?;
+        for (name, field) in fields.unwrap_or_default() {
+            mdata = mdata.with_field(&name, field.as_array().to_owned())?;
+        }
+        if let Some(length_unit) = length_unit {
+            mdata = mdata.with_length_unit(length_unit.parse::<LengthUnit>()?);
         }
+        let selector = ParticleSelector::default();
+        Ok(PyData {
+            data: Box::new(mdata),
+            selector: Box::new(selector),
+        })
     }
 
     /// Calculate statistics of the dataset and print to terminal. Currently these are:
@@ -248,6 +460,75 @@ impl PyData {
         *self.data.global_stats().nparticles()
     }
 
+    /// Number of particles of each type. Files that do not list the particle types are
+    /// counted on the first call, which reads the types of every timestep.
+    ///
+    /// Returns
+    /// -------
+    /// dict[int, int]
+    ///     Particle type mapped to the largest number of particles of this type found in
+    ///     one timestep.
+    fn particle_types(&mut self) -> PyResult<BTreeMap<usize, usize>> {
+        Ok(self.data.particle_types()?)
+    }
+
+    /// Unit of the positions and radii of the dataset, e.g. "mm".
+    ///
+    /// Returns
+    /// -------
+    /// str or None
+    ///     Symbol of the unit of length, None if the file does not record it.
+    fn length_unit(&self) -> Option<String> {
+        self.data
+            .global_stats()
+            .provenance()
+            .length_unit()
+            .map(|unit| unit.symbol().to_string())
+    }
+
+    /// Units and origin of the dataset as recorded by the converter.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///     Provenance with the keys "length_unit", "source_files", "converter",
+    ///     "converter_settings" and "version". Unknown entries are None or empty.
+    fn provenance<'py>(&self, py: Python<'py>) -> &'py pyo3::types::PyDict {
+        let stats = self.data.global_stats();
+        let provenance = stats.provenance();
+        let key_vals: Vec<(&str, PyObject)> = vec![
+            (
+                "length_unit",
+                provenance
+                    .length_unit()
+                    .map(|unit| unit.symbol())
+                    .to_object(py),
+            ),
+            ("source_files", provenance.source_files().to_object(py)),
+            ("converter", provenance.converter().to_object(py)),
+            (
+                "converter_settings",
+                provenance.converter_settings().to_object(py),
+            ),
+            ("version", provenance.version().to_object(py)),
+        ];
+        key_vals.into_py_dict(py)
+    }
+
+    /// Select only particles of the given types. All following functions only use these
+    /// particles.
+    ///
+    /// Parameters
+    /// ----------
+    /// types : list[int], optional
+    ///     Particle types to select, all types are selected if not given.
+    #[pyo3(signature = (types = None))]
+    fn set_particle_types(&mut self, types: Option<Vec<usize>>) {
+        let types = types.unwrap_or_default();
+        self.selector
+            .set_particle_types(types.into_iter().map(|ptype| ptype as f64).collect());
+    }
+
     /// Select the dataset between two different times.
     ///
     /// Parameters
@@ -256,15 +537,139 @@ impl PyData {
     ///     Starting time for selection.
     /// max_time : float
     ///     End time for selection.
-    fn set_time(&mut self, min_time: f64, max_time: f64) {
-        if self.data.global_stats().max_time() < &min_time {
-            panic!(
-                "selected time range is out of range. Consider changing time range.\n\
-            Max time of system: {}",
-                self.data.global_stats().max_time()
-            )
+    fn set_time(&mut self, min_time: f64, max_time: f64) -> PyResult<()> {
+        let global_stats = self.data.global_stats();
+        if global_stats.max_time() < &min_time {
+            return Err(error::Up4Error::TimeOutOfRange {
+                time: min_time,
+                min_time: *global_stats.min_time(),
+                max_time: *global_stats.max_time(),
+            }
+            .into());
+        }
+        self.selector.set_time(min_time, max_time);
+        Ok(())
+    }
+
+    /// Save the dataset into a new HDF5 file. Only the time window set with `set_time`
+    /// and the particles accepted by the current particle selection are written.
+    ///
+    /// Parameters
+    /// ----------
+    /// filename : str
+    ///     Filename of the new hdf5 dataset.
+    /// layout : str
+    ///     Layout of the new file: "pdata" for particle oriented or "tdata" for time
+    ///     oriented files.
+    fn save(&mut self, filename: &str, layout: &str) -> PyResult<()> {
+        let layout: FileLayout = layout.parse()?;
+        write_hdf5(self.data.as_mut(), self.selector.as_ref(), filename, layout)?;
+        Ok(())
+    }
+
+    /// Check the integrity of the dataset by walking over all timesteps once.
+    ///
+    /// Parameters
+    /// ----------
+    /// gap_factor : float, optional
+    ///     A time step longer than `gap_factor` times the median time step is reported as
+    ///     a gap, by default 2.0.
+    /// outlier_factor : float, optional
+    ///     A speed above `outlier_factor` times the mean speed of the dataset is reported
+    ///     as a velocity outlier, by default 10.0.
+    ///
+    /// Returns
+    /// -------
+    /// dict
+    ///     Report with the keys "timesteps", "particles" (coverage, first_time and
+    ///     last_time of every particle ID), "nan_fractions", "time_gaps" (list of
+    ///     (timestep, gap length) tuples), "non_monotonic", "out_of_bounds",
+    ///     "velocity_outliers", "outlier_speed", "max_speed" and "clean".
+    #[pyo3(signature = (gap_factor = 2.0, outlier_factor = 10.0))]
+    fn inspect<'py>(
+        &mut self,
+        py: Python<'py>,
+        gap_factor: f64,
+        outlier_factor: f64,
+    ) -> PyResult<&'py pyo3::types::PyDict> {
+        let options = InspectOptions {
+            gap_factor,
+            outlier_factor,
+        };
+        let report = inspect(self.data.as_mut(), options)?;
+        let particles = pyo3::types::PyDict::new(py);
+        for (&particleid, particle) in report.particles().iter() {
+            let coverage = vec![
+                ("timesteps", particle.timesteps().to_object(py)),
+                ("coverage", report.coverage(particleid).to_object(py)),
+                ("first_time", particle.first_time().to_object(py)),
+                ("last_time", particle.last_time().to_object(py)),
+            ];
+            particles.set_item(particleid, coverage.into_py_dict(py))?;
         }
-        self.selector.set_time(min_time, max_time)
+        let key_vals: Vec<(&str, PyObject)> = vec![
+            ("timesteps", report.timesteps().to_object(py)),
+            ("particles", particles.to_object(py)),
+            ("nan_fractions", report.nan_fractions().to_object(py)),
+            ("time_gaps", report.time_gaps().to_object(py)),
+            ("non_monotonic", report.non_monotonic().to_object(py)),
+            ("out_of_bounds", report.out_of_bounds().to_object(py)),
+            ("velocity_outliers", report.velocity_outliers().to_object(py)),
+            ("outlier_speed", report.outlier_speed().to_object(py)),
+            ("max_speed", report.max_speed().to_object(py)),
+            ("clean", report.is_clean().to_object(py)),
+        ];
+        Ok(key_vals.into_py_dict(py))
+    }
+
+    /// Return the names of the additional per-particle fields stored in the dataset.
+    ///
+    /// Returns
+    /// -------
+    /// list[str]
+    ///     Names of the fields, e.g. ["omega", "temperature"].
+    fn field_names(&mut self) -> PyResult<Vec<String>> {
+        let timestep = self.data.get_timestep(0)?;
+        Ok(timestep
+            .field_names()
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect())
+    }
+
+    /// Return an additional per-particle field for a single timestep.
+    ///
+    /// Parameters
+    /// ----------
+    /// timestep : int
+    ///     Index of the timestep.
+    /// name : str
+    ///     Name of the field.
+    ///
+    /// Returns
+    /// -------
+    /// numpy.ndarray
+    ///     Field values with shape (N, components).
+    ///
+    /// Raises
+    /// ------
+    /// ValueError
+    ///     If the dataset has no field with this name.
+    fn field<'py>(
+        &mut self,
+        py: Python<'py>,
+        timestep: usize,
+        name: &str,
+    ) -> PyResult<&'py PyArray2<f64>> {
+        let timestep = self.data.get_timestep(timestep)?;
+        let field = timestep.field(name).ok_or_else(|| {
+            error::Up4Error::InvalidArgument(format!(
+                "Field {} does not exist. Available fields are: {:?}",
+                name,
+                timestep.field_names()
+            ))
+        })?;
+        Ok(field.to_owned().into_pyarray(py))
     }
 
     /// Return the time array of the dataset.
@@ -282,6 +687,27 @@ impl PyData {
             .into_pyarray(py)
     }
 
+    /// Return the index of the timestep at a given time.
+    ///
+    /// Parameters
+    /// ----------
+    /// time : float
+    ///     Time in seconds.
+    /// lookup : str, optional
+    ///     "nearest" for the closest timestep, "floor" for the last timestep at or before
+    ///     `time`, "ceil" for the first timestep at or after `time` or "exact" to only
+    ///     accept a timestep at exactly this time.
+    ///
+    /// Returns
+    /// -------
+    /// int
+    ///     Index of the timestep.
+    #[pyo3(signature = (time, lookup = "nearest"))]
+    fn timestep_at(&self, time: f64, lookup: &str) -> PyResult<usize> {
+        let lookup: TimeLookup = lookup.parse()?;
+        Ok(self.data.global_stats().timestep_at(time, lookup)?)
+    }
+
     /// Return velocity data as a vector field.
     ///
     /// Parameters
@@ -293,14 +719,14 @@ impl PyData {
     /// -------
     /// up4.VectorGrid
     ///     VectorGrid class containing each vector component for each grid cell.
-    fn vectorfield<'py>(&mut self, _py: Python<'py>, grid: &PyGrid) -> PyVecGrid {
+    fn vectorfield<'py>(&mut self, _py: Python<'py>, grid: &PyGrid) -> PyResult<PyVecGrid> {
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
                 Some(b) => b,
                 None => panic!("Can not convert PyGrid to Grid1D as "),
             };
-        let grid = self.data.vectorfield(grid.grid.clone(), selector);
-        PyVecGrid { grid }
+        let grid = self.data.vectorfield(grid.grid.clone(), selector)?;
+        Ok(PyVecGrid { grid })
     }
 
     /// Return the velocity data as a velocity field of their norms.
@@ -322,7 +748,7 @@ impl PyData {
         mode: &str,
         min_velocity: f64,
         max_velocity: f64,
-    ) -> PyGrid {
+    ) -> PyResult<PyGrid> {
         print_debug!("Starting Vectorfield function");
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
@@ -335,9 +761,89 @@ impl PyData {
             mode,
             min_velocity,
             max_velocity,
-        );
+        )?;
 
-        PyGrid { grid }
+        Ok(PyGrid { grid })
+    }
+
+    /// Bin any per-particle property onto a grid and reduce the values in each cell.
+    ///
+    /// Parameters
+    /// ----------
+    /// grid : up4.Grid
+    ///     Grid class containing the grid layout.
+    /// property : str
+    ///     Property to bin: "radius", "density", "ptype", "id", "ppcloud", "vx", "vy", "vz",
+    ///     "speed", "kinetic_energy" or the name of an additional field. A component of a
+    ///     vector field is selected with "name[component]", e.g. "omega[2]".
+    /// reducer : str, optional
+    ///     Reduction applied to the values in each cell: "mean", "median", "min", "max",
+    ///     "std" or "count", by default "mean".
+    ///
+    /// Returns
+    /// -------
+    /// up4.Grid
+    ///     Grid class containing the reduced property. Empty cells are NaN.
+    #[pyo3(signature = (grid, property, reducer = "mean"))]
+    fn property_field<'py>(
+        &mut self,
+        _py: Python<'py>,
+        grid: &PyGrid,
+        property: &str,
+        reducer: &str,
+    ) -> PyResult<PyGrid> {
+        let property: Property = property.parse()?;
+        let reducer: Reducer = reducer.parse()?;
+        let selector: &ParticleSelector =
+            match self.selector.as_any().downcast_ref::<ParticleSelector>() {
+                Some(b) => b,
+                None => panic!("Can not convert PyGrid to Grid1D as "),
+            };
+        let grid = self
+            .data
+            .property_field(grid.grid.clone(), selector, &property, reducer)?;
+        Ok(PyGrid { grid })
+    }
+
+    /// Bin a per-particle property onto a grid separately for each particle type. All
+    /// types are binned in a single pass over the data, e.g. to compare the fields of
+    /// small and large particles in segregation studies.
+    ///
+    /// Parameters
+    /// ----------
+    /// grid : up4.Grid
+    ///     Grid class containing the grid layout.
+    /// property : str
+    ///     Property to bin, see `property_field`.
+    /// reducer : str, optional
+    ///     Reduction applied to the values in each cell, by default "mean".
+    ///
+    /// Returns
+    /// -------
+    /// dict[int, up4.Grid]
+    ///     Particle type mapped to the grid containing the reduced property of this type.
+    #[pyo3(signature = (grid, property, reducer = "mean"))]
+    fn property_field_by_type<'py>(
+        &mut self,
+        _py: Python<'py>,
+        grid: &PyGrid,
+        property: &str,
+        reducer: &str,
+    ) -> PyResult<BTreeMap<usize, PyGrid>> {
+        let property: Property = property.parse()?;
+        let reducer: Reducer = reducer.parse()?;
+        let selector: &ParticleSelector =
+            match self.selector.as_any().downcast_ref::<ParticleSelector>() {
+                Some(b) => b,
+                None => panic!("Can not convert PyGrid to Grid1D as "),
+            };
+        let grids = self
+            .data
+            .property_field_by_type(grid.grid.clone(), selector, &property, reducer)?;
+        Ok(grids
+            .into_iter()
+            .map(|(ptype, grid)| (ptype, PyGrid { grid }))
+            .collect())
     }
 
     /// Return particle information over specified duration.
@@ -345,7 +851,8 @@ impl PyData {
     /// Parameters
     /// ----------
     /// particle_id : int
-    ///     Particle ID.
+    ///     Particle ID. The particle is matched by its ID in every timestep, so particles may
+    ///     be inserted, deleted or reordered between timesteps.
     /// timestep : tuple[int, int]
     ///     Start and end timesteps for particle selection.
     ///
@@ -353,13 +860,14 @@ impl PyData {
     /// -------
     /// numpy.ndarray
     ///     2D numpy array with shape (timestep[1] - timestep[0]) X 7. Where in each row is the current time, particle position and its velocity components.
+    ///     Position and velocity are NaN in timesteps that do not contain the particle.
     fn extract<'py>(
         &mut self,
         _py: Python<'py>,
         particle_id: usize,
         timestep: (usize, usize),
-    ) -> &'py PyArray2<f64> {
-        self.data.extract(particle_id, timestep).into_pyarray(_py)
+    ) -> PyResult<&'py PyArray2<f64>> {
+        Ok(self.data.extract(particle_id, timestep)?.into_pyarray(_py))
     }
 
     /// Return the number density field.
@@ -373,16 +881,16 @@ impl PyData {
     /// -------
     /// up4.Grid
     ///     Grid class containing the number field
-    fn numberfield<'py>(&mut self, _py: Python<'py>, grid: &PyGrid) -> PyGrid {
+    fn numberfield<'py>(&mut self, _py: Python<'py>, grid: &PyGrid) -> PyResult<PyGrid> {
         print_debug!("Starting Vectorfield function");
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
                 Some(b) => b,
                 None => panic!("Can not convert PyGrid to Grid1D as "),
             };
-        let grid = self.data.numberfield(grid.grid.clone(), selector);
+        let grid = self.data.numberfield(grid.grid.clone(), selector)?;
 
-        PyGrid { grid }
+        Ok(PyGrid { grid })
     }
 
     /// Return the occupancy field.
@@ -400,7 +908,12 @@ impl PyData {
     /// up4.Grid
     ///     Grid class containing the number field
     #[pyo3(signature=(grid, min_vel = 0.0))]
-    fn occupancyfield<'py>(&mut self, _py: Python<'py>, grid: &PyGrid, min_vel: f64) -> PyGrid {
+    fn occupancyfield<'py>(
+        &mut self,
+        _py: Python<'py>,
+        grid: &PyGrid,
+        min_vel: f64,
+    ) -> PyResult<PyGrid> {
         print_debug!("Starting Vectorfield function");
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
@@ -409,9 +922,9 @@ impl PyData {
             };
         let grid = self
             .data
-            .occupancyfield(grid.grid.clone(), selector, min_vel);
+            .occupancyfield(grid.grid.clone(), selector, min_vel)?;
 
-        PyGrid { grid }
+        Ok(PyGrid { grid })
     }
 
     /// Return the mean velocity of all valid particles in the system.
@@ -442,6 +955,9 @@ impl PyData {
     ///    Grid class containing the grid layout.
     /// time_for_dispersion : float
     ///   Time for which the dispersion is calculated.
+    /// interpolate : bool, optional
+    ///   Interpolate the positions exactly `time_for_dispersion` later instead of using the
+    ///   closest recorded timestep. Recommended for irregularly sampled PEPT data.
     ///
     /// Returns
     /// -------
@@ -454,12 +970,14 @@ impl PyData {
     /// References
     /// ----------
     /// .. [1] Martin, T. W., J. P. K. Seville, and D. J. Parker. "A general method for quantifying dispersion in multiscale systems using trajectory analysis."
+    #[pyo3(signature = (grid, time_for_dispersion, interpolate = false))]
     fn dispersion<'py>(
         &mut self,
         _py: Python<'py>,
         grid: &PyGrid,
         time_for_dispersion: f64,
-    ) -> (PyGrid, f64) {
+        interpolate: bool,
+    ) -> PyResult<(PyGrid, f64)> {
         print_debug!("Starting Dispersion function");
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
@@ -468,9 +986,9 @@ impl PyData {
             };
         let (grid, mixing_efficiency) =
             self.data
-                .dispersion(grid.grid.clone(), selector, time_for_dispersion);
+                .dispersion(grid.grid.clone(), selector, time_for_dispersion, interpolate)?;
 
-        (PyGrid { grid }, mixing_efficiency)
+        Ok((PyGrid { grid }, mixing_efficiency))
     }
 
     /// Calculate a histogram of a specific property in a region of the system.
@@ -507,7 +1025,7 @@ impl PyData {
         property: &str,
         bins: usize,
         limit: f64,
-    ) -> (&'py numpy::PyArray1<f64>, &'py numpy::PyArray1<f64>) {
+    ) -> PyResult<(&'py numpy::PyArray1<f64>, &'py numpy::PyArray1<f64>)> {
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
                 Some(b) => b,
@@ -515,8 +1033,8 @@ impl PyData {
             };
         let (histogram, bin_edges) =
             self.data
-                .histogram(grid.grid.clone(), selector, property, limit, bins);
-        (histogram.into_pyarray(_py), bin_edges.into_pyarray(_py))
+                .histogram(grid.grid.clone(), selector, property, limit, bins)?;
+        Ok((histogram.into_pyarray(_py), bin_edges.into_pyarray(_py)))
     }
 
     /// Calculate the granular temperature of the system.
@@ -545,7 +1063,12 @@ impl PyData {
     /// PyGrid
     ///     The granular temperature of the system.
     #[pyo3(signature = (grid, mode = "xyz"))]
-    fn granular_temperature<'py>(&mut self, _py: Python<'py>, grid: &PyGrid, mode: &str) -> PyGrid {
+    fn granular_temperature<'py>(
+        &mut self,
+        _py: Python<'py>,
+        grid: &PyGrid,
+        mode: &str,
+    ) -> PyResult<PyGrid> {
         print_debug!("Starting Granular Temperature function");
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
@@ -554,9 +1077,9 @@ impl PyData {
             };
         let grid = self
             .data
-            .granular_temperature_field(grid.grid.clone(), selector, mode);
+            .granular_temperature_field(grid.grid.clone(), selector, mode)?;
 
-        PyGrid { grid }
+        Ok(PyGrid { grid })
     }
 
     /// Calculate the Lacey mixing index for two particle types in a region of the system for a time period.
@@ -587,7 +1110,7 @@ impl PyData {
         type_a: usize,
         type_b: usize,
         threshold: usize,
-    ) -> (&'py numpy::PyArray1<f64>, &'py numpy::PyArray1<f64>) {
+    ) -> PyResult<(&'py numpy::PyArray1<f64>, &'py numpy::PyArray1<f64>)> {
         print_debug!("Starting Lacey Mixing Index function");
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
@@ -596,9 +1119,9 @@ impl PyData {
             };
         let (time, mixing_index) =
             self.data
-                .lacey_mixing(grid.grid.clone(), selector, type_a, type_b, threshold);
+                .lacey_mixing(grid.grid.clone(), selector, type_a, type_b, threshold)?;
 
-        (time.into_pyarray(_py), mixing_index.into_pyarray(_py))
+        Ok((time.into_pyarray(_py), mixing_index.into_pyarray(_py)))
     }
 
     /// Calculate the circulation time of a particle in a system.
@@ -617,7 +1140,12 @@ impl PyData {
     /// circulation_time : list
     ///     The circulation time of the particle.
     #[pyo3(signature = (position, axis = 0))]
-    fn circulation_time<'py>(&mut self, _py: Python<'py>, position: f64, axis: usize) -> Vec<f64> {
+    fn circulation_time<'py>(
+        &mut self,
+        _py: Python<'py>,
+        position: f64,
+        axis: usize,
+    ) -> PyResult<Vec<f64>> {
         print_debug!("Starting Circulation Time function");
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
@@ -625,7 +1153,7 @@ impl PyData {
                 None => panic!("Can not convert PyGrid to Grid1D as "),
             };
 
-        self.data.circulation_time(selector, axis, position)
+        Ok(self.data.circulation_time(selector, axis, position)?)
     }
 
     /// Calculate the circulation time of a particle in a system.
@@ -646,7 +1174,12 @@ impl PyData {
     ///
     // TODO: also return the poarticle ID that did the circulation
     #[pyo3(signature = (position, axis = 0))]
-    fn circulation_time_two_boundary<'py>(&mut self, _py: Python<'py>, position: (f64,f64), axis: usize) -> Vec<(f64,f64)> {
+    fn circulation_time_two_boundary<'py>(
+        &mut self,
+        _py: Python<'py>,
+        position: (f64, f64),
+        axis: usize,
+    ) -> PyResult<Vec<(f64, f64)>> {
         print_debug!("Starting Circulation Time function");
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
@@ -654,7 +1187,9 @@ impl PyData {
                 None => panic!("Can not convert PyGrid to Grid1D as "),
             };
 
-        self.data.circulation_time_two_boundary(selector, axis, position)
+        Ok(self
+            .data
+            .circulation_time_two_boundary(selector, axis, position)?)
     }
 
     /// Calculate the concentration field of the system
@@ -683,7 +1218,7 @@ impl PyData {
         grid: &PyGrid,
         type_a: usize,
         type_b: usize,
-    ) -> PyGrid {
+    ) -> PyResult<PyGrid> {
         print_debug!("Starting Concentration Field function");
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
@@ -692,9 +1227,9 @@ impl PyData {
             };
         let grid = self
             .data
-            .concentration_field(grid.grid.clone(), selector, type_a, type_b);
+            .concentration_field(grid.grid.clone(), selector, type_a, type_b)?;
 
-        PyGrid { grid }
+        Ok(PyGrid { grid })
     }
 
     /// Calculate the homogenity index.
@@ -705,15 +1240,21 @@ impl PyData {
     /// grid : PyGrid
     ///   The grid that defines the region of the system.
     #[pyo3(signature = (grid, min_vel = 0.0))]
-    fn homogenity_index<'py>(&mut self, _py: Python<'py>, grid: &PyGrid, min_vel: f64) -> f64 {
+    fn homogenity_index<'py>(
+        &mut self,
+        _py: Python<'py>,
+        grid: &PyGrid,
+        min_vel: f64,
+    ) -> PyResult<f64> {
         print_debug!("Starting Homogenity Index function");
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
                 Some(b) => b,
                 None => panic!("Can not convert PyGrid to Grid1D as "),
             };
-        self.data
-            .homogenity_index(grid.grid.clone(), selector, min_vel)
+        Ok(self
+            .data
+            .homogenity_index(grid.grid.clone(), selector, min_vel)?)
     }
 
     /// Calculate the mean squared displacement field of the system.
@@ -725,21 +1266,36 @@ impl PyData {
     /// grid : PyGrid
     ///   The grid that defines the region of the system.
     ///
+    /// time : float
+    ///   Lag time of the displacement.
+    ///
+    /// interpolate : bool, optional
+    ///   Interpolate the positions exactly `time` later instead of using the closest
+    ///   recorded timestep.
+    ///
     /// Returns
     /// -------
     /// msd_field : PyGrid
     ///  The mean squared displacement field of the system.
-    #[pyo3(signature = (grid,time))]
-    fn msd_field<'py>(&mut self, _py: Python<'py>, grid: &PyGrid, time: f64) -> PyGrid {
+    #[pyo3(signature = (grid, time, interpolate = false))]
+    fn msd_field<'py>(
+        &mut self,
+        _py: Python<'py>,
+        grid: &PyGrid,
+        time: f64,
+        interpolate: bool,
+    ) -> PyResult<PyGrid> {
         print_debug!("Starting MSD Field function");
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
                 Some(b) => b,
                 None => panic!("Can not convert PyGrid to Grid1D as "),
             };
-        let grid = self.data.msd_field(grid.grid.clone(), selector, time);
+        let grid = self
+            .data
+            .msd_field(grid.grid.clone(), selector, time, interpolate)?;
 
-        PyGrid { grid }
+        Ok(PyGrid { grid })
     }
 
     /// Calculate the mean squared displacement of a particle in a system.
@@ -755,6 +1311,13 @@ impl PyData {
     /// max_time : float
     ///   The maximum time to stop calculating the MSD.
     ///
+    /// steps : int
+    ///   Number of lag times between `min_time` and `max_time`.
+    ///
+    /// interpolate : bool, optional
+    ///   Interpolate the positions at every lag time instead of using the closest
+    ///   recorded timestep.
+    ///
     /// Returns
     /// -------
     /// msd : ndarray
@@ -762,7 +1325,7 @@ impl PyData {
     ///
     /// time : ndarray
     ///   The time at which the MSD was calculated.
-    #[pyo3(signature = (grid, min_time = 0.0, max_time = 0.0, steps = 100))]
+    #[pyo3(signature = (grid, min_time = 0.0, max_time = 0.0, steps = 100, interpolate = false))]
     fn msd<'py>(
         &mut self,
         _py: Python<'py>,
@@ -770,7 +1333,8 @@ impl PyData {
         min_time: f64,
         max_time: f64,
         steps: usize,
-    ) -> (&'py numpy::PyArray1<f64>, &'py numpy::PyArray1<f64>) {
+        interpolate: bool,
+    ) -> PyResult<(&'py numpy::PyArray1<f64>, &'py numpy::PyArray1<f64>)> {
         print_debug!("Starting MSD function");
         let selector: &ParticleSelector =
             match self.selector.as_any().downcast_ref::<ParticleSelector>() {
@@ -779,49 +1343,115 @@ impl PyData {
             };
         let (msd, time) = self
             .data
-            .msd(grid.grid.clone(), selector, min_time, max_time, steps);
+            .msd(grid.grid.clone(), selector, min_time, max_time, steps, interpolate)?;
 
-        (msd.into_pyarray(_py), time.into_pyarray(_py))
+        Ok((msd.into_pyarray(_py), time.into_pyarray(_py)))
     }
 
-    /// set the rotation of the system
-    /// The rotation is set by rotating the system around a axis specified by the user.
+    /// Rotate the system around an axis. The rotation is added to all transforms set
+    /// before and is applied to positions and velocities of every timestep read.
     ///
     /// Parameters
     /// ----------
-    /// anker : list
-    ///     A point around which the system is rotated.
+    /// axis : list
+    ///     Direction of the rotation axis, e.g. [0, 0, 1] for the z axis.
+    ///
+    /// angle : float
+    ///     Angle of the rotation in radians, following the right hand rule.
     ///
-    /// angle : list
-    ///     The angle of rotation defined in 3 rotations around the x, y and z axis.
-    ///     The rotation is defined in radians.
+    /// center : list, optional
+    ///     A point on the rotation axis, default is the origin.
     ///
     /// Returns
     /// -------
     /// None
-    // #[pyo3(signature = (anker, angle))]
-    // fn set_rotation(&mut self, anker: Vec<f64>, angle: Vec<f64>) {
-    //     print_debug!("Starting set_rotation function");
-
-    //     if anker.len() != 3 {
-    //         panic!("The anker must be a list of 3 elements");
-    //     }
-    //     if angle.len() != 3 {
-    //         panic!("The angle must be a list of 3 elements");
-    //     }
-    //     for i in 0..3 {
-    //         self.data.set_rotation_angle(angle[i], i);
-    //     }
-
-    //     self.data.set_rotation_anker([anker[0], anker[1], anker[2]]);
-    // }
+    #[pyo3(signature = (axis, angle, center = vec![0.0, 0.0, 0.0]))]
+    fn rotate(&mut self, axis: Vec<f64>, angle: f64, center: Vec<f64>) -> PyResult<()> {
+        let rotation = Transform::rotation(
+            vector3(&axis, "axis")?,
+            angle,
+            vector3(&center, "center")?,
+        )?;
+        self.add_transform(rotation)
+    }
+
+    /// Move the system by `offset`. The translation is added to all transforms set before.
+    ///
+    /// Parameters
+    /// ----------
+    /// offset : list
+    ///     Translation in x, y and z.
+    fn translate(&mut self, offset: Vec<f64>) -> PyResult<()> {
+        self.add_transform(Transform::translation(vector3(&offset, "offset")?))
+    }
+
+    /// Scale all positions and velocities by `factor` around the origin, e.g. 0.001 to
+    /// convert data recorded in mm to m. The scaling is added to all transforms set before.
+    ///
+    /// Parameters
+    /// ----------
+    /// factor : float
+    ///     Positive scaling factor.
+    fn scale(&mut self, factor: f64) -> PyResult<()> {
+        self.add_transform(Transform::scaling(factor)?)
+    }
+
+    /// Swap two axes of the system, e.g. to align a vertical y axis with the z axis
+    /// of another dataset. The swap is added to all transforms set before.
+    ///
+    /// Parameters
+    /// ----------
+    /// first : int
+    ///     First axis (0: x, 1: y, 2: z).
+    /// second : int
+    ///     Second axis.
+    fn swap_axes(&mut self, first: usize, second: usize) -> PyResult<()> {
+        self.add_transform(Transform::swap_axes(first, second)?)
+    }
+
+    /// Remove all rotations, translations, scalings and axis swaps.
+    fn reset_transform(&mut self) -> PyResult<()> {
+        self.data.set_transform(Transform::identity())?;
+        Ok(())
+    }
+
+    /// Return the current transform as a 4x4 matrix in homogeneous coordinates.
+    ///
+    /// Returns
+    /// -------
+    /// numpy.ndarray
+    ///     Matrix M with [x', y', z', 1] = M @ [x, y, z, 1].
+    fn transform<'py>(&self, py: Python<'py>) -> &'py PyArray2<f64> {
+        let transform = self.data.transform();
+        let mut matrix = ndarray::Array2::<f64>::eye(4);
+        for i in 0..3 {
+            for j in 0..3 {
+                matrix[[i, j]] = transform.matrix()[i][j];
+            }
+            matrix[[i, 3]] = transform.offset()[i];
+        }
+        matrix.into_pyarray(py)
+    }
+
+    /// Set the memory used to keep timesteps read from the file. Timesteps are cached in
+    /// chunks of `buffersize` and the least recently used chunks are dropped first, so
+    /// functions that look several timesteps ahead do not read the file again.
+    ///
+    /// Parameters
+    /// ----------
+    /// cache_memory : int
+    ///     Memory in bytes. At least one chunk is always kept, to use less memory than one
+    ///     chunk open the file with a smaller `buffersize`.
+    fn set_cache_memory(&mut self, cache_memory: usize) {
+        self.data.set_cache_memory(cache_memory);
+    }
 
     fn __str__(&self) -> PyResult<String> {
-        Ok(self.data.info().expect("Could not get info"))
+        Ok(self.data.info()?)
     }
 
     fn __repr__(&self) -> PyResult<String> {
-        Ok(self.data.info().expect("Could not get info"))
+        Ok(self.data.info()?)
     }
 
     fn __getitem__(&mut self, idx: SliceIntOrVec, py: Python) -> PyResult<PyObject> {
@@ -834,7 +1464,7 @@ impl PyData {
                 _idx = index;
             }
             // Single index
-            let timestep = self.data.get_timestep(_idx as usize);
+            let timestep = self.data.get_timestep(_idx as usize)?;
             // make it an array
             let position = timestep.position().to_owned();
             let velocity = timestep.velocity().to_owned();
@@ -871,7 +1501,7 @@ impl PyData {
                 } else {
                     _idx = *index;
                 }
-                let timestep = self.data.get_timestep(_idx as usize);
+                let timestep = self.data.get_timestep(_idx as usize)?;
                 // make it an array
                 let position = timestep.position().to_owned();
                 let velocity = timestep.velocity().to_owned();
@@ -903,7 +1533,7 @@ impl PyData {
                 [((stop - start) / step) as usize, *self.data.global_stats().nparticles(), 7]
             );
             for (i, index) in (start..stop).step_by(step as usize).enumerate() {
-                let timestep = self.data.get_timestep(index as usize);
+                let timestep = self.data.get_timestep(index as usize)?;
                 // make it an array
                 let position = timestep.position().to_owned();
                 let velocity = timestep.velocity().to_owned();
@@ -934,15 +1564,42 @@ impl PyData {
 
 }
 
+impl PyData {
+    /// Apply `next` after the transform already set on the dataset
+    fn add_transform(&mut self, next: Transform) -> PyResult<()> {
+        let transform = self.data.transform().then(&next);
+        self.data.set_transform(transform)?;
+        Ok(())
+    }
+}
+
+/// Convert a list given from python into a 3D vector
+fn vector3(values: &[f64], name: &str) -> PyResult<[f64; 3]> {
+    match values {
+        [x, y, z] => Ok([*x, *y, *z]),
+        _ => Err(PyValueError::new_err(format!(
+            "{} must be a list of 3 elements, got {}",
+            name,
+            values.len()
+        ))),
+    }
+}
+
 /// A Python module implemented in Rust. The name of this function must match
 /// the `lib.name` setting in the `Cargo.toml`, else Python will not be able to
 /// import the module.
 #[pymodule]
-fn upppp_rust(_py: Python, m: &PyModule) -> PyResult<()> {
+fn upppp_rust(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<PyData>()?;
     m.add_class::<PyGrid>()?;
     m.add_class::<PyConverter>()?;
     m.add_class::<PyPlotter2D>()?;
     m.add_class::<PyVecGrid>()?;
+    m.add("Up4Error", py.get_type::<Up4Error>())?;
+    m.add("FileOpenError", py.get_type::<FileOpenError>())?;
+    m.add("MissingDataError", py.get_type::<MissingDataError>())?;
+    m.add("MalformedDataError", py.get_type::<MalformedDataError>())?;
+    m.add("FileKindError", py.get_type::<FileKindError>())?;
+    m.add("TimestepError", py.get_type::<TimestepError>())?;
     Ok(())
 }
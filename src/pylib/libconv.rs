@@ -1,7 +1,10 @@
 //! This file provides coupling of functions to convert data to HDF5 file format
 //! Functions are taken from base::converter.rs
 use crate::converter::{self, *};
+use crate::datamanager::provenance;
+use crate::units::LengthUnit;
 use pyo3::prelude::*;
+use std::collections::HashMap;
 
 /// Convert particle data from a given format to HDF5 (H5Part-like).
 ///
@@ -52,6 +55,13 @@ impl PyConverter {
     /// diameter_field_name : str, optional
     ///     Name of the diameter field in the vtk files, by default None.
     ///
+    /// extra_fields : list[str], optional
+    ///     Names of additional point fields stored for every particle, by default [].
+    ///
+    /// length_unit : str, optional
+    ///     Unit of the positions and radii in the files ("m", "cm", "mm" or "um"), recorded
+    ///     in the output file, by default None.
+    ///
     /// Returns
     /// -------
     /// None
@@ -93,7 +103,8 @@ impl PyConverter {
     /// 
     #[pyo3(signature = (filenames, timestep, outname, filter = "(\\d+).vtk", 
         velocity_field_name = "v", radius_field_name = "radius", 
-        id_field_name = "id", type_field_name = "type", diameter_field_name = None))]
+        id_field_name = "id", type_field_name = "type", diameter_field_name = None,
+        extra_fields = vec![], length_unit = None))]
     #[staticmethod]
     fn vtk(
         filenames: Vec<&str>,
@@ -105,7 +116,10 @@ impl PyConverter {
         id_field_name: &str,
         type_field_name: &str,
         diameter_field_name: Option<&str>,
-    ) {
+        extra_fields: Vec<&str>,
+        length_unit: Option<&str>,
+    ) -> PyResult<()> {
+        let length_unit = parse_length_unit(length_unit)?;
         // Check filter extension to decide on how to proceed
         if filter.contains(".vtk")
         // legacy files to convert - defaults to LIGGGHTS naming
@@ -123,6 +137,9 @@ impl PyConverter {
                     .add_type_field(type_field_name)
                     .add_velocity_field(velocity_field_name),
             };
+            let converter = extra_fields
+                .iter()
+                .fold(converter, |converter, name| converter.add_extra_field(name));
             converter.write_hdf5_from_files(filenames, timestep, outname, filter);
         } else if filter.contains(".vtu")
         // xml files to convert
@@ -135,6 +152,9 @@ impl PyConverter {
                         .add_id_field(id_field_name)
                         .add_type_field(type_field_name)
                         .add_velocity_field(velocity_field_name);
+                    let converter = extra_fields
+                        .iter()
+                        .fold(converter, |converter, name| converter.add_extra_field(name));
                     converter.write_hdf5_from_files(filenames, timestep, outname, filter);
                 }
                 None => {
@@ -143,6 +163,9 @@ impl PyConverter {
                         .add_radius_field(radius_field_name)
                         .add_type_field(type_field_name)
                         .add_velocity_field(velocity_field_name);
+                    let converter = extra_fields
+                        .iter()
+                        .fold(converter, |converter, name| converter.add_extra_field(name));
                     converter.write_hdf5_from_files(filenames, timestep, outname, filter);
                 }
             }
@@ -157,6 +180,9 @@ impl PyConverter {
                         .add_id_field(id_field_name)
                         .add_type_field(type_field_name)
                         .add_velocity_field(velocity_field_name);
+                    let converter = extra_fields
+                        .iter()
+                        .fold(converter, |converter, name| converter.add_extra_field(name));
                     converter.write_hdf5_from_files(filenames, timestep, outname, filter);
                 }
                 None => {
@@ -165,10 +191,14 @@ impl PyConverter {
                         .add_radius_field(radius_field_name)
                         .add_type_field(type_field_name)
                         .add_velocity_field(velocity_field_name);
+                    let converter = extra_fields
+                        .iter()
+                        .fold(converter, |converter, name| converter.add_extra_field(name));
                     converter.write_hdf5_from_files(filenames, timestep, outname, filter);
                 }
             }
         }
+        record_length_unit(outname, length_unit)
     }
 
     /// Convert all VTK files in a folder to a HDF5 file.
@@ -203,6 +233,13 @@ impl PyConverter {
     /// diameter_field_name : str, optional
     ///     Name of the diameter field in the vtk files, by default None
     ///
+    /// extra_fields : list[str], optional
+    ///     Names of additional point fields stored for every particle, by default []
+    ///
+    /// length_unit : str, optional
+    ///     Unit of the positions and radii in the files ("m", "cm", "mm" or "um"), recorded
+    ///     in the output file, by default None
+    ///
     /// Returns
     /// -------
     /// None
@@ -242,7 +279,8 @@ impl PyConverter {
     /// 
     #[pyo3(signature = (folder, timestep, outname, filter = "(\\d+).vtk", 
     velocity_field_name = "v", radius_field_name = "radius", 
-    id_field_name = "id", type_field_name = "type", diameter_field_name = None))]
+    id_field_name = "id", type_field_name = "type", diameter_field_name = None,
+    extra_fields = vec![], length_unit = None))]
     #[staticmethod]
     fn vtk_from_folder(
         folder: &str,
@@ -254,7 +292,10 @@ impl PyConverter {
         id_field_name: &str,
         type_field_name: &str,
         diameter_field_name: Option<&str>,
-    ) {
+        extra_fields: Vec<&str>,
+        length_unit: Option<&str>,
+    ) -> PyResult<()> {
+        let length_unit = parse_length_unit(length_unit)?;
         // Check filter extension to decide on how to proceed
         if filter.contains(".vtk")
         // legacy files to convert - defaults to LIGGGHTS naming
@@ -272,6 +313,9 @@ impl PyConverter {
                     .add_type_field(type_field_name)
                     .add_velocity_field(velocity_field_name),
             };
+            let converter = extra_fields
+                .iter()
+                .fold(converter, |converter, name| converter.add_extra_field(name));
             converter.write_hdf5_from_folder(folder, timestep, outname, filter);
         } else if filter.contains(".vtu")
         // xml files to convert
@@ -284,6 +328,9 @@ impl PyConverter {
                         .add_id_field(id_field_name)
                         .add_type_field(type_field_name)
                         .add_velocity_field(velocity_field_name);
+                    let converter = extra_fields
+                        .iter()
+                        .fold(converter, |converter, name| converter.add_extra_field(name));
                     converter.write_hdf5_from_folder(folder, timestep, outname, filter);
                 }
                 None => {
@@ -292,6 +339,9 @@ impl PyConverter {
                         .add_radius_field(radius_field_name)
                         .add_type_field(type_field_name)
                         .add_velocity_field(velocity_field_name);
+                    let converter = extra_fields
+                        .iter()
+                        .fold(converter, |converter, name| converter.add_extra_field(name));
                     converter.write_hdf5_from_folder(folder, timestep, outname, filter);
                 }
             }
@@ -306,6 +356,9 @@ impl PyConverter {
                         .add_id_field(id_field_name)
                         .add_type_field(type_field_name)
                         .add_velocity_field(velocity_field_name);
+                    let converter = extra_fields
+                        .iter()
+                        .fold(converter, |converter, name| converter.add_extra_field(name));
                     converter.write_hdf5_from_folder(folder, timestep, outname, filter);
                 }
                 None => {
@@ -314,10 +367,14 @@ impl PyConverter {
                         .add_radius_field(radius_field_name)
                         .add_type_field(type_field_name)
                         .add_velocity_field(velocity_field_name);
+                    let converter = extra_fields
+                        .iter()
+                        .fold(converter, |converter, name| converter.add_extra_field(name));
                     converter.write_hdf5_from_folder(folder, timestep, outname, filter);
                 }
             }
         }
+        record_length_unit(outname, length_unit)
     }
 
     // TODO ensure this doesn't get accidentally swept up into doctests
@@ -355,6 +412,14 @@ impl PyConverter {
     /// sampling_steps : int, optional
     ///    Number of sampling steps for the savitzky-golay filter to calculate the velocity, by default 9
     ///
+    /// fields : dict[str, list[int]], optional
+    ///    Additional fields to store, mapping the field name to its column(s), e.g.
+    ///    {"temperature": [7], "omega": [8, 9, 10]}, by default None
+    ///
+    /// length_unit : str, optional
+    ///    Unit of the positions and the radius ("m", "cm", "mm" or "um"), recorded in the
+    ///    output file, by default None
+    ///
     /// Returns
     /// -------
     /// None
@@ -369,7 +434,9 @@ impl PyConverter {
         vel = false,
         interpolate = false,
         radius = 0.0,
-        sampling_steps = 9
+        sampling_steps = 9,
+        fields = None,
+        length_unit = None
     ))]
     #[staticmethod]
     fn csv(
@@ -383,11 +450,16 @@ impl PyConverter {
         interpolate: bool,
         radius: f64,
         sampling_steps: usize,
-    ) {
+        fields: Option<HashMap<String, Vec<i64>>>,
+        length_unit: Option<&str>,
+    ) -> PyResult<()> {
         // check if sampling_steps is odd
         if sampling_steps % 2 == 0 {
             panic!("Sampling steps must be a odd number!");
         }
+        let length_unit = parse_length_unit(length_unit)?;
+        let mut fields: Vec<(String, Vec<i64>)> = fields.unwrap_or_default().into_iter().collect();
+        fields.sort();
         csv_converter(
             filename,
             outname,
@@ -399,7 +471,9 @@ impl PyConverter {
             interpolate,
             radius,
             sampling_steps,
-        )
+            fields,
+        );
+        record_length_unit(outname, length_unit)
     }
 
     // TODO ensure this doesn't get accidentally swept up into doctests
@@ -456,6 +530,10 @@ impl PyConverter {
     ///
     ///     , by default chain
     ///
+    /// length_unit : str, optional
+    ///     Unit of the positions and the radius ("m", "cm", "mm" or "um"), recorded in the
+    ///     output file, by default None
+    ///
     //#[allow(unreachable_code, unused_variables)]
     #[pyo3(signature = (
         filename,
@@ -467,7 +545,8 @@ impl PyConverter {
         vel = false,
         interpolate = false,
         radius = 0.0,
-        method = "\"id_line\""
+        method = "\"id_line\"",
+        length_unit = None
     ))]
     #[staticmethod]
     fn csv_multi(
@@ -481,7 +560,9 @@ impl PyConverter {
         interpolate: bool,
         radius: f64,
         method: &str,
+        length_unit: Option<&str>,
     ) -> PyResult<()> {
+        let length_unit = parse_length_unit(length_unit)?;
         //errors out immediately because of the function is not implemented
         //return Err(PyErr::new::<pyo3::exceptions::PyNotImplementedError, _>(
         //"Multi CSV reader is not implemented yet. This feature comes in future!",
@@ -498,7 +579,7 @@ impl PyConverter {
             radius,
             method,
         );
-        Ok(())
+        record_length_unit(outname, length_unit)
     }
 
     /// Convert CSV file containing multiple particles into a HDF5 file.
@@ -547,6 +628,10 @@ impl PyConverter {
     /// radius : float, optional
     ///     Radius of the particle
     ///
+    /// length_unit : str, optional
+    ///     Unit of the positions and the radius ("m", "cm", "mm" or "um"), recorded in the
+    ///     output file
+    ///
     //#[allow(unreachable_code, unused_variables)]
     #[pyo3(signature = (
         filenames,
@@ -558,7 +643,8 @@ impl PyConverter {
         comment = "#",
         vel = false,
         interpolate = false,
-        radius = 0.0
+        radius = 0.0,
+        length_unit = None
     ))]
     #[staticmethod]
     fn csv_multi_files(
@@ -572,7 +658,9 @@ impl PyConverter {
         vel: bool,
         interpolate: bool,
         radius: f64,
+        length_unit: Option<&str>,
     ) -> PyResult<()> {
+        let length_unit = parse_length_unit(length_unit)?;
         // errors out immediately because of the function is not implemented
         //return Err(PyErr::new::<pyo3::exceptions::PyNotImplementedError, _>(
         //    "Multi CSV reader is not implemented yet. This feature comes in future!",
@@ -589,6 +677,35 @@ impl PyConverter {
             interpolate,
             radius,
         );
+        record_length_unit(outname, length_unit)
+    }
+
+    /// Record the unit of length of an existing up4 file, e.g. of a file converted
+    /// without `length_unit`.
+    ///
+    /// Parameters
+    /// ----------
+    /// filename : str
+    ///     Path to the HDF5 file
+    ///
+    /// length_unit : str
+    ///     Unit of the positions and radii: "m", "cm", "mm" or "um"
+    ///
+    #[staticmethod]
+    fn set_length_unit(filename: &str, length_unit: &str) -> PyResult<()> {
+        provenance::set_length_unit(filename, length_unit.parse::<LengthUnit>()?)?;
         Ok(())
     }
 }
+
+fn parse_length_unit(length_unit: Option<&str>) -> PyResult<Option<LengthUnit>> {
+    Ok(length_unit.map(str::parse::<LengthUnit>).transpose()?)
+}
+
+/// Record the unit of length of a converted file if it was given
+fn record_length_unit(outname: &str, length_unit: Option<LengthUnit>) -> PyResult<()> {
+    if let Some(length_unit) = length_unit {
+        provenance::set_length_unit(outname, length_unit)?;
+    }
+    Ok(())
+}
@@ -2,10 +2,15 @@ use super::PyData;
 use crate::grid;
 use crate::grid::*;
 use ndarray_stats::QuantileExt;
-use numpy::{IntoPyArray, PyArray1, PyArray2, PyArray3};
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyArray3, PyReadonlyArray4};
 use plotly::{HeatMap, Plot};
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyDict};
+use crate::error::Up4Error;
+use crate::units::LengthUnit;
 use crate::utilities::{nan_mean, nan_std};
+use std::collections::{BTreeMap, HashMap};
 
 /// A class containing all information for a 3D grid wrapping your system
 ///
@@ -23,6 +28,15 @@ use crate::utilities::{nan_mean, nan_std};
 /// cylindrical3d:
 ///     Create a 3D cylindrical grid with limits and spacing provided by user
 ///
+/// cylindrical3d_oriented:
+///     Create a 3D cylindrical grid around an arbitrary axis through an arbitrary point
+///
+/// spherical3d_from_data:
+///     Create a 3D spherical grid with limits and spacing from a PyData object
+///
+/// spherical3d:
+///     Create a 3D spherical grid with limits and spacing provided by user
+///
 /// cell_positions:
 ///     Return the positions of all cells in the grid
 ///
@@ -35,6 +49,17 @@ use crate::utilities::{nan_mean, nan_std};
 /// shape:
 ///     Return the shape of the grid
 ///
+/// minimum, maximum, merge:
+///     Combine the grid with another grid of the same kind, cells and limits. Grids can
+///     also be added, subtracted, multiplied and divided with +, -, * and /
+///
+/// save, load, saved_parameters:
+///     Save the grid with its geometry, weights and analysis parameters to a HDF5 or NPZ
+///     file and load it back
+///
+/// to_vtk:
+///     Export the grid to a VTK file, e.g. for ParaView
+///
 /// to_numpy:
 ///     Return the grid as a numpy array
 ///
@@ -123,6 +148,132 @@ impl PyGrid {
         }
     }
 
+    /// Create a 1D cartesian grid resolving a single axis, e.g. for a height profile.
+    /// Positions along the other two axes are ignored.
+    ///
+    /// Parameters
+    /// ----------
+    /// axis : int
+    ///     The resolved axis, 0: x, 1: y, 2: z
+    /// cells : int
+    ///     The number of cells along the axis
+    /// limit : List(float)
+    ///     The limits [min, max] along the axis
+    ///
+    /// Returns
+    /// -------
+    /// grid : Grid
+    ///     A up4.Grid object with a single cell along the other two axes
+    #[staticmethod]
+    fn cartesian1d(axis: usize, cells: usize, limit: Vec<f64>) -> PyResult<Self> {
+        check_axis(axis)?;
+        if limit.len() != 2 {
+            return Err(PyValueError::new_err(
+                "Cartesian1D requires the 2 limits [min, max] of the axis",
+            ));
+        }
+        let grid = Grid1D::new(axis, cells, grid::Dim::OneD([[limit[0], limit[1]]]))
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyGrid {
+            grid: Box::new(grid),
+        })
+    }
+
+    /// Create a 1D cartesian grid resolving a single axis with the limits of a PyData
+    /// object.
+    ///
+    /// Parameters
+    /// ----------
+    /// pydata : PyData
+    ///     A PyData object containing the data to be used to generate the grid
+    /// axis : int
+    ///     The resolved axis, 0: x, 1: y, 2: z
+    /// cells : int
+    ///     The number of cells along the axis
+    ///
+    /// Returns
+    /// -------
+    /// grid : Grid
+    ///     A up4.Grid object with a single cell along the other two axes
+    #[staticmethod]
+    fn cartesian1d_from_data(pydata: &PyData, axis: usize, cells: usize) -> PyResult<Self> {
+        check_axis(axis)?;
+        let grid = Grid1D::new(axis, cells, data_limits(pydata))
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyGrid {
+            grid: Box::new(grid),
+        })
+    }
+
+    /// Create a 2D cartesian grid resolving the plane normal to an axis. Positions along
+    /// the normal axis are ignored.
+    ///
+    /// Parameters
+    /// ----------
+    /// normal : int
+    ///     The axis normal to the plane, 0: yz plane, 1: xz plane, 2: xy plane
+    /// cells : List(int)
+    ///     The number of cells along the two axes of the plane. Must be of length 2
+    /// limit : List(float)
+    ///     The limits of the two axes of the plane. Must be of length 4, e.g.
+    ///     [xmin, xmax, ymin, ymax] for the xy plane
+    ///
+    /// Returns
+    /// -------
+    /// grid : Grid
+    ///     A up4.Grid object with a single cell along the normal axis
+    #[staticmethod]
+    fn cartesian2d(normal: usize, cells: Vec<usize>, limit: Vec<f64>) -> PyResult<Self> {
+        check_axis(normal)?;
+        if cells.len() != 2 || limit.len() != 4 {
+            return Err(PyValueError::new_err(
+                "Cartesian2D requires cells for 2 dimensions and 4 limits",
+            ));
+        }
+        let grid = Grid2D::new(
+            normal,
+            [cells[0], cells[1]],
+            grid::Dim::TwoD([[limit[0], limit[1]], [limit[2], limit[3]]]),
+        )
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyGrid {
+            grid: Box::new(grid),
+        })
+    }
+
+    /// Create a 2D cartesian grid resolving the plane normal to an axis with the limits
+    /// of a PyData object.
+    ///
+    /// Parameters
+    /// ----------
+    /// pydata : PyData
+    ///     A PyData object containing the data to be used to generate the grid
+    /// normal : int
+    ///     The axis normal to the plane, 0: yz plane, 1: xz plane, 2: xy plane
+    /// cells : List(int)
+    ///     The number of cells along the two axes of the plane. Must be of length 2
+    ///
+    /// Returns
+    /// -------
+    /// grid : Grid
+    ///     A up4.Grid object with a single cell along the normal axis
+    #[staticmethod]
+    fn cartesian2d_from_data(
+        pydata: &PyData,
+        normal: usize,
+        cells: Vec<usize>,
+    ) -> PyResult<Self> {
+        check_axis(normal)?;
+        if cells.len() != 2 {
+            return Err(PyValueError::new_err("Cartesian2D requires cells for 2 dimensions"));
+        }
+        let grid = Grid2D::new(normal, [cells[0], cells[1]], data_limits(pydata))
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyGrid {
+            grid: Box::new(grid),
+        })
+    }
+
     /// Return the positions of all cells in the grid.
     ///
     /// Returns
@@ -147,38 +298,59 @@ impl PyGrid {
     /// cells : List(int)
     ///     A list containing the number of cells in each direction. Must be of length 3
     /// limit : List(float)
-    ///     A list containing the limits of the grid in each direction. Must be of length 6
-    ///     The order is [rmin, rmax, zmin, zmax, phimin, phimax]
+    ///     The cartesian limits of the box enclosing the cylinder. Must be of length 6
+    ///     The order is [xmin, xmax, ymin, ymax, zmin, zmax]
     /// mode : str, optional
-    ///     The mode of the grid. Can be "volume". Other methods are not implemented yet, by default
-    ///     "volume"
+    ///     The radial spacing of the cells: "volume" for cells of equal volume, "radius" for
+    ///     cells of equal radial width or "edges" for the radial edges given in
+    ///     `radial_edges`, by default "volume"
+    /// inner_radius : float, optional
+    ///     Radius of a central shaft, positions closer to the axis are outside of the grid,
+    ///     by default 0.0. Can not be combined with `radial_edges`
+    /// radial_edges : List(float), optional
+    ///     The radial cell boundaries from the inner to the outer radius for the "edges"
+    ///     mode. Must be of length cells[0] + 1, a central shaft is given by the first edge
+    /// axis : int, optional
+    ///     The cartesian axis along the axis of the cylinder, 0: x (e.g. horizontal drums),
+    ///     1: y or 2: z, by default 2
     ///
     /// Returns
     /// -------
     /// grid : Grid
     ///     A up4.Grid object with the same dimensions dimensions defined by user
-    #[pyo3(signature = (cells, limit, mode = "volume"))]
+    #[pyo3(signature = (cells, limit, mode = "volume", inner_radius = 0.0, radial_edges = None, axis = 2))]
     #[staticmethod]
-    fn cylindrical3d(cells: Vec<usize>, limit: Vec<f64>, mode: &str) -> Self {
-        if cells.len() != 3 {
-            panic!("Cylindrical3D requires cells for 3 dimensions --> shape mismatch");
-        }
-        if limit.len() != 6 {
-            panic!("Cylindrical3D requires 2 limits for all 3 Dimensions --> shape mismatch ");
+    fn cylindrical3d(
+        cells: Vec<usize>,
+        limit: Vec<f64>,
+        mode: &str,
+        inner_radius: f64,
+        radial_edges: Option<Vec<f64>>,
+        axis: usize,
+    ) -> PyResult<Self> {
+        if cells.len() != 3 || limit.len() != 6 {
+            return Err(PyValueError::new_err(
+                "Cylindrical3D requires cells for 3 dimensions and 6 limits",
+            ));
         }
-        let grid = Box::new(grid::CylindricalGrid3D::new(
-            [cells[0], cells[1], cells[2]],
-            grid::Dim::ThreeD([
-                [limit[0], limit[1]],
-                [limit[2], limit[3]],
-                [limit[4], limit[5]],
-            ]),
-            mode,
-        ));
+        let grid = Box::new(
+            grid::CylindricalGrid3D::along_axis(
+                [cells[0], cells[1], cells[2]],
+                grid::Dim::ThreeD([
+                    [limit[0], limit[1]],
+                    [limit[2], limit[3]],
+                    [limit[4], limit[5]],
+                ]),
+                axis,
+                cylindrical_spacing(mode, inner_radius, radial_edges)?,
+                inner_radius,
+            )
+            .map_err(|e| PyValueError::new_err(e.to_string()))?,
+        );
 
-        PyGrid {
+        Ok(PyGrid {
             grid, //Box::new(grid),
-        }
+        })
     }
 
     /// Create a 3D cylindrical grid with limits and spacing from a PyData object.
@@ -190,29 +362,197 @@ impl PyGrid {
     /// cells : List(int)
     ///     A list containing the number of cells in each direction. Must be of length 3
     /// mode : str, optional
-    ///     The mode of the grid. Can be "volume". Other methods are not implemented yet, by default "volume"
+    ///     The radial spacing of the cells: "volume", "radius" or "edges", see
+    ///     `cylindrical3d`, by default "volume"
+    /// inner_radius : float, optional
+    ///     Radius of a central shaft, by default 0.0. Can not be combined with `radial_edges`
+    /// radial_edges : List(float), optional
+    ///     The radial cell boundaries for the "edges" mode, starting at the inner radius
+    /// axis : int, optional
+    ///     The cartesian axis along the axis of the cylinder, by default 2 (z)
     ///
     /// Returns
     /// -------
     /// grid : Grid
     ///     A up4.Grid object with the same dimensions as the input data
-    #[pyo3(signature = (pydata, cells, mode = "volume"))]
+    #[pyo3(signature = (pydata, cells, mode = "volume", inner_radius = 0.0, radial_edges = None, axis = 2))]
     #[staticmethod]
-    fn cylindrical3d_from_data(pydata: &PyData, cells: Vec<usize>, mode: &str) -> Self {
-        let stats = pydata.data.global_stats();
-        let dim = stats.dimensions();
-        let grid = CylindricalGrid3D::new(
+    fn cylindrical3d_from_data(
+        pydata: &PyData,
+        cells: Vec<usize>,
+        mode: &str,
+        inner_radius: f64,
+        radial_edges: Option<Vec<f64>>,
+        axis: usize,
+    ) -> PyResult<Self> {
+        if cells.len() != 3 {
+            return Err(PyValueError::new_err("Cylindrical3D requires cells for 3 dimensions"));
+        }
+        let grid = CylindricalGrid3D::along_axis(
+            [cells[0], cells[1], cells[2]],
+            data_limits(pydata),
+            axis,
+            cylindrical_spacing(mode, inner_radius, radial_edges)?,
+            inner_radius,
+        )
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyGrid {
+            grid: Box::new(grid),
+        })
+    }
+
+    /// Create a 3D cylindrical grid around an arbitrary axis, e.g. for inclined mixers.
+    /// The cells are resolved in r, theta and the height along the axis, which is the
+    /// projection of a position onto the normalised axis.
+    ///
+    /// Parameters
+    /// ----------
+    /// cells : List(int)
+    ///     A list containing the number of cells in r, theta and height. Must be of length 3
+    /// center : List(float)
+    ///     A point on the axis of the cylinder. Must be of length 3
+    /// axis : List(float)
+    ///     The direction of the axis, it does not need to be normalised. Must be of length 3
+    /// radius : float
+    ///     The outer radius of the cylinder
+    /// height : List(float)
+    ///     The limits [min, max] of the height along the axis
+    /// mode : str, optional
+    ///     The radial spacing of the cells: "volume", "radius" or "edges", see
+    ///     `cylindrical3d`, by default "volume"
+    /// inner_radius : float, optional
+    ///     Radius of a central shaft, by default 0.0. Can not be combined with `radial_edges`
+    /// radial_edges : List(float), optional
+    ///     The radial cell boundaries for the "edges" mode, starting at the inner radius
+    ///
+    /// Returns
+    /// -------
+    /// grid : Grid
+    ///     A up4.Grid object around the given axis
+    #[pyo3(signature = (cells, center, axis, radius, height, mode = "volume", inner_radius = 0.0, radial_edges = None))]
+    #[staticmethod]
+    #[allow(clippy::too_many_arguments)]
+    fn cylindrical3d_oriented(
+        cells: Vec<usize>,
+        center: Vec<f64>,
+        axis: Vec<f64>,
+        radius: f64,
+        height: Vec<f64>,
+        mode: &str,
+        inner_radius: f64,
+        radial_edges: Option<Vec<f64>>,
+    ) -> PyResult<Self> {
+        if cells.len() != 3 || center.len() != 3 || axis.len() != 3 || height.len() != 2 {
+            return Err(PyValueError::new_err(
+                "Cylindrical3D requires 3 cells, a centre and an axis of length 3 and 2 height \
+                limits",
+            ));
+        }
+        let grid = CylindricalGrid3D::oriented(
+            [cells[0], cells[1], cells[2]],
+            [center[0], center[1], center[2]],
+            [axis[0], axis[1], axis[2]],
+            radius,
+            [height[0], height[1]],
+            cylindrical_spacing(mode, inner_radius, radial_edges)?,
+            inner_radius,
+        )
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyGrid {
+            grid: Box::new(grid),
+        })
+    }
+
+    /// Create a 3D spherical grid with limits provided by user. The sphere is centred in
+    /// the middle of the limits and its radius is the largest half width of the limits.
+    /// The cells are resolved in r, theta (polar angle from the z axis, 0 to pi) and phi
+    /// (azimuth, -pi to pi), in that order.
+    ///
+    /// Parameters
+    /// ----------
+    /// cells : List(int)
+    ///     A list containing the number of cells in r, theta and phi. Must be of length 3
+    /// limit : List(float)
+    ///     A list containing the limits of the system in each direction. Must be of length 6
+    ///     The order is [xmin, xmax, ymin, ymax, zmin, zmax]
+    /// mode : str, optional
+    ///     The radial spacing of the cells: "volume" for cells of equal volume, "radius" for
+    ///     cells of equal radial width or "edges" for the radial edges given in
+    ///     `radial_edges`, by default "volume"
+    /// radial_edges : List(float), optional
+    ///     The radial cell boundaries from the inner to the outer radius for the "edges"
+    ///     mode. Must be of length cells[0] + 1
+    ///
+    /// Returns
+    /// -------
+    /// grid : Grid
+    ///     A up4.Grid object with the dimensions defined by user
+    #[pyo3(signature = (cells, limit, mode = "volume", radial_edges = None))]
+    #[staticmethod]
+    fn spherical3d(
+        cells: Vec<usize>,
+        limit: Vec<f64>,
+        mode: &str,
+        radial_edges: Option<Vec<f64>>,
+    ) -> PyResult<Self> {
+        if cells.len() != 3 || limit.len() != 6 {
+            return Err(PyValueError::new_err(
+                "Spherical3D requires cells for 3 dimensions and 6 limits",
+            ));
+        }
+        let grid = SphericalGrid3D::with_spacing(
             [cells[0], cells[1], cells[2]],
             grid::Dim::ThreeD([
-                [dim[[0, 0]], dim[[1, 0]]],
-                [dim[[0, 1]], dim[[1, 1]]],
-                [dim[[0, 2]], dim[[1, 2]]],
+                [limit[0], limit[1]],
+                [limit[2], limit[3]],
+                [limit[4], limit[5]],
             ]),
-            mode,
-        );
-        PyGrid {
+            radial_spacing(mode, radial_edges)?,
+        )
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyGrid {
             grid: Box::new(grid),
+        })
+    }
+
+    /// Create a 3D spherical grid with limits from a PyData object, see `spherical3d`.
+    ///
+    /// Parameters
+    /// ----------
+    /// pydata : PyData
+    ///     A PyData object containing the data to be used to generate the grid
+    /// cells : List(int)
+    ///     A list containing the number of cells in r, theta and phi. Must be of length 3
+    /// mode : str, optional
+    ///     The radial spacing of the cells: "volume", "radius" or "edges", by default
+    ///     "volume"
+    /// radial_edges : List(float), optional
+    ///     The radial cell boundaries for the "edges" mode
+    ///
+    /// Returns
+    /// -------
+    /// grid : Grid
+    ///     A up4.Grid object enclosing the input data
+    #[pyo3(signature = (pydata, cells, mode = "volume", radial_edges = None))]
+    #[staticmethod]
+    fn spherical3d_from_data(
+        pydata: &PyData,
+        cells: Vec<usize>,
+        mode: &str,
+        radial_edges: Option<Vec<f64>>,
+    ) -> PyResult<Self> {
+        if cells.len() != 3 {
+            return Err(PyValueError::new_err("Spherical3D requires cells for 3 dimensions"));
         }
+        let grid = SphericalGrid3D::with_spacing(
+            [cells[0], cells[1], cells[2]],
+            data_limits(pydata),
+            radial_spacing(mode, radial_edges)?,
+        )
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyGrid {
+            grid: Box::new(grid),
+        })
     }
 
     /// Check if the particle is within the grid.
@@ -411,6 +751,34 @@ impl PyGrid {
         self.grid.get_zpositions().to_owned().into_pyarray(_py)
     }
 
+    /// Return the units of the grid.
+    ///
+    /// Returns
+    /// -------
+    ///
+    /// units : dict
+    ///     "length": unit of the cell positions, e.g. "mm", and "value": unit of the
+    ///     values, e.g. "mm/s". Unknown units are None.
+    fn units<'py>(&self, py: Python<'py>) -> &'py PyDict {
+        units_dict(py, self.grid.get_units())
+    }
+
+    /// Set the units of the grid, e.g. of a grid created from numpy data.
+    ///
+    /// Parameters
+    /// ----------
+    ///
+    /// length : str, optional
+    ///     Unit of the cell positions: "m", "cm", "mm" or "um", unknown if not given
+    /// value : str, optional
+    ///     Unit of the values, unknown if not given
+    #[pyo3(signature = (length = None, value = None))]
+    fn set_units(&mut self, length: Option<&str>, value: Option<String>) -> PyResult<()> {
+        let length = length.map(str::parse::<LengthUnit>).transpose()?;
+        self.grid.set_units(GridUnits::new(length, value));
+        Ok(())
+    }
+
     /// Detect outliers and remove them depending on the mode
     /// Three different modes are available:
     /// 1. Set the outlier to zero
@@ -484,6 +852,183 @@ impl PyGrid {
     fn cell_id(&self, x: f64, y: f64, z: f64) -> [usize; 3] {
         self.grid.cell_id([x, y, z]).unwrap()
     }
+
+    fn __add__(&self, other: GridOperand) -> PyResult<PyGrid> {
+        self.operate(other, GridOperation::Add)
+    }
+
+    fn __radd__(&self, other: f64) -> PyGrid {
+        self.operate_reversed(other, GridOperation::Add)
+    }
+
+    fn __sub__(&self, other: GridOperand) -> PyResult<PyGrid> {
+        self.operate(other, GridOperation::Subtract)
+    }
+
+    fn __rsub__(&self, other: f64) -> PyGrid {
+        self.operate_reversed(other, GridOperation::Subtract)
+    }
+
+    fn __mul__(&self, other: GridOperand) -> PyResult<PyGrid> {
+        self.operate(other, GridOperation::Multiply)
+    }
+
+    fn __rmul__(&self, other: f64) -> PyGrid {
+        self.operate_reversed(other, GridOperation::Multiply)
+    }
+
+    fn __truediv__(&self, other: GridOperand) -> PyResult<PyGrid> {
+        self.operate(other, GridOperation::Divide)
+    }
+
+    fn __rtruediv__(&self, other: f64) -> PyGrid {
+        self.operate_reversed(other, GridOperation::Divide)
+    }
+
+    /// Return the smaller value of this and another grid (or a number) in each cell.
+    ///
+    /// Parameters
+    /// ----------
+    /// other : Grid or float
+    ///     A grid with the same kind, cells and limits, or a number
+    ///
+    /// Returns
+    /// -------
+    /// grid : Grid
+    ///     A new grid, NaN values are ignored
+    fn minimum(&self, other: GridOperand) -> PyResult<PyGrid> {
+        self.operate(other, GridOperation::Minimum)
+    }
+
+    /// Return the larger value of this and another grid (or a number) in each cell.
+    ///
+    /// Parameters
+    /// ----------
+    /// other : Grid or float
+    ///     A grid with the same kind, cells and limits, or a number
+    ///
+    /// Returns
+    /// -------
+    /// grid : Grid
+    ///     A new grid, NaN values are ignored
+    fn maximum(&self, other: GridOperand) -> PyResult<PyGrid> {
+        self.operate(other, GridOperation::Maximum)
+    }
+
+    /// Merge two partial results of the same quantity, e.g. the velocity fields of two
+    /// experiments, into their mean weighted by the weights of the cells.
+    ///
+    /// Parameters
+    /// ----------
+    /// other : Grid
+    ///     A grid with the same kind, cells and limits
+    ///
+    /// Returns
+    /// -------
+    /// grid : Grid
+    ///     A new grid with the weighted mean values and the summed weights. Cells without
+    ///     weight in both grids are NaN
+    fn merge(&self, other: &PyGrid) -> PyResult<PyGrid> {
+        let grid = arithmetic::merge(self.grid.as_ref(), other.grid.as_ref())
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyGrid { grid })
+    }
+
+    /// Save the grid with its geometry, values, weights, units and the parameters of the
+    /// analysis that created it.
+    ///
+    /// Parameters
+    /// ----------
+    /// filename : str
+    ///     Path of the file, files ending in ".npz" are written with numpy, all others as
+    ///     HDF5. An existing file is overwritten
+    /// parameters : dict, optional
+    ///     Parameters of the analysis, e.g. {"function": "vectorfield", "min_velocity": 0.1}.
+    ///     The values are stored as strings
+    ///
+    #[pyo3(signature = (filename, parameters=None))]
+    fn save(
+        &self,
+        py: Python,
+        filename: &str,
+        parameters: Option<HashMap<String, &PyAny>>,
+    ) -> PyResult<()> {
+        let state = GridState::new(self.grid.as_ref(), string_parameters(parameters)?)?;
+        save_state(py, &state, filename)
+    }
+
+    /// Load a grid saved with `save`.
+    ///
+    /// Parameters
+    /// ----------
+    /// filename : str
+    ///     Path of a HDF5 or NPZ (ending in ".npz") grid file
+    ///
+    /// Returns
+    /// -------
+    /// grid : RustGrid or RustVectorGrid
+    ///     The grid with the geometry, values, weights and units it was saved with
+    ///
+    #[staticmethod]
+    fn load(py: Python, filename: &str) -> PyResult<PyObject> {
+        let grid = load_state(py, filename)?.to_grid()?;
+        if let Some(vector_grid) = grid.as_any().downcast_ref::<VectorGrid>() {
+            let grid = vector_grid.clone();
+            return Ok(PyVecGrid { grid }.into_py(py));
+        }
+        Ok(PyGrid { grid }.into_py(py))
+    }
+
+    /// Return the analysis parameters a grid file was saved with as a dict of strings.
+    #[staticmethod]
+    fn saved_parameters(py: Python, filename: &str) -> PyResult<BTreeMap<String, String>> {
+        Ok(load_state(py, filename)?.parameters)
+    }
+
+    /// Export the grid to a VTK file with the values and weights as the cell arrays "data"
+    /// and "weights", e.g. to open it in ParaView.
+    ///
+    /// Parameters
+    /// ----------
+    /// filename : str
+    ///     Path of the file. Files ending in ".vtk" are legacy VTK files, otherwise the
+    ///     extension must be ".vtr" for cartesian grids (a rectilinear grid) or ".vts" for
+    ///     cylindrical and spherical grids (a structured grid of the curved cells)
+    ///
+    fn to_vtk(&self, filename: &str) -> PyResult<()> {
+        grid::write_vtk(self.grid.as_ref(), filename)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+impl PyGrid {
+    /// Apply `operation` with the grid on the left and `other` on the right
+    fn operate(&self, other: GridOperand, operation: GridOperation) -> PyResult<PyGrid> {
+        let grid = match other {
+            GridOperand::Grid(other) => {
+                arithmetic::combine(self.grid.as_ref(), other.grid.as_ref(), operation)
+                    .map_err(|e| PyValueError::new_err(e.to_string()))?
+            }
+            GridOperand::Scalar(value) => {
+                arithmetic::map_values(self.grid.as_ref(), |x| operation.apply(x, value))
+            }
+        };
+        Ok(PyGrid { grid })
+    }
+
+    /// Apply `operation` with the number `other` on the left and the grid on the right
+    fn operate_reversed(&self, other: f64, operation: GridOperation) -> PyGrid {
+        PyGrid {
+            grid: arithmetic::map_values(self.grid.as_ref(), |x| operation.apply(other, x)),
+        }
+    }
+}
+
+/// Right hand side of an operation on a grid
+#[derive(FromPyObject)]
+enum GridOperand<'a> {
+    Grid(PyRef<'a, PyGrid>),
+    Scalar(f64),
 }
 
 /// A 3D Grid containing Vector Data
@@ -497,6 +1042,11 @@ pub struct PyVecGrid {
 
 #[pymethods]
 impl PyVecGrid {
+    /// Return the units of the grid as a dict with the keys "length" and "value".
+    fn units<'py>(&self, py: Python<'py>) -> &'py PyDict {
+        units_dict(py, self.grid.get_units())
+    }
+
     fn to_numpy<'py>(
         &self,
         _py: Python<'py>,
@@ -595,6 +1145,95 @@ impl PyVecGrid {
         )
     }
 
+    fn __add__(&self, other: VectorOperand) -> PyResult<PyVecGrid> {
+        self.operate(other, GridOperation::Add)
+    }
+
+    fn __radd__(&self, other: f64) -> PyVecGrid {
+        self.operate_reversed(other, GridOperation::Add)
+    }
+
+    fn __sub__(&self, other: VectorOperand) -> PyResult<PyVecGrid> {
+        self.operate(other, GridOperation::Subtract)
+    }
+
+    fn __rsub__(&self, other: f64) -> PyVecGrid {
+        self.operate_reversed(other, GridOperation::Subtract)
+    }
+
+    fn __mul__(&self, other: VectorOperand) -> PyResult<PyVecGrid> {
+        self.operate(other, GridOperation::Multiply)
+    }
+
+    fn __rmul__(&self, other: f64) -> PyVecGrid {
+        self.operate_reversed(other, GridOperation::Multiply)
+    }
+
+    fn __truediv__(&self, other: VectorOperand) -> PyResult<PyVecGrid> {
+        self.operate(other, GridOperation::Divide)
+    }
+
+    fn __rtruediv__(&self, other: f64) -> PyVecGrid {
+        self.operate_reversed(other, GridOperation::Divide)
+    }
+
+    /// Return the smaller value of each component of this and another vector grid (or a
+    /// number) in each cell.
+    fn minimum(&self, other: VectorOperand) -> PyResult<PyVecGrid> {
+        self.operate(other, GridOperation::Minimum)
+    }
+
+    /// Return the larger value of each component of this and another vector grid (or a
+    /// number) in each cell.
+    fn maximum(&self, other: VectorOperand) -> PyResult<PyVecGrid> {
+        self.operate(other, GridOperation::Maximum)
+    }
+
+    /// Merge each component with the weighted mean of this and another vector grid, e.g.
+    /// of two experiments.
+    fn merge(&self, other: &PyVecGrid) -> PyResult<PyVecGrid> {
+        let grid = self
+            .grid
+            .merge(&other.grid)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyVecGrid { grid })
+    }
+
+    /// Save the vector grid with its geometry, values and weights of all components, units
+    /// and the parameters of the analysis that created it. See `RustGrid.save`.
+    #[pyo3(signature = (filename, parameters=None))]
+    fn save(
+        &self,
+        py: Python,
+        filename: &str,
+        parameters: Option<HashMap<String, &PyAny>>,
+    ) -> PyResult<()> {
+        let state = GridState::new(&self.grid, string_parameters(parameters)?)?;
+        save_state(py, &state, filename)
+    }
+
+    /// Export the vector grid to a VTK file with the vectors in cartesian components as the
+    /// cell array "data" and the weights of the components as "weights". See
+    /// `RustGrid.to_vtk`.
+    fn to_vtk(&self, filename: &str) -> PyResult<()> {
+        grid::write_vtk(&self.grid, filename).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Load a vector grid saved with `save`.
+    #[staticmethod]
+    fn load(py: Python, filename: &str) -> PyResult<PyVecGrid> {
+        let grid = load_state(py, filename)?.to_grid()?;
+        match grid.as_any().downcast_ref::<VectorGrid>() {
+            Some(vector_grid) => Ok(PyVecGrid {
+                grid: vector_grid.clone(),
+            }),
+            None => Err(PyValueError::new_err(format!(
+                "{} contains a scalar grid, load it with RustGrid.load",
+                filename
+            ))),
+        }
+    }
+
     fn __str__(&self) -> PyResult<String> {
         Ok(format!(
             "3D Vector Grid: \n\tCells: {:?} \n\txlim: {:?} \
@@ -632,3 +1271,196 @@ impl PyVecGrid {
         ))
     }
 }
+
+impl PyVecGrid {
+    /// Apply `operation` to each component with the grid on the left and `other` on the right
+    fn operate(&self, other: VectorOperand, operation: GridOperation) -> PyResult<PyVecGrid> {
+        let grid = match other {
+            VectorOperand::Grid(other) => self
+                .grid
+                .combine(&other.grid, operation)
+                .map_err(|e| PyValueError::new_err(e.to_string()))?,
+            VectorOperand::Scalar(value) => self.grid.map_values(|x| operation.apply(x, value)),
+        };
+        Ok(PyVecGrid { grid })
+    }
+
+    /// Apply `operation` to each component with the number `other` on the left
+    fn operate_reversed(&self, other: f64, operation: GridOperation) -> PyVecGrid {
+        PyVecGrid {
+            grid: self.grid.map_values(|x| operation.apply(other, x)),
+        }
+    }
+}
+
+/// Right hand side of an operation on a vector grid
+#[derive(FromPyObject)]
+enum VectorOperand<'a> {
+    Grid(PyRef<'a, PyVecGrid>),
+    Scalar(f64),
+}
+
+fn radial_spacing(mode: &str, radial_edges: Option<Vec<f64>>) -> PyResult<RadialSpacing> {
+    match (mode, radial_edges) {
+        ("edges", Some(edges)) => Ok(RadialSpacing::Edges(edges)),
+        ("edges", None) => Err(PyValueError::new_err(
+            "The \"edges\" mode requires the radial_edges",
+        )),
+        (_, Some(_)) => Err(PyValueError::new_err(
+            "radial_edges can only be used with the \"edges\" mode",
+        )),
+        (mode, None) => mode
+            .parse()
+            .map_err(|e: anyhow::Error| PyValueError::new_err(e.to_string())),
+    }
+}
+
+fn cylindrical_spacing(
+    mode: &str,
+    inner_radius: f64,
+    radial_edges: Option<Vec<f64>>,
+) -> PyResult<RadialSpacing> {
+    if inner_radius != 0.0 && radial_edges.is_some() {
+        return Err(PyValueError::new_err(
+            "inner_radius can not be used with radial_edges, the first radial edge is the inner \
+            radius",
+        ));
+    }
+    radial_spacing(mode, radial_edges)
+}
+
+fn check_axis(axis: usize) -> PyResult<()> {
+    if axis > 2 {
+        return Err(PyValueError::new_err(format!(
+            "Axis {} does not exist, valid axes are 0, 1 and 2",
+            axis
+        )));
+    }
+    Ok(())
+}
+
+/// Limits of all three axes of the dataset
+fn data_limits(pydata: &PyData) -> grid::Dim {
+    let stats = pydata.data.global_stats();
+    let dim = stats.dimensions();
+    grid::Dim::ThreeD([
+        [dim[[0, 0]], dim[[1, 0]]],
+        [dim[[0, 1]], dim[[1, 1]]],
+        [dim[[0, 2]], dim[[1, 2]]],
+    ])
+}
+
+fn units_dict<'py>(py: Python<'py>, units: &GridUnits) -> &'py PyDict {
+    let key_vals: Vec<(&str, PyObject)> = vec![
+        ("length", units.length.map(|unit| unit.symbol()).to_object(py)),
+        ("value", units.value.to_object(py)),
+    ];
+    key_vals.into_py_dict(py)
+}
+
+/// Convert the values of analysis parameters to strings
+fn string_parameters(
+    parameters: Option<HashMap<String, &PyAny>>,
+) -> PyResult<BTreeMap<String, String>> {
+    parameters
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, value)| Ok((name, value.str()?.to_string())))
+        .collect()
+}
+
+/// Names of the NPZ entries that are not part of the grid geometry
+const NPZ_ENTRIES: [&str; 6] = [
+    "grid kind",
+    "data",
+    "weights",
+    "length unit",
+    "value unit",
+    "parameters",
+];
+
+/// Write `state` as HDF5, or with the same names as NPZ if `filename` ends in ".npz"
+fn save_state(py: Python, state: &GridState, filename: &str) -> PyResult<()> {
+    if !filename.ends_with(".npz") {
+        return Ok(state.write_hdf5(filename)?);
+    }
+    let arrays = PyDict::new(py);
+    arrays.set_item(NPZ_ENTRIES[0], state.kind.name())?;
+    arrays.set_item(NPZ_ENTRIES[1], state.data.clone().into_pyarray(py))?;
+    arrays.set_item(NPZ_ENTRIES[2], state.weights.clone().into_pyarray(py))?;
+    if let Some(length) = state.units.length {
+        arrays.set_item(NPZ_ENTRIES[3], length.symbol())?;
+    }
+    if let Some(value) = &state.units.value {
+        arrays.set_item(NPZ_ENTRIES[4], value)?;
+    }
+    let parameters: Vec<String> = state
+        .parameters
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    arrays.set_item(NPZ_ENTRIES[5], parameters)?;
+    for (name, values) in &state.geometry {
+        arrays.set_item(name, values.clone().into_pyarray(py))?;
+    }
+    py.import("numpy")?
+        .call_method("savez", (filename,), Some(arrays))?;
+    Ok(())
+}
+
+/// Read a state written by `save_state`
+fn load_state(py: Python, filename: &str) -> PyResult<GridState> {
+    if !filename.ends_with(".npz") {
+        return Ok(GridState::read_hdf5(filename)?);
+    }
+    let kwargs = [("allow_pickle", false)].into_py_dict(py);
+    let npz = py
+        .import("numpy")?
+        .call_method("load", (filename,), Some(kwargs))?;
+    let files: Vec<String> = npz.getattr("files")?.extract()?;
+    if !files.iter().any(|name| name == NPZ_ENTRIES[0]) {
+        npz.call_method0("close")?;
+        return Err(Up4Error::WrongFileKind {
+            filename: filename.to_string(),
+            expected: "up4 grid".to_string(),
+            found: format!("NPZ file without a \"{}\" entry", NPZ_ENTRIES[0]),
+        }
+        .into());
+    }
+    let item = |name: &str| npz.get_item(name)?.call_method0("tolist");
+    let optional = |name: &str| -> PyResult<Option<String>> {
+        match files.iter().any(|file| file == name) {
+            true => item(name)?.extract(),
+            false => Ok(None),
+        }
+    };
+    let array = |name: &str| -> PyResult<ndarray::Array4<f64>> {
+        let array: PyReadonlyArray4<f64> = npz.get_item(name)?.extract()?;
+        Ok(array.as_array().to_owned())
+    };
+    let mut geometry = BTreeMap::new();
+    for name in files.iter().filter(|name| !NPZ_ENTRIES.contains(&name.as_str())) {
+        geometry.insert(name.clone(), item(name)?.extract()?);
+    }
+    let parameters = item(NPZ_ENTRIES[5])?
+        .extract::<Vec<String>>()?
+        .into_iter()
+        .map(|parameter| match parameter.split_once('=') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => (parameter, String::new()),
+        })
+        .collect();
+    let length = optional(NPZ_ENTRIES[3])?
+        .map(|unit| unit.parse::<LengthUnit>())
+        .transpose()?;
+    let state = GridState {
+        kind: item(NPZ_ENTRIES[0])?.extract::<String>()?.parse()?,
+        geometry,
+        data: array(NPZ_ENTRIES[1])?,
+        weights: array(NPZ_ENTRIES[2])?,
+        units: GridUnits::new(length, optional(NPZ_ENTRIES[4])?),
+        parameters,
+    };
+    npz.call_method0("close")?;
+    Ok(state)
+}
@@ -14,7 +14,12 @@ use crate::{
 };
 use colorous::Gradient;
 
-use plotly::{contour::Contours, layout::themes::PLOTLY_WHITE, Layout, Plot, Trace};
+use plotly::{
+    common::Title,
+    contour::Contours,
+    layout::{themes::PLOTLY_WHITE, Axis},
+    Layout, Plot, Trace,
+};
 use pyo3::{exceptions::PyValueError, prelude::*};
 
 #[pyclass(name = "RustPlotter2D", subclass)]
@@ -106,7 +111,7 @@ impl PyPlotter2D {
         //     traces.push(trace);
         // }
         let template = &*PLOTLY_WHITE;
-        let layout = Layout::new().template(template);
+        let layout = self.labelled_layout(axis).template(template);
         let plot: Plot = plot(quiver_traces, layout);
         let plotting_string = plot.to_json();
         self.plotting_string = plotting_string;
@@ -167,7 +172,7 @@ impl PyPlotter2D {
             _ => unit_vector_plotter.create_quiver_traces(1.0, cmap),
         };
 
-        let layout: Layout = Layout::new();
+        let layout: Layout = self.labelled_layout(axis);
         let plot: Plot = plot(unit_vector_traces, layout);
         let plotting_string = plot.to_json();
         self.plotting_string = plotting_string;
@@ -215,7 +220,7 @@ impl PyPlotter2D {
         for trace in heatmap_traces {
             traces.push(trace)
         }
-        let layout: Layout = Layout::new();
+        let layout: Layout = self.labelled_layout(axis);
         let plot: Plot = plot(traces, layout);
         let plotting_string = plot.to_json();
         self.plotting_string = plotting_string;
@@ -276,7 +281,7 @@ impl PyPlotter2D {
                     .auto_contour(false),
             );
         }
-        let layout: Layout = Layout::new();
+        let layout: Layout = self.labelled_layout(axis);
         let plot: Plot = plot(traces, layout);
         let plotting_string = plot.to_json();
         self.plotting_string = plotting_string;
@@ -545,6 +550,34 @@ impl PyPlotter2D {
 }
 
 impl PyPlotter2D {
+    /// Layout of a plot of the plane normal to `axis`, with the axes labelled with the
+    /// names of the grid dimensions and their units
+    fn labelled_layout(&self, axis: usize) -> Layout {
+        let units = self.grid.get_units();
+        let cylindrical = self.grid.is_cylindrical();
+        let spherical = self.grid.is_spherical();
+        let label = |dim: usize| {
+            if spherical && dim > 0 {
+                ["", "theta [rad]", "phi [rad]"][dim].to_string()
+            } else if cylindrical && dim == 1 {
+                "theta [rad]".to_string()
+            } else if (cylindrical || spherical) && dim == 0 {
+                units.axis_label("r")
+            } else {
+                units.axis_label(["x", "y", "z"][dim])
+            }
+        };
+        // select yz (0), xz (1) or xy (2) plane
+        let (horizontal, vertical) = match axis {
+            0 => (1, 2),
+            1 => (0, 2),
+            _ => (0, 1),
+        };
+        Layout::new()
+            .x_axis(Axis::new().title(Title::new(&label(horizontal))))
+            .y_axis(Axis::new().title(Title::new(&label(vertical))))
+    }
+
     fn get_gradient(&self, colour_map: Option<&str>) -> Option<Gradient> {
         // Once we get reflection, maybe this will look less bad??
         match colour_map {
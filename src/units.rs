@@ -0,0 +1,158 @@
+//! Units of the quantities stored in up4 files
+//!
+//! Tracking systems and simulation engines write positions in metres or millimetres.
+//! Files record their unit of length in the "length unit" attribute, times are always
+//! stored in seconds. Grids carry the unit of their cell positions and of their values,
+//! so plots can label their axes and comparisons can convert between two datasets.
+
+use crate::error::{Result, Up4Error};
+use std::fmt;
+
+/// Unit of all positions, lengths and radii in a dataset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthUnit {
+    Metre,
+    Centimetre,
+    Millimetre,
+    Micrometre,
+}
+
+impl LengthUnit {
+    /// Symbol of the unit, e.g. "mm"
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            LengthUnit::Metre => "m",
+            LengthUnit::Centimetre => "cm",
+            LengthUnit::Millimetre => "mm",
+            LengthUnit::Micrometre => "um",
+        }
+    }
+
+    /// Length of one unit in metres
+    pub fn in_metres(&self) -> f64 {
+        match self {
+            LengthUnit::Metre => 1.0,
+            LengthUnit::Centimetre => 1e-2,
+            LengthUnit::Millimetre => 1e-3,
+            LengthUnit::Micrometre => 1e-6,
+        }
+    }
+
+    /// Return the unit whose length is `metres`, if there is one
+    pub fn from_metres(metres: f64) -> Option<Self> {
+        [
+            LengthUnit::Metre,
+            LengthUnit::Centimetre,
+            LengthUnit::Millimetre,
+            LengthUnit::Micrometre,
+        ]
+        .into_iter()
+        .find(|unit| (unit.in_metres() / metres - 1.0).abs() < 1e-9)
+    }
+
+    /// Factor converting a length in this unit into a length in `other`
+    ///
+    /// # Examples
+    /// ```
+    /// use upppp_rust::units::LengthUnit;
+    /// assert_eq!(LengthUnit::Metre.factor_to(LengthUnit::Millimetre), 1000.0);
+    /// ```
+    pub fn factor_to(&self, other: LengthUnit) -> f64 {
+        self.in_metres() / other.in_metres()
+    }
+
+    /// Symbol of a velocity in this unit, e.g. "mm/s"
+    pub fn velocity_symbol(&self) -> String {
+        format!("{}/s", self.symbol())
+    }
+}
+
+impl fmt::Display for LengthUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl std::str::FromStr for LengthUnit {
+    type Err = Up4Error;
+
+    fn from_str(unit: &str) -> Result<Self> {
+        match unit.trim() {
+            "m" | "metre" | "meter" => Ok(LengthUnit::Metre),
+            "cm" | "centimetre" | "centimeter" => Ok(LengthUnit::Centimetre),
+            "mm" | "millimetre" | "millimeter" => Ok(LengthUnit::Millimetre),
+            "um" | "µm" | "micrometre" | "micrometer" => Ok(LengthUnit::Micrometre),
+            _ => Err(Up4Error::InvalidArgument(format!(
+                "Length unit {} is not valid. Valid units are: \"m\", \"cm\", \"mm\", \"um\"",
+                unit
+            ))),
+        }
+    }
+}
+
+/// Units of the cell positions and the values of a grid. Unknown units are `None`, e.g.
+/// for grids of datasets written before units were recorded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GridUnits {
+    pub length: Option<LengthUnit>,
+    /// unit of the values in the cells, e.g. "m/s" for a velocity field
+    pub value: Option<String>,
+}
+
+impl GridUnits {
+    pub fn new(length: Option<LengthUnit>, value: Option<String>) -> Self {
+        GridUnits { length, value }
+    }
+
+    /// Units of a field of velocities of a dataset with the unit of length `length`
+    pub fn velocity(length: Option<LengthUnit>) -> Self {
+        GridUnits {
+            length,
+            value: length.map(|unit| unit.velocity_symbol()),
+        }
+    }
+
+    /// Label of an axis along the cell positions, e.g. "x [mm]"
+    pub fn axis_label(&self, name: &str) -> String {
+        with_unit(name, self.length.map(|unit| unit.symbol()))
+    }
+
+    /// Label of the values of the grid, e.g. "velocity [m/s]"
+    pub fn value_label(&self, name: &str) -> String {
+        with_unit(name, self.value.as_deref())
+    }
+}
+
+fn with_unit(name: &str, unit: Option<&str>) -> String {
+    match unit {
+        Some(unit) => format!("{} [{}]", name, unit),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversion() {
+        let unit: LengthUnit = "mm".parse().unwrap();
+        assert_eq!(unit, LengthUnit::Millimetre);
+        assert!((unit.factor_to(LengthUnit::Metre) - 1e-3).abs() < 1e-15);
+        assert!("inch".parse::<LengthUnit>().is_err());
+        // a dataset in mm scaled by 0.001 is in m
+        assert_eq!(
+            LengthUnit::from_metres(unit.in_metres() / 0.001),
+            Some(LengthUnit::Metre)
+        );
+        assert_eq!(LengthUnit::from_metres(0.5), None);
+    }
+
+    #[test]
+    fn labels() {
+        let units = GridUnits::velocity(Some(LengthUnit::Millimetre));
+        assert_eq!(units.axis_label("x"), "x [mm]");
+        assert_eq!(units.value_label("velocity"), "velocity [mm/s]");
+        assert_eq!(GridUnits::default().axis_label("x"), "x");
+    }
+}
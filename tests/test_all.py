@@ -51,6 +51,62 @@ def extension(request):
         return "vtu"
 
 
+@pytest.fixture
+def synthetic(request):
+    """Returns a instance of data created from numpy arrays, random positions are seeded"""
+    rng = np.random.default_rng(2021)
+    time = np.linspace(0, 1, 11)
+    if request.param == "lines":
+        position = np.zeros((11, 2, 3))
+        position[:, 0, 0] = time
+        position[:, 1, 0] = 2 * time
+        position[:, 1, 1:] = 1.0
+        return up4.Data.from_numpy(time, position)
+    elif request.param == "fields":
+        position = rng.random((11, 2, 3))
+        temperature = np.arange(22, dtype=float).reshape((11, 2, 1))
+        return up4.Data.from_numpy(time, position, fields={"temperature": temperature})
+    elif request.param == "swapped ids":
+        # the two particles swap their place in the arrays from timestep 5 on
+        position = np.zeros((11, 2, 3))
+        position[:, 0, 0] = time
+        position[:, 1, 0] = 2 * time
+        particle_id = np.zeros((11, 2))
+        particle_id[:, 1] = 1
+        position[5:] = position[5:, ::-1]
+        particle_id[5:] = particle_id[5:, ::-1]
+        return up4.Data.from_numpy(time, position, particle_id=particle_id)
+    elif request.param == "uneven time":
+        time = np.array([0.0, 0.1, 0.25, 0.7])
+        position = np.zeros((4, 1, 3))
+        position[:, 0, 0] = time
+        return up4.Data.from_numpy(time, position)
+    elif request.param == "types":
+        time = np.linspace(0, 1, 5)
+        position = np.full((5, 3, 3), 0.25)
+        position[:, 2, :] = 0.75
+        velocity = np.zeros((5, 3, 3))
+        velocity[:, :, 2] = [1.0, 3.0, 5.0]
+        # two small particles of type 0 and one large particle of type 1
+        particle_type = np.tile([0.0, 0.0, 1.0], (5, 1))
+        return up4.Data.from_numpy(
+            time, position, velocity=velocity, particle_type=particle_type
+        )
+    elif request.param == "millimetres":
+        position = rng.random((11, 2, 3))
+        velocity = np.ones((11, 2, 3))
+        return up4.Data.from_numpy(time, position, velocity=velocity, length_unit="mm")
+    elif request.param == "random":
+        position = rng.random((11, 3, 3))
+        velocity = np.ones((11, 3, 3))
+        return up4.Data.from_numpy(time, position, velocity=velocity)
+    elif request.param == "path":
+        time = np.linspace(0, 1, 5)
+        position = np.full((5, 1, 3), 0.5)
+        position[:, 0, 0] = np.linspace(0.1, 0.9, 5)
+        return up4.Data.from_numpy(time, position)
+
+
 @pytest.mark.parametrize("extension", ["vtk", "vtu"], indirect=True)
 class TestVtk:
     def test_generated(self, extension):
@@ -266,3 +322,622 @@ class TestConditional:
         xmin, xmax = data.min_position()[0], data.max_position()[0]
         circ_time = data.circulation_time(position=(xmin + xmax) / 2, axis=0)
         assert np.nanmean(circ_time) > 0
+
+
+class TestErrors:
+    def test_missing_file(self):
+        with pytest.raises(up4.FileOpenError):
+            up4.Data(os.path.join(destination, "does-not-exist.hdf5"))
+
+    def test_timestep_out_of_range(self):
+        data = up4.Data(os.path.join(destination, "csvs", "26mbq_day2.hdf5"))
+        with pytest.raises(up4.TimestepError):
+            data.extract(0, (0, len(data)))
+
+    def test_invalid_mode(self):
+        data = up4.Data(os.path.join(destination, "csvs", "26mbq_day2.hdf5"))
+        grid = up4.Grid(data, num_cells=[10, 10, 10])
+        with pytest.raises(ValueError):
+            data.velocityfield(grid, mode="not-a-mode")
+
+    def test_wrong_file_kind(self):
+        with pytest.raises(up4.FileKindError):
+            up4.Data.from_tdata(os.path.join(destination, "csvs", "26mbq_day2.hdf5"))
+
+    def test_errors_share_base_class(self):
+        for error in [
+            up4.FileOpenError,
+            up4.MissingDataError,
+            up4.MalformedDataError,
+            up4.FileKindError,
+            up4.TimestepError,
+        ]:
+            assert issubclass(error, up4.Up4Error)
+
+
+class TestBuffer:
+    def test_small_buffer(self):
+        filename = os.path.join(destination, "csvs", "26mbq_day2.hdf5")
+        data = up4.Data(filename)
+        small = up4.Data(filename, buffersize=7)
+        for timestep in [0, 6, 7, 20, len(data) - 1, 3]:
+            assert np.allclose(data[timestep], small[timestep], equal_nan=True)
+
+    def test_lagged_access_small_cache(self):
+        filename = os.path.join(destination, "csvs", "26mbq_day2.hdf5")
+        data = up4.Data(filename)
+        small = up4.Data(filename, buffersize=7)
+        # only the most recently used chunk is kept
+        small.set_cache_memory(0)
+        for timestep in [20, 0, 21, 1, 13, 6, len(data) - 1, 2]:
+            assert np.allclose(data[timestep], small[timestep], equal_nan=True)
+        grid = up4.Grid.cartesian3d_from_data(data, [5, 5, 5])
+        assert np.allclose(
+            data.msd_field(grid, 0.2).to_numpy(),
+            small.msd_field(grid, 0.2).to_numpy(),
+            equal_nan=True,
+        )
+
+    def test_zero_buffer(self):
+        filename = os.path.join(destination, "csvs", "26mbq_day2.hdf5")
+        with pytest.raises(ValueError):
+            up4.Data(filename, buffersize=0)
+
+    def test_small_tdata_buffer(self):
+        filename = os.path.join(destination, "vtk", "rotating-drum", "drum.hdf5")
+        data = up4.Data(filename)
+        small = up4.Data(filename, buffersize=3)
+        small.set_cache_memory(0)
+        for timestep in [0, len(data) // 2, len(data) - 1, 1]:
+            assert np.allclose(data[timestep], small[timestep], equal_nan=True)
+        with pytest.raises(ValueError):
+            up4.Data.from_tdata(filename, buffersize=0)
+
+
+class TestNumpy:
+    @pytest.mark.parametrize("synthetic", ["lines"], indirect=True)
+    def test_from_numpy(self, synthetic):
+        assert len(synthetic) == 11
+        # columns: id, position, velocity
+        assert np.allclose(synthetic[5][:, 4], [1.0, 2.0])
+
+    @pytest.mark.parametrize("synthetic", ["lines"], indirect=True)
+    def test_fields(self, synthetic):
+        grid = up4.Grid(synthetic, num_cells=[5, 2, 2])
+        field = synthetic.numberfield(grid)
+        assert np.nansum(field.to_numpy()) > 0
+
+    def test_parallel_fields(self):
+        # more timesteps than are processed in one parallel chunk
+        time = np.linspace(0, 10, 1001)
+        position = np.full((1001, 3, 3), 0.5)
+        position[:, 0, 0] = 0.1 + 0.8 * time / 10
+        data = up4.Data.from_numpy(time, position)
+        grid = up4.Grid.cartesian3d([4, 2, 2], [0.0, 1.0, 0.0, 1.0, 0.0, 1.0])
+        field = data.numberfield(grid)
+        assert np.isclose(np.nansum(field.to_numpy()), 3 * 1001)
+        hist, _ = data.histogram(grid, bins=10)
+        assert np.sum(hist) <= 3 * 1001
+
+    def test_wrong_shape(self):
+        with pytest.raises(ValueError):
+            up4.Data.from_numpy(np.linspace(0, 1, 11), np.zeros((10, 2, 3)))
+
+
+class TestSave:
+    @pytest.mark.parametrize("layout", ["pdata", "tdata"])
+    def test_save_time_window(self, layout):
+        data = up4.Data(os.path.join(destination, "csvs", "26mbq_day2.hdf5"))
+        time = data.time()
+        min_time, max_time = time[len(time) // 4], time[len(time) // 2]
+        data.set_time(min_time, max_time)
+        file = os.path.join(destination, "csvs", "26mbq_day2_{}_trimmed.hdf5".format(layout))
+        data.save(file, layout)
+        trimmed = up4.Data(file)
+        assert len(trimmed) == np.count_nonzero((time >= min_time) & (time <= max_time))
+        assert np.allclose(trimmed.time()[0], min_time)
+        os.remove(file)
+
+    def test_invalid_layout(self):
+        data = up4.Data(os.path.join(destination, "csvs", "26mbq_day2.hdf5"))
+        with pytest.raises(ValueError):
+            data.save(os.path.join(destination, "invalid.hdf5"), "xdata")
+
+
+@pytest.mark.parametrize("synthetic", ["fields"], indirect=True)
+class TestExtraFields:
+    def test_field(self, synthetic):
+        assert synthetic.field_names() == ["temperature"]
+        assert np.allclose(synthetic.field(5, "temperature")[:, 0], [10.0, 11.0])
+
+    def test_missing_field(self, synthetic):
+        with pytest.raises(ValueError):
+            synthetic.field(0, "omega")
+
+    @pytest.mark.parametrize("layout", ["pdata", "tdata"])
+    def test_save_field(self, synthetic, layout):
+        file = os.path.join(destination, "extra_fields_{}.hdf5".format(layout))
+        synthetic.save(file, layout)
+        saved = up4.Data(file)
+        assert np.allclose(saved.field(5, "temperature")[:, 0], [10.0, 11.0])
+        os.remove(file)
+
+    @pytest.mark.parametrize("reducer", ["mean", "median", "min", "max", "std", "count"])
+    def test_property_field(self, synthetic, reducer):
+        grid = up4.Grid(synthetic, num_cells=[2, 2, 2])
+        field = synthetic.property_field(grid, "temperature", reducer).to_numpy()
+        if reducer == "count":
+            assert 0 < np.sum(field) <= 22
+        else:
+            assert np.nanmin(field) >= 0.0
+            assert np.nanmax(field) <= 21.0
+
+    def test_invalid_reducer(self, synthetic):
+        grid = up4.Grid(synthetic, num_cells=[2, 2, 2])
+        with pytest.raises(ValueError):
+            synthetic.property_field(grid, "radius", "mode")
+
+
+class TestConcat:
+    def test_concat_restart(self):
+        filename = os.path.join(destination, "csvs", "26mbq_day2.hdf5")
+        single = up4.Data(filename)
+        data = up4.Data.concat([filename, filename])
+        assert len(data) == 2 * len(single)
+        time = data.time()
+        assert np.all(np.diff(time) > 0)
+        assert np.allclose(data[len(single)][:, 1:4], single[0][:, 1:4], equal_nan=True)
+
+    def test_overlapping_offsets(self):
+        filename = os.path.join(destination, "csvs", "26mbq_day2.hdf5")
+        with pytest.raises(ValueError):
+            up4.Data.concat([filename, filename], time_offsets=[0.0, 0.0])
+
+
+@pytest.mark.parametrize("synthetic", ["swapped ids"], indirect=True)
+class TestParticleIds:
+    def test_extract_by_id(self, synthetic):
+        trajectory = synthetic.extract(1, (0, 10))
+        assert np.allclose(trajectory[:, 1], 2 * trajectory[:, 0])
+
+    def test_extract_missing_particle(self, synthetic):
+        trajectory = synthetic.extract(7, (0, 10))
+        assert np.all(np.isnan(trajectory[:, 1:]))
+
+
+class TestTransform:
+    def test_transform_pdata(self):
+        filename = os.path.join(destination, "csvs", "26mbq_day2.hdf5")
+        data = up4.Data(filename)
+        original = data[0][:, 1:4]
+        dimensions = data.dimensions()
+        data.scale(1000.0)
+        data.translate([0.0, 0.0, 1.0])
+        assert np.allclose(data[0][:, 1:4], original * 1000 + [0, 0, 1], equal_nan=True)
+        assert np.isclose(data.dimensions()["xmax"], dimensions["xmax"] * 1000)
+        data.reset_transform()
+        assert np.allclose(data[0][:, 1:4], original, equal_nan=True)
+
+    def test_rotate_and_swap(self):
+        time = np.linspace(0, 1, 11)
+        position = np.zeros((11, 1, 3))
+        position[:, 0, 0] = time
+        data = up4.Data.from_numpy(time, position)
+        data.rotate([0, 0, 1], np.pi / 2)
+        assert np.allclose(data[10][0, 1:4], [0, 1, 0])
+        data.swap_axes(1, 2)
+        assert np.allclose(data[10][0, 1:4], [0, 0, 1])
+        assert np.allclose(data.transform()[:3, :3] @ [1, 0, 0], [0, 0, 1])
+
+    def test_invalid_transform(self):
+        data = up4.Data(os.path.join(destination, "csvs", "26mbq_day2.hdf5"))
+        with pytest.raises(ValueError):
+            data.scale(0.0)
+        with pytest.raises(ValueError):
+            data.translate([1.0, 2.0])
+
+
+@pytest.mark.parametrize("synthetic", ["uneven time"], indirect=True)
+class TestTimeLookup:
+    def test_lookup(self, synthetic):
+        assert synthetic.timestep_at(0.25, "exact") == 2
+        assert synthetic.timestep_at(0.3) == 2
+        assert synthetic.timestep_at(0.3, "floor") == 2
+        assert synthetic.timestep_at(0.3, "ceil") == 3
+        with pytest.raises(ValueError):
+            synthetic.timestep_at(0.3, "exact")
+        with pytest.raises(up4.TimestepError):
+            synthetic.timestep_at(1.0)
+
+    def test_interpolated_msd(self, synthetic):
+        grid = up4.Grid.cartesian3d([1, 1, 1], [-1.0, 1.0, -1.0, 1.0, -1.0, 1.0])
+        interpolated = synthetic.msd_field(grid, 0.05, interpolate=True).to_numpy()
+        nearest = synthetic.msd_field(grid, 0.05).to_numpy()
+        # the closest recorded timestep to 0.05 s is the starting timestep itself, so
+        # only the interpolated positions see the particle move
+        assert np.nanmax(interpolated) > np.nanmax(nearest)
+
+
+@pytest.mark.parametrize("synthetic", ["types"], indirect=True)
+class TestParticleTypes:
+    def test_stats(self, synthetic):
+        assert synthetic.particle_types() == {0: 2, 1: 1}
+
+    def test_selection(self, synthetic):
+        grid = up4.Grid.cartesian3d([1, 1, 1], [0.0, 1.0, 0.0, 1.0, 0.0, 1.0])
+        synthetic.set_particle_types([1])
+        assert np.allclose(synthetic.property_field(grid, "vz").to_numpy(), 5.0)
+        synthetic.set_particle_types()
+        assert np.allclose(synthetic.property_field(grid, "vz").to_numpy(), 3.0)
+
+    def test_field_by_type(self, synthetic):
+        grid = up4.Grid.cartesian3d([2, 2, 2], [0.0, 1.0, 0.0, 1.0, 0.0, 1.0])
+        fields = synthetic.property_field_by_type(grid, "vz")
+        assert sorted(fields.keys()) == [0, 1]
+        small = fields[0].to_numpy()
+        large = fields[1].to_numpy()
+        assert np.isclose(small[0, 0, 0], 2.0)
+        assert np.isnan(small[1, 1, 1])
+        assert np.isclose(large[1, 1, 1], 5.0)
+        assert np.isnan(large[0, 0, 0])
+
+
+class TestInspect:
+    def test_clean(self):
+        time = np.linspace(0, 1, 6)
+        position = np.random.default_rng(2021).random((6, 4, 3))
+        velocity = np.ones((6, 4, 3))
+        data = up4.Data.from_numpy(time, position, velocity=velocity)
+        report = data.inspect()
+        assert report["clean"]
+        assert report["timesteps"] == 6
+        assert report["particles"][3]["coverage"] == 1.0
+        assert report["nan_fractions"]["position"] == 0.0
+
+    def test_problems(self):
+        time = np.array([0.0, 0.1, 0.2, 0.6, 0.7, 0.8])
+        position = np.random.default_rng(2021).random((6, 4, 3))
+        position[2, 1, :] = np.nan
+        velocity = np.ones((6, 4, 3))
+        velocity[4, 0, :] = 1000.0
+        data = up4.Data.from_numpy(time, position, velocity=velocity)
+        report = data.inspect()
+        assert not report["clean"]
+        assert [timestep for timestep, _ in report["time_gaps"]] == [2]
+        assert report["velocity_outliers"] == 1
+        assert report["particles"][1]["timesteps"] == 5
+        assert np.isclose(report["nan_fractions"]["position"], 1 / 24)
+
+
+@pytest.mark.parametrize("synthetic", ["millimetres"], indirect=True)
+class TestUnits:
+    def test_provenance(self, synthetic):
+        assert synthetic.length_unit() == "mm"
+        assert synthetic.provenance()["length_unit"] == "mm"
+        synthetic.scale(0.001)
+        assert synthetic.length_unit() == "m"
+
+    def test_field_units(self, synthetic):
+        grid = up4.Grid(synthetic, num_cells=[2, 2, 2])
+        assert synthetic.velocityfield(grid).units() == {"length": "mm", "value": "mm/s"}
+        assert synthetic.numberfield(grid).units() == {"length": "mm", "value": None}
+        assert synthetic.property_field(grid, "radius").units()["value"] == "mm"
+
+    @pytest.mark.parametrize("layout", ["pdata", "tdata"])
+    def test_save_unit(self, synthetic, layout):
+        file = os.path.join(destination, "units_{}.hdf5".format(layout))
+        synthetic.save(file, layout)
+        assert up4.Data(file).length_unit() == "mm"
+        up4.Converter.set_length_unit(file, "m")
+        assert up4.Data(file).length_unit() == "m"
+        os.remove(file)
+
+    def test_invalid_unit(self, synthetic):
+        grid = up4.Grid(synthetic, num_cells=[2, 2, 2])
+        with pytest.raises(ValueError):
+            grid.set_units(length="inch")
+        with pytest.raises(ValueError):
+            up4.Data.from_numpy(np.linspace(0, 1, 11), np.zeros((11, 2, 3)), length_unit="inch")
+
+
+class TestReducedGrids:
+    @pytest.mark.parametrize("synthetic", ["random"], indirect=True)
+    def test_profile(self, synthetic):
+        grid = up4.Grid.cartesian1d_from_data(synthetic, 2, 5)
+        assert grid.shape() == [1, 1, 5]
+        profile = np.squeeze(synthetic.velocityfield(grid).to_numpy())
+        assert profile.shape == (5,)
+        assert np.allclose(profile[~np.isnan(profile)], np.sqrt(3))
+        assert np.isclose(np.sum(synthetic.numberfield(grid).to_numpy()), 3 * 11)
+
+    @pytest.mark.parametrize("synthetic", ["random"], indirect=True)
+    def test_plane(self, synthetic):
+        grid = up4.Grid.cartesian2d(2, [4, 3], [0.0, 1.0, 0.0, 1.0])
+        assert grid.shape() == [4, 3, 1]
+        # positions along z are ignored
+        assert grid.is_inside([0.5, 0.5, 100.0])
+        plane = synthetic.numberfield(grid).to_numpy()
+        assert np.isclose(np.sum(plane), 3 * 11)
+
+    def test_invalid_axis(self):
+        with pytest.raises(ValueError):
+            up4.Grid.cartesian1d(3, 10, [0.0, 1.0])
+        with pytest.raises(ValueError):
+            up4.Grid.cartesian2d(2, [4, 3], [0.0, 1.0])
+        with pytest.raises(ValueError):
+            up4.Grid.cartesian1d(2, 0, [0.0, 1.0])
+        with pytest.raises(ValueError):
+            up4.Grid.cartesian2d(2, [4, 3], [0.0, 1.0, 1.0, 1.0])
+
+    def test_unresolved_extent(self):
+        grid = up4.Grid.cartesian1d(2, 5, [0.0, 1.0])
+        # the unresolved axes keep a size, so their cell index is well defined
+        assert grid.cell_id(0.3, -0.2, 0.5) == [0, 0, 2]
+        for axis in grid.cell_positions()[:2]:
+            assert np.all(np.isfinite(axis))
+
+
+class TestCylindricalModes:
+    limits = [-1.0, 1.0, -1.0, 1.0, 0.0, 1.0]
+
+    def test_equal_radius(self):
+        grid = up4.Grid.cylindrical3d([4, 4, 2], self.limits, mode="radius", inner_radius=0.2)
+        assert grid.is_inside([0.5, 0.0, 0.5])
+        # inside of the central shaft
+        assert not grid.is_inside([0.1, 0.0, 0.5])
+
+    def test_edges(self):
+        grid = up4.Grid(
+            num_cells=[2, 4, 2],
+            grid_style="cylindrical",
+            limits=[0.0, 2.0, 0.0, 2.0, 0.0, 1.0],
+            radial_mode="edges",
+            radial_edges=[0.3, 0.5, 0.9],
+        )
+        assert grid.is_inside([1.6, 1.0, 0.5])
+        assert not grid.is_inside([1.95, 1.0, 0.5])
+
+    def test_invalid_mode(self):
+        with pytest.raises(ValueError):
+            up4.Grid.cylindrical3d([4, 4, 2], self.limits, mode="area")
+        with pytest.raises(ValueError):
+            up4.Grid.cylindrical3d([2, 4, 2], self.limits, mode="edges", radial_edges=[0.5, 0.1, 0.9])
+        with pytest.raises(ValueError):
+            up4.Grid.cylindrical3d([2, 4, 2], self.limits, mode="edges")
+        with pytest.raises(ValueError):
+            up4.Grid.cylindrical3d(
+                [2, 4, 2], self.limits, mode="edges", inner_radius=0.2, radial_edges=[0.2, 0.5, 1.0]
+            )
+        with pytest.raises(ValueError):
+            up4.Grid.cylindrical3d([2, 4], self.limits)
+        with pytest.raises(ValueError):
+            up4.Grid.cylindrical3d([2, 4, 2], self.limits[:4])
+
+
+class TestCylindricalTrajectory:
+    def test_occupancy_along_path(self):
+        time = np.linspace(0, 1, 5)
+        angle = np.linspace(0, 2 * np.pi, 5)
+        position = np.zeros((5, 1, 3))
+        position[:, 0, 0] = 0.5 * np.cos(angle)
+        position[:, 0, 1] = 0.5 * np.sin(angle)
+        position[:, 0, 2] = 0.5
+        data = up4.Data.from_numpy(time, position)
+        grid = up4.Grid.cylindrical3d([2, 8, 1], [-1.0, 1.0, -1.0, 1.0, 0.0, 1.0])
+        occupancy = data.occupancyfield(grid).to_numpy()
+        assert np.isclose(np.nansum(occupancy), 1.0)
+        # the chords between the four sampled points cross more than four cells
+        assert np.count_nonzero(np.nan_to_num(occupancy)) > 4
+
+
+class TestSphericalGrid:
+    limits = [-1.0, 1.0, -1.0, 1.0, -1.0, 1.0]
+
+    def test_cells(self):
+        grid = up4.Grid.spherical3d([4, 6, 8], self.limits, mode="radius")
+        assert grid.shape() == [4, 6, 8]
+        assert grid.is_inside([0.5, 0.5, 0.5])
+        assert not grid.is_inside([0.8, 0.8, 0.8])
+        # above the centre, in the first theta cell
+        assert grid.cell_id(0.0, 0.01, 0.9)[:2] == [3, 0]
+        with pytest.raises(ValueError):
+            up4.Grid.spherical3d([4, 6, 8], self.limits, mode="area")
+        with pytest.raises(ValueError):
+            up4.Grid.spherical3d([2, 0, 2], self.limits)
+
+    def test_radial_velocity(self):
+        time = np.linspace(0, 1, 5)
+        position = np.zeros((5, 1, 3))
+        position[:, 0, 0] = np.linspace(0.1, 0.9, 5)
+        position[:, 0, 1] = 0.05
+        position[:, 0, 2] = 0.05
+        data = up4.Data.from_numpy(time, position)
+        grid = up4.Grid.spherical3d([2, 4, 4], self.limits)
+        radial, _, _ = data.vectorfield(grid).to_numpy()
+        radial = radial[np.isfinite(radial) & (radial != 0)]
+        assert radial.size > 0
+        assert np.all(radial > 0)
+
+
+class TestCylinderAxis:
+    def test_horizontal_drum(self):
+        grid = up4.Grid.cylindrical3d(
+            [2, 4, 2], [0.0, 1.0, -1.0, 1.0, -1.0, 1.0], mode="radius", axis=0
+        )
+        # along the x axis, so x is the height and y, z span the cross section
+        assert grid.is_inside([0.9, 0.5, -0.5])
+        assert not grid.is_inside([1.5, 0.0, 0.0])
+        assert not grid.is_inside([0.5, 0.0, 1.2])
+        with pytest.raises(ValueError):
+            up4.Grid.cylindrical3d([2, 4, 2], [0.0, 1.0, -1.0, 1.0, -1.0, 1.0], axis=3)
+        data = up4.Data(os.path.join(destination, "csvs", "26mbq_day2.hdf5"))
+        with pytest.raises(ValueError):
+            up4.Grid.cylindrical3d_from_data(data, [2, 4], axis=0)
+
+    def test_oriented(self):
+        grid = up4.Grid.cylindrical3d_oriented(
+            [2, 4, 2], [0.0, 0.0, 0.0], [1.0, 1.0, 0.0], 1.0, [-1.0, 1.0]
+        )
+        assert grid.is_inside([0.7, 0.7, 0.0])
+        assert not grid.is_inside([0.7, -0.7, 0.8])
+        with pytest.raises(ValueError):
+            up4.Grid.cylindrical3d_oriented(
+                [2, 4, 2], [0.0, 0.0, 0.0], [0.0, 0.0, 0.0], 1.0, [-1.0, 1.0]
+            )
+
+    def test_drum_rotation(self):
+        # rotating around the x axis at one revolution per 2 pi seconds
+        time = np.linspace(0, 2 * np.pi, 41)
+        position = np.zeros((41, 1, 3))
+        position[:, 0, 0] = 0.5
+        position[:, 0, 1] = 0.5 * np.cos(time)
+        position[:, 0, 2] = 0.5 * np.sin(time)
+        velocity = np.zeros((41, 1, 3))
+        velocity[:, 0, 1] = -0.5 * np.sin(time)
+        velocity[:, 0, 2] = 0.5 * np.cos(time)
+        data = up4.Data.from_numpy(time, position, velocity=velocity)
+        grid = up4.Grid.cylindrical3d(
+            [2, 4, 1], [0.0, 1.0, -1.0, 1.0, -1.0, 1.0], mode="radius", axis=0
+        )
+        _, omega, axial = data.vectorfield(grid).to_numpy()
+        omega = omega[np.isfinite(omega) & (omega != 0)]
+        assert omega.size > 0
+        assert np.allclose(omega, 1.0)
+        assert np.allclose(axial[np.isfinite(axial)], 0.0)
+
+
+class TestGridArithmetic:
+    limits = [0.0, 1.0, 0.0, 1.0, 0.0, 1.0]
+
+    def field(self, x):
+        time = np.linspace(0, 1, 3)
+        position = np.full((3, 1, 3), 0.5)
+        position[:, 0, 0] = x
+        data = up4.Data.from_numpy(time, position)
+        return data.numberfield(up4.Grid.cartesian3d([2, 1, 1], self.limits))
+
+    def test_operators(self):
+        a, b = self.field(0.25), self.field(0.75)
+        total = a + b
+        assert np.allclose(total.to_numpy(), a.to_numpy() + b.to_numpy(), equal_nan=True)
+        assert np.allclose((a - b).to_numpy(), a.to_numpy() - b.to_numpy(), equal_nan=True)
+        assert np.allclose((2 * a).to_numpy(), 2 * a.to_numpy(), equal_nan=True)
+        assert np.allclose((a / 2).to_numpy(), a.to_numpy() / 2, equal_nan=True)
+        assert np.allclose((1 - a).to_numpy(), 1 - a.to_numpy(), equal_nan=True)
+        assert np.allclose(a.maximum(b).to_numpy(), np.fmax(a.to_numpy(), b.to_numpy()), equal_nan=True)
+        assert np.allclose(total.weights_to_numpy(), a.weights_to_numpy() + b.weights_to_numpy(), equal_nan=True)
+
+    def test_merge(self):
+        a, b = self.field(0.25), self.field(0.75)
+        merged = a.merge(b)
+        weights = a.weights_to_numpy() + b.weights_to_numpy()
+        assert np.allclose(merged.weights_to_numpy(), weights)
+
+    def test_incompatible(self):
+        a = self.field(0.25)
+        other = up4.Grid.cartesian3d([1, 2, 1], self.limits)
+        with pytest.raises(ValueError):
+            a + other
+        cylinder = up4.Grid.cylindrical3d([2, 1, 1], self.limits)
+        with pytest.raises(ValueError):
+            a.merge(cylinder)
+        with pytest.raises(TypeError):
+            a + "grid"
+
+    def test_different_geometry(self):
+        limits = [-1.0, 1.0, -1.0, 1.0, -1.0, 1.0]
+        volume = up4.Grid.cylindrical3d([2, 4, 2], limits, mode="volume")
+        radius = up4.Grid.cylindrical3d([2, 4, 2], limits, mode="radius")
+        horizontal = up4.Grid.cylindrical3d([2, 4, 2], limits, mode="volume", axis=0)
+        for other in [radius, horizontal]:
+            with pytest.raises(ValueError):
+                volume + other
+
+    def test_vector_grids(self):
+        time = np.linspace(0, 1, 3)
+        position = np.full((3, 1, 3), 0.5)
+        position[:, 0, 0] = [0.1, 0.2, 0.3]
+        data = up4.Data.from_numpy(time, position)
+        grid = up4.Grid.cartesian3d([2, 1, 1], self.limits)
+        field = data.vectorfield(grid)
+        doubled = field + field
+        for component, double in zip(field.to_numpy(), doubled.to_numpy()):
+            assert np.allclose(double, 2 * component, equal_nan=True)
+        halved = field / 2.0
+        for component, half in zip(field.to_numpy(), halved.to_numpy()):
+            assert np.allclose(half, component / 2, equal_nan=True)
+
+
+class TestGridStorage:
+    limits = [0.0, 1.0, 0.0, 1.0, 0.0, 1.0]
+
+    @pytest.mark.parametrize("synthetic", ["path"], indirect=True)
+    @pytest.mark.parametrize("suffix", [".hdf5", ".npz"])
+    def test_cartesian(self, synthetic, tmp_path, suffix):
+        field = synthetic.numberfield(up4.Grid.cartesian3d([2, 2, 1], self.limits))
+        filename = str(tmp_path / ("field" + suffix))
+        field.save(filename, {"function": "numberfield", "cells": 4})
+        loaded = up4.Grid.load(filename)
+        assert np.array_equal(loaded.to_numpy(), field.to_numpy(), equal_nan=True)
+        assert np.array_equal(loaded.weights_to_numpy(), field.weights_to_numpy())
+        for axis, loaded_axis in zip(field.cell_positions(), loaded.cell_positions()):
+            assert np.array_equal(axis, loaded_axis)
+        assert loaded.units() == field.units()
+        parameters = up4.Grid.saved_parameters(filename)
+        assert parameters == {"function": "numberfield", "cells": "4"}
+
+    @pytest.mark.parametrize("synthetic", ["path"], indirect=True)
+    @pytest.mark.parametrize("suffix", [".hdf5", ".npz"])
+    def test_cylindrical(self, synthetic, tmp_path, suffix):
+        grid = up4.Grid.cylindrical3d_oriented(
+            [3, 4, 2], [0.5, 0.5, 0.5], [1.0, 1.0, 0.0], 0.5, [-0.5, 0.5], inner_radius=0.1
+        )
+        field = synthetic.numberfield(grid)
+        filename = str(tmp_path / ("cylinder" + suffix))
+        field.save(filename)
+        loaded = up4.Grid.load(filename)
+        assert np.array_equal(loaded.to_numpy(), field.to_numpy(), equal_nan=True)
+        for axis, loaded_axis in zip(field.cell_positions(), loaded.cell_positions()):
+            assert np.allclose(axis, loaded_axis)
+        for position in [[0.5, 0.5, 0.5], [0.7, 0.6, 0.5]]:
+            assert loaded.is_inside(position) == field.is_inside(position)
+
+    @pytest.mark.parametrize("synthetic", ["path"], indirect=True)
+    @pytest.mark.parametrize("suffix", [".hdf5", ".npz"])
+    def test_vector(self, synthetic, tmp_path, suffix):
+        field = synthetic.vectorfield(up4.Grid.cartesian3d([2, 1, 1], self.limits))
+        filename = str(tmp_path / ("vectors" + suffix))
+        field.save(filename, {"function": "vectorfield"})
+        loaded = up4.Grid.load(filename)
+        assert type(loaded).__name__ == "RustVectorGrid"
+        for component, loaded_component in zip(field.to_numpy(), loaded.to_numpy()):
+            assert np.array_equal(component, loaded_component, equal_nan=True)
+
+    def test_wrong_file(self, tmp_path):
+        filename = str(tmp_path / "other.npz")
+        np.savez(filename, values=np.zeros(3))
+        with pytest.raises(up4.FileKindError):
+            up4.Grid.load(filename)
+
+
+@pytest.mark.parametrize("synthetic", ["path"], indirect=True)
+class TestVTKExport:
+    limits = [0.0, 1.0, 0.0, 1.0, 0.0, 1.0]
+
+    def test_cartesian(self, synthetic, tmp_path):
+        field = synthetic.numberfield(up4.Grid.cartesian3d([2, 2, 1], self.limits))
+        for name in ["field.vtr", "field.vtk"]:
+            field.to_vtk(str(tmp_path / name))
+            assert os.path.getsize(tmp_path / name) > 0
+        with pytest.raises(ValueError):
+            field.to_vtk(str(tmp_path / "field.vts"))
+
+    def test_cylindrical_vectors(self, synthetic, tmp_path):
+        grid = up4.Grid.cylindrical3d([2, 4, 2], self.limits)
+        field = synthetic.vectorfield(grid)
+        field.to_vtk(str(tmp_path / "field.vts"))
+        content = open(tmp_path / "field.vts").read()
+        assert "StructuredGrid" in content
+        assert 'Name="data"' in content and 'Name="weights"' in content
+        with pytest.raises(ValueError):
+            field.to_vtk(str(tmp_path / "field.vtr"))
@@ -9,8 +9,28 @@
 # Desc    :   None
 
 from upppp_rust import Data, Converter
+from upppp_rust import (
+    Up4Error,
+    FileOpenError,
+    MissingDataError,
+    MalformedDataError,
+    FileKindError,
+    TimestepError,
+)
 from .grid import Grid
 from .plotting import save_fig, Plotter2D
 
 
-__all__ = ["Data", "Converter", "Grid", "Plotter2D", "save_fig"]
+__all__ = [
+    "Data",
+    "Converter",
+    "Grid",
+    "Plotter2D",
+    "save_fig",
+    "Up4Error",
+    "FileOpenError",
+    "MissingDataError",
+    "MalformedDataError",
+    "FileKindError",
+    "TimestepError",
+]
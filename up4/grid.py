@@ -59,6 +59,9 @@ class Grid(RustGrid):
         xlim=None,
         ylim=None,
         zlim=None,
+        radial_mode="volume",
+        inner_radius=0.0,
+        radial_edges=None,
     ):
         # check types
         if not isinstance(limits, (list, np.ndarray)) and limits is not None:
@@ -201,14 +204,22 @@ class Grid(RustGrid):
                 and zlim is None
             ):
                 return self.cylindrical3d_from_data(
-                    data, np.asarray(num_cells, dtype=np.int64)
+                    data,
+                    np.asarray(num_cells, dtype=np.int64),
+                    mode=radial_mode,
+                    inner_radius=inner_radius,
+                    radial_edges=radial_edges,
                 )
 
             # data is given and cell size is given
             elif data is not None and limits is not None:
                 if not any(i < 0 for i in limits):
                     return self.cylindrical3d(
-                        np.asarray(num_cells, dtype=np.int64), np.asarray(limits)
+                        np.asarray(num_cells, dtype=np.int64),
+                        np.asarray(limits),
+                        mode=radial_mode,
+                        inner_radius=inner_radius,
+                        radial_edges=radial_edges,
                     )
                 else:
                     raise ValueError("limits must be positive")
@@ -229,20 +240,32 @@ class Grid(RustGrid):
                     zmax = zlim[1]
                 dim = [xmin, xmax, ymin, ymax, zmin, zmax]
                 return self.cylindrical3d(
-                    np.asarray(num_cells, dtype=np.int64), np.asarray(dim)
+                    np.asarray(num_cells, dtype=np.int64),
+                    np.asarray(dim),
+                    mode=radial_mode,
+                    inner_radius=inner_radius,
+                    radial_edges=radial_edges,
                 )
             elif data is None:
                 if limits is not None:
                     if not any(i < 0 for i in limits):
                         return self.cylindrical3d(
-                            np.asarray(num_cells, dtype=np.int64), np.asarray(limits)
+                            np.asarray(num_cells, dtype=np.int64),
+                            np.asarray(limits),
+                            mode=radial_mode,
+                            inner_radius=inner_radius,
+                            radial_edges=radial_edges,
                         )
                     else:
                         raise ValueError("limits must be positive")
                 elif xlim is not None and ylim is not None and zlim is not None:
                     dim = [xlim[0], xlim[1], ylim[0], ylim[1], zlim[0], zlim[1]]
                     return self.cylindrical3d(
-                        np.asarray(num_cells, dtype=np.int64), np.asarray(dim)
+                        np.asarray(num_cells, dtype=np.int64),
+                        np.asarray(dim),
+                        mode=radial_mode,
+                        inner_radius=inner_radius,
+                        radial_edges=radial_edges,
                     )
                 else:
                     raise ValueError("Either limits or xlim,ylim,zlim must be given")